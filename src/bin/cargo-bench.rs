@@ -0,0 +1,78 @@
+#![feature(phase)]
+
+extern crate serialize;
+extern crate cargo;
+extern crate docopt;
+#[phase(plugin)] extern crate docopt_macros;
+
+use std::io::process::ExitStatus;
+
+use cargo::ops;
+use cargo::{execute_main_without_stdin};
+use cargo::core::{MultiShell};
+use cargo::util::{CliResult, CliError};
+use cargo::util::important_paths::{find_root_manifest_for_cwd};
+
+docopt!(Options, "
+Execute all benchmarks of a local package
+
+Usage:
+    cargo-bench [options] [--] [<args>...]
+
+Options:
+    -h, --help              Print this message
+    -j N, --jobs N          The number of jobs to run in parallel
+    -u, --update-remotes    Deprecated option, use `cargo update` instead
+    --features FEATURES     Space-separated list of features to also build
+    --no-default-features   Do not build the `default` feature
+    --target-dir DIR        Directory for all generated artifacts
+    --manifest-path PATH    Path to the manifest to build benchmarks for
+    -v, --verbose           Use verbose output
+    --color WHEN            Coloring: auto, always, never
+
+All of the trailing arguments are passed to the benchmark binaries generated
+for filtering benchmarks and generally providing options configuring how they
+run.
+",  flag_jobs: Option<uint>, flag_manifest_path: Option<String>,
+    flag_target_dir: Option<String>, flag_features: Option<String>)
+
+fn main() {
+    execute_main_without_stdin(execute, true);
+}
+
+fn execute(options: Options, shell: &mut MultiShell) -> CliResult<Option<()>> {
+    let root = try!(find_root_manifest_for_cwd(options.flag_manifest_path));
+    shell.set_verbose(options.flag_verbose);
+
+    let target_dir = options.flag_target_dir.as_ref().map(|dir| {
+        Path::new(dir.as_slice())
+    });
+    let features = options.flag_features.as_ref().map(|s| {
+        s.as_slice().words().map(|f| f.to_string()).collect::<Vec<String>>()
+    }).unwrap_or(Vec::new());
+
+    let mut bench_opts = ops::BenchOptions {
+        compile_opts: ops::CompileOptions {
+            update: options.flag_update_remotes,
+            jobs: options.flag_jobs,
+            target_dir: target_dir.as_ref(),
+            features: features.as_slice(),
+            no_default_features: options.flag_no_default_features,
+            ..ops::CompileOptions::new("bench", shell)
+        },
+        args: options.arg_args.as_slice(),
+    };
+
+    let err = try!(ops::run_benches(&root, &mut bench_opts).map_err(|err| {
+        CliError::from_boxed(err, 101)
+    }));
+    match err {
+        None => Ok(None),
+        Some(err) => {
+            Err(match err.exit {
+                Some(ExitStatus(i)) => CliError::from_boxed(box err, i as uint),
+                _ => CliError::from_boxed(box err, 101),
+            })
+        }
+    }
+}
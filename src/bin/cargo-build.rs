@@ -19,18 +19,38 @@ docopt!(Options, "
 Compile a local package and all of its dependencies
 
 Usage:
-    cargo-build [options]
+    cargo-build [options] [--bin NAME]... [--example NAME]... [--target TRIPLE]...
 
 Options:
-    -h, --help              Print this message
-    -j N, --jobs N          The number of jobs to run in parallel
-    --release               Build artifacts in release mode, with optimizations
-    --target TRIPLE         Build for the target triple
-    -u, --update-remotes    Deprecated option, use `cargo update` instead
-    --manifest-path PATH    Path to the manifest to compile
-    -v, --verbose           Use verbose output
-",  flag_jobs: Option<uint>, flag_target: Option<String>,
-    flag_manifest_path: Option<String>)
+    -h, --help               Print this message
+    -p, --package SPEC       Build only the specified workspace member
+    -j N, --jobs N           The number of jobs to run in parallel
+    --release                Build artifacts in release mode, with optimizations
+    --lib                    Build only this package's library
+    --bin NAME               Build only the specified binary, may be repeated
+    --example NAME           Build only the specified example, may be repeated
+    --target TRIPLE          Build for the target triple, may be repeated
+    -u, --update-remotes     Deprecated option, use `cargo update` instead
+    --locked                 Require Cargo.lock and its checksums to be up to date
+    --offline                Run without accessing the network
+    --frozen                 Equivalent to specifying both --locked and --offline
+    --dep-info-out DIR       Write a copy of each target's dep-info into DIR
+    --features FEATURES      Space-separated list of features to also build
+    --no-default-features    Do not build the `default` feature
+    --target-dir DIR         Directory for all generated artifacts
+    --message-format FMT     Report progress and errors as `human` or `json`
+    --keep-going             Build as many targets as possible despite failures
+    --timings                Report how long each package took to compile
+    --deny-primary-warnings  Turn warnings into errors, except in dependencies
+    --manifest-path PATH     Path to the manifest to compile
+    -v, --verbose            Use verbose output
+    --color WHEN             Coloring: auto, always, never
+",  flag_jobs: Option<uint>, flag_target: Vec<String>,
+    flag_manifest_path: Option<String>, flag_dep_info_out: Option<String>,
+    flag_bin: Vec<String>, flag_example: Vec<String>,
+    flag_message_format: Option<String>,
+    flag_target_dir: Option<String>, flag_features: Option<String>,
+    flag_package: Option<String>)
 
 fn main() {
     execute_main_without_stdin(execute, false);
@@ -42,18 +62,62 @@ fn execute(options: Options, shell: &mut MultiShell) -> CliResult<Option<()>> {
 
     let root = try!(find_root_manifest_for_cwd(options.flag_manifest_path));
 
-    let env = if options.flag_release {
+    // Examples are only built along with the tests, sharing their profile
+    // and dev-dependencies, so there is no optimized build of them.
+    let env = if !options.flag_example.is_empty() {
+        if options.flag_release {
+            return Err(CliError::new("examples are always built with the \
+                                      `test` profile, so `--example` can't \
+                                      be combined with `--release`", 1))
+        }
+        "test"
+    } else if options.flag_release {
         "release"
     } else {
         "compile"
     };
 
+    let dep_info_out = options.flag_dep_info_out.as_ref().map(|dir| {
+        os::make_absolute(&Path::new(dir.as_slice()))
+    });
+
+    let message_format = match options.flag_message_format {
+        None => ops::Human,
+        Some(ref s) if s.as_slice() == "human" => ops::Human,
+        Some(ref s) if s.as_slice() == "json" => ops::Json,
+        Some(s) => {
+            return Err(CliError::new(format!("invalid message format `{}`, \
+                                              expected `human` or `json`", s),
+                                     1))
+        }
+    };
+
+    let target_dir = options.flag_target_dir.as_ref().map(|dir| {
+        Path::new(dir.as_slice())
+    });
+    let features = options.flag_features.as_ref().map(|s| {
+        s.as_slice().words().map(|f| f.to_string()).collect::<Vec<String>>()
+    }).unwrap_or(Vec::new());
+
     let mut opts = CompileOptions {
         update: options.flag_update_remotes,
-        env: env,
-        shell: shell,
         jobs: options.flag_jobs,
-        target: options.flag_target.as_ref().map(|t| t.as_slice()),
+        targets: options.flag_target.as_slice(),
+        locked: options.flag_locked || options.flag_frozen,
+        offline: options.flag_offline || options.flag_frozen,
+        dep_info_out: dep_info_out.as_ref(),
+        target_dir: target_dir.as_ref(),
+        lib: options.flag_lib,
+        bins: options.flag_bin.as_slice(),
+        examples: options.flag_example.as_slice(),
+        message_format: message_format,
+        features: features.as_slice(),
+        no_default_features: options.flag_no_default_features,
+        keep_going: options.flag_keep_going,
+        timings: options.flag_timings,
+        deny_primary_warnings: options.flag_deny_primary_warnings,
+        spec: options.flag_package.as_ref().map(|s| s.as_slice()),
+        ..CompileOptions::new(env, shell)
     };
 
     ops::compile(&root, &mut opts).map(|_| None).map_err(|err| {
@@ -22,20 +22,37 @@ Usage:
 
 Options:
     -h, --help              Print this message
+    -p, --package SPEC      Package to clean artifacts for
     --manifest-path PATH    Path to the manifest to the package to clean
+    --target TRIPLE         Target triple to clean output for
+    --target-dir DIR        Directory for all generated artifacts
+    --keep-doc              Leave the generated documentation in place
     -v, --verbose           Use verbose output
-",  flag_manifest_path: Option<String>)
+    --color WHEN            Coloring: auto, always, never
+",  flag_manifest_path: Option<String>, flag_package: Option<String>,
+    flag_target: Option<String>, flag_target_dir: Option<String>)
 
 fn main() {
     execute_main_without_stdin(execute, false);
 }
 
-fn execute(options: Options, _shell: &mut MultiShell) -> CliResult<Option<()>> {
+fn execute(options: Options, shell: &mut MultiShell) -> CliResult<Option<()>> {
     debug!("executing; cmd=cargo-clean; args={}", os::args());
 
     let root = try!(find_root_manifest_for_cwd(options.flag_manifest_path));
 
-    ops::clean(&root).map(|_| None).map_err(|err| {
+    let target_dir = options.flag_target_dir.as_ref().map(|dir| {
+        Path::new(dir.as_slice())
+    });
+
+    let mut opts = ops::CleanOptions {
+        spec: options.flag_package.as_ref().map(|s| s.as_slice()),
+        target: options.flag_target.as_ref().map(|s| s.as_slice()),
+        target_dir: target_dir.as_ref(),
+        keep_doc: options.flag_keep_doc,
+        shell: shell,
+    };
+    ops::clean(&root, &mut opts).map(|_| None).map_err(|err| {
       CliError::from_boxed(err, 101)
     })
 }
@@ -21,16 +21,24 @@ Usage:
 
 Options:
     -h, --help              Print this message
-    --no-deps               Don't build documentation for dependencies
+    --open                  Opens the docs in a browser after the operation
+    --deps                  Also build documentation for dependencies
+    --no-deps               Don't build documentation for dependencies (default)
     -j N, --jobs N          The number of jobs to run in parallel
     -u, --update-remotes    Deprecated option, use `cargo update` instead
+    --features FEATURES     Space-separated list of features to also build
+    --no-default-features   Do not build the `default` feature
+    --target-dir DIR        Directory for all generated artifacts
     --manifest-path PATH    Path to the manifest to document
     -v, --verbose           Use verbose output
+    --color WHEN            Coloring: auto, always, never
 
-By default the documentation for the local package and all dependencies is
-built. The output is all placed in `target/doc` in rustdoc's usual format.
+By default only the documentation for the local package is built, and with
+`--deps` that of all of its dependencies as well. The output is all placed in
+`target/doc` in rustdoc's usual format.
 ",  flag_jobs: Option<uint>,
-    flag_manifest_path: Option<String>)
+    flag_manifest_path: Option<String>,
+    flag_target_dir: Option<String>, flag_features: Option<String>)
 
 fn main() {
     execute_main_without_stdin(execute, false)
@@ -48,14 +56,23 @@ fn execute(options: Options, shell: &mut MultiShell) -> CliResult<Option<()>> {
                     }))
     };
 
+    let target_dir = options.flag_target_dir.as_ref().map(|dir| {
+        Path::new(dir.as_slice())
+    });
+    let features = options.flag_features.as_ref().map(|s| {
+        s.as_slice().words().map(|f| f.to_string()).collect::<Vec<String>>()
+    }).unwrap_or(Vec::new());
+
     let mut doc_opts = ops::DocOptions {
-        all: !options.flag_no_deps,
+        no_deps: options.flag_no_deps || !options.flag_deps,
+        open: options.flag_open,
         compile_opts: ops::CompileOptions {
             update: options.flag_update_remotes,
-            env: if options.flag_no_deps {"doc"} else {"doc-all"},
-            shell: shell,
             jobs: options.flag_jobs,
-            target: None,
+            target_dir: target_dir.as_ref(),
+            features: features.as_slice(),
+            no_default_features: options.flag_no_default_features,
+            ..ops::CompileOptions::new("doc", shell)
         },
     };
 
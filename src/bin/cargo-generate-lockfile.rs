@@ -23,6 +23,7 @@ Options:
     -h, --help              Print this message
     --manifest-path PATH    Path to the manifest to generate a lockfile for
     -v, --verbose           Use verbose output
+    --color WHEN            Coloring: auto, always, never
 ",  flag_manifest_path: Option<String>)
 
 fn main() {
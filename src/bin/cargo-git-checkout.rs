@@ -10,7 +10,7 @@ extern crate docopt;
 
 use cargo::{execute_main_without_stdin};
 use cargo::core::MultiShell;
-use cargo::core::source::{Source, SourceId};
+use cargo::core::source::{Source, SourceId, Branch};
 use cargo::sources::git::{GitSource};
 use cargo::util::{Config, CliResult, CliError, human};
 use url::Url;
@@ -21,6 +21,7 @@ Usage:
 
 Options:
     -h, --help              Print this message
+    --offline               Fail instead of accessing the network
     -v, --verbose           Use verbose output
 ")
 
@@ -29,7 +30,9 @@ fn main() {
 }
 
 fn execute(options: Options, shell: &mut MultiShell) -> CliResult<Option<()>> {
-    let Options { flag_url: url, flag_reference: reference, .. } = options;
+    let Options {
+        flag_url: url, flag_reference: reference, flag_offline: offline, ..
+    } = options;
 
     let url: Url = try!(Url::parse(url.as_slice()).map_err(|e| {
                             human(format!("The URL `{}` you passed was \
@@ -37,11 +40,12 @@ fn execute(options: Options, shell: &mut MultiShell) -> CliResult<Option<()>> {
                         })
                         .map_err(|e| CliError::from_boxed(e, 1)));
 
-    let source_id = SourceId::for_git(&url, reference.as_slice(), None);
+    let source_id = SourceId::for_git(&url, Branch(reference), None);
 
     let mut config = try!(Config::new(shell, true, None, None).map_err(|e| {
         CliError::from_boxed(e, 1)
     }));
+    if offline { config.set_offline() }
     let mut source = GitSource::new(&source_id, &mut config);
 
     try!(source.update().map_err(|e| {
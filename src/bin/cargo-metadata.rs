@@ -0,0 +1,48 @@
+#![feature(phase)]
+
+extern crate serialize;
+extern crate cargo;
+extern crate docopt;
+#[phase(plugin)] extern crate docopt_macros;
+#[phase(plugin, link)] extern crate log;
+
+use std::os;
+use cargo::ops;
+use cargo::{execute_main_without_stdin};
+use cargo::core::MultiShell;
+use cargo::util::{CliResult, CliError};
+use cargo::util::important_paths::find_root_manifest_for_cwd;
+
+docopt!(Options, "
+Output the resolved dependencies of a project, the concrete used versions
+including overrides, in machine-readable format
+
+Usage:
+    cargo-metadata [options]
+
+Options:
+    -h, --help              Print this message
+    --manifest-path PATH    Path to the manifest of the package to describe
+    --no-deps               Output information only about the root package
+                            and don't fetch dependencies
+    -v, --verbose           Use verbose output
+    --color WHEN            Coloring: auto, always, never
+",  flag_manifest_path: Option<String>)
+
+fn main() {
+    execute_main_without_stdin(execute, false);
+}
+
+fn execute(options: Options,
+           shell: &mut MultiShell) -> CliResult<Option<ops::ExportInfo>> {
+    debug!("executing; cmd=cargo-metadata; args={}", os::args());
+    shell.set_verbose(options.flag_verbose);
+    let root = try!(find_root_manifest_for_cwd(options.flag_manifest_path));
+
+    let mut opts = ops::OutputMetadataOptions {
+        no_deps: options.flag_no_deps,
+        shell: shell,
+    };
+    ops::output_metadata(&root, &mut opts)
+        .map(|info| Some(info)).map_err(|err| CliError::from_boxed(err, 101))
+}
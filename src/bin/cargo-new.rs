@@ -20,10 +20,16 @@ Usage:
 
 Options:
     -h, --help          Print this message
-    --git               Initialize a new git repository with a .gitignore
-    --bin               Use a binary instead of a library template
+    --vcs VCS           Initialize a new repository for the given version
+                        control system (git, hg or none), defaults to git
+    --git               Equivalent to `--vcs git`
+    --bin               Use a binary template (the default)
+    --lib               Use a library template
+    --name NAME         Name the package NAME instead of after <path>
+    --force             Overwrite the files of an existing directory
     -v, --verbose       Use verbose output
-")
+    --color WHEN        Coloring: auto, always, never
+",  flag_name: Option<String>, flag_vcs: Option<String>)
 
 fn main() {
     cargo::execute_main_without_stdin(execute, false)
@@ -33,12 +39,35 @@ fn execute(options: Options, shell: &mut MultiShell) -> CliResult<Option<()>> {
     debug!("executing; cmd=cargo-new; args={}", os::args());
     shell.set_verbose(options.flag_verbose);
 
-    let Options { flag_git, flag_bin, arg_path, .. } = options;
+    let Options { flag_git, flag_bin, flag_lib, flag_force, flag_name,
+                  flag_vcs, arg_path, .. } = options;
+
+    if flag_bin && flag_lib {
+        return Err(CliError::new("can't specify both --lib and --bin", 1))
+    }
+
+    let vcs = match flag_vcs {
+        None => ops::GitRepo,
+        Some(ref s) if s.as_slice() == "git" => ops::GitRepo,
+        Some(ref s) if s.as_slice() == "hg" => ops::HgRepo,
+        Some(ref s) if s.as_slice() == "none" => ops::NoVcs,
+        Some(s) => {
+            return Err(CliError::new(format!("invalid version control system \
+                                              `{}`, expected `git`, `hg` or \
+                                              `none`", s), 1))
+        }
+    };
+    if flag_git && vcs != ops::GitRepo {
+        return Err(CliError::new("can't specify both --git and another \
+                                  --vcs", 1))
+    }
 
     let opts = ops::NewOptions {
-        git: flag_git,
+        vcs: vcs,
         path: arg_path.as_slice(),
-        bin: flag_bin,
+        bin: !flag_lib,
+        force: flag_force,
+        name: flag_name.as_ref().map(|s| s.as_slice()),
     };
 
     ops::new(opts, shell).map(|_| None).map_err(|err| {
@@ -14,21 +14,28 @@ use cargo::util::{CliResult, CliError};
 use cargo::util::important_paths::{find_root_manifest_for_cwd};
 
 docopt!(Options, "
-Run the main binary of the local package (src/main.rs)
+Run a binary of the local package
 
 Usage:
     cargo-run [options] [--] [<args>...]
 
 Options:
     -h, --help              Print this message
+    --bin NAME              Name of the bin target to run
     -j N, --jobs N          The number of jobs to run in parallel
     -u, --update-remotes    Deprecated option, use `cargo update` instead
+    --features FEATURES     Space-separated list of features to also build
+    --no-default-features   Do not build the `default` feature
+    --target-dir DIR        Directory for all generated artifacts
     --manifest-path PATH    Path to the manifest to execute
     -v, --verbose           Use verbose output
+    --color WHEN            Coloring: auto, always, never
 
-All of the trailing arguments are passed as to the binary to run.
+If the package has more than one bin target, `--bin` selects which one to run.
+All of the trailing arguments are passed to the binary to run.
 ",  flag_jobs: Option<uint>, flag_target: Option<String>,
-    flag_manifest_path: Option<String>)
+    flag_manifest_path: Option<String>, flag_bin: Option<String>,
+    flag_target_dir: Option<String>, flag_features: Option<String>)
 
 fn main() {
     execute_main_without_stdin(execute, true);
@@ -38,23 +45,34 @@ fn execute(options: Options, shell: &mut MultiShell) -> CliResult<Option<()>> {
     shell.set_verbose(options.flag_verbose);
     let root = try!(find_root_manifest_for_cwd(options.flag_manifest_path));
 
+    let target_dir = options.flag_target_dir.as_ref().map(|dir| {
+        Path::new(dir.as_slice())
+    });
+    let features = options.flag_features.as_ref().map(|s| {
+        s.as_slice().words().map(|f| f.to_string()).collect::<Vec<String>>()
+    }).unwrap_or(Vec::new());
+
     let mut compile_opts = ops::CompileOptions {
         update: options.flag_update_remotes,
-        env: "compile",
-        shell: shell,
         jobs: options.flag_jobs,
-        target: None,
+        target_dir: target_dir.as_ref(),
+        features: features.as_slice(),
+        no_default_features: options.flag_no_default_features,
+        ..ops::CompileOptions::new("compile", shell)
     };
 
     let err = try!(ops::run(&root, &mut compile_opts,
+                            options.flag_bin.as_ref().map(|s| s.as_slice()),
                             options.arg_args.as_slice()).map_err(|err| {
         CliError::from_boxed(err, 101)
     }));
     match err {
         None => Ok(None),
         Some(err) => {
+            // The binary has already had its say on stderr, so an ordinary
+            // non-zero exit is only mirrored as cargo's own exit status.
             Err(match err.exit {
-                Some(ExitStatus(i)) => CliError::from_boxed(box err, i as uint),
+                Some(ExitStatus(i)) => CliError::new("", i as uint),
                 _ => CliError::from_boxed(box err, 101),
             })
         }
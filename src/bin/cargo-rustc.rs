@@ -1,6 +1,104 @@
+#![feature(phase)]
+
+extern crate serialize;
+#[phase(plugin, link)] extern crate log;
+
 extern crate cargo;
+extern crate docopt;
+#[phase(plugin)] extern crate docopt_macros;
+
+use std::os;
+use cargo::{execute_main_without_stdin};
+use cargo::ops;
+use cargo::ops::CompileOptions;
+use cargo::core::MultiShell;
+use cargo::util::{CliResult, CliError};
+use cargo::util::important_paths::{find_root_manifest_for_cwd};
+
+docopt!(Options, "
+Compile a local package and all of its dependencies, passing extra flags to
+the compiler invocation of the package itself
+
+Usage:
+    cargo-rustc [options] [--bin NAME] [--example NAME] [--] [<opts>...]
+
+Options:
+    -h, --help              Print this message
+    -j N, --jobs N          The number of jobs to run in parallel
+    --release               Build artifacts in release mode, with optimizations
+    --lib                   Build only this package's library
+    --bin NAME              Build only the specified binary
+    --example NAME          Build only the specified example
+    --target TRIPLE         Build for the target triple
+    --locked                Require Cargo.lock and its checksums to be up to date
+    --offline               Run without accessing the network
+    --frozen                Equivalent to specifying both --locked and --offline
+    --features FEATURES     Space-separated list of features to also build
+    --no-default-features   Do not build the `default` feature
+    --target-dir DIR        Directory for all generated artifacts
+    --manifest-path PATH    Path to the manifest to compile
+    -v, --verbose           Use verbose output
+    --color WHEN            Coloring: auto, always, never
+
+The trailing arguments are passed to rustc when compiling the selected target
+of the package, which has to be the only target selected. Dependencies are
+still compiled with the flags they would get from `cargo build`.
+",  flag_jobs: Option<uint>, flag_target: Option<String>,
+    flag_manifest_path: Option<String>, flag_bin: Option<String>,
+    flag_example: Option<String>, flag_target_dir: Option<String>,
+    flag_features: Option<String>)
 
 fn main() {
-    // Standalone cargo-rustc will go here
-    unimplemented!();
+    execute_main_without_stdin(execute, true);
+}
+
+fn execute(options: Options, shell: &mut MultiShell) -> CliResult<Option<()>> {
+    debug!("executing; cmd=cargo-rustc; args={}", os::args());
+    shell.set_verbose(options.flag_verbose);
+
+    let root = try!(find_root_manifest_for_cwd(options.flag_manifest_path));
+
+    // Examples are only built along with the tests, sharing their profile
+    // and dev-dependencies, so there is no optimized build of them.
+    let env = if options.flag_example.is_some() {
+        if options.flag_release {
+            return Err(CliError::new("examples are always built with the \
+                                      `test` profile, so `--example` can't \
+                                      be combined with `--release`", 1))
+        }
+        "test"
+    } else if options.flag_release {
+        "release"
+    } else {
+        "compile"
+    };
+
+    let targets: Vec<String> = options.flag_target.move_iter().collect();
+    let bins: Vec<String> = options.flag_bin.move_iter().collect();
+    let examples: Vec<String> = options.flag_example.move_iter().collect();
+    let target_dir = options.flag_target_dir.as_ref().map(|dir| {
+        Path::new(dir.as_slice())
+    });
+    let features = options.flag_features.as_ref().map(|s| {
+        s.as_slice().words().map(|f| f.to_string()).collect::<Vec<String>>()
+    }).unwrap_or(Vec::new());
+
+    let mut opts = CompileOptions {
+        jobs: options.flag_jobs,
+        targets: targets.as_slice(),
+        locked: options.flag_locked || options.flag_frozen,
+        offline: options.flag_offline || options.flag_frozen,
+        target_dir: target_dir.as_ref(),
+        lib: options.flag_lib,
+        bins: bins.as_slice(),
+        examples: examples.as_slice(),
+        features: features.as_slice(),
+        no_default_features: options.flag_no_default_features,
+        target_rustc_args: Some(options.arg_opts.as_slice()),
+        ..CompileOptions::new(env, shell)
+    };
+
+    ops::compile(&root, &mut opts).map(|_| None).map_err(|err| {
+        CliError::from_boxed(err, 101)
+    })
 }
@@ -5,13 +5,10 @@ extern crate cargo;
 extern crate docopt;
 #[phase(plugin)] extern crate docopt_macros;
 
-use std::io::process::ExitStatus;
-
 use cargo::ops;
 use cargo::{execute_main_without_stdin};
 use cargo::core::{MultiShell};
-use cargo::util;
-use cargo::util::{CliResult, CliError, CargoError};
+use cargo::util::{CliResult, CliError};
 use cargo::util::important_paths::{find_root_manifest_for_cwd};
 
 docopt!(Options, "
@@ -24,13 +21,23 @@ Options:
     -h, --help              Print this message
     -j N, --jobs N          The number of jobs to run in parallel
     -u, --update-remotes    Deprecated option, use `cargo update` instead
+    --locked                Require Cargo.lock and its checksums to be up to date
+    --offline               Run without accessing the network
+    --frozen                Equivalent to specifying both --locked and --offline
+    --target TRIPLE         Build and run the tests for the target triple
+    --no-run                Compile the tests, but don't run them
+    --features FEATURES     Space-separated list of features to also build
+    --no-default-features   Do not build the `default` feature
+    --target-dir DIR        Directory for all generated artifacts
     --manifest-path PATH    Path to the manifest to build tests for
     -v, --verbose           Use verbose output
+    --color WHEN            Coloring: auto, always, never
 
 All of the trailing arguments are passed to the test binaries generated for
 filtering tests and generally providing options configuring how they run.
 ",  flag_jobs: Option<uint>, flag_target: Option<String>,
-    flag_manifest_path: Option<String>)
+    flag_manifest_path: Option<String>,
+    flag_target_dir: Option<String>, flag_features: Option<String>)
 
 fn main() {
     execute_main_without_stdin(execute, true);
@@ -40,32 +47,33 @@ fn execute(options: Options, shell: &mut MultiShell) -> CliResult<Option<()>> {
     let root = try!(find_root_manifest_for_cwd(options.flag_manifest_path));
     shell.set_verbose(options.flag_verbose);
 
-    let mut compile_opts = ops::CompileOptions {
-        update: options.flag_update_remotes,
-        env: "test",
-        shell: shell,
-        jobs: options.flag_jobs,
-        target: None,
-    };
-
-    let test_executables = try!(ops::compile(&root,
-                                             &mut compile_opts).map_err(|err| {
-        CliError::from_boxed(err, 101)
-    }));
+    let target_dir = options.flag_target_dir.as_ref().map(|dir| {
+        Path::new(dir.as_slice())
+    });
+    let features = options.flag_features.as_ref().map(|s| {
+        s.as_slice().words().map(|f| f.to_string()).collect::<Vec<String>>()
+    }).unwrap_or(Vec::new());
 
-    let test_dir = root.dir_path().join("target").join("test");
+    let targets = options.flag_target.iter().map(|t| t.clone())
+                         .collect::<Vec<String>>();
 
-    for file in test_executables.iter() {
-        try!(util::process(test_dir.join(file.as_slice()))
-                  .args(options.arg_args.as_slice())
-                  .exec().map_err(|e| {
-            let exit_status = match e.exit {
-                Some(ExitStatus(i)) => i as uint,
-                _ => 1,
-            };
-            CliError::from_boxed(e.mark_human(), exit_status)
-        }));
-    }
+    let mut test_opts = ops::TestOptions {
+        compile_opts: ops::CompileOptions {
+            update: options.flag_update_remotes,
+            jobs: options.flag_jobs,
+            targets: targets.as_slice(),
+            locked: options.flag_locked || options.flag_frozen,
+            offline: options.flag_offline || options.flag_frozen,
+            target_dir: target_dir.as_ref(),
+            features: features.as_slice(),
+            no_default_features: options.flag_no_default_features,
+            ..ops::CompileOptions::new("test", shell)
+        },
+        args: options.arg_args.as_slice(),
+        no_run: options.flag_no_run,
+    };
 
-    Ok(None)
+    ops::run_tests(&root, &mut test_opts).map(|_| None).map_err(|err| {
+        CliError::from_boxed(err, 101)
+    })
 }
@@ -17,24 +17,33 @@ docopt!(Options, "
 Update dependencies as recorded in the local lock file.
 
 Usage:
-    cargo-update [options] [<name>]
+    cargo-update [options] [<spec>]
 
 Options:
-    -h, --help              Print this message
-    --manifest-path PATH    Path to the manifest to compile
-    -v, --verbose           Use verbose output
+    -h, --help                 Print this message
+    -p SPEC, --package SPEC    Package to update
+    --precise REV              Update the package to exactly this git revision
+    --manifest-path PATH       Path to the manifest to compile
+    -v, --verbose              Use verbose output
+    --color WHEN               Coloring: auto, always, never
 
 This command requires that a `Cargo.lock` already exists as generated by
 `cargo build` or related commands.
 
-If <name> is specified, then a conservative update of the lockfile will be
-performed. This means that only the dependency <name> (and all of its transitive
-dependencies) will be updated. All other dependencies will remain locked at
-their currently recorded versions.
+If a package is specified, either with -p or as <spec>, then a conservative
+update of the lockfile will be performed. This means that only that package
+(and all of its transitive dependencies) will be updated. All other
+dependencies will remain locked at their currently recorded versions. The
+package is specified as either `name` or `name:version`, the latter being
+needed when several versions of `name` are in the lockfile.
 
-If <name> is not specified, then all dependencies will be re-resolved and
+With --precise the package, which must come from a git repository, is pinned
+to the given revision instead of the latest one.
+
+If no package is specified, then all dependencies will be re-resolved and
 updated.
-",  flag_manifest_path: Option<String>, arg_name: Option<String>)
+",  flag_manifest_path: Option<String>, arg_spec: Option<String>,
+    flag_package: Option<String>, flag_precise: Option<String>)
 
 fn main() {
     execute_main_without_stdin(execute, false);
@@ -45,7 +54,13 @@ fn execute(options: Options, shell: &mut MultiShell) -> CliResult<Option<()>> {
     shell.set_verbose(options.flag_verbose);
     let root = try!(find_root_manifest_for_cwd(options.flag_manifest_path));
 
-    ops::update_lockfile(&root, shell, options.arg_name)
+    let spec = options.flag_package.or(options.arg_spec);
+    let mut update_opts = ops::UpdateOptions {
+        shell: shell,
+        to_update: spec.as_ref().map(|s| s.as_slice()),
+        precise: options.flag_precise.as_ref().map(|s| s.as_slice()),
+    };
+    ops::update_lockfile(&root, &mut update_opts)
         .map(|_| None).map_err(|err| CliError::from_boxed(err, 101))
 }
 
@@ -18,7 +18,7 @@ use docopt::FlagParser;
 use cargo::{execute_main_without_stdin, handle_error, shell};
 use cargo::core::MultiShell;
 use cargo::util::important_paths::find_project;
-use cargo::util::{CliError, CliResult, Require, config, human};
+use cargo::util::{CargoResult, CliError, CliResult, Require, config, human};
 
 fn main() {
     execute_main_without_stdin(execute, true)
@@ -110,13 +110,28 @@ fn execute(flags: Flags, shell: &mut MultiShell) -> CliResult<Option<()>> {
 }
 
 fn execute_subcommand(cmd: &str, is_help: bool, flags: &Flags, shell: &mut MultiShell) -> () {
-    match find_command(cmd) {
+    let mut args = flags.arg_args.clone();
+    let command = match find_command(cmd) {
+        Some(command) => Some(command),
+        None => match aliased_command(cmd) {
+            Ok(Some(alias)) => {
+                let mut alias = alias.move_iter();
+                let real = alias.next().unwrap();
+                args = alias.chain(args.move_iter()).collect();
+                find_command(real.as_slice())
+            }
+            Ok(None) => None,
+            Err(err) => return handle_error(CliError::from_boxed(err, 101), shell),
+        },
+    };
+
+    match command {
         Some(command) => {
             let mut command = Command::new(command);
             let command = if is_help {
                 command.arg("-h")
             } else {
-                command.args(flags.arg_args.as_slice())
+                command.args(args.as_slice())
             };
             let status = command
                 .stdin(InheritFd(0))
@@ -145,6 +160,34 @@ fn execute_subcommand(cmd: &str, is_help: bool, flags: &Flags, shell: &mut Multi
     }
 }
 
+/// Aliases which are available even without any `[alias]` configuration.
+static BUILTIN_ALIASES: &'static [(&'static str, &'static str)] = &[
+    ("ci", "build --locked --offline --deny-primary-warnings"),
+];
+
+/// Expands `cmd` into a command and its leading arguments if it's an alias,
+/// either one defined under `[alias]` in a `.cargo/config` or a built-in one.
+fn aliased_command(cmd: &str) -> CargoResult<Option<Vec<String>>> {
+    let configs = try!(config::all_configs(os::getcwd()));
+    let configured = match configs.find_equiv(&"alias") {
+        Some(aliases) => try!(aliases.table()).find_equiv(&cmd),
+        None => None,
+    };
+    let alias = match configured {
+        Some(alias) => try!(alias.string()),
+        None => match BUILTIN_ALIASES.iter().find(|&&(name, _)| name == cmd) {
+            Some(&(_, alias)) => alias,
+            None => return Ok(None),
+        },
+    };
+
+    let words: Vec<String> = alias.words().map(|s| s.to_string()).collect();
+    if words.is_empty() {
+        return Err(human(format!("the alias `{}` is empty", cmd)))
+    }
+    Ok(Some(words))
+}
+
 /// List all runnable commands. find_command should always succeed
 /// if given one of returned command.
 fn list_commands() -> TreeSet<String> {
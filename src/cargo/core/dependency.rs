@@ -1,4 +1,4 @@
-use core::{VersionReq,SourceId,Summary};
+use core::{VersionReq,SourceId,Summary,PackageId};
 use util::CargoResult;
 
 #[deriving(PartialEq,Clone,Show)]
@@ -7,7 +7,12 @@ pub struct Dependency {
     source_id: SourceId,
     req: VersionReq,
     transitive: bool,
+    build: bool,
     only_match_name: bool,
+    rename: Option<String>,
+    only_for_platform: Option<String>,
+    optional: bool,
+    features: Vec<String>,
 }
 
 impl Dependency {
@@ -23,7 +28,12 @@ impl Dependency {
             source_id: source_id.clone(),
             req: version,
             transitive: true,
+            build: false,
             only_match_name: false,
+            rename: None,
+            only_for_platform: None,
+            optional: false,
+            features: Vec::new(),
         })
     }
 
@@ -33,7 +43,12 @@ impl Dependency {
             source_id: source_id.clone(),
             req: VersionReq::any(),
             transitive: true,
+            build: false,
             only_match_name: true,
+            rename: None,
+            only_for_platform: None,
+            optional: false,
+            features: Vec::new(),
         }
     }
 
@@ -59,6 +74,78 @@ impl Dependency {
         self.transitive
     }
 
+    /// Makes the dependency only available to the build commands of the
+    /// package, compiled for the host rather than linked into its crates.
+    pub fn as_build(&self) -> Dependency {
+        let mut dep = self.clone();
+        dep.build = true;
+        dep
+    }
+
+    pub fn is_build(&self) -> bool {
+        self.build
+    }
+
+    /// Makes the dependency available to the crate under `name` instead of
+    /// the name of its library.
+    pub fn renamed(&self, name: &str) -> Dependency {
+        let mut dep = self.clone();
+        dep.rename = Some(name.to_string());
+        dep
+    }
+
+    pub fn get_rename(&self) -> Option<&str> {
+        self.rename.as_ref().map(|s| s.as_slice())
+    }
+
+    /// Restricts the dependency to builds for the target triple `platform`.
+    pub fn for_platform(&self, platform: &str) -> Dependency {
+        let mut dep = self.clone();
+        dep.only_for_platform = Some(platform.to_string());
+        dep
+    }
+
+    pub fn get_only_for_platform(&self) -> Option<&str> {
+        self.only_for_platform.as_ref().map(|s| s.as_slice())
+    }
+
+    /// Makes the dependency only built when a feature of the package
+    /// enables it.
+    pub fn as_optional(&self) -> Dependency {
+        let mut dep = self.clone();
+        dep.optional = true;
+        dep
+    }
+
+    pub fn is_optional(&self) -> bool {
+        self.optional
+    }
+
+    /// Enables `features` of the package depended upon.
+    pub fn with_features(&self, features: Vec<String>) -> Dependency {
+        let mut dep = self.clone();
+        dep.features = features;
+        dep
+    }
+
+    pub fn get_features(&self) -> &[String] {
+        self.features.as_slice()
+    }
+
+    /// The name the dependency is known by in the manifest depending on it,
+    /// which is also the name of the feature enabling it if it is optional.
+    pub fn get_local_name(&self) -> &str {
+        self.get_rename().unwrap_or(self.get_name())
+    }
+
+    /// Whether the dependency is needed when building for `platform`.
+    pub fn is_active_for_platform(&self, platform: &str) -> bool {
+        match self.only_for_platform {
+            Some(ref p) => p.as_slice() == platform,
+            None => true,
+        }
+    }
+
     pub fn matches(&self, sum: &Summary) -> bool {
         debug!("matches; self={}; summary={}", self, sum);
         debug!("         a={}; b={}", self.source_id, sum.get_source_id());
@@ -67,6 +154,15 @@ impl Dependency {
             (self.only_match_name || (self.req.matches(sum.get_version()) &&
                                       &self.source_id == sum.get_source_id()))
     }
+
+    /// Whether the package `id` satisfies this dependency, which unlike a
+    /// match on the name alone tells apart same-named packages from different
+    /// sources.
+    pub fn matches_id(&self, id: &PackageId) -> bool {
+        self.name.as_slice() == id.get_name() &&
+            (self.only_match_name || (self.req.matches(id.get_version()) &&
+                                      &self.source_id == id.get_source_id()))
+    }
 }
 
 #[deriving(PartialEq,Clone,Encodable)]
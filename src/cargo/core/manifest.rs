@@ -11,7 +11,7 @@ use core::{
 };
 use core::package_id::Metadata;
 use core::dependency::SerializedDependency;
-use util::{CargoResult, human};
+use util::{CargoResult, human, paths};
 
 #[deriving(PartialEq,Clone)]
 pub struct Manifest {
@@ -22,7 +22,15 @@ pub struct Manifest {
     doc_dir: Path,
     sources: Vec<SourceId>,
     build: Vec<String>,
+    build_env: Vec<String>,
+    build_dir: Option<Path>,
+    default_target: Option<String>,
+    exclude: Vec<String>,
+    include: Vec<String>,
+    replace: Vec<PackageId>,
     unused_keys: Vec<String>,
+    warnings: Vec<String>,
+    workspace_members: Vec<Path>,
 }
 
 impl Show for Manifest {
@@ -63,7 +71,7 @@ impl<E, S: Encoder<E>> Encodable<S, E> for Manifest {
     }
 }
 
-#[deriving(Show, Clone, PartialEq, Hash, Encodable)]
+#[deriving(Show, Clone, PartialEq, Eq, Hash, Encodable)]
 pub enum LibKind {
     Lib,
     Rlib,
@@ -97,17 +105,21 @@ impl LibKind {
     }
 }
 
-#[deriving(Show, Clone, Hash, PartialEq, Encodable)]
+#[deriving(Show, Clone, Hash, PartialEq, Eq, Encodable)]
 pub enum TargetKind {
     LibTarget(Vec<LibKind>),
-    BinTarget
+    BinTarget,
+    ExampleTarget,
 }
 
-#[deriving(Encodable, Decodable, Clone, Hash, PartialEq, Show)]
+#[deriving(Encodable, Decodable, Clone, Hash, PartialEq, Eq, Show)]
 pub struct Profile {
     env: String, // compile, test, dev, bench, etc.
     opt_level: uint,
     debug: bool,
+    debuginfo: bool,
+    lto: bool,
+    codegen_units: Option<uint>,
     test: bool,
     dest: Option<String>,
     plugin: bool,
@@ -119,6 +131,9 @@ impl Profile {
             env: "compile".to_string(), // run in the default environment only
             opt_level: 0,
             debug: true,
+            debuginfo: false,
+            lto: false,
+            codegen_units: None,
             test: false, // whether or not to pass --test
             dest: None,
             plugin: false,
@@ -130,6 +145,9 @@ impl Profile {
             env: "test".to_string(), // run in the default environment only
             opt_level: 0,
             debug: true,
+            debuginfo: false,
+            lto: false,
+            codegen_units: None,
             test: true, // whether or not to pass --test
             dest: Some("test".to_string()),
             plugin: false,
@@ -140,7 +158,12 @@ impl Profile {
         Profile {
             env: "bench".to_string(), // run in the default environment only
             opt_level: 3,
+            // Benchmarks measure what a release build would do, so debug
+            // assertions are compiled out just as they are there.
             debug: false,
+            debuginfo: false,
+            lto: false,
+            codegen_units: None,
             test: true, // whether or not to pass --test
             dest: Some("bench".to_string()),
             plugin: false,
@@ -152,6 +175,9 @@ impl Profile {
             env: "release".to_string(), // run in the default environment only
             opt_level: 3,
             debug: false,
+            debuginfo: false,
+            lto: false,
+            codegen_units: None,
             test: false, // whether or not to pass --test
             dest: Some("release".to_string()),
             plugin: false,
@@ -163,6 +189,9 @@ impl Profile {
             env: "doc".to_string(),
             opt_level: 0,
             debug: false,
+            debuginfo: false,
+            lto: false,
+            codegen_units: None,
             test: false,
             dest: Some("doc-build".to_string()),
             plugin: false,
@@ -181,6 +210,10 @@ impl Profile {
         self.test
     }
 
+    pub fn is_bench(&self) -> bool {
+        self.env.as_slice() == "bench"
+    }
+
     pub fn is_plugin(&self) -> bool {
         self.plugin
     }
@@ -193,6 +226,18 @@ impl Profile {
         self.debug
     }
 
+    pub fn get_debuginfo(&self) -> bool {
+        self.debuginfo
+    }
+
+    pub fn get_lto(&self) -> bool {
+        self.lto
+    }
+
+    pub fn get_codegen_units(&self) -> Option<uint> {
+        self.codegen_units
+    }
+
     pub fn get_env(&self) -> &str {
         self.env.as_slice()
     }
@@ -211,6 +256,21 @@ impl Profile {
         self
     }
 
+    pub fn debuginfo(mut self, debuginfo: bool) -> Profile {
+        self.debuginfo = debuginfo;
+        self
+    }
+
+    pub fn lto(mut self, lto: bool) -> Profile {
+        self.lto = lto;
+        self
+    }
+
+    pub fn codegen_units(mut self, units: Option<uint>) -> Profile {
+        self.codegen_units = units;
+        self
+    }
+
     pub fn test(mut self, test: bool) -> Profile {
         self.test = test;
         self
@@ -222,7 +282,7 @@ impl Profile {
     }
 }
 
-#[deriving(Clone, Hash, PartialEq)]
+#[deriving(Clone, Hash, PartialEq, Eq)]
 pub struct Target {
     kind: TargetKind,
     name: String,
@@ -244,7 +304,7 @@ impl<E, S: Encoder<E>> Encodable<S, E> for Target {
     fn encode(&self, s: &mut S) -> Result<(), E> {
         let kind = match self.kind {
             LibTarget(ref kinds) => kinds.iter().map(|k| k.crate_type()).collect(),
-            BinTarget => vec!("bin")
+            BinTarget | ExampleTarget => vec!("bin")
         };
 
         SerializedTarget {
@@ -268,7 +328,11 @@ impl Show for Target {
 impl Manifest {
     pub fn new(summary: &Summary, targets: &[Target],
                target_dir: &Path, doc_dir: &Path, sources: Vec<SourceId>,
-               build: Vec<String>) -> Manifest {
+               build: Vec<String>, build_env: Vec<String>,
+               build_dir: Option<Path>,
+               default_target: Option<String>,
+               exclude: Vec<String>, include: Vec<String>,
+               replace: Vec<PackageId>) -> Manifest {
         Manifest {
             summary: summary.clone(),
             authors: Vec::new(),
@@ -277,7 +341,15 @@ impl Manifest {
             doc_dir: doc_dir.clone(),
             sources: sources,
             build: build,
+            build_env: build_env,
+            build_dir: build_dir,
+            default_target: default_target,
+            exclude: exclude,
+            include: include,
+            replace: replace,
             unused_keys: Vec::new(),
+            warnings: Vec::new(),
+            workspace_members: Vec::new(),
         }
     }
 
@@ -325,6 +397,79 @@ impl Manifest {
         self.build.as_slice()
     }
 
+    /// Extra environment variables, beyond the usual compiler variables,
+    /// whose values the build command depends on.
+    pub fn get_build_env(&self) -> &[String] {
+        self.build_env.as_slice()
+    }
+
+    /// The directory, relative to the package root, whose contents the
+    /// build command depends on, if one was given in the manifest.
+    pub fn get_build_dir(&self) -> Option<&Path> {
+        self.build_dir.as_ref()
+    }
+
+    /// The target triple to build for when none is given on the command line.
+    pub fn get_default_target(&self) -> Option<&str> {
+        self.default_target.as_ref().map(|s| s.as_slice())
+    }
+
+    /// Globs of the files, relative to the package root, which are not part
+    /// of the package.
+    pub fn get_exclude(&self) -> &[String] {
+        self.exclude.as_slice()
+    }
+
+    /// Globs of the files, relative to the package root, which make up the
+    /// package. When given, `exclude` is ignored.
+    pub fn get_include(&self) -> &[String] {
+        self.include.as_slice()
+    }
+
+    /// Packages which, wherever they appear in the dependency graph, are
+    /// swapped for the package of the same name and version from the source
+    /// of the given id.
+    pub fn get_replace(&self) -> &[PackageId] {
+        self.replace.as_slice()
+    }
+
+    pub fn set_workspace_members(&mut self, members: Vec<Path>) {
+        self.workspace_members = members;
+    }
+
+    /// The directories, relative to the package root, of the other packages
+    /// in this package's `[workspace]`.
+    pub fn get_workspace_members(&self) -> &[Path] {
+        self.workspace_members.as_slice()
+    }
+
+    /// Returns whether the file at `path`, relative to the package root, is
+    /// part of the package according to its `include` and `exclude` lists.
+    ///
+    /// A glob naming a directory covers everything beneath it, and the
+    /// manifest itself always belongs to the package.
+    pub fn includes_file(&self, path: &Path) -> bool {
+        if path.as_str() == Some("Cargo.toml") { return true }
+
+        let components: Vec<&str> = path.str_components().map(|c| {
+            c.unwrap_or("")
+        }).collect();
+        let matches = |globs: &[String]| {
+            globs.iter().any(|glob| {
+                range(1, components.len() + 1).any(|i| {
+                    let prefix = components.slice_to(i).connect("/");
+                    paths::glob_matches(glob.as_slice(), prefix.as_slice())
+                })
+            })
+        };
+
+        if !self.include.is_empty() {
+            matches(self.include.as_slice())
+        } else {
+            !matches(self.exclude.as_slice())
+        }
+    }
+
     pub fn add_unused_key(&mut self, s: String) {
         self.unused_keys.push(s)
     }
@@ -332,6 +477,16 @@ impl Manifest {
     pub fn get_unused_keys(&self) -> &[String] {
         self.unused_keys.as_slice()
     }
+
+    pub fn add_warning(&mut self, s: String) {
+        self.warnings.push(s)
+    }
+
+    /// Warnings about deprecated parts of the manifest or package layout
+    /// which still work for now.
+    pub fn get_warnings(&self) -> &[String] {
+        self.warnings.as_slice()
+    }
 }
 
 impl Target {
@@ -367,7 +522,7 @@ impl Target {
 
     pub fn example_target(name: &str, src_path: &Path, profile: &Profile) -> Target {
         Target {
-            kind: BinTarget,
+            kind: ExampleTarget,
             name: name.to_string(),
             src_path: src_path.clone(),
             profile: profile.clone(),
@@ -416,9 +571,17 @@ impl Target {
         }
     }
 
+    /// Whether this target is an executable, which examples are as well.
     pub fn is_bin(&self) -> bool {
         match self.kind {
-            BinTarget => true,
+            BinTarget | ExampleTarget => true,
+            _ => false
+        }
+    }
+
+    pub fn is_example(&self) -> bool {
+        match self.kind {
+            ExampleTarget => true,
             _ => false
         }
     }
@@ -436,7 +599,7 @@ impl Target {
             LibTarget(ref kinds) => {
                 kinds.iter().map(|kind| kind.crate_type()).collect()
             },
-            BinTarget => vec!("bin")
+            BinTarget | ExampleTarget => vec!("bin")
         }
     }
 }
@@ -24,6 +24,7 @@ pub use self::source::{
     SourceMap,
     SourceSet,
     GitKind,
+    GitReference,
     PathKind,
     RegistryKind
 };
@@ -35,7 +36,8 @@ pub use self::summary::{
 pub use self::shell::{
     Shell,
     MultiShell,
-    ShellConfig
+    ShellConfig,
+    ColorConfig
 };
 
 pub use self::dependency::{
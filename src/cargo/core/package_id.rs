@@ -91,7 +91,7 @@ impl CargoError for PackageIdError {
     fn is_human(&self) -> bool { true }
 }
 
-#[deriving(PartialEq, Hash, Clone, Encodable)]
+#[deriving(PartialEq, Eq, Hash, Clone, Encodable)]
 pub struct Metadata {
     pub metadata: String,
     pub extra_filename: String
@@ -120,9 +120,14 @@ impl PackageId {
         &self.source_id
     }
 
+    /// The hash given to rustc as `-C metadata` and appended to the names of
+    /// this package's artifacts. It covers the whole id, source included, so
+    /// that same-named packages from different places never clash, and it is
+    /// the same hash naming the package's fingerprint and native directories.
+    /// The targets of a library further mix in the profile they are built
+    /// with.
     pub fn generate_metadata(&self) -> Metadata {
-        let metadata = short_hash(
-            &(self.name.as_slice(), self.version.to_string(), &self.source_id));
+        let metadata = short_hash(self);
         let extra_filename = format!("-{}", metadata);
 
         Metadata { metadata: metadata, extra_filename: extra_filename }
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use core::{Source, SourceId, SourceMap, Summary, Dependency, PackageId, Package};
 use util::{CargoResult, ChainError, Config, human, profile};
 
@@ -18,7 +20,12 @@ impl Registry for Vec<Summary> {
 pub struct PackageRegistry<'a> {
     sources: SourceMap,
     overrides: Vec<SourceId>,
-    config: &'a mut Config<'a>
+    replacements: Vec<PackageId>,
+    config: &'a mut Config<'a>,
+    /// Names of the packages already warned about being found more than once
+    warned: HashSet<String>,
+    /// Path overrides already warned about not satisfying a requirement
+    warned_overrides: HashSet<String>,
 }
 
 impl<'a> PackageRegistry<'a> {
@@ -26,7 +33,10 @@ impl<'a> PackageRegistry<'a> {
         PackageRegistry {
             sources: SourceMap::new(),
             overrides: vec!(),
-            config: config
+            replacements: vec!(),
+            config: config,
+            warned: HashSet::new(),
+            warned_overrides: HashSet::new(),
         }
     }
 
@@ -78,6 +88,17 @@ impl<'a> PackageRegistry<'a> {
         Ok(())
     }
 
+    /// Swaps each package named by `ids` for the one of the same name and
+    /// version in the source of its id. Unlike `add_overrides`, packages of
+    /// other names or versions from those sources are left alone.
+    pub fn add_replacements(&mut self, ids: Vec<PackageId>) -> CargoResult<()> {
+        for id in ids.iter() {
+            try!(self.ensure_loaded(id.get_source_id()));
+        }
+        self.replacements.push_all_move(ids);
+        Ok(())
+    }
+
     fn load(&mut self, source_id: &SourceId, override: bool) -> CargoResult<()> {
         (|| {
             let mut source = source_id.load(self.config);
@@ -98,16 +119,95 @@ impl<'a> PackageRegistry<'a> {
         }).chain_error(|| human(format!("Unable to update {}", source_id)))
     }
 
+    /// Finds the packages in the override paths which can stand in for
+    /// `dep`. Packages of the right name but the wrong version are skipped
+    /// with a warning, and their descriptions are returned as well to explain
+    /// a failure to find `dep` elsewhere.
     fn query_overrides(&mut self, dep: &Dependency)
-                       -> CargoResult<Vec<Summary>> {
+                       -> CargoResult<(Vec<Summary>, Vec<String>)> {
         let mut ret = Vec::new();
         for s in self.overrides.iter() {
             let src = self.sources.get_mut(s).unwrap();
             let dep = Dependency::new_override(dep.get_name(), s);
             ret.push_all_move(try!(src.query(&dep)));
         }
+
+        let (matching, mismatched) = ret.partition(|s| {
+            dep.get_version_req().matches(s.get_version())
+        });
+        let mismatched = mismatched.iter().map(|s| {
+            format!("path override for `{}` found at {} but its version {} \
+                     does not satisfy requirement {}", dep.get_name(),
+                    s.get_source_id(), s.get_version(), dep.get_version_req())
+        }).collect::<Vec<String>>();
+        for msg in mismatched.iter() {
+            if self.warned_overrides.insert(msg.clone()) {
+                try!(self.config.shell().warn(msg.as_slice()));
+            }
+        }
+        Ok((matching, mismatched))
+    }
+
+    fn query_replacements(&mut self, dep: &Dependency)
+                          -> CargoResult<Vec<Summary>> {
+        let mut ret = Vec::new();
+        for id in self.replacements.iter() {
+            if id.get_name() != dep.get_name() ||
+               !dep.get_version_req().matches(id.get_version()) {
+                continue
+            }
+            let src = self.sources.get_mut(id.get_source_id()).unwrap();
+            let replacement = Dependency::new_override(id.get_name(),
+                                                       id.get_source_id());
+            let found = try!(src.query(&replacement));
+            match found.move_iter().find(|s| s.get_package_id() == id) {
+                Some(summary) => ret.push(summary),
+                None => return Err(human(format!("no package `{}` found at \
+                                                  `{}` to replace it with",
+                                                 id, id.get_source_id()))),
+            }
+        }
         Ok(ret)
     }
+
+    /// Settles on one of `summaries` when more than one location provides a
+    /// package named `dep`: the highest version wins, with a warning, while
+    /// the same version at two locations is an error.
+    fn pick_one(&mut self, dep: &Dependency,
+                summaries: Vec<Summary>) -> CargoResult<Vec<Summary>> {
+        if summaries.len() < 2 { return Ok(summaries) }
+
+        let mut best = 0;
+        for (i, s) in summaries.iter().enumerate() {
+            if s.get_version() > summaries[best].get_version() { best = i }
+        }
+        let highest = &summaries[best];
+        match summaries.iter().enumerate().find(|&(i, s)| {
+            i != best && s.get_version() == highest.get_version()
+        }) {
+            Some((_, other)) => {
+                return Err(human(format!("Could not choose a location for \
+                                          `{} v{}`, the same version is \
+                                          provided by more than \
+                                          one.\n  {}\n  {}",
+                                         dep.get_name(), highest.get_version(),
+                                         highest.get_source_id(),
+                                         other.get_source_id())))
+            }
+            None => {}
+        }
+
+        if self.warned.insert(dep.get_name().to_string()) {
+            let versions = summaries.iter().map(|s| {
+                format!("v{} ({})", s.get_version(), s.get_source_id())
+            }).collect::<Vec<String>>();
+            try!(self.config.shell().warn(format!(
+                "found more than one `{}` package: {}; using the highest \
+                 version, v{}", dep.get_name(), versions.connect(", "),
+                highest.get_version())));
+        }
+        Ok(vec![highest.clone()])
+    }
 }
 
 fn dedup(ids: Vec<SourceId>) -> Vec<SourceId> {
@@ -123,19 +223,29 @@ fn dedup(ids: Vec<SourceId>) -> Vec<SourceId> {
 
 impl<'a> Registry for PackageRegistry<'a> {
     fn query(&mut self, dep: &Dependency) -> CargoResult<Vec<Summary>> {
-        let overrides = try!(self.query_overrides(dep));
+        let replacements = try!(self.query_replacements(dep));
+        if replacements.len() > 0 { return Ok(replacements) }
+
+        let (overrides, mismatched) = try!(self.query_overrides(dep));
 
-        if overrides.len() == 0 {
+        let ret = if overrides.len() == 0 {
             // Ensure the requested source_id is loaded
             try!(self.ensure_loaded(dep.get_source_id()));
             let mut ret = Vec::new();
             for src in self.sources.sources_mut() {
                 ret.push_all_move(try!(src.query(dep)));
             }
-            Ok(ret)
+            ret
         } else {
-            Ok(overrides)
+            overrides
+        };
+        if ret.is_empty() && !mismatched.is_empty() {
+            return Err(human(mismatched.connect("\n"))).chain_error(|| {
+                human(format!("Could not find `{}` satisfying requirement {}.",
+                              dep.get_name(), dep.get_version_req()))
+            })
         }
+        self.pick_one(dep, ret)
     }
 }
 
@@ -1,5 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, TreeMap};
 use std::fmt;
+use std::mem;
 
 use serialize::{Encodable, Encoder, Decodable, Decoder};
 use util::graph::{Nodes,Edges};
@@ -9,22 +10,42 @@ use core::{
     PackageId,
     Registry,
     SourceId,
+    Summary,
 };
 
 use semver;
 
 use util::{CargoResult, Graph, human, internal};
 
-#[deriving(PartialEq, Eq)]
+/// Extra key/value pairs recorded in the `[metadata]` section of a lockfile.
+pub type Metadata = TreeMap<String, String>;
+
 pub struct Resolve {
     graph: Graph<PackageId>,
-    root: PackageId
+    root: PackageId,
+    metadata: Option<Metadata>,
+    // The features activated for each package, which aren't recorded in the
+    // lockfile and so don't take part in comparing two resolves.
+    features: HashMap<PackageId, HashSet<String>>,
+    // Packages whose consumers asked for disjoint sets of features, which are
+    // nonetheless unified into one build.
+    disjoint_features: Vec<String>,
+}
+
+impl PartialEq for Resolve {
+    fn eq(&self, other: &Resolve) -> bool {
+        self.graph == other.graph && self.root == other.root &&
+            self.metadata == other.metadata
+    }
 }
 
+impl Eq for Resolve {}
+
 #[deriving(Encodable, Decodable, Show)]
 pub struct EncodableResolve {
     package: Option<Vec<EncodableDependency>>,
-    root: EncodableDependency
+    root: EncodableDependency,
+    metadata: Option<Metadata>,
 }
 
 impl EncodableResolve {
@@ -43,7 +64,13 @@ impl EncodableResolve {
         }
 
         let root = self.root.to_package_id(default);
-        Ok(Resolve { graph: g, root: try!(root) })
+        Ok(Resolve {
+            graph: g,
+            root: try!(root),
+            metadata: self.metadata.clone(),
+            features: HashMap::new(),
+            disjoint_features: Vec::new(),
+        })
     }
 }
 
@@ -149,7 +176,8 @@ impl<E, S: Encoder<E>> Encodable<S, E> for Resolve {
 
         EncodableResolve {
             package: Some(encodable),
-            root: encodable_resolve_node(&self.root, &self.root, &self.graph)
+            root: encodable_resolve_node(&self.root, &self.root, &self.graph),
+            metadata: self.metadata.clone(),
         }.encode(s)
     }
 }
@@ -193,7 +221,45 @@ fn encodable_package_id(id: &PackageId, root: &PackageId) -> EncodablePackageId
 
 impl Resolve {
     fn new(root: PackageId) -> Resolve {
-        Resolve { graph: Graph::new(), root: root }
+        Resolve {
+            graph: Graph::new(),
+            root: root,
+            metadata: None,
+            features: HashMap::new(),
+            disjoint_features: Vec::new(),
+        }
+    }
+
+    /// Returns the features activated for `pkg`, including the names of the
+    /// optional dependencies which were enabled.
+    pub fn features(&self, pkg: &PackageId) -> Option<&HashSet<String>> {
+        self.features.find(pkg)
+    }
+
+    /// Describes each package which two of its consumers asked for disjoint
+    /// features of. Both consumers are built against a single instance of it
+    /// with all of those features enabled.
+    pub fn disjoint_features(&self) -> &[String] {
+        self.disjoint_features.as_slice()
+    }
+
+    pub fn get_metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Returns the checksum recorded for `pkg`, if any.
+    pub fn checksum(&self, pkg: &PackageId) -> Option<&str> {
+        self.metadata.as_ref().and_then(|m| {
+            m.find(&checksum_key(pkg)).map(|s| s.as_slice())
+        })
+    }
+
+    /// Records the checksum of `pkg` to be emitted into the lockfile.
+    pub fn set_checksum(&mut self, pkg: &PackageId, checksum: String) {
+        if self.metadata.is_none() {
+            self.metadata = Some(TreeMap::new());
+        }
+        self.metadata.as_mut().unwrap().insert(checksum_key(pkg), checksum);
     }
 
     pub fn iter(&self) -> Nodes<PackageId> {
@@ -203,6 +269,55 @@ impl Resolve {
     pub fn deps(&self, pkg: &PackageId) -> Option<Edges<PackageId>> {
         self.graph.edges(pkg)
     }
+
+    /// Removes the edge from `pkg` to `dep`, such as one only added so that
+    /// the members of a workspace are resolved along with its root.
+    pub fn unlink(&mut self, pkg: &PackageId, dep: &PackageId) {
+        self.graph.unlink(pkg, dep)
+    }
+
+    /// Finds the package described by `spec`, which is either a package name
+    /// or `name:version`.
+    pub fn query(&self, spec: &str) -> CargoResult<&PackageId> {
+        let mut parts = spec.splitn(':', 1);
+        let name = parts.next().unwrap();
+        let version = parts.next();
+
+        let mut matches = self.iter().filter(|p| {
+            p.get_name() == name && match version {
+                Some(v) => p.get_version().to_string().as_slice() == v,
+                None => true,
+            }
+        }).collect::<Vec<&PackageId>>();
+
+        match matches.len() {
+            1 => Ok(matches.pop().unwrap()),
+            0 => {
+                let mut names = self.iter().map(|p| {
+                    p.get_name().to_string()
+                }).collect::<Vec<String>>();
+                names.sort();
+                names.dedup();
+                Err(human(format!("package `{}` is not in the lockfile, valid \
+                                   package names are: {}", spec,
+                                  names.connect(", "))))
+            }
+            _ => {
+                let ids = matches.iter().map(|p| {
+                    format!("{}:{}", p.get_name(), p.get_version())
+                }).collect::<Vec<String>>();
+                Err(human(format!("package `{}` is ambiguous, specify one of: \
+                                   {}", spec, ids.connect(", "))))
+            }
+        }
+    }
+}
+
+// The precise revision of a source is deliberately left out of the key as a
+// `#` can't appear in a bare TOML key.
+fn checksum_key(pkg: &PackageId) -> String {
+    format!("checksum {} {} ({})", pkg.get_name(), pkg.get_version(),
+            pkg.get_source_id().get_location())
 }
 
 impl fmt::Show for Resolve {
@@ -218,29 +333,143 @@ struct Context<'a, R> {
     // Eventually, we will have smarter logic for checking for conflicts in the
     // resolve, but without the registry, conflicts should not exist in
     // practice, so this is just a sanity check.
-    seen: HashMap<(String, SourceId), semver::Version>
+    seen: HashMap<(String, SourceId), semver::Version>,
+
+    // Which package asked for each package of `seen`, with which version
+    // requirement, in order to explain conflicting requirements.
+    requested: HashMap<(String, SourceId), Vec<String>>,
+
+    // The chain of packages from the root down to the one whose dependencies
+    // are currently being resolved, used to detect dependency cycles. It
+    // starts over at each dev-dependency, as those are only needed by tests
+    // which may well be built against the package depending on them.
+    stack: Vec<PackageId>,
+
+    // The packages which asked for features of each package, along with the
+    // features they asked for, to point out consumers which disagree.
+    consumers: HashMap<PackageId, Vec<(PackageId, Vec<String>)>>,
+
+    // When set, dependencies which only apply to other target triples are
+    // left out of the resolve.
+    platform: Option<&'a str>,
 }
 
 impl<'a, R: Registry> Context<'a, R> {
-    fn new(registry: &'a mut R, root: PackageId) -> Context<'a, R> {
+    fn new(registry: &'a mut R, root: PackageId,
+           platform: Option<&'a str>) -> Context<'a, R> {
         Context {
             registry: registry,
-            resolve: Resolve::new(root),
-            seen: HashMap::new()
+            resolve: Resolve::new(root.clone()),
+            seen: HashMap::new(),
+            requested: HashMap::new(),
+            stack: vec![root],
+            consumers: HashMap::new(),
+            platform: platform,
         }
     }
 }
 
-pub fn resolve<R: Registry>(root: &PackageId, deps: &[Dependency],
+/// Resolves the dependencies of `root`, including its dev-dependencies, for
+/// every platform. Optional dependencies are only included if one of
+/// `features` enables them.
+pub fn resolve<R: Registry>(root: &Summary, features: &[String],
                             registry: &mut R) -> CargoResult<Resolve> {
-    log!(5, "resolve; deps={}", deps);
+    resolve_with(root, features, true, registry, None)
+}
+
+/// Resolves the dependencies of `root` as they are recorded in the lockfile.
+///
+/// Every feature and optional dependency of `root` is enabled, so that the
+/// lockfile is the same whichever features a build later asks for.
+pub fn resolve_lockfile<R: Registry>(root: &Summary, registry: &mut R)
+                                     -> CargoResult<Resolve> {
+    let mut features: Vec<String> = root.get_features().keys().map(|f| {
+        f.clone()
+    }).collect();
+    features.extend(root.get_dependencies().iter().filter(|d| {
+        d.is_optional()
+    }).map(|d| d.get_local_name().to_string()));
+    resolve(root, features.as_slice(), registry)
+}
 
-    let mut context = Context::new(registry, root.clone());
-    try!(resolve_deps(root, deps, &mut context));
+/// Resolves the dependencies of `root` leaving out any dependency which
+/// doesn't apply when building for the target triple `platform`, as well as
+/// dev-dependencies unless `dev_deps` is set.
+pub fn resolve_for_platform<R: Registry>(root: &Summary, features: &[String],
+                                         dev_deps: bool, registry: &mut R,
+                                         platform: &str)
+                                         -> CargoResult<Resolve> {
+    resolve_with(root, features, dev_deps, registry, Some(platform))
+}
+
+fn resolve_with<R: Registry>(root: &Summary, features: &[String],
+                             dev_deps: bool, registry: &mut R,
+                             platform: Option<&str>)
+                             -> CargoResult<Resolve> {
+    log!(5, "resolve; root={}; features={}; platform={}", root, features,
+         platform);
+
+    let id = root.get_package_id();
+    let mut context = Context::new(registry, id.clone(), platform);
+    // The root's `default` feature is up to the caller, which may have been
+    // asked to leave it out.
+    let deps = try!(activate(&mut context.resolve, root, features, dev_deps,
+                             false));
+    try!(resolve_deps(id, deps.as_slice(), &mut context));
     log!(5, "  result={}", context.resolve);
     Ok(context.resolve)
 }
 
+/// Activates `features` of the package `summary`, returning the dependencies
+/// which now need to be resolved: all of the required ones along with the
+/// enabled optional ones the first time a package is activated, and
+/// afterwards only optional ones which weren't enabled before.
+///
+/// With `default` set, the package's `default` feature is activated as well
+/// the first time around.
+fn activate(resolve: &mut Resolve, summary: &Summary, features: &[String],
+            dev_deps: bool, default: bool) -> CargoResult<Vec<Dependency>> {
+    let id = summary.get_package_id();
+    let first = !resolve.features.contains_key(id);
+    let mut active = resolve.features.pop(id).unwrap_or(HashSet::new());
+    let before = active.clone();
+
+    let mut pending: Vec<&str> = features.iter().map(|f| f.as_slice()).collect();
+    if first && default &&
+       summary.get_features().find_equiv(&"default").is_some() {
+        pending.push("default");
+    }
+    loop {
+        let name = match pending.pop() { Some(name) => name, None => break };
+        if !active.insert(name.to_string()) { continue }
+        match summary.get_features().find_equiv(&name) {
+            Some(enables) => {
+                pending.extend(enables.iter().map(|f| f.as_slice()));
+            }
+            None if summary.get_dependencies().iter().any(|d| {
+                d.is_optional() && d.get_local_name() == name
+            }) => {}
+            None => {
+                return Err(human(format!("package `{} v{}` does not have the \
+                                          feature `{}`", id.get_name(),
+                                         id.get_version(), name)))
+            }
+        }
+    }
+
+    let deps = summary.get_dependencies().iter().filter(|d| {
+        (dev_deps || d.is_transitive()) && if d.is_optional() {
+            let name = d.get_local_name().to_string();
+            active.contains(&name) && (first || !before.contains(&name))
+        } else {
+            first
+        }
+    }).map(|d| d.clone()).collect();
+
+    resolve.features.insert(id.clone(), active);
+    Ok(deps)
+}
+
 fn resolve_deps<'a, R: Registry>(parent: &PackageId,
                                  deps: &[Dependency],
                                  ctx: &mut Context<'a, R>)
@@ -250,6 +479,11 @@ fn resolve_deps<'a, R: Registry>(parent: &PackageId,
     }
 
     for dep in deps.iter() {
+        match ctx.platform {
+            Some(platform) if !dep.is_active_for_platform(platform) => continue,
+            _ => {}
+        }
+
         let pkgs = try!(ctx.registry.query(dep));
 
         if pkgs.is_empty() {
@@ -268,54 +502,138 @@ fn resolve_deps<'a, R: Registry>(parent: &PackageId,
         }
 
         let summary = &pkgs[0];
+        match ctx.stack.iter().position(|id| id == summary.get_package_id()) {
+            Some(i) => {
+                let cycle = ctx.stack.slice_from(i).iter()
+                               .chain(Some(summary.get_package_id()).move_iter())
+                               .map(|id| format!("{} v{}", id.get_name(),
+                                                 id.get_version()))
+                               .collect::<Vec<String>>();
+                return Err(human(format!("cyclic package dependency: {}",
+                                         cycle.connect(" -> "))))
+            }
+            None => {}
+        }
+
         let name = summary.get_name().to_string();
         let source_id = summary.get_source_id().clone();
         let version = summary.get_version().clone();
 
         ctx.resolve.graph.link(parent.clone(), summary.get_package_id().clone());
 
-        let found = {
-            let found = ctx.seen.find(&(name.clone(), source_id.clone()));
-
-            if found.is_some() {
-                if found == Some(&version) { continue; }
-                return Err(human(format!("Cargo found multiple copies of {} in {}. This \
-                                        is not currently supported",
-                                        summary.get_name(), summary.get_source_id())));
-            } else {
-                false
+        let key = (name.clone(), source_id.clone());
+        let request = format!("Required by `{} v{}`: {} (found v{})",
+                              parent.get_name(), parent.get_version(),
+                              dep.get_version_req(), version);
+        if !ctx.requested.contains_key(&key) {
+            ctx.requested.insert(key.clone(), Vec::new());
+        }
+        ctx.requested.get_mut(&key).push(request);
+
+        let found = match ctx.seen.find(&key) {
+            Some(seen) if *seen != version => {
+                let mut requests = ctx.requested.get(&key).clone();
+                requests.sort();
+                return Err(human(format!("No single version of `{}` \
+                                          satisfies all of its \
+                                          requirements.\n{}", name,
+                                         requests.connect("\n"))))
             }
+            Some(..) => true,
+            None => false,
         };
 
         if !found {
-            ctx.seen.insert((name, source_id), version);
+            ctx.seen.insert(key, version);
+            ctx.resolve.graph.add(summary.get_package_id().clone(), []);
         }
 
-        ctx.resolve.graph.add(summary.get_package_id().clone(), []);
+        check_disjoint_features(parent, summary.get_package_id(), dep, ctx);
 
-        let deps: Vec<Dependency> = summary.get_dependencies().iter()
-            .filter(|d| d.is_transitive())
-            .map(|d| d.clone())
-            .collect();
+        // A package which was already resolved may still need the optional
+        // dependencies enabled by the features this dependency asks for.
+        let deps = try!(activate(&mut ctx.resolve, summary, dep.get_features(),
+                                 false, true));
 
+        let stack = if dep.is_transitive() {
+            None
+        } else {
+            Some(mem::replace(&mut ctx.stack, Vec::new()))
+        };
+        ctx.stack.push(summary.get_package_id().clone());
         try!(resolve_deps(summary.get_package_id(), deps.as_slice(), ctx));
+        ctx.stack.pop();
+        match stack {
+            Some(stack) => ctx.stack = stack,
+            None => {}
+        }
     }
 
     Ok(())
 }
 
+/// Records `parent` as asking for the features of `dep` from `id`, noting
+/// when a previous consumer asked for features none of which `parent` wants.
+fn check_disjoint_features<R>(parent: &PackageId, id: &PackageId,
+                              dep: &Dependency, ctx: &mut Context<R>) {
+    let features = dep.get_features();
+    if features.is_empty() { return }
+
+    if !ctx.consumers.contains_key(id) {
+        ctx.consumers.insert(id.clone(), Vec::new());
+    }
+    let consumers = ctx.consumers.get_mut(id);
+    for &(ref other, ref theirs) in consumers.iter() {
+        if other == parent || features.iter().any(|f| theirs.contains(f)) {
+            continue
+        }
+        let mut pair = vec![describe(parent, features),
+                            describe(other, theirs.as_slice())];
+        pair.sort();
+        ctx.resolve.disjoint_features.push(format!(
+            "{} and {} ask for disjoint features of `{} v{}`, which is built \
+             once with all of them enabled", pair[0], pair[1], id.get_name(),
+            id.get_version()));
+    }
+    consumers.push((parent.clone(), Vec::from_slice(features)));
+
+    fn describe(id: &PackageId, features: &[String]) -> String {
+        let mut features = Vec::from_slice(features);
+        features.sort();
+        format!("`{} v{}` ({})", id.get_name(), id.get_version(),
+                features.connect(", "))
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use std::collections::HashMap;
+
     use hamcrest::{assert_that, equal_to, contains};
 
-    use core::source::{SourceId, RegistryKind, GitKind, Location, Remote};
+    use core::source::{SourceId, RegistryKind, GitKind, GitReference, Location,
+                       Remote};
     use core::{Dependency, PackageId, Summary, Registry};
     use util::{CargoResult, ToUrl};
 
     fn resolve<R: Registry>(pkg: &PackageId, deps: &[Dependency],
                             registry: &mut R)
                             -> CargoResult<Vec<PackageId>> {
-        Ok(try!(super::resolve(pkg, deps, registry)).iter().map(|p| p.clone()).collect())
+        resolve_features(&Summary::new(pkg, deps), [], registry)
+    }
+
+    fn resolve_features<R: Registry>(root: &Summary, features: &[String],
+                                     registry: &mut R)
+                                     -> CargoResult<Vec<PackageId>> {
+        let res = try!(super::resolve(root, features, registry));
+        Ok(res.iter().map(|p| p.clone()).collect())
+    }
+
+    fn feature(name: &str, enables: &[&str]) -> HashMap<String, Vec<String>> {
+        let mut map = HashMap::new();
+        map.insert(name.to_string(),
+                   enables.iter().map(|s| s.to_string()).collect());
+        map
     }
 
     trait ToDep {
@@ -365,7 +683,7 @@ mod test {
 
     fn pkg_id_loc(name: &str, loc: &str) -> PackageId {
         let remote = Location::parse(loc);
-        let source_id = SourceId::new(GitKind("master".to_string()),
+        let source_id = SourceId::new(GitKind(GitReference::master()),
                                       remote.unwrap());
 
         PackageId::new(name, "1.0.0", &source_id).unwrap()
@@ -383,7 +701,8 @@ mod test {
 
     fn dep_loc(name: &str, location: &str) -> Dependency {
         let url = location.to_url().unwrap();
-        let source_id = SourceId::new(GitKind("master".to_string()), Remote(url));
+        let source_id = SourceId::new(GitKind(GitReference::master()),
+                                      Remote(url));
         Dependency::parse(name, Some("1.0.0"), &source_id).unwrap()
     }
 
@@ -481,4 +800,117 @@ mod test {
 
         assert_that(&res, contains(names(["root", "foo", "bar", "baz"])));
     }
+
+    #[test]
+    pub fn test_resolving_conflicting_requirements() {
+        let baz = |version: &str| {
+            Summary::new(&PackageId::new("baz", version, &registry_loc())
+                                    .unwrap(), [])
+        };
+        let baz_dep = |req: &str| {
+            Dependency::parse("baz", Some(req), &registry_loc()).unwrap()
+        };
+        let mut reg = registry(vec!(
+            pkg!("foo" => baz_dep("=0.5.0")),
+            pkg!("bar" => baz_dep("=0.6.0")),
+            baz("0.5.0"),
+            baz("0.6.0")
+        ));
+
+        let res = resolve(&pkg_id("root"), [dep("foo"), dep("bar")], &mut reg);
+
+        let err = res.unwrap_err().to_string();
+        let lines: Vec<&str> = err.as_slice().lines().collect();
+        assert_that(lines.len(), equal_to(3u));
+        assert_that(lines[0], equal_to("No single version of `baz` satisfies \
+                                        all of its requirements."));
+        assert!(lines[1].starts_with("Required by `bar v1.0.0`: "), "{}", err);
+        assert!(lines[1].ends_with(" (found v0.6.0)"), "{}", err);
+        assert!(lines[2].starts_with("Required by `foo v1.0.0`: "), "{}", err);
+        assert!(lines[2].ends_with(" (found v0.5.0)"), "{}", err);
+    }
+
+    #[test]
+    pub fn test_resolving_cycle() {
+        let mut reg = registry(vec!(
+            pkg!("foo" => "bar"),
+            pkg!("bar" => "baz"),
+            pkg!("baz" => "foo")
+        ));
+
+        let res = super::resolve(&Summary::new(&pkg_id("root"), [dep("foo")]),
+                                 [], &mut reg);
+
+        assert_that(res.unwrap_err().to_string(), equal_to(
+            "cyclic package dependency: foo v1.0.0 -> bar v1.0.0 -> \
+             baz v1.0.0 -> foo v1.0.0".to_string()));
+    }
+
+    #[test]
+    pub fn test_resolving_cycle_through_dev_dep() {
+        let mut reg = registry(vec!(
+            pkg!("root"),
+            pkg!("foo" => "root")
+        ));
+
+        let root = Summary::new(&pkg_id("root"), [dep("foo").as_dev()]);
+        let res = super::resolve(&root, [], &mut reg).unwrap();
+
+        assert_that(&res, contains(names(["root", "foo"])));
+    }
+
+    #[test]
+    pub fn test_resolving_optional_dep_without_feature() {
+        let mut reg = registry(vec!(pkg!("foo"), pkg!("bar")));
+        let root = Summary::new(&pkg_id("root"),
+                                [dep("foo"), dep("bar").as_optional()]);
+        let res = resolve_features(&root, [], &mut reg).unwrap();
+
+        assert_that(&res, contains(names(["root", "foo"])).exactly());
+    }
+
+    #[test]
+    pub fn test_resolving_optional_dep_enabled_by_feature() {
+        let mut reg = registry(vec!(pkg!("foo"), pkg!("bar")));
+        let root = Summary::new(&pkg_id("root"), [dep("bar").as_optional()])
+                           .with_features(feature("fast", ["bar"]))
+                           .unwrap();
+        let res = resolve_features(&root, ["fast".to_string()], &mut reg);
+
+        assert_that(&res.unwrap(), contains(names(["root", "bar"])).exactly());
+    }
+
+    #[test]
+    pub fn test_resolving_optional_dep_of_dep() {
+        let foo = Summary::new(&pkg_id("foo"), [dep("bar").as_optional()]);
+        let mut reg = registry(vec!(foo, pkg!("bar")));
+        let root = Summary::new(&pkg_id("root"), [
+            dep("foo").with_features(vec!["bar".to_string()])
+        ]);
+        let res = resolve_features(&root, [], &mut reg).unwrap();
+
+        assert_that(&res, contains(names(["root", "foo", "bar"])).exactly());
+    }
+
+    #[test]
+    pub fn test_resolving_default_feature_of_dep() {
+        let foo = Summary::new(&pkg_id("foo"), [dep("bar").as_optional()])
+                          .with_features(feature("default", ["bar"]))
+                          .unwrap();
+        let mut reg = registry(vec!(foo, pkg!("bar")));
+        let root = Summary::new(&pkg_id("root"), [dep("foo")]);
+        let res = resolve_features(&root, [], &mut reg).unwrap();
+
+        assert_that(&res, contains(names(["root", "foo", "bar"])).exactly());
+    }
+
+    #[test]
+    pub fn test_resolving_unknown_feature() {
+        let mut reg = registry(vec!(pkg!("foo")));
+        let root = Summary::new(&pkg_id("root"), [dep("foo")]);
+        let res = super::resolve(&root, ["foo".to_string()], &mut reg);
+
+        assert_that(res.unwrap_err().to_string(), equal_to(
+            "package `root v1.0.0` does not have the feature `foo`".to_string()));
+    }
 }
@@ -4,9 +4,42 @@ use term::color::{Color, BLACK, RED, GREEN, YELLOW};
 use term::attr::{Attr, Bold};
 use std::io::{IoResult, stderr};
 use std::fmt::Show;
+use std::from_str::FromStr;
+
+/// When to color output, as chosen with `--color`.
+#[deriving(Clone, PartialEq, Show)]
+pub enum ColorConfig {
+    /// Color output only if it goes to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorConfig {
+    /// Returns whether output should be colored, given whether it goes to a
+    /// terminal.
+    pub fn use_color(&self, tty: bool) -> bool {
+        match *self {
+            Auto => tty,
+            Always => true,
+            Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorConfig {
+    fn from_str(s: &str) -> Option<ColorConfig> {
+        match s {
+            "auto" => Some(Auto),
+            "always" => Some(Always),
+            "never" => Some(Never),
+            _ => None,
+        }
+    }
+}
 
 pub struct ShellConfig {
-    pub color: bool,
+    pub color: ColorConfig,
     pub verbose: bool,
     pub tty: bool
 }
@@ -71,13 +104,17 @@ impl MultiShell {
     pub fn set_verbose(&mut self, verbose: bool) {
         self.verbose = verbose;
     }
+
+    pub fn get_verbose(&self) -> bool {
+        self.verbose
+    }
 }
 
 pub type ShellCallback<'a> = |&mut Shell|:'a -> IoResult<()>;
 
 impl Shell {
     pub fn create(out: Box<Writer>, config: ShellConfig) -> Shell {
-        if config.tty && config.color {
+        if config.color.use_color(config.tty) {
             let term: Option<term::TerminfoTerminal<Box<Writer>>> = Terminal::new(out);
             term.map(|t| Shell {
                 terminal: Color(box t as Box<Terminal<Box<Writer>>>),
@@ -127,7 +164,7 @@ impl Terminal<Box<Writer>> for Shell {
         Some(Shell {
             terminal: NoColor(out),
             config: ShellConfig {
-                color: true,
+                color: Auto,
                 verbose: false,
                 tty: false,
             }
@@ -203,3 +240,26 @@ impl Writer for Shell {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Auto, Always, Never, ColorConfig};
+
+    #[test]
+    fn color_decision() {
+        assert!(Auto.use_color(true));
+        assert!(!Auto.use_color(false));
+        assert!(Always.use_color(true));
+        assert!(Always.use_color(false));
+        assert!(!Never.use_color(true));
+        assert!(!Never.use_color(false));
+    }
+
+    #[test]
+    fn parse_color_config() {
+        assert_eq!(from_str::<ColorConfig>("auto"), Some(Auto));
+        assert_eq!(from_str::<ColorConfig>("always"), Some(Always));
+        assert_eq!(from_str::<ColorConfig>("never"), Some(Never));
+        assert_eq!(from_str::<ColorConfig>("sometimes"), None);
+    }
+}
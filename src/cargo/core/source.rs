@@ -12,7 +12,7 @@ use serialize::{Decodable, Decoder, Encodable, Encoder};
 use url::Url;
 
 use core::{Summary, Package, PackageId, Registry, Dependency};
-use sources::{PathSource, GitSource, DummyRegistrySource};
+use sources::{PathSource, GitSource, DummyRegistrySource, VendorSource};
 use sources::git;
 use util::{human, Config, CargoResult, CargoError, ToUrl};
 
@@ -49,13 +49,77 @@ pub trait Source: Registry {
 #[deriving(Encodable, Decodable, Show, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum SourceKind {
     /// GitKind(<git reference>) represents a git repository
-    GitKind(String),
+    GitKind(GitReference),
     /// represents a local path
     PathKind,
     /// represents the central registry
     RegistryKind
 }
 
+/// Information to find a specific commit in a git repository
+#[deriving(Encodable, Decodable, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GitReference {
+    /// The current head of the named branch
+    Branch(String),
+    /// The commit that the named tag points at
+    Tag(String),
+    /// An exact commit, used verbatim
+    Rev(String),
+}
+
+impl GitReference {
+    pub fn master() -> GitReference {
+        Branch("master".to_string())
+    }
+
+    pub fn is_master(&self) -> bool {
+        match *self {
+            Branch(ref s) => s.as_slice() == "master",
+            _ => false,
+        }
+    }
+
+    /// Parses one `key=value` pair of a serialized git `SourceId`. The legacy
+    /// `ref` key is read as a branch.
+    fn from_query(key: &str, value: &str) -> Option<GitReference> {
+        match key {
+            "branch" | "ref" => Some(Branch(value.to_string())),
+            "tag" => Some(Tag(value.to_string())),
+            "rev" => Some(Rev(value.to_string())),
+            _ => None,
+        }
+    }
+
+    /// The `?key=value` suffix used when serializing a git `SourceId`, empty
+    /// for the default `master` branch.
+    fn to_query(&self) -> String {
+        match *self {
+            _ if self.is_master() => "".to_string(),
+            Branch(ref s) => format!("?branch={}", s),
+            Tag(ref s) => format!("?tag={}", s),
+            Rev(ref s) => format!("?rev={}", s),
+        }
+    }
+}
+
+impl Str for GitReference {
+    fn as_slice(&self) -> &str {
+        match *self {
+            Branch(ref s) | Tag(ref s) | Rev(ref s) => s.as_slice(),
+        }
+    }
+}
+
+impl Show for GitReference {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Branch(ref s) => write!(f, "branch `{}`", s),
+            Tag(ref s) => write!(f, "tag `{}`", s),
+            Rev(ref s) => write!(f, "rev `{}`", s),
+        }
+    }
+}
+
 #[deriving(Clone, PartialEq, Eq, Hash)]
 pub enum Location {
     Local(Path),
@@ -112,12 +176,31 @@ impl Location {
             Ok(Local(Path::new(s.slice_from(5))))
         } else {
             s.to_url().map(Remote).map_err(|e| {
-                human(format!("invalid url `{}`: `{}", s, e))
+                match scp_like(s) {
+                    Some((host, path)) => {
+                        human(format!("invalid url `{}`: `{}`; SSH remotes \
+                                       must be written as \
+                                       `ssh://{}/{}`", s, e, host, path))
+                    }
+                    None => human(format!("invalid url `{}`: `{}", s, e)),
+                }
             })
         }
     }
 }
 
+/// Splits an scp-like SSH location such as `git@host:repo.git` into its host
+/// and path.
+fn scp_like(s: &str) -> Option<(&str, &str)> {
+    let i = match s.find(':') { Some(i) => i, None => return None };
+    let (host, path) = (s.slice_to(i), s.slice_from(i + 1));
+    if host.len() == 0 || host.contains("/") || path.starts_with("//") {
+        None
+    } else {
+        Some((host, path))
+    }
+}
+
 impl<'a> ToCStr for &'a Location {
     fn to_c_str(&self) -> CString {
         match **self {
@@ -153,10 +236,7 @@ impl Show for SourceId {
                 try!(write!(f, "{}", location))
             },
             SourceId { kind: GitKind(ref reference), ref location, ref precise, .. } => {
-                try!(write!(f, "{}", location));
-                if reference.as_slice() != "master" {
-                    try!(write!(f, "?ref={}", reference));
-                }
+                try!(write!(f, "{}{}", location, reference.to_query()));
 
                 match *precise {
                     Some(ref s) => {
@@ -232,14 +312,17 @@ impl SourceId {
                     }
                     None => (url, None)
                 };
-                let (url, reference) = match url.find_str("?ref=") {
+                let (url, reference) = match url.find('?') {
                     Some(pos) => {
-                        (url.slice_to(pos), Some(url.slice_from(pos + 5)))
+                        let mut pair = url.slice_from(pos + 1).splitn('=', 1);
+                        let key = pair.next().unwrap();
+                        let value = pair.next().unwrap_or("");
+                        (url.slice_to(pos), GitReference::from_query(key, value))
                     }
                     None => (url, None)
                 };
-                let reference = reference.unwrap_or("master");
-                let id = SourceId::new(GitKind(reference.to_string()),
+                let reference = reference.unwrap_or(GitReference::master());
+                let id = SourceId::new(GitKind(reference),
                                        Local(Path::new(url)));
                 match precise {
                     Some(p) => id.with_precise(p.to_string()),
@@ -248,16 +331,17 @@ impl SourceId {
             }
             "git" => {
                 let mut url = url.to_url().unwrap();
-                let mut reference = "master".to_string();
+                let mut reference = GitReference::master();
                 let pairs = url.query_pairs().unwrap_or(Vec::new());
                 for &(ref k, ref v) in pairs.iter() {
-                    if k.as_slice() == "ref" {
-                        reference = v.clone();
+                    match GitReference::from_query(k.as_slice(), v.as_slice()) {
+                        Some(r) => reference = r,
+                        None => {}
                     }
                 }
                 url.query = None;
                 let precise = mem::replace(&mut url.fragment, None);
-                SourceId::for_git(&url, reference.as_slice(), precise)
+                SourceId::for_git(&url, reference, precise)
             },
             "registry" => SourceId::for_central(),
             "path" => SourceId::for_path(&Path::new(url.slice_from(5))),
@@ -267,18 +351,15 @@ impl SourceId {
 
     pub fn to_url(&self) -> String {
         match *self {
-            SourceId { kind: PathKind, .. } => {
-                fail!("Path sources are not included in the lockfile, \
-                       so this is unimplemented")
+            // Path sources are never written to the lockfile, but they are
+            // still described to external tools by `cargo metadata`
+            SourceId { kind: PathKind, ref location, .. } => {
+                format!("path+{}", location)
             },
             SourceId {
                 kind: GitKind(ref reference), ref location, ref precise, ..
             } => {
-                let ref_str = if reference.as_slice() != "master" {
-                    format!("?ref={}", reference)
-                } else {
-                    "".to_string()
-                };
+                let ref_str = reference.to_query();
 
                 let precise_str = if precise.is_some() {
                     format!("#{}", precise.get_ref())
@@ -300,8 +381,9 @@ impl SourceId {
         SourceId::new(PathKind, Local(path.clone()))
     }
 
-    pub fn for_git(url: &Url, reference: &str, precise: Option<String>) -> SourceId {
-        let mut id = SourceId::new(GitKind(reference.to_string()), Remote(url.clone()));
+    pub fn for_git(url: &Url, reference: GitReference,
+                   precise: Option<String>) -> SourceId {
+        let mut id = SourceId::new(GitKind(reference), Remote(url.clone()));
         if precise.is_some() {
             id = id.with_precise(precise.unwrap());
         }
@@ -340,6 +422,10 @@ impl SourceId {
                 };
                 box PathSource::new(path, self) as Box<Source>
             },
+            RegistryKind if config.vendor_dirs().len() > 0 => {
+                let dirs = config.vendor_dirs().to_vec();
+                box VendorSource::new(self, dirs) as Box<Source>
+            }
             RegistryKind => box DummyRegistrySource::new(self) as Box<Source>,
         }
     }
@@ -469,20 +555,45 @@ impl Source for SourceSet {
 
 #[cfg(test)]
 mod tests {
-    use super::{SourceId, Remote, GitKind};
+    use super::{SourceId, Remote, GitKind, GitReference, Branch, Tag, Rev};
     use util::ToUrl;
 
     #[test]
     fn github_sources_equal() {
         let loc = Remote("https://github.com/foo/bar".to_url().unwrap());
-        let s1 = SourceId::new(GitKind("master".to_string()), loc);
+        let s1 = SourceId::new(GitKind(GitReference::master()), loc);
 
         let loc = Remote("git://github.com/foo/bar".to_url().unwrap());
-        let mut s2 = SourceId::new(GitKind("master".to_string()), loc);
+        let mut s2 = SourceId::new(GitKind(GitReference::master()), loc);
 
         assert_eq!(s1, s2);
 
-        s2.kind = GitKind("foo".to_string());
+        s2.kind = GitKind(Branch("foo".to_string()));
         assert!(s1 != s2);
     }
+
+    #[test]
+    fn git_references_round_trip() {
+        let url = "https://github.com/foo/bar".to_url().unwrap();
+        let refs = [GitReference::master(),
+                    Branch("foo".to_string()),
+                    Tag("foo".to_string()),
+                    Rev("0123abcd".to_string())];
+        for r in refs.iter() {
+            let s1 = SourceId::for_git(&url, r.clone(), None);
+            let s2 = SourceId::from_url(s1.to_url());
+            assert_eq!(s1, s2);
+        }
+
+        let legacy = SourceId::from_url("git+https://github.com/foo/bar?ref=foo"
+                                            .to_string());
+        assert_eq!(legacy.kind, GitKind(Branch("foo".to_string())));
+    }
+
+    #[test]
+    fn path_sources_round_trip() {
+        let s1 = SourceId::for_path(&Path::new("/foo/bar"));
+        assert_eq!(s1.to_url().as_slice(), "path+file:/foo/bar");
+        assert_eq!(s1, SourceId::from_url(s1.to_url()));
+    }
 }
@@ -1,15 +1,19 @@
+use std::collections::HashMap;
+
 use semver::Version;
 use core::{
     Dependency,
     PackageId,
     SourceId
 };
+use util::{CargoResult, human};
 
 /// Summaries are cloned, and should not be mutated after creation
 #[deriving(Show,Clone,PartialEq)]
 pub struct Summary {
     package_id: PackageId,
-    dependencies: Vec<Dependency>
+    dependencies: Vec<Dependency>,
+    features: HashMap<String, Vec<String>>,
 }
 
 impl Summary {
@@ -17,7 +21,38 @@ impl Summary {
         Summary {
             package_id: pkg_id.clone(),
             dependencies: Vec::from_slice(dependencies),
+            features: HashMap::new(),
+        }
+    }
+
+    /// Adds the `[features]` of the package, each of which names the other
+    /// features and optional dependencies it enables.
+    pub fn with_features(mut self, features: HashMap<String, Vec<String>>)
+                         -> CargoResult<Summary> {
+        for (feature, enables) in features.iter() {
+            for name in enables.iter() {
+                if features.contains_key(name) { continue }
+                match self.dependencies.iter().find(|d| {
+                    d.get_local_name() == name.as_slice()
+                }) {
+                    Some(dep) if dep.is_optional() => {}
+                    Some(..) => {
+                        return Err(human(format!("feature `{}` includes `{}`, \
+                                                  but `{}` is not an optional \
+                                                  dependency", feature, name,
+                                                 name)))
+                    }
+                    None => {
+                        return Err(human(format!("feature `{}` includes `{}` \
+                                                  which is neither a \
+                                                  dependency nor another \
+                                                  feature", feature, name)))
+                    }
+                }
+            }
         }
+        self.features = features;
+        Ok(self)
     }
 
     pub fn get_package_id(&self) -> &PackageId {
@@ -39,6 +74,10 @@ impl Summary {
     pub fn get_dependencies(&self) -> &[Dependency] {
         self.dependencies.as_slice()
     }
+
+    pub fn get_features(&self) -> &HashMap<String, Vec<String>> {
+        &self.features
+    }
 }
 
 pub trait SummaryVec {
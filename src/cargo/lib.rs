@@ -7,6 +7,7 @@
 
 extern crate collections;
 extern crate debug;
+extern crate libc;
 extern crate regex;
 extern crate semver;
 extern crate serialize;
@@ -27,10 +28,12 @@ use std::io;
 use serialize::{Decoder, Encoder, Decodable, Encodable, json};
 use docopt::FlagParser;
 
-use core::{Shell, MultiShell, ShellConfig};
+use core::{Shell, MultiShell, ShellConfig, ColorConfig};
+use core::shell::Auto;
 use term::color::{BLACK};
 
 pub use util::{CargoError, CliError, CliResult, human};
+use util::Require;
 
 macro_rules! some(
     ($e:expr) => (
@@ -122,12 +125,39 @@ pub fn call_main_without_stdin<'a,
 
 fn process<'a, V: Encodable<json::Encoder<'a>, io::IoError>>(
                callback: |&[String], &mut MultiShell| -> CliResult<Option<V>>) {
-    let mut shell = shell(true);
     let mut args = os::args();
     args.remove(0);
+    let mut shell = match color_config(args.as_slice()) {
+        Ok(color) => shell_with_color(true, color),
+        Err(e) => return handle_error(e, &mut shell(true)),
+    };
     process_executed(callback(args.as_slice(), &mut shell), &mut shell)
 }
 
+/// Finds the `--color` flag among `args` ahead of parsing them, so that even
+/// errors in the other arguments are reported accordingly.
+fn color_config(args: &[String]) -> CliResult<ColorConfig> {
+    let mut args = args.iter().map(|a| a.as_slice());
+    let mut color = None;
+    loop {
+        match args.next() {
+            Some("--") | None => break,
+            Some("--color") => color = args.next(),
+            Some(arg) if arg.starts_with("--color=") => {
+                color = Some(arg.slice_from("--color=".len()))
+            }
+            Some(..) => {}
+        }
+    }
+    match color {
+        Some(s) => from_str::<ColorConfig>(s).require(|| {
+            human(format!("argument for --color must be auto, always, or \
+                           never, but found `{}`", s))
+        }).map_err(|e| CliError::from_boxed(e, 1)),
+        None => Ok(Auto),
+    }
+}
+
 pub fn process_executed<'a,
                         T: Encodable<json::Encoder<'a>, io::IoError>>(
                             result: CliResult<Option<T>>,
@@ -144,16 +174,23 @@ pub fn process_executed<'a,
 }
 
 pub fn shell(verbose: bool) -> MultiShell {
+    shell_with_color(verbose, Auto)
+}
+
+/// Creates the shell printing to stdout and stderr, coloring either of them
+/// as `color` says.
+pub fn shell_with_color(verbose: bool, color: ColorConfig) -> MultiShell {
     let tty = stderr_raw().isatty();
     let stderr = box stderr() as Box<Writer>;
 
-    let config = ShellConfig { color: true, verbose: verbose, tty: tty };
+    let config = ShellConfig { color: color.clone(), verbose: verbose,
+                               tty: tty };
     let err = Shell::create(stderr, config);
 
     let tty = stdout_raw().isatty();
     let stdout = box stdout() as Box<Writer>;
 
-    let config = ShellConfig { color: true, verbose: verbose, tty: tty };
+    let config = ShellConfig { color: color, verbose: verbose, tty: tty };
     let out = Shell::create(stdout, config);
 
     MultiShell::new(out, err, verbose)
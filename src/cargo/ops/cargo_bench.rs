@@ -0,0 +1,53 @@
+use std::os;
+
+use ops;
+use util::{CargoResult, ProcessError, process, process_error};
+
+pub struct BenchOptions<'a> {
+    pub compile_opts: ops::CompileOptions<'a>,
+    pub args: &'a [String],
+}
+
+/// Compiles all benchmark targets of the package at `manifest_path` with the
+/// optimized `bench` profile and runs each resulting binary with `--bench`
+/// followed by `options.args`.
+///
+/// A failing binary does not prevent the remaining ones from being run. Once
+/// all of them have finished, any failures are returned together as one
+/// error listing the offending binaries, which carries the exit status of the
+/// first of them so the caller can exit with it.
+pub fn run_benches(manifest_path: &Path,
+                   options: &mut BenchOptions) -> CargoResult<Option<ProcessError>> {
+    let compilation = try!(ops::compile(manifest_path,
+                                        &mut options.compile_opts));
+    let cwd = os::getcwd();
+
+    let mut failures = Vec::new();
+    let mut exit = None;
+    for exe in compilation.tests.iter() {
+        let exe = match exe.path_relative_from(&cwd) {
+            Some(path) => path,
+            None => exe.clone(),
+        };
+        let process = process(exe.clone()).arg("--bench").args(options.args);
+
+        try!(options.compile_opts.shell.status("Running", process.to_string()));
+        match process.exec() {
+            Ok(()) => {}
+            Err(e) => {
+                let file = exe.filename_str().unwrap().to_string();
+                debug!("bench binary failed; file={}; err={}", file, e);
+                if exit.is_none() { exit = e.exit; }
+                failures.push(file);
+            }
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(None)
+    } else {
+        let msg = format!("the following bench binaries failed:\n    {}",
+                          failures.connect("\n    "));
+        Ok(Some(process_error(msg, None, exit.as_ref(), None)))
+    }
+}
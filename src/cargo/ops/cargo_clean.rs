@@ -1,26 +1,122 @@
-use std::io::fs::{rmdir_recursive};
+use std::io::IoResult;
+use std::io::fs::{readdir, rmdir_recursive, unlink};
 
+use core::registry::PackageRegistry;
+use core::{MultiShell, Package, PackageSet, PackageId};
 use core::source::Source;
+use ops;
 use sources::PathSource;
+use util::config::Config;
 use util::{CargoResult, human, ChainError};
 
-/// Cleans the project from build artifacts.
+pub struct CleanOptions<'a> {
+    pub spec: Option<&'a str>,
+    pub target: Option<&'a str>,
+    pub target_dir: Option<&'a Path>,
+    /// Leave the generated documentation and its fingerprints in place
+    pub keep_doc: bool,
+    pub shell: &'a mut MultiShell,
+}
 
-pub fn clean(manifest_path: &Path) -> CargoResult<()> {
+/// Cleans the project from build artifacts.
+///
+/// If a package spec is given, only the artifacts, fingerprints, native
+/// output and documentation of that package are removed, leaving everything
+/// else fresh.
+pub fn clean(manifest_path: &Path, opts: &mut CleanOptions) -> CargoResult<()> {
     let mut src = PathSource::for_path(&manifest_path.dir_path());
     try!(src.update());
     let root = try!(src.get_root_package());
     let manifest = root.get_manifest();
+    let target_dir = try!(ops::target_dir(&root.get_root(), opts.target_dir));
+
+    let spec = match opts.spec {
+        Some(spec) => spec,
+        None => return clean_all(&root, &target_dir, opts.keep_doc),
+    };
+
+    let lockfile = root.get_root().join("Cargo.lock");
+    let source_id = root.get_package_id().get_source_id();
+    let resolve = match try!(ops::load_lockfile(&lockfile, source_id)) {
+        Some(resolve) => resolve,
+        None => return Err(human("A Cargo.lock must exist before cleaning a \
+                                  single package")),
+    };
+    let pkgid = try!(resolve.query(spec)).clone();
+
+    let (packages, sources) = {
+        let mut config = try!(Config::new(&mut *opts.shell, false, None,
+                                          opts.target.map(|s| s.to_string())));
+        let mut registry = PackageRegistry::new(&mut config);
+        try!(registry.add_sources(resolve.iter().map(|p| {
+            p.get_source_id().clone()
+        }).collect()));
+
+        let ids: Vec<PackageId> = resolve.iter().map(|p| p.clone()).collect();
+        let packages = try!(registry.get(ids.as_slice()).chain_error(|| {
+            human("Unable to get packages from source")
+        }));
+        (packages, registry.move_sources())
+    };
+    let pkg = match packages.iter().find(|p| *p.get_package_id() == pkgid) {
+        Some(pkg) => pkg,
+        None => return Err(human(format!("Unable to find package `{}`",
+                                         pkgid))),
+    };
+
+    // Each output directory used by the root package (e.g. `target` and
+    // `target/release`) has its own copy of the package's artifacts.
+    let mut dests: Vec<Option<&str>> = manifest.get_targets().iter().filter(|t| {
+        !(opts.keep_doc && t.get_profile().is_doc())
+    }).map(|t| t.get_profile().get_dest()).collect();
+    dests.sort();
+    dests.dedup();
+
+    let set = PackageSet::new(packages.as_slice());
+    for dest in dests.iter() {
+        let mut config = try!(Config::new(&mut *opts.shell, false, None,
+                                          opts.target.map(|s| s.to_string())));
+        config.set_target_dir(target_dir.clone());
+        try!(ops::clean_package(pkg, &root, *dest, &set, &resolve, &sources,
+                                &mut config));
+    }
+    if opts.keep_doc { return Ok(()) }
+
+    // rustdoc writes the documentation of each crate to a directory of its
+    // own, next to the output directory of the target.
+    let doc_dir = match opts.target {
+        Some(target) => target_dir.join(target).join("doc"),
+        None => target_dir.join("doc"),
+    };
+    for target in pkg.get_targets().iter().filter(|t| t.is_lib()) {
+        let dir = doc_dir.join(target.get_name());
+        if !dir.exists() { continue }
+        try!(opts.shell.status("Removing", dir.display()));
+        try!(rmdir_recursive(&dir).chain_error(|| {
+            human(format!("Could not remove `{}`", dir.display()))
+        }));
+    }
+
+    Ok(())
+}
+
+fn clean_all(root: &Package, build_dir: &Path,
+             keep_doc: bool) -> CargoResult<()> {
+    let manifest = root.get_manifest();
 
-    let build_dir = manifest.get_target_dir();
     if build_dir.exists() {
-        try!(rmdir_recursive(build_dir).chain_error(|| {
+        let res = if keep_doc {
+            remove_all_but_docs(build_dir)
+        } else {
+            rmdir_recursive(build_dir)
+        };
+        try!(res.chain_error(|| {
             human("Could not remove build directory")
         }))
     }
 
     let doc_dir = manifest.get_doc_dir();
-    if doc_dir.exists() {
+    if !keep_doc && doc_dir.exists() {
         try!(rmdir_recursive(doc_dir).chain_error(|| {
             human("Could not remove documentation directory")
         }))
@@ -28,3 +124,23 @@ pub fn clean(manifest_path: &Path) -> CargoResult<()> {
 
     Ok(())
 }
+
+/// Removes everything in `dir` but the `doc` directory rustdoc writes to and
+/// the `doc-build` directory holding the fingerprints of the documentation,
+/// keeping those of each target triple's directory as well.
+fn remove_all_but_docs(dir: &Path) -> IoResult<()> {
+    for path in try!(readdir(dir)).iter() {
+        match path.filename_str() {
+            Some("doc") | Some("doc-build") => continue,
+            _ => {}
+        }
+        if !path.is_dir() {
+            try!(unlink(path));
+        } else if path.join("doc").exists() || path.join("doc-build").exists() {
+            try!(remove_all_but_docs(path));
+        } else {
+            try!(rmdir_recursive(path));
+        }
+    }
+    Ok(())
+}
@@ -23,29 +23,156 @@
 //!
 
 use std::os;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use time;
 
 use core::registry::PackageRegistry;
 use core::{MultiShell, Source, SourceId, PackageSet, Target, PackageId};
+use core::{Package, Resolve, Summary};
 use core::resolver;
 use ops;
-use sources::{PathSource};
+use ops::cargo_rustc::report_status;
+use sources::{PathSource, GitSource};
 use util::config::{Config, ConfigValue};
 use util::{CargoResult, Wrap, config, internal, human, ChainError};
 use util::profile;
+use util::FileLock;
 
 pub struct CompileOptions<'a> {
     pub update: bool,
     pub env: &'a str,
     pub shell: &'a mut MultiShell,
     pub jobs: Option<uint>,
-    pub target: Option<&'a str>,
+    /// Target triples to build for (`--target`), the host if empty
+    pub targets: &'a [String],
+    pub locked: bool,
+    pub offline: bool,
+    pub dep_info_out: Option<&'a Path>,
+    /// Where to place build output instead of `target` (`--target-dir`)
+    pub target_dir: Option<&'a Path>,
+    /// Only build the library target (`--lib`)
+    pub lib: bool,
+    /// Only build the named bin targets (`--bin`), along with the library
+    pub bins: &'a [String],
+    /// Only build the named examples (`--example`), along with the library
+    pub examples: &'a [String],
+    pub message_format: MessageFormat,
+    /// Features of the root package to activate (`--features`)
+    pub features: &'a [String],
+    /// Leave out the `default` feature of the root package
+    /// (`--no-default-features`)
+    pub no_default_features: bool,
+    /// Keep building after a target fails to compile (`--keep-going`)
+    pub keep_going: bool,
+    /// Extra flags passed to rustc when compiling the one selected target of
+    /// the root package, but not its dependencies (`cargo rustc -- ...`)
+    pub target_rustc_args: Option<&'a [String]>,
+    /// Whether to print per-package compile durations and the critical path
+    /// once the build has finished
+    pub timings: bool,
+    /// Turn the warnings of the root package into errors, but not those of
+    /// its dependencies (`--deny-primary-warnings`)
+    pub deny_primary_warnings: bool,
+    /// Only build the workspace member with this name (`-p`), rather than
+    /// the whole workspace
+    pub spec: Option<&'a str>,
+}
+
+impl<'a> CompileOptions<'a> {
+    /// The options for building with the profile of `env` when given no
+    /// flags, which subcommands then adjust to those passed to them.
+    pub fn new(env: &'a str, shell: &'a mut MultiShell) -> CompileOptions<'a> {
+        CompileOptions {
+            update: false,
+            env: env,
+            shell: shell,
+            jobs: None,
+            targets: &[],
+            locked: false,
+            offline: false,
+            dep_info_out: None,
+            target_dir: None,
+            lib: false,
+            bins: &[],
+            examples: &[],
+            message_format: Human,
+            features: &[],
+            no_default_features: false,
+            keep_going: false,
+            target_rustc_args: None,
+            timings: false,
+            deny_primary_warnings: false,
+            spec: None,
+        }
+    }
+}
+
+/// How the progress of a build and the diagnostics of the compiler are
+/// reported.
+#[deriving(PartialEq, Show)]
+pub enum MessageFormat {
+    Human,
+    Json,
+}
+
+/// The executables produced by a compilation of the root package.
+pub struct Compilation {
+    /// Test and benchmark binaries, which are run by `cargo test` and
+    /// `cargo bench`.
+    pub tests: Vec<Path>,
+    /// All other executables, such as bins and examples.
+    pub binaries: Vec<Path>,
+}
+
+/// The files a target of the root package is built into.
+pub struct Artifact {
+    pub target: Target,
+    /// The target triple the files are built for, `None` for the host
+    pub platform: Option<String>,
+    pub files: Vec<Path>,
 }
 
 pub fn compile(manifest_path: &Path,
-               options: &mut CompileOptions) -> CargoResult<Vec<String>> {
-    let CompileOptions { update, env, ref mut shell, jobs, target } = *options;
-    let target = target.map(|s| s.to_string());
+               options: &mut CompileOptions) -> CargoResult<Compilation> {
+    let exec = Arc::new(box ops::DefaultExecutor as Box<ops::Executor + Send + Sync>);
+    compile_with_exec(manifest_path, options, exec)
+}
+
+/// Like `compile`, but every rustc invocation is run through `exec`.
+pub fn compile_with_exec(manifest_path: &Path, options: &mut CompileOptions,
+                         exec: Arc<Box<ops::Executor + Send + Sync>>)
+                         -> CargoResult<Compilation> {
+    let (compilation, _) = try!(compile_or_plan(manifest_path, options, exec,
+                                                false));
+    Ok(compilation)
+}
+
+/// Returns the files which building the package at `manifest_path` with
+/// `options` produces for each selected target, without building anything.
+///
+/// Dependencies are resolved as for a build, so a lockfile may need to be
+/// generated and sources fetched, but no lockfile is written.
+pub fn output_artifacts(manifest_path: &Path, options: &mut CompileOptions)
+                        -> CargoResult<Vec<Artifact>> {
+    let exec = Arc::new(box ops::DefaultExecutor as Box<ops::Executor + Send + Sync>);
+    let (_, artifacts) = try!(compile_or_plan(manifest_path, options, exec,
+                                              true));
+    Ok(artifacts)
+}
+
+/// Compiles the package at `manifest_path`, or if `dry_run` is set only
+/// works out which files compiling it would produce.
+fn compile_or_plan(manifest_path: &Path, options: &mut CompileOptions,
+                   exec: Arc<Box<ops::Executor + Send + Sync>>, dry_run: bool)
+                   -> CargoResult<(Compilation, Vec<Artifact>)> {
+    let CompileOptions {
+        update, env, ref mut shell, jobs, targets, locked, offline, dep_info_out,
+        target_dir: target_dir_flag, lib, bins, examples, message_format,
+        features, no_default_features, keep_going, target_rustc_args,
+        timings, deny_primary_warnings, spec
+    } = *options;
+    let start = time::precise_time_ns();
 
     log!(4, "compile; manifest-path={}", manifest_path.display());
 
@@ -65,87 +192,475 @@ pub fn compile(manifest_path: &Path,
     for key in package.get_manifest().get_unused_keys().iter() {
         try!(shell.warn(format!("unused manifest key: {}", key)));
     }
+    for warning in package.get_manifest().get_warnings().iter() {
+        try!(shell.warn(warning.as_slice()));
+    }
+
+    // The members of a workspace are resolved along with the root package,
+    // sharing its lockfile and target directory.
+    let members = try!(ops::read_workspace_members(&package));
+    let summary = try!(ops::workspace_summary(&package, members.as_slice()));
+    let source_ids = ops::workspace_source_ids(&package, members.as_slice());
+
+    let target_dir = try!(target_dir(&package.get_root(), target_dir_flag));
+
+    let features = root_features(package.get_summary(), features,
+                                 no_default_features);
+    let features = features.as_slice();
+
+    // A `--target` on the command line wins over the manifest's default
+    let triples: Vec<Option<String>> = if targets.is_empty() {
+        vec![package.get_manifest().get_default_target().map(|s| s.to_string())]
+    } else {
+        targets.iter().map(|t| Some(t.clone())).collect()
+    };
 
     let user_configs = try!(config::all_configs(os::getcwd()));
     let override_ids = try!(source_ids_from_config(&user_configs,
                                                    manifest_path.dir_path()));
 
-    let (packages, resolve, resolve_with_overrides, sources) = {
+    let (mut resolve, platforms, sources) = {
         let _p = profile::start("resolving...");
         let lockfile = manifest_path.dir_path().join("Cargo.lock");
         let source_id = package.get_package_id().get_source_id();
 
-        let mut config = try!(Config::new(*shell, update, jobs, target.clone()));
+        let mut config = try!(Config::new(*shell, update, jobs,
+                                          triples[0].clone()));
+        apply_flags(&mut config, locked, offline);
+        try!(scrape_git_config(&mut config, &user_configs));
+        try!(scrape_build_config(&mut config, &user_configs));
+        try!(scrape_vendor_config(&mut config, &user_configs));
+        let locked = config.locked();
+
+        let lockfile_resolve = try!(ops::load_lockfile(&lockfile, source_id));
+        if config.offline() {
+            try!(check_offline(&package, lockfile_resolve.as_ref(),
+                               &mut config));
+        }
+
+        let host = try!(ops::host_triple(config.rustc(), &target_dir));
 
         let mut registry = PackageRegistry::new(&mut config);
+        try!(registry.add_replacements(Vec::from_slice(
+            package.get_manifest().get_replace())));
 
-        let resolved = match try!(ops::load_lockfile(&lockfile, source_id)) {
+        let resolved = match lockfile_resolve {
+            None if locked => {
+                return Err(human("the lockfile needs to be generated but \
+                                  --locked was passed to prevent this"))
+            }
             Some(r) => {
+                if locked { try!(ops::check_locked(&package, &r)) }
                 try!(registry.add_sources(r.iter().map(|p| {
                     p.get_source_id().clone()
                 }).collect()));
                 r
             }
             None => {
-                try!(registry.add_sources(package.get_source_ids()));
-                try!(resolver::resolve(package.get_package_id(),
-                                       package.get_dependencies(),
-                                       &mut registry))
+                try!(registry.add_sources(source_ids));
+                try!(resolver::resolve_lockfile(&summary, &mut registry))
             }
         };
 
         try!(registry.add_overrides(override_ids));
 
-        let resolved_with_overrides =
-                try!(resolver::resolve(package.get_package_id(),
-                                       package.get_dependencies(),
-                                       &mut registry));
+        // Dev-dependencies are only needed for tests, benchmarks and examples,
+        // so any other build leaves them out of the graph entirely.
+        let dev_deps = env == "test" || env == "bench";
+
+        // Only the dependencies of the platforms being built for are needed,
+        // while the lockfile above covers every platform. The sources are
+        // shared, so nothing is fetched more than once.
+        let mut platforms = Vec::new();
+        for triple in triples.iter() {
+            let platform = triple.as_ref().unwrap_or(&host);
+            let mut resolved_with_overrides =
+                    try!(resolver::resolve_for_platform(&summary,
+                                                        features, dev_deps,
+                                                        &mut registry,
+                                                        platform.as_slice()));
+            // The root package isn't built against the workspace members
+            // unless it depends on them itself.
+            for member in members.iter() {
+                if !package.get_dependencies().iter().any(|d| {
+                    d.matches_id(member.get_package_id())
+                }) {
+                    resolved_with_overrides.unlink(package.get_package_id(),
+                                                   member.get_package_id());
+                }
+            }
+
+            let req: Vec<PackageId> = resolved_with_overrides.iter().map(|r| {
+                r.clone()
+            }).collect();
+            let packages = try!(registry.get(req.as_slice()).wrap({
+                human("Unable to get packages from source")
+            }));
+            debug!("packages={}", packages);
 
-        let req: Vec<PackageId> = resolved_with_overrides.iter().map(|r| {
-            r.clone()
-        }).collect();
-        let packages = try!(registry.get(req.as_slice()).wrap({
-            human("Unable to get packages from source")
-        }));
+            platforms.push((triple.clone(), resolved_with_overrides, packages));
+        }
 
-        (packages, resolved, resolved_with_overrides, registry.move_sources())
+        (resolved, platforms, registry.move_sources())
     };
 
-    debug!("packages={}", packages);
+    // Consumers asking for disjoint features of a package still share one
+    // build of it, which they may not expect.
+    let mut disjoint = HashSet::new();
+    for &(_, ref resolved, _) in platforms.iter() {
+        for msg in resolved.disjoint_features().iter() {
+            if disjoint.insert(msg.clone()) {
+                try!(shell.warn(msg.as_slice()));
+            }
+        }
+    }
 
-    let targets = package.get_targets().iter().filter(|target| {
-        match env {
-            // doc-all == document everything, so look for doc targets
-            "doc" | "doc-all" => target.get_profile().get_env() == "doc",
-            env => target.get_profile().get_env() == env,
+    for id in package.get_manifest().get_replace().iter() {
+        if !resolve.iter().any(|r| {
+            r.get_name() == id.get_name() && r.get_version() == id.get_version()
+        }) {
+            try!(shell.warn(format!("replacement `{}:{}` does not match any \
+                                     package in the dependency graph",
+                                    id.get_name(), id.get_version())));
         }
-    }).collect::<Vec<&Target>>();
+    }
 
-    {
+    // Without `-p` the root package and every workspace member are built,
+    // the target filters only applying to the package they were meant for.
+    let mut units = Vec::new();
+    for &pkg in try!(select_packages(&package, members.as_slice(), spec)).iter() {
+        let targets = pkg.get_targets().iter().filter(|target| {
+            match env {
+                // doc-all == document everything, so look for doc targets
+                "doc" | "doc-all" => target.get_profile().get_env() == "doc",
+                env => target.get_profile().get_env() == env,
+            }
+        }).collect::<Vec<&Target>>();
+        let targets = if spec.is_some() || pkg == &package {
+            try!(select_targets(targets, lib, bins, examples))
+        } else {
+            targets
+        };
+        units.push((pkg, targets));
+    }
+    let (root_units, member_units) = units.partition(|&(pkg, _)| pkg == &package);
+    let root_targets = root_units.move_iter().next().map(|(_, targets)| {
+        targets
+    }).unwrap_or(Vec::new());
+    let mut targets = root_targets.clone();
+    for &(_, ref member_targets) in member_units.iter() {
+        targets.push_all(member_targets.as_slice());
+    }
+    if target_rustc_args.is_some() && targets.len() != 1 {
+        return Err(human("extra arguments to `rustc` can only be passed to one \
+                          target, consider filtering the package by passing \
+                          e.g. `--lib` or `--bin NAME`"))
+    }
+
+    // Another cargo building into the same directory at the same time would
+    // trip over our half-updated fingerprints and artifacts.
+    let _lock = if dry_run {
+        None
+    } else {
+        Some(try!(FileLock::lock(&target_dir.join(".cargo-lock"),
+                                 "target directory", *shell)))
+    };
+
+    // Each platform is built in turn into its own layout. Plugins all go in
+    // the layout of the host, so they are only built for the first one and
+    // are fresh for the others.
+    let last = platforms.len() - 1;
+    let mut artifacts = Vec::new();
+    for (i, &(ref triple, ref resolve_with_overrides, ref packages))
+            in platforms.iter().enumerate() {
         let _p = profile::start("compiling");
-        let mut config = try!(Config::new(*shell, update, jobs, target));
+        let mut config = try!(Config::new(*shell, update, jobs, triple.clone()));
+        apply_flags(&mut config, locked, offline);
+        try!(check_checksums(packages.as_slice(), &mut resolve,
+                             config.locked()));
         try!(scrape_target_config(&mut config, &user_configs));
+        try!(scrape_build_config(&mut config, &user_configs));
+        match dep_info_out {
+            Some(dir) => config.set_dep_info_out(dir.clone()),
+            None => {}
+        }
+        config.set_target_dir(target_dir.clone());
+        config.set_json_messages(message_format == Json);
+        config.set_keep_going(keep_going);
+        config.set_timings(timings);
+        config.set_deny_primary_warnings(deny_primary_warnings);
+        match target_rustc_args {
+            Some(args) => config.set_target_rustc_args(Vec::from_slice(args)),
+            None => {}
+        }
+
+        if dry_run {
+            let files = try!(ops::target_artifacts(env.as_slice(),
+                                                   root_targets.as_slice(), &package,
+                                                   member_units.as_slice(),
+                                                   &PackageSet::new(packages.as_slice()),
+                                                   resolve_with_overrides,
+                                                   &sources, &mut config));
+            artifacts.extend(files.move_iter().map(|(target, files)| {
+                Artifact {
+                    target: target,
+                    platform: triple.clone(),
+                    files: files,
+                }
+            }));
+            continue
+        }
+
+        match *triple {
+            Some(ref triple) if platforms.len() > 1 => {
+                try!(report_status(config, "Building",
+                                   format!("for target `{}`", triple)));
+            }
+            _ => {}
+        }
 
-        try!(ops::compile_targets(env.as_slice(), targets.as_slice(), &package,
-                                  &PackageSet::new(packages.as_slice()),
-                                  &resolve_with_overrides, &sources,
-                                  &mut config));
+        let res = ops::compile_targets(env.as_slice(), root_targets.as_slice(),
+                                       &package, member_units.as_slice(),
+                                       &PackageSet::new(packages.as_slice()),
+                                       resolve_with_overrides, &sources,
+                                       &mut config, exec.clone());
+        if res.is_err() || i == last {
+            try!(print_finished(&mut config, env, targets.as_slice(), start));
+        }
+        try!(res);
+
+        if i == last {
+            try!(ops::write_resolve(&package, &resolve, &config));
+        }
     }
 
-    try!(ops::write_resolve(&package, &resolve));
+    let mut compilation = Compilation { tests: Vec::new(), binaries: Vec::new() };
+    for &(ref triple, _, _) in platforms.iter() {
+        // Everything but plugins is built for the target platform, and hence
+        // lands in the layout of the target if one was requested.
+        let output_dir = |t: &Target| {
+            let dir = match *triple {
+                Some(ref triple) if !t.get_profile().is_plugin() => {
+                    target_dir.join(triple.as_slice())
+                }
+                _ => target_dir.clone(),
+            };
+            match t.get_profile().get_dest() {
+                Some(dest) => dir.join(dest),
+                None => dir,
+            }
+        };
 
-    let test_executables: Vec<String> = targets.iter()
-        .filter_map(|target| {
+        for target in targets.iter() {
             if target.get_profile().is_test() {
                 debug!("Run  Target: {}", target.get_name());
-                Some(target.file_stem())
+                compilation.tests.push(output_dir(*target).join(target.file_stem()));
+            } else if target.is_bin() {
+                compilation.binaries.push(output_dir(*target).join(target.get_name()));
             } else {
                 debug!("Skip Target: {}", target.get_name());
-                None
             }
-    }).collect();
+        }
+    }
+
+    Ok((compilation, artifacts))
+}
+
+/// Reports how long the build took since `start`.
+fn print_finished(config: &mut Config, env: &str, targets: &[&Target],
+                  start: u64) -> CargoResult<()> {
+    let name = match env {
+        "release" => "release",
+        "test" => "test",
+        "bench" => "bench",
+        _ => "dev",
+    };
+    let optimized = targets.iter().any(|t| t.get_profile().get_opt_level() > 0);
+    let secs = (time::precise_time_ns() - start) as f64 / 1_000_000_000.0;
+    let msg = format!("{} [{}] target(s) in {:.2}s",
+        name, if optimized {"optimized"} else {"unoptimized"}, secs);
+    try!(report_status(config, "Finished", msg));
+    Ok(())
+}
+
+/// Returns the directory all build output of the package rooted at `root` is
+/// placed in.
+///
+/// The first of `--target-dir` (relative to `root`), `CARGO_TARGET_DIR` and
+/// `build.target-dir` in `.cargo/config` (both relative to the current
+/// directory) which is present wins, falling back to `root/target`.
+pub fn target_dir(root: &Path, flag: Option<&Path>) -> CargoResult<Path> {
+    match flag {
+        Some(dir) => return Ok(root.join(dir)),
+        None => {}
+    }
+    match os::getenv("CARGO_TARGET_DIR") {
+        Some(dir) => return Ok(os::getcwd().join(dir)),
+        None => {}
+    }
+
+    let configs = try!(config::all_configs(os::getcwd()));
+    let build = match configs.find_equiv(&"build") {
+        Some(build) => try!(build.table().chain_error(|| {
+            internal("invalid configuration for the key `build`")
+        })),
+        None => return Ok(root.join("target")),
+    };
+    match build.find_equiv(&"target-dir") {
+        Some(dir) => {
+            let dir = try!(dir.string().chain_error(|| {
+                internal("invalid configuration for key `build.target-dir`")
+            }));
+            Ok(os::getcwd().join(dir))
+        }
+        None => Ok(root.join("target")),
+    }
+}
+
+/// Narrows `targets` down to those requested with `--lib`, `--bin` and
+/// `--example`. The library is kept whenever a bin or an example is selected
+/// as they may link against it.
+fn select_targets<'a>(targets: Vec<&'a Target>, lib: bool, bins: &[String],
+                      examples: &[String]) -> CargoResult<Vec<&'a Target>> {
+    if !lib && bins.is_empty() && examples.is_empty() { return Ok(targets) }
+
+    // Test harnesses are never selected, but the plain library and binaries
+    // are kept even when building along with the tests for examples.
+    let targets = targets.move_iter().filter(|t| {
+        !t.get_profile().is_test()
+    }).collect::<Vec<&Target>>();
+    let is_bin = |t: &Target| t.is_bin() && !t.is_example();
+    let is_example = |t: &Target| t.is_example();
+
+    if lib && !targets.iter().any(|t| t.is_lib()) {
+        return Err(human("no library targets found"))
+    }
+    try!(check_names(targets.as_slice(), bins, "bin", is_bin));
+    try!(check_names(targets.as_slice(), examples, "example", is_example));
+
+    Ok(targets.move_iter().filter(|t| {
+        if t.is_lib() {
+            true
+        } else if t.is_example() {
+            examples.iter().any(|name| t.get_name() == name.as_slice())
+        } else if t.is_bin() {
+            bins.iter().any(|name| t.get_name() == name.as_slice())
+        } else {
+            false
+        }
+    }).collect())
+}
 
-    Ok(test_executables)
+/// Returns the packages to build: the workspace member named by `spec`, or
+/// else the root package along with all of its workspace `members`.
+fn select_packages<'a>(root: &'a Package, members: &'a [Package],
+                       spec: Option<&str>) -> CargoResult<Vec<&'a Package>> {
+    let spec = match spec {
+        Some(spec) => spec,
+        None => return Ok(Some(root).move_iter().chain(members.iter()).collect()),
+    };
+    match Some(root).move_iter().chain(members.iter()).find(|p| p.get_name() == spec) {
+        Some(pkg) => Ok(vec![pkg]),
+        None => Err(human(format!("package `{}` is not a member of the \
+                                   workspace", spec))),
+    }
+}
+
+/// Makes sure that each of `names` is the name of a target of the given
+/// `kind`, listing the available ones otherwise.
+fn check_names(targets: &[&Target], names: &[String], kind: &str,
+               is_kind: |&Target| -> bool) -> CargoResult<()> {
+    let available = targets.iter().filter(|t| is_kind(**t)).map(|t| {
+        t.get_name()
+    }).collect::<Vec<&str>>();
+    for name in names.iter() {
+        if !available.iter().any(|n| *n == name.as_slice()) {
+            return Err(human(format!("no {} target named `{}`, available {} \
+                                      targets: {}", kind, name, kind,
+                                     available.connect(", "))))
+        }
+    }
+    Ok(())
+}
+
+/// Makes sure that every git repository the build needs has already been
+/// fetched when network access is disabled, so that all of the missing ones
+/// are reported up front instead of failing midway through resolution.
+///
+/// With a lockfile each locked git package names the exact revision it needs,
+/// otherwise the git dependencies of `root` are checked as they're declared.
+fn check_offline(root: &Package, lockfile: Option<&Resolve>,
+                 config: &mut Config) -> CargoResult<()> {
+    let source_ids: Vec<SourceId> = match lockfile {
+        Some(resolve) => resolve.iter().map(|id| id.get_source_id().clone())
+                                .collect(),
+        None => root.get_source_ids(),
+    };
+    let mut missing = Vec::new();
+    for id in source_ids.iter().filter(|id| id.is_git()) {
+        let source = GitSource::new(id, config);
+        if !source.is_cached() {
+            missing.push(source.offline_message());
+        }
+    }
+    missing.sort();
+    missing.dedup();
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(human(missing.connect("\n")))
+    }
+}
+
+/// The features of the root package to activate: the ones asked for along
+/// with, unless `no_default` is set, the package's `default` feature.
+fn root_features(summary: &Summary, features: &[String],
+                 no_default: bool) -> Vec<String> {
+    let mut ret = Vec::from_slice(features);
+    if !no_default && summary.get_features().find_equiv(&"default").is_some() {
+        ret.push("default".to_string());
+    }
+    ret
+}
+
+/// Applies the `--locked` and `--offline` flags on top of whatever the
+/// environment already requested.
+fn apply_flags(config: &mut Config, locked: bool, offline: bool) {
+    if locked { config.set_locked() }
+    if offline { config.set_offline() }
+}
+
+/// Verifies the checksums recorded in the lockfile against the current
+/// contents of each package. Packages without a checksum yet have theirs
+/// recorded so it is written out with the lockfile, unless `locked` is true.
+fn check_checksums(packages: &[Package], resolve: &mut Resolve,
+                   locked: bool) -> CargoResult<()> {
+    for pkg in packages.iter() {
+        let actual = match try!(ops::package_checksum(pkg)) {
+            Some(checksum) => checksum,
+            None => continue,
+        };
+        let id = pkg.get_package_id();
+
+        match resolve.checksum(id) {
+            Some(expected) if expected == actual.as_slice() => {}
+            Some(expected) => {
+                return Err(human(format!("checksum for `{}` does not match \
+                                          the lockfile\n\
+                                          expected: {}\n\
+                                          actual:   {}", id, expected, actual)))
+            }
+            None if locked => {
+                return Err(human(format!("the lockfile has no checksum for \
+                                          `{}` but --locked was passed to \
+                                          prevent adding one", id)))
+            }
+            None => {}
+        }
+        resolve.set_checksum(id, actual);
+    }
+    Ok(())
 }
 
 fn source_ids_from_config(configs: &HashMap<String, config::ConfigValue>,
@@ -208,3 +723,92 @@ fn scrape_target_config(config: &mut Config,
 
     Ok(())
 }
+
+fn scrape_build_config(config: &mut Config,
+                       configs: &HashMap<String, config::ConfigValue>)
+                       -> CargoResult<()> {
+    let build = match configs.find_equiv(&"build") {
+        None => return Ok(()),
+        Some(build) => try!(build.table().chain_error(|| {
+            internal("invalid configuration for the key `build`")
+        })),
+    };
+
+    match build.find_equiv(&"rustc") {
+        None => {}
+        Some(rustc) => {
+            config.set_rustc(try!(rustc.string().chain_error(|| {
+                internal("invalid configuration for key `build.rustc`")
+            })).to_string());
+        }
+    }
+
+    match build.find_equiv(&"freshness") {
+        None => {}
+        Some(freshness) => {
+            let freshness = try!(freshness.string().chain_error(|| {
+                internal("invalid configuration for key `build.freshness`")
+            }));
+            match freshness {
+                "mtime" => config.set_hash_contents(false),
+                "content" => config.set_hash_contents(true),
+                other => {
+                    return Err(human(format!("`build.freshness` must be \
+                                              either `mtime` or `content`, \
+                                              found `{}`", other)))
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the `vendor` list of directories holding unpacked packages which
+/// stand in for the registry.
+fn scrape_vendor_config(config: &mut Config,
+                        configs: &HashMap<String, config::ConfigValue>)
+                        -> CargoResult<()> {
+    let dirs = match configs.find_equiv(&"vendor") {
+        None => return Ok(()),
+        Some(dirs) => try!(dirs.list().chain_error(|| {
+            internal("invalid configuration for the key `vendor`")
+        })),
+    };
+    config.set_vendor_dirs(dirs.iter().map(|d| {
+        os::make_absolute(&Path::new(d.as_slice()))
+    }).collect());
+    Ok(())
+}
+
+fn scrape_git_config(config: &mut Config,
+                     configs: &HashMap<String, config::ConfigValue>)
+                     -> CargoResult<()> {
+    let git = match configs.find_equiv(&"git") {
+        None => return Ok(()),
+        Some(git) => try!(git.table().chain_error(|| {
+            internal("invalid configuration for the key `git`")
+        })),
+    };
+
+    match git.find_equiv(&"shallow") {
+        None => {}
+        Some(shallow) => {
+            config.set_git_shallow(try!(shallow.boolean().chain_error(|| {
+                internal("invalid configuration for key `git.shallow`")
+            })));
+        }
+    }
+
+    match git.find_equiv(&"ssh-command") {
+        None => {}
+        Some(cmd) => {
+            let cmd = try!(cmd.string().chain_error(|| {
+                internal("invalid configuration for key `git.ssh-command`")
+            }));
+            config.set_git_ssh_command(cmd.to_string());
+        }
+    }
+
+    Ok(())
+}
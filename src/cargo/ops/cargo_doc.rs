@@ -1,13 +1,73 @@
+use std::os;
+
+use core::source::Source;
 use ops;
-use util::CargoResult;
+use sources::PathSource;
+use util::{CargoResult, ProcessBuilder, human, process};
 
 pub struct DocOptions<'a> {
-    pub all: bool,
+    /// Only document the local package rather than the whole dependency
+    /// graph, which is what `cargo doc` does unless given `--deps`
+    pub no_deps: bool,
+    /// Open the documentation of the local library in a browser afterwards
+    pub open: bool,
     pub compile_opts: ops::CompileOptions<'a>,
 }
 
+/// Documents the package at `manifest_path` into `target/doc`.
+///
+/// Dependencies are always compiled so that rustdoc can resolve their crates,
+/// and unless `no_deps` is set they are documented as well, each only once,
+/// into the same directory so that links between the crates work.
 pub fn doc(manifest_path: &Path,
            options: &mut DocOptions) -> CargoResult<()> {
+    options.compile_opts.env = if options.no_deps {"doc"} else {"doc-all"};
     try!(ops::compile(manifest_path, &mut options.compile_opts));
+
+    if options.open {
+        let mut src = PathSource::for_path(&manifest_path.dir_path());
+        try!(src.update());
+        let root = try!(src.get_root_package());
+
+        let target_dir = try!(ops::target_dir(&root.get_root(),
+                                              options.compile_opts.target_dir));
+        let lib = root.get_targets().iter().find(|t| t.is_lib());
+        let index = lib.map(|lib| {
+            target_dir.join("doc").join(lib.get_name()).join("index.html")
+        });
+        let shell = &mut *options.compile_opts.shell;
+        match index {
+            Some(ref index) if index.exists() => {
+                try!(shell.status("Opening", index.display()));
+                try!(opener().arg(index).exec().map_err(|e| {
+                    human(format!("could not open `{}` in a browser: {}",
+                                  index.display(), e))
+                }));
+            }
+            _ => {
+                try!(shell.warn(format!("no documentation to open, `{}` has \
+                                         no library target",
+                                        root.get_name())));
+            }
+        }
+    }
+
     Ok(())
 }
+
+/// The command which opens a file in the user's browser, as named by
+/// `$BROWSER` or else the platform's default.
+fn opener() -> ProcessBuilder {
+    match os::getenv("BROWSER") {
+        Some(browser) => return process(browser),
+        None => {}
+    }
+
+    if cfg!(target_os = "macos") {
+        process("open")
+    } else if cfg!(windows) {
+        process("cmd").arg("/C").arg("start").arg("")
+    } else {
+        process("xdg-open")
+    }
+}
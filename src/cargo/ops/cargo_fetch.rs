@@ -0,0 +1,64 @@
+use core::registry::PackageRegistry;
+use core::{MultiShell, Source, PackageId, resolver};
+use ops;
+use sources::PathSource;
+use util::config::Config;
+use util::CargoResult;
+
+/// Downloads every dependency of the package at `manifest_path` without
+/// compiling anything, so that later builds can happen offline.
+///
+/// The dependencies are the ones recorded in the lockfile, which is generated
+/// first if there isn't one yet. Like the lockfile, they include the optional
+/// dependencies of every feature, whether or not it is built by default.
+pub fn fetch(manifest_path: &Path, shell: &mut MultiShell) -> CargoResult<()> {
+    let mut source = PathSource::for_path(&manifest_path.dir_path());
+    try!(source.update());
+    let package = try!(source.get_root_package());
+
+    let lockfile = package.get_root().join("Cargo.lock");
+    let source_id = package.get_package_id().get_source_id();
+    let lockfile_resolve = try!(ops::load_lockfile(&lockfile, source_id));
+
+    let mut config = try!(Config::new(shell, false, None, None));
+    let (resolve, sources) = {
+        let mut registry = PackageRegistry::new(&mut config);
+        try!(registry.add_replacements(Vec::from_slice(
+            package.get_manifest().get_replace())));
+        let resolve = match lockfile_resolve {
+            Some(resolve) => {
+                try!(registry.add_sources(resolve.iter().map(|id| {
+                    id.get_source_id().clone()
+                }).collect()));
+                resolve
+            }
+            None => {
+                let members = try!(ops::read_workspace_members(&package));
+                try!(registry.add_sources(ops::workspace_source_ids(
+                    &package, members.as_slice())));
+                let summary = try!(ops::workspace_summary(&package,
+                                                          members.as_slice()));
+                try!(resolver::resolve_lockfile(&summary, &mut registry))
+            }
+        };
+        (resolve, registry.move_sources())
+    };
+
+    // Path dependencies, including the package itself, are already on disk,
+    // so there is nothing to fetch for them.
+    let mut ids: Vec<PackageId> = resolve.iter().filter(|id| {
+        !id.get_source_id().is_path()
+    }).map(|id| id.clone()).collect();
+    ids.sort();
+
+    for id in ids.iter() {
+        try!(config.shell().status("Fetching", id));
+    }
+    for source in sources.sources() {
+        try!(source.download(ids.as_slice()));
+        try!(source.get(ids.as_slice()));
+    }
+
+    try!(ops::write_resolve(&package, &resolve, &config));
+    Ok(())
+}
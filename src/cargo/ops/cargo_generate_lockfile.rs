@@ -1,5 +1,6 @@
 #![warn(warnings)]
-use std::collections::HashSet;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::io::File;
 
 use serialize::{Encodable, Decodable};
@@ -9,9 +10,10 @@ use toml = toml;
 use core::registry::PackageRegistry;
 use core::{MultiShell, Source, Resolve, resolver, Package, SourceId};
 use core::PackageId;
+use ops;
 use sources::{PathSource};
 use util::config::{Config};
-use util::{CargoResult, human};
+use util::{CargoResult, human, hash_tree, short_hash};
 use cargo_toml = util::toml;
 
 pub fn generate_lockfile(manifest_path: &Path,
@@ -28,62 +30,90 @@ pub fn generate_lockfile(manifest_path: &Path,
     let package = try!(source.get_root_package());
     debug!("loaded package; package={}", package);
 
-    let source_ids = package.get_source_ids();
+    let members = try!(ops::read_workspace_members(&package));
+    let summary = try!(ops::workspace_summary(&package, members.as_slice()));
+    let source_ids = ops::workspace_source_ids(&package, members.as_slice());
 
+    let mut config = try!(Config::new(shell, update, None, None));
     let resolve = {
-        let mut config = try!(Config::new(shell, update, None, None));
-
         let mut registry = PackageRegistry::new(&mut config);
         try!(registry.add_sources(source_ids));
-        try!(resolver::resolve(package.get_package_id(),
-                               package.get_dependencies(),
-                               &mut registry))
+        try!(registry.add_replacements(Vec::from_slice(
+            package.get_manifest().get_replace())));
+        try!(resolver::resolve_lockfile(&summary, &mut registry))
     };
 
-    try!(write_resolve(&package, &resolve));
+    try!(write_resolve(&package, &resolve, &config));
     Ok(())
 }
 
+pub struct UpdateOptions<'a> {
+    pub shell: &'a mut MultiShell,
+    pub to_update: Option<&'a str>,
+    pub precise: Option<&'a str>,
+}
+
 pub fn update_lockfile(manifest_path: &Path,
-                       shell: &mut MultiShell,
-                       to_update: Option<String>) -> CargoResult<()> {
+                       opts: &mut UpdateOptions) -> CargoResult<()> {
     let mut source = PathSource::for_path(&manifest_path.dir_path());
     try!(source.update());
     let package = try!(source.get_root_package());
+    let members = try!(ops::read_workspace_members(&package));
+    let summary = try!(ops::workspace_summary(&package, members.as_slice()));
 
     let lockfile = package.get_root().join("Cargo.lock");
     let source_id = package.get_package_id().get_source_id();
-    let resolve = match try!(load_lockfile(&lockfile, source_id)) {
+    let previous = match try!(load_lockfile(&lockfile, source_id)) {
         Some(resolve) => resolve,
         None => return Err(human("A Cargo.lock must exist before it is updated"))
     };
 
-    let mut config = try!(Config::new(shell, true, None, None));
-    let mut registry = PackageRegistry::new(&mut config);
+    if opts.precise.is_some() && opts.to_update.is_none() {
+        return Err(human("--precise requires a package to be specified"))
+    }
 
-    let sources = match to_update {
-        Some(name) => {
-            let mut to_avoid = HashSet::new();
-            match resolve.deps(package.get_package_id()) {
-                Some(deps) => {
-                    for dep in deps.filter(|d| d.get_name() == name.as_slice()) {
-                        fill_with_deps(&resolve, dep, &mut to_avoid);
+    let mut config = try!(Config::new(&mut *opts.shell, true, None, None));
+    let resolve = {
+        let mut registry = PackageRegistry::new(&mut config);
+
+        let sources = match opts.to_update {
+            // Everything outside of the named package and its own dependencies
+            // stays locked at the precise revision recorded in the lockfile,
+            // leaving only that subgraph to be re-resolved.
+            Some(spec) => {
+                let mut to_avoid = HashSet::new();
+                let pkgid = try!(previous.query(spec));
+                fill_with_deps(&previous, pkgid, &mut to_avoid);
+                let mut sources: Vec<SourceId> = previous.iter().filter(|id| {
+                    !to_avoid.contains(id)
+                }).map(|id| id.get_source_id().clone()).collect();
+
+                match opts.precise {
+                    Some(precise) => {
+                        let source_id = pkgid.get_source_id();
+                        if !source_id.is_git() {
+                            return Err(human(format!("--precise can only be \
+                                                      used with git \
+                                                      dependencies, `{}` is \
+                                                      not one", spec)))
+                        }
+                        sources.push(source_id.with_precise(precise.to_string()));
                     }
+                    None => {}
                 }
-                None => {}
+                sources
             }
-            resolve.iter().filter(|pkgid| !to_avoid.contains(pkgid))
-                   .map(|pkgid| pkgid.get_source_id().clone()).collect()
-        }
-        None => package.get_source_ids(),
-    };
-    try!(registry.add_sources(sources));
+            None => ops::workspace_source_ids(&package, members.as_slice()),
+        };
+        try!(registry.add_sources(sources));
+        try!(registry.add_replacements(Vec::from_slice(
+            package.get_manifest().get_replace())));
 
-    let resolve = try!(resolver::resolve(package.get_package_id(),
-                                         package.get_dependencies(),
-                                         &mut registry));
+        try!(resolver::resolve_lockfile(&summary, &mut registry))
+    };
 
-    try!(write_resolve(&package, &resolve));
+    try!(print_changes(&previous, &resolve, config.shell()));
+    try!(write_resolve(&package, &resolve, &config));
     return Ok(());
 
     fn fill_with_deps<'a>(resolve: &'a Resolve, dep: &'a PackageId,
@@ -100,6 +130,59 @@ pub fn update_lockfile(manifest_path: &Path,
     }
 }
 
+/// Reports each package whose version or revision differs between the
+/// `previous` and the updated resolve, along with any added or removed ones.
+fn print_changes(previous: &Resolve, resolve: &Resolve,
+                 shell: &mut MultiShell) -> CargoResult<()> {
+    fn key(id: &PackageId) -> (String, SourceId) {
+        (id.get_name().to_string(), id.get_source_id().clone())
+    }
+    fn describe(id: &PackageId) -> String {
+        match id.get_source_id().precise {
+            Some(ref rev) if id.get_source_id().is_git() => {
+                let short = rev.as_slice().slice_to(cmp::min(8, rev.len()));
+                format!("v{} ({})", id.get_version(), short)
+            }
+            _ => format!("v{}", id.get_version()),
+        }
+    }
+
+    let old: HashMap<(String, SourceId), &PackageId> = previous.iter().map(|id| {
+        (key(id), id)
+    }).collect();
+    let new: HashMap<(String, SourceId), &PackageId> = resolve.iter().map(|id| {
+        (key(id), id)
+    }).collect();
+
+    let mut changes = Vec::new();
+    for (k, id) in new.iter() {
+        match old.find(k) {
+            Some(prev) if describe(*prev) != describe(*id) => {
+                changes.push(("Updating", format!("{} {} -> {}", id.get_name(),
+                                                  describe(*prev),
+                                                  describe(*id))));
+            }
+            Some(..) => {}
+            None => {
+                changes.push(("Adding", format!("{} {}", id.get_name(),
+                                                describe(*id))));
+            }
+        }
+    }
+    for (k, id) in old.iter() {
+        if !new.contains_key(k) {
+            changes.push(("Removing", format!("{} {}", id.get_name(),
+                                              describe(*id))));
+        }
+    }
+    changes.sort_by(|a, b| a.ref1().cmp(b.ref1()));
+
+    for &(status, ref msg) in changes.iter() {
+        try!(shell.status(status, msg.as_slice()));
+    }
+    Ok(())
+}
+
 pub fn load_lockfile(path: &Path, sid: &SourceId) -> CargoResult<Option<Resolve>> {
     // If there is no lockfile, return none.
     let mut f = match File::open(path) {
@@ -115,10 +198,58 @@ pub fn load_lockfile(path: &Path, sid: &SourceId) -> CargoResult<Option<Resolve>
     Ok(Some(try!(v.to_resolve(sid))))
 }
 
-pub fn write_resolve(pkg: &Package, resolve: &Resolve) -> CargoResult<()> {
+/// Checks that the lockfile `resolve` of `pkg` still satisfies every
+/// non-optional dependency in the manifest, naming the first one which doesn't.
+pub fn check_locked(pkg: &Package, resolve: &Resolve) -> CargoResult<()> {
+    let locked: Vec<&PackageId> = match resolve.deps(pkg.get_package_id()) {
+        Some(deps) => deps.collect(),
+        None => Vec::new(),
+    };
+    for dep in pkg.get_dependencies().iter().filter(|d| !d.is_optional()) {
+        let satisfied = locked.iter().any(|id| {
+            id.get_name() == dep.get_name() &&
+                dep.get_version_req().matches(id.get_version()) &&
+                id.get_source_id() == dep.get_source_id()
+        });
+        if !satisfied {
+            return Err(human(format!("the lockfile needs to be updated but \
+                                      --locked was passed to prevent this; \
+                                      `{} {}` is not satisfied by Cargo.lock",
+                                     dep.get_name(), dep.get_version_req())))
+        }
+    }
+    Ok(())
+}
+
+/// Names a package which is in `next` but not in `prev`, or the other way
+/// around if `next` only drops packages.
+fn describe_change(prev: &Resolve, next: &Resolve) -> String {
+    match next.iter().find(|id| !prev.iter().any(|p| p == *id)) {
+        Some(id) => return format!("`{}` would be added", id),
+        None => {}
+    }
+    match prev.iter().find(|id| !next.iter().any(|n| n == *id)) {
+        Some(id) => format!("`{}` would be removed", id),
+        None => "its metadata would change".to_string(),
+    }
+}
+
+/// Writes `resolve` out as the lockfile of `pkg` if it differs from the
+/// current one. A locked `config` turns any such change into an error.
+pub fn write_resolve(pkg: &Package, resolve: &Resolve,
+                     config: &Config) -> CargoResult<()> {
     let loc = pkg.get_root().join("Cargo.lock");
     match load_lockfile(&loc, pkg.get_package_id().get_source_id()) {
         Ok(Some(ref prev_resolve)) if prev_resolve == resolve => return Ok(()),
+        Ok(Some(ref prev_resolve)) if config.locked() => {
+            return Err(human(format!("the lockfile needs to be updated but \
+                                      --locked was passed to prevent this; {}",
+                                     describe_change(prev_resolve, resolve))))
+        }
+        _ if config.locked() => {
+            return Err(human("the lockfile needs to be generated but \
+                              --locked was passed to prevent this"))
+        }
         _ => {}
     }
 
@@ -144,10 +275,36 @@ pub fn write_resolve(pkg: &Package, resolve: &Resolve) -> CargoResult<()> {
         emit_package(dep, &mut out);
     }
 
+    match e.toml.find(&"metadata".to_string()) {
+        Some(metadata) => {
+            out.push_str("[metadata]\n");
+            for (k, v) in metadata.as_table().unwrap().iter() {
+                out.push_str(format!("{} = {}\n", k, v).as_slice());
+            }
+        }
+        None => {}
+    }
+
     try!(File::create(&loc).write_str(out.as_slice()));
     Ok(())
 }
 
+/// Calculates the checksum which is recorded in the lockfile for `pkg`.
+///
+/// Checksums are only recorded for git sources. They cover both the precise
+/// revision which was checked out and the contents of the checkout, so any
+/// modification made to a checkout after the lockfile was written is noticed.
+pub fn package_checksum(pkg: &Package) -> CargoResult<Option<String>> {
+    let source_id = pkg.get_package_id().get_source_id();
+    if !source_id.is_git() { return Ok(None) }
+
+    let contents = try!(hash_tree(&pkg.get_root(), |dir| {
+        let name = dir.filename_str();
+        name == Some(".git") || name == Some("target")
+    }));
+    Ok(Some(short_hash(&(source_id.precise.as_ref(), contents))))
+}
+
 fn emit_package(dep: &toml::Table, out: &mut String) {
     out.push_str(format!("name = {}\n", lookup(dep, "name")).as_slice());
     out.push_str(format!("version = {}\n", lookup(dep, "version")).as_slice());
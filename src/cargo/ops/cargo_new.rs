@@ -9,44 +9,126 @@ macro_rules! git( ($($a:expr),*) => ({
     process("git") $(.arg($a))* .exec_with_output()
 }) )
 
+/// The version control system a new project is set up for.
+#[deriving(PartialEq, Show)]
+pub enum VersionControl {
+    GitRepo,
+    HgRepo,
+    NoVcs,
+}
+
 pub struct NewOptions<'a> {
-    pub git: bool,
+    pub vcs: VersionControl,
+    /// Generate a binary (`src/main.rs`) rather than a library (`src/lib.rs`)
     pub bin: bool,
+    pub force: bool,
     pub path: &'a str,
+    /// The name of the package, if it isn't the name of the directory
+    pub name: Option<&'a str>,
 }
 
-pub fn new(opts: NewOptions, _shell: &mut MultiShell) -> CargoResult<()> {
+pub fn new(opts: NewOptions, shell: &mut MultiShell) -> CargoResult<()> {
     let path = os::getcwd().join(opts.path);
-    if path.exists() {
-        return Err(human(format!("Destination `{}` already exists",
+    if path.exists() && !opts.force {
+        return Err(human(format!("Destination `{}` already exists, pass \
+                                  --force to overwrite it", path.display())))
+    }
+    if path.join("Cargo.toml").exists() {
+        return Err(human(format!("`{}` already contains a Cargo.toml, which \
+                                  `cargo new` will not overwrite",
                                  path.display())))
     }
-    let name = path.filename_str().unwrap();
-    mk(&path, name, &opts).chain_error(|| {
+
+    let name = match opts.name {
+        Some(name) => name,
+        None => path.filename_str().unwrap(),
+    };
+    if !valid_name(name) {
+        return Err(human(format!("`{}` is not a valid crate name, use \
+                                  --name to choose another one", name)))
+    }
+
+    mk(&path, name, &opts, shell).chain_error(|| {
         human(format!("Failed to create project `{}` at `{}`",
                       name, path.display()))
     })
 }
 
-fn mk(path: &Path, name: &str, opts: &NewOptions) -> CargoResult<()> {
+/// Whether `name` can be used as the name of a crate: only alphanumerics,
+/// `_` and `-`, not starting with a digit.
+fn valid_name(name: &str) -> bool {
+    match name.chars().next() {
+        Some(c) if !c.is_digit() => {}
+        _ => return false,
+    }
+    name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
 
-    if opts.git {
-        try!(git!("init", path));
-        try!(File::create(&path.join(".gitignore")).write(b"/target\n"));
-    } else {
+fn mk(path: &Path, name: &str, opts: &NewOptions,
+      shell: &mut MultiShell) -> CargoResult<()> {
+    if !path.exists() {
         try!(fs::mkdir(path, io::UserRWX));
     }
 
-    let author = try!(discover_author());
+    // Libraries leave the choice of dependency versions to the packages using
+    // them, so their lockfile isn't worth committing.
+    let mut ignore = vec!["target"];
+    if !opts.bin {
+        ignore.push("Cargo.lock");
+    }
+
+    match opts.vcs {
+        GitRepo => {
+            if !installed("git") {
+                try!(shell.warn("`git` could not be found, skipping the \
+                                 initialization of a git repository"));
+            } else if in_git_repo(path) {
+                debug!("already in a git repository; path={}", path.display());
+            } else {
+                try!(git!("init", path));
+            }
+            let contents: Vec<String> = ignore.iter().map(|f| {
+                format!("/{}\n", f)
+            }).collect();
+            try!(File::create(&path.join(".gitignore"))
+                      .write_str(contents.concat().as_slice()));
+        }
+        HgRepo => {
+            if !installed("hg") {
+                try!(shell.warn("`hg` could not be found, skipping the \
+                                 initialization of a mercurial repository"));
+            } else {
+                try!(process("hg").arg("init").arg(path).exec_with_output());
+            }
+            let contents: Vec<String> = ignore.iter().map(|f| {
+                format!("^{}$\n", f)
+            }).collect();
+            try!(File::create(&path.join(".hgignore"))
+                      .write_str(contents.concat().as_slice()));
+        }
+        NoVcs => {}
+    }
+
+    let authors = match discover_author() {
+        Some(author) => format!("\"{}\"", author),
+        None => String::new(),
+    };
+    let target = if opts.bin {"[[bin]]"} else {"[[lib]]"};
     try!(File::create(&path.join("Cargo.toml")).write_str(format!(
 r#"[package]
 
-name = "{}"
+name = "{name}"
 version = "0.0.1"
-authors = ["{}"]
-"#, name, author).as_slice()));
+authors = [{authors}]
 
-    try!(fs::mkdir(&path.join("src"), io::UserRWX));
+{target}
+
+name = "{name}"
+"#, name = name, authors = authors, target = target).as_slice()));
+
+    if !path.join("src").exists() {
+        try!(fs::mkdir(&path.join("src"), io::UserRWX));
+    }
 
     if opts.bin {
         try!(File::create(&path.join("src/main.rs")).write_str("\
@@ -56,8 +138,13 @@ fn main() {
 "));
     } else {
         try!(File::create(&path.join("src/lib.rs")).write_str("\
+pub fn hello() -> &'static str {
+    \"Hello, world!\"
+}
+
 #[test]
 fn it_works() {
+    assert_eq!(hello(), \"Hello, world!\");
 }
 "));
     }
@@ -65,13 +152,25 @@ fn it_works() {
     Ok(())
 }
 
-fn discover_author() -> CargoResult<String> {
+/// Whether the version control program `cmd` can be run.
+fn installed(cmd: &str) -> bool {
+    process(cmd).arg("--version").exec_with_output().is_ok()
+}
+
+/// Whether `path` is already within the work tree of a git repository.
+fn in_git_repo(path: &Path) -> bool {
+    process("git").arg("rev-parse").cwd(path.clone())
+                  .exec_with_output().is_ok()
+}
+
+/// Finds the author to list in the manifest, preferring the name and email
+/// configured for git over `$USER`.
+fn discover_author() -> Option<String> {
     let name = match git!("config", "user.name") {
         Ok(out) => String::from_utf8_lossy(out.output.as_slice()).into_string(),
         Err(..) => match os::getenv("USER") {
             Some(user) => user,
-            None => return Err(human("could not determine the current user, \
-                                      please set $USER"))
+            None => return None,
         }
     };
 
@@ -83,7 +182,7 @@ fn discover_author() -> CargoResult<String> {
     let name = name.as_slice().trim().to_string();
     let email = email.map(|s| s.as_slice().trim().to_string());
 
-    Ok(match (name, email) {
+    Some(match (name, email) {
         (name, Some(email)) => format!("{} <{}>", name, email),
         (name, None) => name,
     })
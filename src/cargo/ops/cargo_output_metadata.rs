@@ -0,0 +1,128 @@
+use core::registry::PackageRegistry;
+use core::{MultiShell, Source, Package, PackageId, Resolve, Target, resolver};
+use ops;
+use sources::PathSource;
+use util::config::Config;
+use util::{CargoResult, Wrap, human};
+
+/// Version of the format of `ExportInfo`, bumped whenever it changes in a way
+/// which tools reading it need to know about.
+static VERSION: uint = 1;
+
+pub struct OutputMetadataOptions<'a> {
+    /// Only describe the local package, without resolving its dependencies
+    pub no_deps: bool,
+    pub shell: &'a mut MultiShell,
+}
+
+/// Everything a tool needs to know about a package and its dependency graph,
+/// encoded as JSON by `cargo metadata`.
+#[deriving(Encodable)]
+pub struct ExportInfo {
+    packages: Vec<SerializedPackage>,
+    resolve: Option<Vec<SerializedNode>>,
+    root: PackageId,
+    version: uint,
+}
+
+#[deriving(Encodable)]
+struct SerializedPackage {
+    id: PackageId,
+    name: String,
+    version: String,
+    source: String,
+    manifest_path: String,
+    targets: Vec<Target>,
+}
+
+#[deriving(Encodable)]
+struct SerializedNode {
+    id: PackageId,
+    dependencies: Vec<PackageId>,
+}
+
+/// Loads the package at `manifest_path` along with, unless `no_deps` is set,
+/// all of its resolved dependencies.
+///
+/// An existing lockfile is used as is, otherwise the dependencies are
+/// resolved without writing a new lockfile.
+pub fn output_metadata(manifest_path: &Path,
+                       opts: &mut OutputMetadataOptions)
+                       -> CargoResult<ExportInfo> {
+    let mut source = PathSource::for_path(&manifest_path.dir_path());
+    try!(source.update());
+    let package = try!(source.get_root_package());
+    let root = package.get_package_id().clone();
+
+    if opts.no_deps {
+        return Ok(ExportInfo {
+            packages: vec![serialize_package(&package)],
+            resolve: None,
+            root: root,
+            version: VERSION,
+        })
+    }
+
+    let lockfile = package.get_root().join("Cargo.lock");
+    let source_id = package.get_package_id().get_source_id();
+    let lockfile_resolve = try!(ops::load_lockfile(&lockfile, source_id));
+
+    let mut config = try!(Config::new(&mut *opts.shell, false, None, None));
+    let mut registry = PackageRegistry::new(&mut config);
+    try!(registry.add_replacements(Vec::from_slice(
+        package.get_manifest().get_replace())));
+    let resolve = match lockfile_resolve {
+        Some(resolve) => {
+            try!(registry.add_sources(resolve.iter().map(|id| {
+                id.get_source_id().clone()
+            }).collect()));
+            resolve
+        }
+        None => {
+            let members = try!(ops::read_workspace_members(&package));
+            try!(registry.add_sources(ops::workspace_source_ids(
+                &package, members.as_slice())));
+            let summary = try!(ops::workspace_summary(&package,
+                                                      members.as_slice()));
+            try!(resolver::resolve_lockfile(&summary, &mut registry))
+        }
+    };
+
+    let ids: Vec<PackageId> = resolve.iter().map(|id| id.clone()).collect();
+    let mut packages = try!(registry.get(ids.as_slice()).wrap({
+        human("Unable to get packages from source")
+    }));
+    packages.sort_by(|a, b| a.get_package_id().cmp(b.get_package_id()));
+
+    Ok(ExportInfo {
+        packages: packages.iter().map(serialize_package).collect(),
+        resolve: Some(serialize_resolve(&resolve)),
+        root: root,
+        version: VERSION,
+    })
+}
+
+fn serialize_package(package: &Package) -> SerializedPackage {
+    let id = package.get_package_id();
+    SerializedPackage {
+        id: id.clone(),
+        name: id.get_name().to_string(),
+        version: id.get_version().to_string(),
+        source: id.get_source_id().to_url(),
+        manifest_path: package.get_manifest_path().display().to_string(),
+        targets: Vec::from_slice(package.get_targets()),
+    }
+}
+
+fn serialize_resolve(resolve: &Resolve) -> Vec<SerializedNode> {
+    let mut ids: Vec<&PackageId> = resolve.iter().collect();
+    ids.sort();
+    ids.move_iter().map(|id| {
+        let mut deps: Vec<PackageId> = match resolve.deps(id) {
+            Some(deps) => deps.map(|dep| dep.clone()).collect(),
+            None => Vec::new(),
+        };
+        deps.sort();
+        SerializedNode { id: id.clone(), dependencies: deps }
+    }).collect()
+}
@@ -1,7 +1,7 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{File, fs};
 use util;
-use core::{Package,Manifest,SourceId};
+use core::{Package,Manifest,SourceId,Target,Summary,Dependency};
 use util::{CargoResult, human};
 use util::important_paths::find_project_manifest_exact;
 use util::toml::{Layout, project_layout};
@@ -20,23 +20,131 @@ pub fn read_package(path: &Path, source_id: &SourceId)
     let layout = project_layout(&path.dir_path());
     let (manifest, nested) =
         try!(read_manifest(data.as_slice(), layout, source_id));
+    try!(validate_targets(manifest.get_targets(), &path.dir_path()));
 
     Ok((Package::new(manifest, path, source_id), nested))
 }
 
+/// Reads the packages listed in the `[workspace]` of `root`.
+pub fn read_workspace_members(root: &Package) -> CargoResult<Vec<Package>> {
+    let source_id = root.get_package_id().get_source_id();
+    root.get_manifest().get_workspace_members().iter().map(|member| {
+        let manifest = root.get_root().join(member).join("Cargo.toml");
+        if !manifest.is_file() {
+            return Err(human(format!("workspace member `{}` has no \
+                                      Cargo.toml", member.display())))
+        }
+        let (pkg, _) = try!(read_package(&manifest, source_id));
+        Ok(pkg)
+    }).collect()
+}
+
+/// Returns the summary of `root` extended with a dependency on each of its
+/// workspace `members`, so that resolving it resolves the whole workspace
+/// at once and members share the versions of their common dependencies.
+pub fn workspace_summary(root: &Package,
+                         members: &[Package]) -> CargoResult<Summary> {
+    let summary = root.get_summary();
+    if members.is_empty() { return Ok(summary.clone()) }
+
+    let mut deps = Vec::from_slice(summary.get_dependencies());
+    for member in members.iter() {
+        let version = member.get_version().to_string();
+        deps.push(try!(Dependency::parse(member.get_name(),
+                                         Some(version.as_slice()),
+                                         member.get_package_id().get_source_id())));
+    }
+    Summary::new(summary.get_package_id(), deps.as_slice())
+            .with_features(summary.get_features().clone())
+}
+
+/// Returns the sources the dependencies of `root` and its workspace
+/// `members` are found in.
+pub fn workspace_source_ids(root: &Package, members: &[Package]) -> Vec<SourceId> {
+    let mut ids = root.get_source_ids();
+    for member in members.iter() {
+        ids.push_all(member.get_manifest().get_source_ids());
+    }
+    ids
+}
+
+/// Checks that every target of the package rooted at `root` has a valid name
+/// and a source file which exists, and that no two targets would be built
+/// into the same file.
+fn validate_targets(targets: &[Target], root: &Path) -> CargoResult<()> {
+    let display = |path: &Path| {
+        path.path_relative_from(root).unwrap_or(path.clone()).display()
+            .to_string()
+    };
+
+    let mut outputs = HashMap::new();
+    for target in targets.iter() {
+        let name = target.get_name();
+        if name.chars().next().map_or(false, |c| c.is_digit()) {
+            return Err(human(format!("invalid target name `{}`, target names \
+                                      can't start with a digit", name)))
+        }
+        if name.contains_char('/') || name.contains_char('\\') {
+            return Err(human(format!("invalid target name `{}`, target names \
+                                      can't contain path separators", name)))
+        }
+
+        let src = root.join(target.get_src_path());
+        if !src.is_file() {
+            return Err(human(format!("can't find `{}` for target `{}`, set \
+                                      its `path` in Cargo.toml if it lives \
+                                      elsewhere", display(&src), name)))
+        }
+
+        // Bins and examples land in the same directory, libs have a name of
+        // their own.
+        let profile = target.get_profile();
+        let key = (profile.get_env().to_string(),
+                   profile.get_dest().map(|d| d.to_string()),
+                   target.is_lib(), target.file_stem());
+        match outputs.find(&key) {
+            Some(prev) if *prev != src => {
+                return Err(human(format!("targets named `{}` would overwrite \
+                                          each other's output:\n  {}\n  {}",
+                                         name, display(prev), display(&src))))
+            }
+            Some(..) => continue,
+            None => {}
+        }
+        outputs.insert(key, src);
+    }
+    Ok(())
+}
+
+/// How many directories deep `read_packages` looks for packages by default.
+pub static DEFAULT_MAX_DEPTH: uint = 10;
+
+/// Finds every package at or below `path`, looking at most
+/// `DEFAULT_MAX_DEPTH` directories deep.
 pub fn read_packages(path: &Path,
                      source_id: &SourceId) -> CargoResult<Vec<Package>> {
+    read_packages_to_depth(path, source_id, DEFAULT_MAX_DEPTH)
+}
+
+/// Finds every package at or below `path`, looking at most `max_depth`
+/// directories deep.
+///
+/// Version control metadata, `target` directories and hidden directories are
+/// never searched, and a directory reached through more than one symlink is
+/// only searched once.
+pub fn read_packages_to_depth(path: &Path, source_id: &SourceId,
+                              max_depth: uint) -> CargoResult<Vec<Package>> {
     let mut all_packages = Vec::new();
     let mut visited = HashSet::<Path>::new();
+    let mut searched = HashSet::<Path>::new();
 
     log!(5, "looking for root package: {}, source_id={}", path.display(), source_id);
     try!(process_possible_package(path, &mut all_packages, source_id, &mut visited));
 
-    try!(walk(path, true, |root, dir| {
+    try!(walk(path, 0, max_depth, &mut searched, |root, dir| {
         log!(5, "looking for child package: {}", dir.display());
         if root && dir.join("target").is_dir() { return Ok(false); }
         if root { return Ok(true) }
-        if dir.filename_str() == Some(".git") { return Ok(false); }
         if dir.join(".git").exists() { return Ok(false); }
         try!(process_possible_package(dir, &mut all_packages, source_id,
                                       &mut visited));
@@ -51,23 +159,42 @@ pub fn read_packages(path: &Path,
     }
 }
 
-fn walk(path: &Path, is_root: bool,
+fn walk(path: &Path, depth: uint, max_depth: uint, searched: &mut HashSet<Path>,
         callback: |bool, &Path| -> CargoResult<bool>) -> CargoResult<()> {
-    if path.is_dir() {
-        let continues = try!(callback(is_root, path));
-        if !continues {
-            log!(5, "not processing {}", path.display());
-            return Ok(());
-        }
+    if !path.is_dir() { return Ok(()) }
+    if depth > 0 && is_skipped_dir(path) { return Ok(()) }
+    if depth > max_depth {
+        log!(5, "too deep to look into {}", path.display());
+        return Ok(())
+    }
+    if !searched.insert(try!(util::realpath(path))) {
+        log!(5, "already looked into {}", path.display());
+        return Ok(())
+    }
 
-        for dir in try!(fs::readdir(path)).iter() {
-            try!(walk(dir, false, |a, x| callback(a, x)))
-        }
+    let continues = try!(callback(depth == 0, path));
+    if !continues {
+        log!(5, "not processing {}", path.display());
+        return Ok(());
+    }
+
+    for dir in try!(fs::readdir(path)).iter() {
+        try!(walk(dir, depth + 1, max_depth, searched, |a, x| callback(a, x)))
     }
 
     Ok(())
 }
 
+/// Directories which can't contain packages of their own: build output and
+/// anything hidden, such as `.git`.
+fn is_skipped_dir(path: &Path) -> bool {
+    match path.filename_str() {
+        Some("target") => true,
+        Some(name) => name.starts_with("."),
+        None => false,
+    }
+}
+
 fn process_possible_package(dir: &Path,
                             all_packages: &mut Vec<Package>,
                             source_id: &SourceId,
@@ -76,9 +203,7 @@ fn process_possible_package(dir: &Path,
     if !has_manifest(dir) { return Ok(()); }
 
     let packages = try!(read_nested_packages(dir, source_id, visited));
-    push_all(all_packages, packages);
-
-    Ok(())
+    push_all(all_packages, packages)
 }
 
 fn has_manifest(path: &Path) -> bool {
@@ -103,10 +228,27 @@ fn read_nested_packages(path: &Path, source_id: &SourceId,
     Ok(ret)
 }
 
-fn push_all(set: &mut Vec<Package>, packages: Vec<Package>) {
+/// Adds `packages` to `set`, failing if one of them is the same package as
+/// one already in `set` but was found at a different location.
+fn push_all(set: &mut Vec<Package>, packages: Vec<Package>) -> CargoResult<()> {
     for package in packages.move_iter() {
-        if set.contains(&package) { continue; }
+        match set.iter().find(|p| **p == package) {
+            Some(prev) if prev.get_manifest_path() == package.get_manifest_path() => {
+                continue
+            }
+            Some(prev) => {
+                return Err(human(format!("Could not read `{} v{}`, it is \
+                                          found at more than one \
+                                          location.\n  {}\n  {}",
+                                         package.get_name(),
+                                         package.get_version(),
+                                         prev.get_manifest_path().display(),
+                                         package.get_manifest_path().display())))
+            }
+            None => {}
+        }
 
         set.push(package)
     }
+    Ok(())
 }
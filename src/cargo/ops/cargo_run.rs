@@ -1,26 +1,60 @@
 use std::os;
 
 use ops;
-use util::{CargoResult, human, process, ProcessError};
+use util::{CargoResult, human, internal, process, ProcessError};
+use core::Target;
 use core::source::Source;
 use sources::PathSource;
 
 pub fn run(manifest_path: &Path,
            options: &mut ops::CompileOptions,
+           bin: Option<&str>,
            args: &[String]) -> CargoResult<Option<ProcessError>> {
-    if !manifest_path.dir_path().join("src").join("main.rs").exists() {
-        return Err(human("`src/main.rs` must be present for `cargo run`"))
-    }
-
     let mut src = PathSource::for_path(&manifest_path.dir_path());
     try!(src.update());
     let root = try!(src.get_root_package());
 
-    try!(ops::compile(manifest_path, options));
-    let exe = manifest_path.dir_path().join("target").join(root.get_name());
+    let env = options.env;
+    let mut bins = root.get_manifest().get_targets().iter().filter(|t| {
+        t.is_bin() && t.get_profile().get_env() == env
+    });
+    let bin = match bin {
+        Some(name) => match bins.find(|t| t.get_name() == name) {
+            Some(bin) => bin,
+            None => return Err(human(format!("no bin target named `{}` to \
+                                              run", name))),
+        },
+        None => {
+            let mut bins = bins.collect::<Vec<&Target>>();
+            match bins.len() {
+                0 => return Err(human("a bin target must be available for \
+                                       `cargo run`")),
+                1 => bins.pop().unwrap(),
+                _ => {
+                    let names = bins.iter().map(|t| t.get_name())
+                                    .collect::<Vec<&str>>();
+                    return Err(human(format!("`cargo run` requires that a \
+                                              project only have one \
+                                              executable; use the `--bin` \
+                                              option to specify which one \
+                                              to run (one of: {})",
+                                             names.connect(", "))))
+                }
+            }
+        }
+    };
+
+    let compilation = try!(ops::compile(manifest_path, options));
+    let exe = match compilation.binaries.iter().find(|p| {
+        p.filename_str() == Some(bin.get_name())
+    }) {
+        Some(exe) => exe,
+        None => return Err(internal(format!("no executable was built for \
+                                             `{}`", bin.get_name()))),
+    };
     let exe = match exe.path_relative_from(&os::getcwd()) {
         Some(path) => path,
-        None => exe,
+        None => exe.clone(),
     };
     let process = process(exe).args(args);
 
@@ -1,12 +1,14 @@
 use std::collections::{HashMap, HashSet};
-use std::str;
+use std::sync::{Arc, Mutex};
 
 use core::{SourceMap, Package, PackageId, PackageSet, Resolve, Target};
-use util;
-use util::{CargoResult, ChainError, internal, Config, profile};
+use core::Dependency;
+use util::{CargoResult, Config, profile};
 
-use super::{Kind, KindPlugin, KindTarget};
+use super::{Kind, KindPlugin, KindTarget, Executor};
+use super::custom_build::BuildState;
 use super::layout::{Layout, LayoutProxy};
+use super::rustc_info::RustcInfo;
 
 #[deriving(Show)]
 pub enum PlatformRequirement {
@@ -18,75 +20,72 @@ pub enum PlatformRequirement {
 pub struct Context<'a, 'b> {
     pub primary: bool,
     pub rustc_version: String,
+    pub host_triple: String,
     pub config: &'b mut Config<'b>,
     pub resolve: &'a Resolve,
     pub sources: &'a SourceMap,
+    pub build_state: BuildState,
+    pub exec: Arc<Box<Executor + Send + Sync>>,
 
     env: &'a str,
     host: Layout,
     target: Option<Layout>,
     host_dylib: (String, String),
+    host_exe: String,
     package_set: &'a PackageSet,
     target_dylib: (String, String),
     target_exe: String,
-    requirements: HashMap<(&'a PackageId, &'a str), PlatformRequirement>,
+    requirements: HashMap<(&'a PackageId, &'a Target), PlatformRequirement>,
+    scheduled: HashSet<(&'a PackageId, &'a Target, Kind)>,
 }
 
 impl<'a, 'b> Context<'a, 'b> {
     pub fn new(env: &'a str, resolve: &'a Resolve, sources: &'a SourceMap,
                deps: &'a PackageSet, config: &'b mut Config<'b>,
-               host: Layout, target: Option<Layout>)
+               host: Layout, target: Option<Layout>,
+               exec: Arc<Box<Executor + Send + Sync>>)
                -> CargoResult<Context<'a, 'b>> {
+        let mut info = RustcInfo::load(config.target_dir()
+                                             .unwrap_or(host.dest()));
         let (target_dylib, target_exe) =
-                try!(Context::filename_parts(config.target()));
-        let host_dylib = if config.target().is_none() {
-            target_dylib.clone()
+                try!(Context::filename_parts(&mut info, config.rustc(),
+                                             config.target()));
+        let (host_dylib, host_exe) = if config.target().is_none() {
+            (target_dylib.clone(), target_exe.clone())
         } else {
-            let (dylib, _) = try!(Context::filename_parts(None));
-            dylib
+            try!(Context::filename_parts(&mut info, config.rustc(), None))
         };
+        let rustc_version = try!(info.version(config.rustc()));
+        try!(info.save());
+        let host_triple = try!(super::parse_host_triple(rustc_version.as_slice()));
         Ok(Context {
-            rustc_version: try!(Context::rustc_version()),
+            rustc_version: rustc_version,
+            host_triple: host_triple,
             env: env,
             host: host,
             target: target,
             primary: false,
             resolve: resolve,
             sources: sources,
+            build_state: Arc::new(Mutex::new(HashMap::new())),
+            exec: exec,
             package_set: deps,
             config: config,
             target_dylib: target_dylib,
             target_exe: target_exe,
             host_dylib: host_dylib,
+            host_exe: host_exe,
             requirements: HashMap::new(),
+            scheduled: HashSet::new(),
         })
     }
 
-    /// Run `rustc` to figure out what its current version string is
-    fn rustc_version() -> CargoResult<String> {
-        let output = try!(util::process("rustc").arg("-v").arg("verbose")
-                               .exec_with_output());
-        Ok(String::from_utf8(output.output).unwrap())
-    }
-
     /// Run `rustc` to discover the dylib prefix/suffix for the target
     /// specified as well as the exe suffix
-    fn filename_parts(target: Option<&str>)
+    fn filename_parts(info: &mut RustcInfo, rustc: &str, target: Option<&str>)
                       -> CargoResult<((String, String), String)> {
-        let process = util::process("rustc")
-                           .arg("-")
-                           .arg("--crate-name").arg("-")
-                           .arg("--crate-type").arg("dylib")
-                           .arg("--crate-type").arg("bin")
-                           .arg("--print-file-name");
-        let process = match target {
-            Some(s) => process.arg("--target").arg(s),
-            None => process,
-        };
-        let output = try!(process.exec_with_output());
-
-        let output = str::from_utf8(output.output.as_slice()).unwrap();
-        let mut lines = output.lines();
+        let output = try!(info.file_names(rustc, target));
+        let mut lines = output.as_slice().lines();
         let dylib_parts: Vec<&str> = lines.next().unwrap().trim()
                                           .split('-').collect();
         assert!(dylib_parts.len() == 2,
@@ -103,26 +102,26 @@ impl<'a, 'b> Context<'a, 'b> {
     pub fn prepare(&mut self, pkg: &'a Package) -> CargoResult<()> {
         let _p = profile::start("preparing layout");
 
-        try!(self.host.prepare().chain_error(|| {
-            internal(format!("couldn't prepare build directories for `{}`",
-                             pkg.get_name()))
-        }));
+        try!(self.host.prepare());
         match self.target {
-            Some(ref mut target) => {
-                try!(target.prepare().chain_error(|| {
-                    internal(format!("couldn't prepare build directories \
-                                      for `{}`", pkg.get_name()))
-                }));
-            }
+            Some(ref mut target) => try!(target.prepare()),
             None => {}
         }
 
+        self.add_requirements(pkg);
+        Ok(())
+    }
+
+    /// Works out which platforms the dependencies of `pkg` are needed for,
+    /// for each further package built in the same context as the one passed
+    /// to `prepare`.
+    pub fn add_requirements(&mut self, pkg: &'a Package) {
+        // Test and example targets are built for the target platform just
+        // like libraries and binaries, so they are walked here as well.
         let targets = pkg.get_targets().iter();
-        for target in targets.filter(|t| t.get_profile().is_compile()) {
+        for target in targets.filter(|t| !t.get_profile().is_doc()) {
             self.build_requirements(pkg, target, Target, &mut HashSet::new());
         }
-
-        Ok(())
     }
 
     fn build_requirements(&mut self, pkg: &'a Package, target: &'a Target,
@@ -130,7 +129,7 @@ impl<'a, 'b> Context<'a, 'b> {
                           visiting: &mut HashSet<&'a PackageId>) {
         if !visiting.insert(pkg.get_package_id()) { return }
 
-        let key = (pkg.get_package_id(), target.get_name());
+        let key = (pkg.get_package_id(), target);
         let req = if target.get_profile().is_plugin() {Plugin} else {req};
         self.requirements.insert_or_update_with(key, req, |_, v| {
             *v = v.combine(req);
@@ -139,16 +138,31 @@ impl<'a, 'b> Context<'a, 'b> {
         for &(pkg, dep) in self.dep_targets(pkg).iter() {
             self.build_requirements(pkg, dep, req, visiting);
         }
+        // Build dependencies are only run on the host, whatever the package
+        // using them is compiled for.
+        for &(pkg, dep) in self.build_dep_targets(pkg).iter() {
+            self.build_requirements(pkg, dep, Plugin, visiting);
+        }
 
         visiting.remove(&pkg.get_package_id());
     }
 
     pub fn get_requirement(&self, pkg: &'a Package,
                            target: &'a Target) -> PlatformRequirement {
-        self.requirements.find(&(pkg.get_package_id(), target.get_name()))
+        self.requirements.find(&(pkg.get_package_id(), target))
             .map(|a| *a).unwrap_or(Target)
     }
 
+    /// Records that `target` of `pkg` is going to be compiled for `kind`,
+    /// returning whether it hadn't been already.
+    ///
+    /// A package reachable along several paths of the dependency graph must
+    /// still only be compiled once for each platform it is needed on.
+    pub fn schedule(&mut self, pkg: &'a Package, target: &'a Target,
+                    kind: Kind) -> bool {
+        self.scheduled.insert((pkg.get_package_id(), target, kind))
+    }
+
     /// Switch this context over to being the primary compilation unit,
     /// affecting the output of `dest()` and such.
     pub fn primary(&mut self) {
@@ -174,17 +188,22 @@ impl<'a, 'b> Context<'a, 'b> {
         (pair.ref0().as_slice(), pair.ref1().as_slice())
     }
 
+    /// Return the suffix of executables for either the host or the target.
+    fn exe(&self, kind: Kind) -> &str {
+        if kind == KindPlugin {self.host_exe.as_slice()} else {self.target_exe.as_slice()}
+    }
+
     /// Return the exact filename of the target.
     pub fn target_filenames(&self, target: &Target) -> Vec<String> {
         let stem = target.file_stem();
+        let plugin = target.get_profile().is_plugin();
+        let kind = if plugin {KindPlugin} else {KindTarget};
 
         let mut ret = Vec::new();
         if target.is_bin() || target.get_profile().is_test() {
-            ret.push(format!("{}{}", stem, self.target_exe));
+            ret.push(format!("{}{}", stem, self.exe(kind)));
         } else {
             if target.is_dylib() {
-                let plugin = target.get_profile().is_plugin();
-                let kind = if plugin {KindPlugin} else {KindTarget};
                 let (prefix, suffix) = self.dylib(kind);
                 ret.push(format!("{}{}{}", prefix, stem, suffix));
             }
@@ -196,17 +215,52 @@ impl<'a, 'b> Context<'a, 'b> {
         return ret;
     }
 
+    /// Returns pairs of an output file of `target` and the name it is also made
+    /// available under with the hash left out of it, so the artifacts of the
+    /// root package can be found at a predictable location.
+    pub fn unhashed_filenames(&self, target: &Target) -> Vec<(String, String)> {
+        if !self.primary || target.get_profile().is_test() ||
+           target.get_metadata().is_none() {
+            return Vec::new()
+        }
+        let stem = target.file_stem();
+        self.target_filenames(target).move_iter().map(|f| {
+            let plain = f.replace(stem.as_slice(), target.get_name());
+            (f, plain)
+        }).collect()
+    }
+
     /// For a package, return all targets which are registered as dependencies
-    /// for that package.
+    /// for that package, leaving out its build dependencies.
     pub fn dep_targets(&self, pkg: &Package) -> Vec<(&'a Package, &'a Target)> {
+        self.deps_of(pkg, false)
+    }
+
+    /// For a package, return the targets of its build dependencies, which are
+    /// only available to its build commands.
+    pub fn build_dep_targets(&self, pkg: &Package)
+                             -> Vec<(&'a Package, &'a Target)> {
+        self.deps_of(pkg, true)
+    }
+
+    fn deps_of(&self, pkg: &Package,
+               build: bool) -> Vec<(&'a Package, &'a Target)> {
         let deps = match self.resolve.deps(pkg.get_package_id()) {
             None => return vec!(),
             Some(deps) => deps,
         };
         deps.map(|pkg_id| self.get_package(pkg_id))
-        .filter_map(|pkg| {
-            pkg.get_targets().iter().find(|&t| self.is_relevant_target(t))
-               .map(|t| (pkg, t))
+        .filter(|dep| {
+            find_dependency(pkg, dep.get_package_id(), build).is_some()
+        })
+        .filter_map(|dep| {
+            dep.get_targets().iter().find(|&t| {
+                if build {
+                    self.is_relevant_build_target(t)
+                } else {
+                    self.is_relevant_target(t)
+                }
+            }).map(|t| (dep, t))
         })
         .collect()
     }
@@ -221,6 +275,8 @@ impl<'a, 'b> Context<'a, 'b> {
     pub fn is_relevant_target(&self, target: &Target) -> bool {
         target.is_lib() && match self.env {
             "doc" | "test" => target.get_profile().is_compile(),
+            // dependencies of benchmarks are built optimized
+            "bench" => target.get_profile().get_env() == "release",
             // doc-all == document everything, so look for doc targets and
             //            compile targets in dependencies
             "doc-all" => target.get_profile().is_compile() ||
@@ -228,6 +284,17 @@ impl<'a, 'b> Context<'a, 'b> {
             _ => target.get_profile().get_env() == self.env,
         }
     }
+
+    /// Like `is_relevant_target`, but for build dependencies, which are
+    /// compiled to be used by build commands even when only documenting.
+    pub fn is_relevant_build_target(&self, target: &Target) -> bool {
+        match self.env {
+            "doc" | "doc-all" => {
+                target.is_lib() && target.get_profile().is_compile()
+            }
+            _ => self.is_relevant_target(target),
+        }
+    }
 }
 
 impl PlatformRequirement {
@@ -239,3 +306,21 @@ impl PlatformRequirement {
         }
     }
 }
+
+/// Finds the dependency, or build dependency if `build` is set, of `pkg`
+/// which resolved to the package `id`.
+///
+/// A dependency from the same source with a matching version requirement is
+/// preferred, while one sharing only its name is the fallback for packages
+/// overridden or replaced by a package from elsewhere.
+pub fn find_dependency<'a>(pkg: &'a Package, id: &PackageId,
+                           build: bool) -> Option<&'a Dependency> {
+    let candidates: Vec<&Dependency> = pkg.get_dependencies().iter().filter(|d| {
+        d.get_name() == id.get_name() && d.is_build() == build
+    }).collect();
+    match candidates.iter().find(|d| d.matches_id(id)) {
+        Some(d) => Some(*d),
+        None if candidates.len() == 1 => Some(candidates[0]),
+        None => None,
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::io::{fs, File};
+use std::sync::{Arc, Mutex};
+
+use core::PackageId;
+use util::{CargoResult, human, ChainError};
+
+use super::job_queue::{JobOutput, OutputWarning, OutputVerbose};
+
+/// The directives a package's build commands printed on stdout.
+///
+/// Lines of the form `cargo:rustc-flags=<flags>` add flags to every rustc
+/// invocation for the package, and the `-L` flags among them to those of the
+/// packages depending on it as well, so that they can link against it. Any
+/// other `cargo:KEY=VALUE` line is metadata handed to the build commands of
+/// dependent packages, except that keys starting with `rustc-` are reserved
+/// for Cargo.
+#[deriving(Clone, Show)]
+pub struct BuildOutput {
+    pub rustc_flags: Vec<String>,
+    pub metadata: Vec<(String, String)>,
+    /// Directives which were ignored, and why
+    pub warnings: Vec<String>,
+}
+
+/// The outputs of the build commands of each package built so far, shared
+/// between the jobs which need them.
+pub type BuildState = Arc<Mutex<HashMap<PackageId, BuildOutput>>>;
+
+impl BuildOutput {
+    /// Parses the stdout of the build commands of package `pkg`, ignoring
+    /// any line which isn't a directive.
+    pub fn parse(input: &str, pkg: &str) -> BuildOutput {
+        let mut out = BuildOutput {
+            rustc_flags: Vec::new(),
+            metadata: Vec::new(),
+            warnings: Vec::new(),
+        };
+        for line in input.lines_any() {
+            if !line.starts_with("cargo:") { continue }
+
+            let mut parts = line.slice_from(6).splitn('=', 1);
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim()),
+                _ => {
+                    out.warnings.push(format!("ignoring invalid output in \
+                                               build command of `{}`: `{}`",
+                                              pkg, line));
+                    continue
+                }
+            };
+            if key == "rustc-flags" {
+                out.rustc_flags.extend(value.words().map(|s| s.to_string()));
+            } else if key.starts_with("rustc-") {
+                out.warnings.push(format!("ignoring unknown directive `{}` in \
+                                           build command of `{}`", key, pkg));
+            } else {
+                out.metadata.push((key.to_string(), value.to_string()));
+            }
+        }
+        out
+    }
+
+    /// The `-L` flags among `rustc_flags`, which packages depending on this
+    /// one need to link against it.
+    pub fn library_paths(&self) -> Vec<String> {
+        let mut ret = Vec::new();
+        let mut flags = self.rustc_flags.iter();
+        loop {
+            match flags.next() {
+                Some(flag) if flag.as_slice() == "-L" => {
+                    ret.push(flag.clone());
+                    ret.extend(flags.next().map(|dir| dir.clone()).move_iter());
+                }
+                Some(flag) if flag.as_slice().starts_with("-L") => {
+                    ret.push(flag.clone());
+                }
+                Some(..) => {}
+                None => break,
+            }
+        }
+        ret
+    }
+
+    /// Returns the environment variables which expose the metadata of
+    /// package `pkg` to the build commands of its dependents.
+    pub fn env(&self, pkg: &str) -> Vec<(String, String)> {
+        self.metadata.iter().map(|&(ref key, ref value)| {
+            (format!("DEP_{}_{}", envify(pkg), envify(key.as_slice())),
+             value.clone())
+        }).collect()
+    }
+}
+
+fn envify(s: &str) -> String {
+    s.chars().map(|c| c.to_uppercase())
+             .map(|c| if c == '-' {'_'} else {c})
+             .collect()
+}
+
+/// Parses the `stdout` of the build commands of `id`, recording it at `loc`
+/// so that it can be restored when the build commands are fresh.
+///
+/// Ignored directives are sent to `out` as warnings, and the lines which
+/// aren't directives to be printed with `--verbose`.
+pub fn record(state: &BuildState, id: PackageId, stdout: &str,
+              loc: &Path, out: &Sender<JobOutput>) -> CargoResult<()> {
+    let output = BuildOutput::parse(stdout, id.get_name());
+    for warning in output.warnings.iter() {
+        out.send(OutputWarning(warning.clone()));
+    }
+    for line in stdout.lines_any().filter(|l| !l.starts_with("cargo:")) {
+        out.send(OutputVerbose(line.to_string()));
+    }
+    try!(File::create(loc).write_str(stdout).chain_error(|| {
+        human(format!("failed to write `{}`", loc.display()))
+    }));
+    state.lock().insert(id, output);
+    Ok(())
+}
+
+/// Restores the output recorded by the previous run of the build commands of
+/// `id`, moving it from `old` to `new`.
+pub fn restore(state: &BuildState, id: PackageId, old: &Path,
+               new: &Path) -> CargoResult<()> {
+    if old.exists() {
+        try!(fs::rename(old, new));
+    }
+    if !new.exists() { return Ok(()) }
+
+    let stdout = try!(File::open(new).read_to_string());
+    let output = BuildOutput::parse(stdout.as_slice(), id.get_name());
+    state.lock().insert(id, output);
+    Ok(())
+}
@@ -1,15 +1,20 @@
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::hash::sip::SipHasher;
 use std::io::{fs, File, UserRWX, BufferedReader};
+use std::mem;
+use std::os;
 
 use core::{Package, Target};
+use sources::path::last_modified_matching;
 use util;
-use util::hex::short_hash;
 use util::{CargoResult, Fresh, Dirty, Freshness, internal, Require, profile};
+use util::ChainError;
 
-use super::{Kind, KindTarget};
+use super::{Kind, KindTarget, KindPlugin};
 use super::job::Work;
 use super::context::Context;
+use super::layout::{pkg_hash, check_writable};
 
 /// A tuple result of the `prepare_foo` functions in this module.
 ///
@@ -36,11 +41,16 @@ pub type Preparation = (Freshness, Work, Work);
 /// understand what the inputs are to a target, so we drive rustc with the
 /// --dep-info flag to learn about all input files to a unit of compilation.
 ///
+/// The fingerprint also covers `cmd`, the full command line used to compile
+/// the target, so any change in the flags passed to the compiler (such as the
+/// optimization level, `--cfg`s, `--target` or the paths of `--extern` crates)
+/// causes the target to be rebuilt.
+///
 /// This function will calculate the fingerprint for a target and prepare the
 /// work necessary to either write the fingerprint or copy over all fresh files
 /// from the old directories to their new locations.
 pub fn prepare_target(cx: &mut Context, pkg: &Package, target: &Target,
-                      kind: Kind) -> CargoResult<Preparation> {
+                      kind: Kind, cmd: &str) -> CargoResult<Preparation> {
     let _p = profile::start(format!("fingerprint: {} / {}",
                                     pkg.get_package_id(), target));
     let (old, new) = dirs(cx, pkg, kind);
@@ -54,29 +64,55 @@ pub fn prepare_target(cx: &mut Context, pkg: &Package, target: &Target,
     // First bit of the freshness calculation, whether the dep-info file
     // indicates that the target is fresh.
     let (old_dep_info, new_dep_info) = dep_info_loc(cx, pkg, target, kind);
-    let are_files_fresh = doc || try!(calculate_target_fresh(pkg, &old_dep_info));
+    let (old_hashes, new_hashes) = hashes_loc(cx, pkg, target, kind);
+    let hash_contents = cx.config.hash_contents();
+    let are_files_fresh = doc || {
+        let hashes = if hash_contents {Some(&old_hashes)} else {None};
+        try!(calculate_target_fresh(cx, pkg, &old_dep_info, hashes))
+    };
 
     // Second bit of the freshness calculation, whether rustc itself and the
     // target are fresh.
     let rustc_fingerprint = if doc {
-        mk_fingerprint(cx, &(target, try!(calculate_pkg_fingerprint(cx, pkg))))
+        mk_fingerprint(cx, &(target, cmd,
+                             try!(calculate_pkg_fingerprint(cx, pkg))))
     } else {
-        mk_fingerprint(cx, target)
+        mk_fingerprint(cx, &(target, cmd))
     };
-    let is_rustc_fresh = try!(is_fresh(&old_loc, rustc_fingerprint.as_slice()));
+    let is_rustc_fresh = try!(is_fresh(cx, &old_loc,
+                                       rustc_fingerprint.as_slice()));
 
     let layout = cx.layout(kind);
-    let mut pairs = vec![(old_loc, new_loc.clone())];
+    let mut pairs = vec![(old_loc.clone(), new_loc.clone())];
+    let dep_info = new_dep_info.clone();
     if !target.get_profile().is_doc() {
         pairs.push((old_dep_info, new_dep_info));
+        if hash_contents && old_hashes.exists() {
+            pairs.push((old_hashes, new_hashes.clone()));
+        }
         pairs.extend(cx.target_filenames(target).iter().map(|filename| {
             let filename = filename.as_slice();
             ((layout.old_root().join(filename), layout.root().join(filename)))
         }));
     }
 
-    Ok(prepare(is_rustc_fresh && are_files_fresh, new_loc, rustc_fingerprint,
-               pairs))
+    let (freshness, dirty, fresh) = prepare(is_rustc_fresh && are_files_fresh,
+                                            old_loc, new_loc, rustc_fingerprint,
+                                            pairs);
+
+    // Record the contents of each input once the target has been rebuilt so
+    // later builds can tell whether a file with a newer mtime really changed.
+    let dirty = if hash_contents && !doc {
+        let root = pkg.get_root();
+        proc() {
+            try!(dirty());
+            write_hashes(&dep_info, &new_hashes, &root)
+        }
+    } else {
+        dirty
+    };
+
+    Ok((freshness, dirty, fresh))
 }
 
 /// Prepare the necessary work for the fingerprint of a build command.
@@ -94,8 +130,12 @@ pub fn prepare_target(cx: &mut Context, pkg: &Package, target: &Target,
 /// 3. A build command must itself provide a dep-info-like file stating how it
 ///    should be considered dirty or not.
 ///
-/// The currently implemented solution is option (1), although it is planned to
-/// migrate to option (2) in the near future.
+/// The currently implemented solution is option (2). The directory is the
+/// `build-dir` given in the manifest, falling back to the directory which
+/// contains the build command's program, and then to `build/`. If none of
+/// these exist within the package, the whole package is considered as in
+/// option (1). The build commands themselves are always part of the
+/// fingerprint.
 pub fn prepare_build_cmd(cx: &mut Context, pkg: &Package)
                          -> CargoResult<Preparation> {
     let _p = profile::start(format!("fingerprint build cmd: {}",
@@ -116,12 +156,23 @@ pub fn prepare_build_cmd(cx: &mut Context, pkg: &Package)
     let new_fingerprint = try!(calculate_build_cmd_fingerprint(cx, pkg));
     let new_fingerprint = mk_fingerprint(cx, &new_fingerprint);
 
-    let is_fresh = try!(is_fresh(&old_loc, new_fingerprint.as_slice()));
+    let is_fresh = try!(is_fresh(cx, &old_loc, new_fingerprint.as_slice()));
     let layout = cx.layout(kind);
-    let pairs = vec![(old_loc, new_loc.clone()),
-                     (layout.old_native(pkg), layout.native(pkg))];
 
-    Ok(prepare(is_fresh, new_loc, new_fingerprint, pairs))
+    // The output directory of the build command may have been removed
+    // without its fingerprint, in which case it has to run again to bring it
+    // back. A build command which writes nothing still leaves it behind.
+    let old_native = layout.old_native(pkg);
+    let has_output = old_native.is_dir();
+    if is_fresh && !has_output {
+        debug!("stale: {} -- no build command output", old_native.display());
+    }
+
+    let pairs = vec![(old_loc.clone(), new_loc.clone()),
+                     (old_native, layout.native(pkg))];
+
+    Ok(prepare(is_fresh && has_output, old_loc, new_loc, new_fingerprint,
+               pairs))
 }
 
 /// Prepare work for when a package starts to build
@@ -130,24 +181,98 @@ pub fn prepare_init(cx: &mut Context, pkg: &Package, kind: Kind)
     let (_, new1) = dirs(cx, pkg, kind);
     let new2 = new1.clone();
 
-    let work1 = proc() { try!(fs::mkdir(&new1, UserRWX)); Ok(()) };
-    let work2 = proc() { try!(fs::mkdir(&new2, UserRWX)); Ok(()) };
+    let work1 = proc() { mkdir(&new1) };
+    let work2 = proc() { mkdir(&new2) };
 
     (work1, work2)
 }
 
+fn mkdir(dir: &Path) -> CargoResult<()> {
+    check_writable(fs::mkdir(dir, UserRWX), "create directory", dir)
+}
+
+/// Prepare the work to copy the dep-info of a target into the directory
+/// requested with `--dep-info-out`, if any.
+///
+/// The copy is named after the target so external build systems can find it,
+/// and the paths inside of it are made relative to the current directory where
+/// possible. As with `prepare_init`, the same work is returned twice so it can
+/// run whether the target ends up being fresh or dirty.
+pub fn prepare_dep_info_out(cx: &Context, pkg: &Package, target: &Target,
+                            kind: Kind) -> Option<(Work, Work)> {
+    let dir = match cx.config.dep_info_out() {
+        Some(dir) if !target.get_profile().is_doc() => dir.clone(),
+        _ => return None,
+    };
+    let prefix = if kind == KindPlugin {"host-"} else {""};
+    let dst1 = dir.join(format!("{}{}.d", prefix, filename(target)));
+    let (_, src1) = dep_info_loc(cx, pkg, target, kind);
+    let root1 = pkg.get_root();
+    let (src2, dst2, root2) = (src1.clone(), dst1.clone(), root1.clone());
+
+    let work1 = proc() { copy_dep_info(&src1, &dst1, &root1) };
+    let work2 = proc() { copy_dep_info(&src2, &dst2, &root2) };
+
+    Some((work1, work2))
+}
+
+fn copy_dep_info(src: &Path, dst: &Path, pkg_root: &Path) -> CargoResult<()> {
+    let contents = try!(File::open(src).read_to_string());
+    let cwd = try!(util::realpath(&os::getcwd()));
+    let pkg_root = try!(util::realpath(pkg_root));
+
+    let mut out = String::new();
+    for line in contents.as_slice().lines() {
+        let words = split_dep_info(line).iter().map(|word| {
+            let word = word.as_slice();
+            let (file, colon) = if word.ends_with(":") {
+                (word.slice_to(word.len() - 1), ":")
+            } else {
+                (word, "")
+            };
+            let file = dep_info_path(&pkg_root, file);
+            let file = if cwd.is_ancestor_of(&file) {
+                file.path_relative_from(&cwd).unwrap_or(file)
+            } else {
+                file
+            };
+            let file = file.display().to_string();
+            format!("{}{}", file.replace(" ", "\\ "), colon)
+        }).collect::<Vec<String>>();
+        out.push_str(words.connect(" ").as_slice());
+        out.push_char('\n');
+    }
+
+    try!(File::create(dst).write_str(out.as_slice()));
+    Ok(())
+}
+
 /// Given the data to build and write a fingerprint, generate some Work
 /// instances to actually perform the necessary work.
-fn prepare(is_fresh: bool, loc: Path, fingerprint: String,
+///
+/// Neither unit of work touches a fingerprint which hasn't changed: when
+/// fresh it is moved over from `old_loc` like all of the other outputs, and
+/// when dirty it is only written if it differs from the one at `old_loc`, so
+/// that its modification time is left alone.
+fn prepare(is_fresh: bool, old_loc: Path, loc: Path, fingerprint: String,
            to_copy: Vec<(Path, Path)>) -> Preparation {
     let write_fingerprint = proc() {
-        try!(File::create(&loc).write_str(fingerprint.as_slice()));
-        Ok(())
+        let unchanged = File::open(&old_loc).read_to_string().map(|old| {
+            old == fingerprint
+        }).unwrap_or(false);
+        if unchanged {
+            return move_file(&old_loc, &loc)
+        }
+        check_writable(File::create(&loc).write_str(fingerprint.as_slice()),
+                       "write fingerprint", &loc)
     };
 
+    // Output which went missing since the last build, such as a partially
+    // cleaned native directory, simply isn't carried over.
     let move_old = proc() {
         for &(ref src, ref dst) in to_copy.iter() {
-            try!(fs::rename(src, dst));
+            if src == dst || !src.exists() { continue }
+            try!(move_file(src, dst));
         }
         Ok(())
     };
@@ -155,10 +280,16 @@ fn prepare(is_fresh: bool, loc: Path, fingerprint: String,
     (if is_fresh {Fresh} else {Dirty}, write_fingerprint, move_old)
 }
 
+fn move_file(src: &Path, dst: &Path) -> CargoResult<()> {
+    fs::rename(src, dst).chain_error(|| {
+        internal(format!("failed to move `{}` to `{}`", src.display(),
+                         dst.display()))
+    })
+}
+
 /// Return the (old, new) location for fingerprints for a package
 pub fn dirs(cx: &Context, pkg: &Package, kind: Kind) -> (Path, Path) {
-    let dirname = format!("{}-{}", pkg.get_name(),
-                          short_hash(pkg.get_package_id()));
+    let dirname = format!("{}-{}", pkg.get_name(), pkg_hash(pkg));
     let dirname = dirname.as_slice();
     let layout = cx.layout(kind);
     let layout = layout.proxy();
@@ -173,13 +304,38 @@ pub fn dep_info_loc(cx: &Context, pkg: &Package, target: &Target,
     (old.join(filename.as_slice()), new.join(filename))
 }
 
-fn is_fresh(loc: &Path, new_fingerprint: &str) -> CargoResult<bool> {
+/// Returns the (old, new) location for the recorded stdout of the build
+/// commands of a package.
+pub fn build_output_loc(cx: &Context, pkg: &Package) -> (Path, Path) {
+    let (old, new) = dirs(cx, pkg, KindTarget);
+    (old.join("build-output"), new.join("build-output"))
+}
+
+/// Returns the (old, new) location for the file recording the hashes of the
+/// contents of each input of a target.
+fn hashes_loc(cx: &Context, pkg: &Package, target: &Target,
+              kind: Kind) -> (Path, Path) {
+    let (old, new) = dirs(cx, pkg, kind);
+    let filename = format!("hashes-{}", filename(target));
+    (old.join(filename.as_slice()), new.join(filename))
+}
+
+fn is_fresh(cx: &mut Context, loc: &Path,
+            new_fingerprint: &str) -> CargoResult<bool> {
     let mut file = match File::open(loc) {
         Ok(file) => file,
         Err(..) => return Ok(false),
     };
 
-    let old_fingerprint = try!(file.read_to_string());
+    let old_fingerprint = match file.read_to_string() {
+        Ok(fingerprint) => fingerprint,
+        Err(e) => return corrupt(cx, "fingerprint", loc, e.to_string()),
+    };
+    if old_fingerprint.is_empty() ||
+       !old_fingerprint.as_slice().chars().all(|c| c.is_digit_radix(16)) {
+        return corrupt(cx, "fingerprint", loc,
+                       "not a fingerprint".to_string())
+    }
 
     log!(5, "old fingerprint: {}", old_fingerprint);
     log!(5, "new fingerprint: {}", new_fingerprint);
@@ -187,45 +343,243 @@ fn is_fresh(loc: &Path, new_fingerprint: &str) -> CargoResult<bool> {
     Ok(old_fingerprint.as_slice() == new_fingerprint)
 }
 
+/// Called when a fingerprint or dep-info file exists but can't be understood.
+///
+/// The target is reported as not fresh so it is safely rebuilt, but unlike a
+/// missing file this is noted to the user in verbose mode so a damaged cache
+/// can be told apart from a genuine change.
+fn corrupt(cx: &mut Context, what: &str, loc: &Path,
+           why: String) -> CargoResult<bool> {
+    debug!("corrupt {}: {} -- {}", what, loc.display(), why);
+    try!(cx.config.shell().verbose(|shell| {
+        shell.warn(format!("{} `{}` is corrupt ({}), rebuilding", what,
+                           loc.display(), why))
+    }));
+    Ok(false)
+}
+
 /// Frob in the necessary data from the context to generate the real
-/// fingerprint.
+/// fingerprint. Both the compiler's path and its version are included so that
+/// switching compilers rebuilds everything.
 fn mk_fingerprint<T: Hash>(cx: &Context, data: &T) -> String {
     let hasher = SipHasher::new_with_keys(0,0);
-    util::to_hex(hasher.hash(&(&cx.rustc_version, data)))
+    util::to_hex(hasher.hash(&(cx.config.rustc(), &cx.rustc_version, data)))
 }
 
-fn calculate_target_fresh(pkg: &Package, dep_info: &Path) -> CargoResult<bool> {
+/// Checks whether all inputs listed in `dep_info` are older than it.
+///
+/// If `hashes` is given, inputs whose mtime is not older than the dep-info
+/// file are considered fresh as long as their contents hash to the value
+/// recorded for them in that file.
+fn calculate_target_fresh(cx: &mut Context, pkg: &Package, dep_info: &Path,
+                          hashes: Option<&Path>) -> CargoResult<bool> {
+    let deps = match dep_info_files(dep_info) {
+        Ok(Some(deps)) => deps,
+        Ok(None) => return Ok(false),
+        Err(e) => return corrupt(cx, "dep-info", dep_info, e.to_string()),
+    };
+    let mtime = try!(fs::stat(dep_info)).modified;
+    let hashes = match hashes {
+        Some(hashes) => Some(try!(read_hashes(hashes))),
+        None => None,
+    };
+    // Sources generated into the target directory are compared by mtime like
+    // any other input, but they may be cleaned away at any point without the
+    // package changing, so missing ones don't make the target stale.
+    let generated = [KindPlugin, KindTarget].iter().map(|&kind| {
+        cx.layout(kind).proxy().dest().clone()
+    }).collect::<Vec<Path>>();
+
+    for file in deps.iter() {
+        let file = file.as_slice();
+        let path = dep_info_path(&pkg.get_root(), file);
+        let stat = match fs::stat(&path) {
+            Ok(stat) => stat,
+            Err(..) if generated.iter().any(|d| d.is_ancestor_of(&path)) => {
+                debug!("missing generated input: {}", file);
+                continue
+            }
+            Err(..) => { debug!("stale: {} -- missing", file); return Ok(false) }
+        };
+        if stat.modified < mtime { continue }
+
+        let fresh = match hashes {
+            None => stat.modified == mtime,
+            Some(ref hashes) => {
+                let actual = try!(hash_file(&path));
+                hashes.find_equiv(&file) == Some(&actual)
+            }
+        };
+        if !fresh {
+            debug!("stale: {} -- {} vs {}", file, stat.modified, mtime);
+            return Ok(false)
+        }
+    }
+
+    Ok(true)
+}
+
+/// Returns the list of input files recorded in a dep-info file, or `None` if
+/// the file does not exist. A file which exists but can't be understood is an
+/// error.
+fn dep_info_files(dep_info: &Path) -> CargoResult<Option<Vec<String>>> {
+    if !dep_info.exists() { return Ok(None) }
+
     let line = match BufferedReader::new(File::open(dep_info)).lines().next() {
         Some(Ok(line)) => line,
-        _ => return Ok(false),
+        Some(Err(e)) => return Err(internal(e.to_string())),
+        None => return Err(internal("the file is empty")),
     };
     let line = line.as_slice();
-    let mtime = try!(fs::stat(dep_info)).modified;
     let pos = try!(line.find_str(": ").require(|| {
-        internal(format!("dep-info not in an understood format: {}",
-                         dep_info.display()))
+        internal("not in an understood format")
     }));
-    let deps = line.slice_from(pos + 2);
-
-    for file in deps.split(' ').map(|s| s.trim()).filter(|s| !s.is_empty()) {
-        match fs::stat(&pkg.get_root().join(file)) {
-            Ok(stat) if stat.modified <= mtime => {}
-            Ok(stat) => {
-                debug!("stale: {} -- {} vs {}", file, stat.modified, mtime);
-                return Ok(false)
+    Ok(Some(split_dep_info(line.slice_from(pos + 2))))
+}
+
+/// Splits a line of a dep-info file into the file names on it, unescaping
+/// the spaces rustc escapes as `\ ` within a name.
+fn split_dep_info(line: &str) -> Vec<String> {
+    let mut ret = Vec::new();
+    let mut cur = String::new();
+    let mut chars = line.trim().chars().peekable();
+    loop {
+        match chars.next() {
+            Some('\\') if chars.peek() == Some(&' ') => {
+                chars.next();
+                cur.push_char(' ');
             }
-            _ => { debug!("stale: {} -- missing", file); return Ok(false) }
+            Some(' ') => {
+                if !cur.is_empty() {
+                    ret.push(mem::replace(&mut cur, String::new()));
+                }
+            }
+            Some(c) => cur.push_char(c),
+            None => break,
         }
     }
+    if !cur.is_empty() { ret.push(cur) }
+    ret
+}
 
-    Ok(true)
+/// Finds the file named by `file` in a dep-info file.
+///
+/// rustc runs in the package root, so relative names are relative to it, and
+/// a name reaching outside of it through `..`, such as an `include!` of a
+/// file in a sibling directory, is normalized to the file's real location.
+/// Absolute names are used as they are.
+fn dep_info_path(root: &Path, file: &str) -> Path {
+    let file = Path::new(file);
+    if file.is_absolute() { file } else { root.join(file) }
+}
+
+fn hash_file(path: &Path) -> CargoResult<String> {
+    let contents = try!(File::open(path).read_to_end());
+    let hasher = SipHasher::new_with_keys(0,0);
+    Ok(util::to_hex(hasher.hash(&contents)))
+}
+
+/// Reads the `<hash> <file>` lines written by `write_hashes`. A missing file
+/// simply has no hashes in it.
+fn read_hashes(loc: &Path) -> CargoResult<HashMap<String, String>> {
+    let mut ret = HashMap::new();
+    if !loc.exists() { return Ok(ret) }
+
+    let contents = try!(File::open(loc).read_to_string());
+    for line in contents.as_slice().lines() {
+        let mut parts = line.splitn(' ', 1);
+        match (parts.next(), parts.next()) {
+            (Some(hash), Some(file)) => {
+                ret.insert(file.to_string(), hash.to_string());
+            }
+            _ => {}
+        }
+    }
+    Ok(ret)
 }
 
+fn write_hashes(dep_info: &Path, loc: &Path, root: &Path) -> CargoResult<()> {
+    let deps = match try!(dep_info_files(dep_info)) {
+        Some(deps) => deps,
+        None => return Ok(()),
+    };
+
+    let mut out = String::new();
+    for file in deps.iter() {
+        let hash = try!(hash_file(&dep_info_path(root, file.as_slice())));
+        out.push_str(format!("{} {}\n", hash, file).as_slice());
+    }
+    try!(File::create(loc).write_str(out.as_slice()));
+    Ok(())
+}
+
+/// Environment variables which commonly influence the output of build
+/// commands, in addition to any listed in the manifest's `build-env`.
+static BUILD_ENV: &'static [&'static str] = &[
+    "CC", "CXX", "CFLAGS", "CXXFLAGS", "LD_LIBRARY_PATH",
+];
+
 fn calculate_build_cmd_fingerprint(cx: &Context, pkg: &Package)
-                                   -> CargoResult<String> {
-    // TODO: this should be scoped to just the `build` directory, not the entire
-    // package.
-    calculate_pkg_fingerprint(cx, pkg)
+        -> CargoResult<(String, Vec<String>, Vec<(String, Option<String>)>)> {
+    // Packages from other sources never change underneath us, so their
+    // source's fingerprint already covers everything.
+    let files = if pkg.get_package_id().get_source_id().is_path() {
+        match build_dir(pkg) {
+            Some(dir) => {
+                let manifest = pkg.get_manifest();
+                let prefix = dir.path_relative_from(&pkg.get_root()).unwrap();
+                try!(last_modified_matching(&dir, |path| {
+                    manifest.includes_file(&prefix.join(path))
+                })).to_string()
+            }
+            None => try!(calculate_pkg_fingerprint(cx, pkg)),
+        }
+    } else {
+        try!(calculate_pkg_fingerprint(cx, pkg))
+    };
+    let cmds = pkg.get_manifest().get_build().to_vec();
+
+    // An unset variable is recorded as `None`, so setting it to the empty
+    // string still counts as a change.
+    let mut names: Vec<String> = BUILD_ENV.iter().map(|s| s.to_string())
+                                          .collect();
+    names.push_all(pkg.get_manifest().get_build_env());
+    names.sort();
+    names.dedup();
+    let env = names.move_iter().map(|name| {
+        let value = os::getenv(name.as_slice());
+        (name, value)
+    }).collect();
+
+    Ok((files, cmds, env))
+}
+
+/// Finds the directory whose contents the build commands of `pkg` depend on,
+/// or `None` if the whole package should be considered.
+fn build_dir(pkg: &Package) -> Option<Path> {
+    let root = pkg.get_root();
+    match pkg.get_manifest().get_build_dir() {
+        Some(dir) => return Some(root.join(dir)),
+        None => {}
+    }
+
+    // A program referenced relative to the package, such as `src/build.sh`,
+    // implies that its directory holds the build command's inputs.
+    let program = pkg.get_manifest().get_build().iter().filter_map(|cmd| {
+        cmd.as_slice().words().next()
+    }).next();
+    match program.map(|p| Path::new(p)) {
+        Some(ref program) if program.is_relative() => {
+            let dir = root.join(program.dir_path());
+            if dir != root && root.is_ancestor_of(&dir) && dir.is_dir() {
+                return Some(dir)
+            }
+        }
+        _ => {}
+    }
+
+    let dir = root.join("build");
+    if dir.is_dir() { Some(dir) } else { None }
 }
 
 fn calculate_pkg_fingerprint(cx: &Context, pkg: &Package) -> CargoResult<String> {
@@ -1,6 +1,11 @@
 use util::{CargoResult, Fresh, Dirty, Freshness};
 
-pub struct Job { dirty: Work, fresh: Work }
+pub struct Job {
+    dirty: Work,
+    fresh: Work,
+    desc: Option<String>,
+    documentation: bool,
+}
 
 pub type Work = proc():Send -> CargoResult<()>;
 
@@ -8,7 +13,28 @@ impl Job {
     /// Create a new job representing a unit of work.
     pub fn new(dirty: proc():Send -> CargoResult<()>,
                fresh: proc():Send -> CargoResult<()>) -> Job {
-        Job { dirty: dirty, fresh: fresh }
+        Job { dirty: dirty, fresh: fresh, desc: None, documentation: false }
+    }
+
+    /// Describes what this job builds, such as "bin `foo`", for when it
+    /// fails.
+    pub fn describe(mut self, desc: String) -> Job {
+        self.desc = Some(desc);
+        self
+    }
+
+    pub fn get_desc(&self) -> Option<&str> {
+        self.desc.as_ref().map(|s| s.as_slice())
+    }
+
+    /// Marks this job as documenting a crate rather than compiling it.
+    pub fn documentation(mut self) -> Job {
+        self.documentation = true;
+        self
+    }
+
+    pub fn is_documentation(&self) -> bool {
+        self.documentation
     }
 
     /// Consumes this job by running it, returning the result of the
@@ -1,11 +1,16 @@
-use std::collections::HashMap;
-use term::color::YELLOW;
+use std::cmp::Equal;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomics::{AtomicBool, SeqCst};
+use term::color::{BLACK, YELLOW};
+use time;
 
 use core::{Package, PackageId, Resolve};
 use util::{Config, TaskPool, DependencyQueue, Fresh, Dirty, Freshness};
-use util::{CargoResult, Dependency, profile};
+use util::{CargoResult, Dependency, human, profile};
 
 use super::job::Job;
+use super::report_status;
 
 /// A management structure of the entire dependency graph to compile.
 ///
@@ -18,10 +23,17 @@ pub struct JobQueue<'a, 'b> {
                            (&'a Package, Vec<(Job, Freshness)>)>,
     tx: Sender<Message>,
     rx: Receiver<Message>,
+    output_tx: Sender<JobOutput>,
+    output_rx: Receiver<JobOutput>,
     resolve: &'a Resolve,
+    dev_deps: DevDeps<'a>,
     active: uint,
     pending: HashMap<(&'a PackageId, TargetStage), PendingBuild>,
     state: HashMap<&'a PackageId, Freshness>,
+    cancelled: Arc<AtomicBool>,
+    failures: Vec<String>,
+    /// Nanoseconds spent running the jobs of each package
+    timings: HashMap<&'a PackageId, u64>,
 }
 
 /// A helper structure for metadata about the state of a building package.
@@ -51,23 +63,49 @@ pub enum TargetStage {
     StageEnd,
 }
 
-type Message = (PackageId, TargetStage, Freshness, CargoResult<()>);
+/// Output which a job wants printed. Only the queue has access to the shell,
+/// so jobs hand it over to be printed once they've finished.
+pub enum JobOutput {
+    /// A warning, always printed to stderr
+    OutputWarning(String),
+    /// A line printed to stdout only with `--verbose`
+    OutputVerbose(String),
+}
+
+type Message = (PackageId, TargetStage, Freshness, Option<String>, u64,
+                CargoResult<()>);
+
+/// Packages along with the dependencies of theirs which are only needed by
+/// their tests and benchmarks.
+type DevDeps<'a> = HashSet<(&'a PackageId, &'a PackageId)>;
 
 impl<'a, 'b> JobQueue<'a, 'b> {
     pub fn new(resolve: &'a Resolve, config: &mut Config) -> JobQueue<'a, 'b> {
         let (tx, rx) = channel();
+        let (output_tx, output_rx) = channel();
         JobQueue {
             pool: TaskPool::new(config.jobs()),
             queue: DependencyQueue::new(),
             tx: tx,
             rx: rx,
+            output_tx: output_tx,
+            output_rx: output_rx,
             resolve: resolve,
+            dev_deps: HashSet::new(),
             active: 0,
             pending: HashMap::new(),
             state: HashMap::new(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            failures: Vec::new(),
+            timings: HashMap::new(),
         }
     }
 
+    /// Returns a channel through which jobs can send output to be printed.
+    pub fn output(&self) -> Sender<JobOutput> {
+        self.output_tx.clone()
+    }
+
     pub fn enqueue(&mut self, pkg: &'a Package, stage: TargetStage,
                    jobs: Vec<(Job, Freshness)>) {
         // Record the freshness state of this package as dirty if any job is
@@ -76,10 +114,16 @@ impl<'a, 'b> JobQueue<'a, 'b> {
         let prev = self.state.find_or_insert(pkg.get_package_id(), fresh);
         *prev = prev.combine(fresh);
 
+        let id = pkg.get_package_id();
+        for dep in self.resolve.deps(id).move_iter().flat_map(|deps| deps) {
+            if is_dev_dep(pkg, dep) {
+                self.dev_deps.insert((id, dep));
+            }
+        }
+
         // Add the package to the dependency graph
-        self.queue.enqueue(&self.resolve, Fresh,
-                           (pkg.get_package_id(), stage),
-                           (pkg, jobs));
+        self.queue.enqueue(&(self.resolve, &self.dev_deps), Fresh,
+                           (id, stage), (pkg, jobs));
     }
 
     /// Execute all jobs necessary to build the dependency graph.
@@ -103,14 +147,20 @@ impl<'a, 'b> JobQueue<'a, 'b> {
                 }
             }
 
+            // With --keep-going, whatever depends on a failed job is left
+            // behind in the queue once everything else is done.
+            if self.active == 0 && self.failures.len() > 0 { break }
+
             // Now that all possible work has been scheduled, wait for a piece
             // of work to finish. If any package fails to build then we stop
             // scheduling work as quickly as possibly.
-            let (id, stage, fresh, result) = self.rx.recv();
+            let (id, stage, fresh, desc, elapsed, result) = self.rx.recv();
+            try!(self.print_output(config));
             let id = *self.state.keys().find(|&k| *k == &id).unwrap();
             self.active -= 1;
             match result {
                 Ok(()) => {
+                    *self.timings.find_or_insert(id, 0) += elapsed;
                     let state = self.pending.get_mut(&(id, stage));
                     state.amt -= 1;
                     state.fresh = state.fresh.combine(fresh);
@@ -119,6 +169,20 @@ impl<'a, 'b> JobQueue<'a, 'b> {
                     }
                 }
                 Err(e) => {
+                    if config.keep_going() {
+                        try!(config.shell().error(e.to_string()));
+                        self.failures.push(match desc {
+                            Some(desc) => format!("`{}` ({})", id.get_name(),
+                                                  desc),
+                            None => format!("`{}`", id.get_name()),
+                        });
+                        continue
+                    }
+
+                    // Jobs which haven't started yet are skipped, but those
+                    // already running are allowed to finish so their output
+                    // isn't cut off halfway through.
+                    self.cancelled.store(true, SeqCst);
                     if self.active > 0 {
                         try!(config.shell().say(
                                     "Build failed, waiting for other \
@@ -132,6 +196,58 @@ impl<'a, 'b> JobQueue<'a, 'b> {
 
         log!(5, "rustc jobs completed");
 
+        if self.failures.len() > 0 {
+            return Err(human(format!("Could not compile {}",
+                                     self.failures.connect(", "))))
+        }
+        if config.timings() {
+            try!(self.report_timings(config));
+        }
+        Ok(())
+    }
+
+    /// Prints the output which jobs have sent so far.
+    fn print_output(&self, config: &mut Config) -> CargoResult<()> {
+        loop {
+            match self.output_rx.try_recv() {
+                Ok(OutputWarning(msg)) => try!(config.shell().warn(msg)),
+                Ok(OutputVerbose(line)) => {
+                    try!(config.shell().verbose(|s| s.say(line.as_slice(), BLACK)))
+                }
+                Err(..) => return Ok(()),
+            }
+        }
+    }
+
+    /// Prints how long the jobs of each package took, slowest first, along
+    /// with the chain of dependencies which took longest to build one after
+    /// another.
+    fn report_timings(&self, config: &mut Config) -> CargoResult<()> {
+        let secs = |ns: u64| ns as f64 / 1_000_000_000.0;
+
+        let mut timings: Vec<(&PackageId, u64)> = self.timings.iter().map(|(&id, &ns)| {
+            (id, ns)
+        }).collect();
+        timings.sort_by(|&(a, a_ns), &(b, b_ns)| {
+            match b_ns.cmp(&a_ns) { Equal => a.cmp(b), ord => ord }
+        });
+        for &(id, ns) in timings.iter() {
+            try!(config.shell().status("Timing", format!("{:.2}s {}", secs(ns), id)));
+        }
+
+        let mut memo = HashMap::new();
+        let (mut total, mut path) = (0, Vec::new());
+        for &(id, _) in timings.iter() {
+            let (ns, p) = critical_path(id, self.resolve, &self.dev_deps,
+                                        &self.timings, &mut memo);
+            if ns > total { total = ns; path = p; }
+        }
+        if path.len() > 0 {
+            let names: Vec<String> = path.iter().map(|id| id.to_string()).collect();
+            try!(config.shell().status("Timing", format!("critical path {:.2}s: {}",
+                                                         secs(total),
+                                                         names.connect(" -> "))));
+        }
         Ok(())
     }
 
@@ -149,7 +265,15 @@ impl<'a, 'b> JobQueue<'a, 'b> {
         if stage == StageStart {
             let fresh = fresh.combine(*self.state.get(&pkg.get_package_id()));
             let msg = match fresh { Fresh => "Fresh", Dirty => "Compiling" };
-            try!(config.shell().status(msg, pkg));
+            try!(report_status(config, msg, pkg));
+        }
+        // Packages may be compiled without being documented, so those whose
+        // documentation is generated are reported once more.
+        if stage == StageLibraries &&
+           jobs.iter().any(|&(ref job, f)| {
+               job.is_documentation() && f.combine(fresh) == Dirty
+           }) {
+            try!(report_status(config, "Documenting", pkg));
         }
 
         // While the jobs are all running, we maintain some metadata about how
@@ -165,42 +289,99 @@ impl<'a, 'b> JobQueue<'a, 'b> {
             let fresh = job_freshness.combine(fresh);
             let my_tx = self.tx.clone();
             let id = id.clone();
+            let cancelled = self.cancelled.clone();
+            let desc = job.get_desc().map(|s| s.to_string());
             self.pool.execute(proc() {
-                my_tx.send((id, stage, fresh, job.run(fresh)));
+                let start = time::precise_time_ns();
+                let res = if cancelled.load(SeqCst) {
+                    Ok(())
+                } else {
+                    job.run(fresh)
+                };
+                let elapsed = time::precise_time_ns() - start;
+                my_tx.send((id, stage, fresh, desc, elapsed, res));
             });
         }
 
         // If no work was scheduled, make sure that a message is actually send
         // on this channel.
         if njobs == 0 {
-            self.tx.send((id, stage, fresh, Ok(())));
+            self.tx.send((id, stage, fresh, None, 0, Ok(())));
         }
         Ok(())
     }
 }
 
-impl<'a> Dependency<&'a Resolve> for (&'a PackageId, TargetStage) {
-    fn dependencies(&self, resolve: &&'a Resolve)
+/// Returns the chain of packages ending with `id` whose jobs took longest in
+/// total, each depending on the one before it, along with that total.
+/// Dev-dependencies are left out, as they may depend on `id` in turn.
+fn critical_path<'a>(id: &'a PackageId, resolve: &'a Resolve,
+                     dev_deps: &DevDeps<'a>,
+                     timings: &HashMap<&'a PackageId, u64>,
+                     memo: &mut HashMap<&'a PackageId, (u64, Vec<&'a PackageId>)>)
+                     -> (u64, Vec<&'a PackageId>) {
+    match memo.find(&id) {
+        Some(&(ns, ref path)) => return (ns, path.clone()),
+        None => {}
+    }
+
+    let (mut slowest, mut path) = (0, Vec::new());
+    for dep in resolve.deps(id).move_iter().flat_map(|deps| deps) {
+        if dep == id || dev_deps.contains(&(id, dep)) ||
+           !timings.contains_key(&dep) {
+            continue
+        }
+        let (ns, p) = critical_path(dep, resolve, dev_deps, timings, memo);
+        if ns > slowest { slowest = ns; path = p; }
+    }
+    path.push(id);
+    let total = slowest + *timings.get(&id);
+    memo.insert(id, (total, path.clone()));
+    (total, path)
+}
+
+/// Whether `pkg` only depends on `dep` for its tests and benchmarks.
+fn is_dev_dep(pkg: &Package, dep: &PackageId) -> bool {
+    let mut found = false;
+    for d in pkg.get_dependencies().iter() {
+        if d.get_name() != dep.get_name() || d.is_build() { continue }
+        if d.is_transitive() { return false }
+        found = true;
+    }
+    found
+}
+
+impl<'a, 'b> Dependency<(&'a Resolve, &'b DevDeps<'a>)>
+    for (&'a PackageId, TargetStage) {
+    fn dependencies(&self, cx: &(&'a Resolve, &'b DevDeps<'a>))
                     -> Vec<(&'a PackageId, TargetStage)> {
         // This implementation of `Dependency` is the driver for the structure
         // of the dependency graph of packages to be built. The "key" here is
         // a pair of the package being built and the stage that it's at.
         //
         // Each stage here lists dependencies on the previous stages except for
-        // the start state which depends on the ending state of all dependent
-        // packages (as determined by the resolve context).
+        // the start state which depends on the libraries of all dependent
+        // packages (as determined by the resolve context). Dev-dependencies
+        // are instead waited for by the binaries, which include the tests and
+        // benchmarks, so that they may depend on the library in turn.
+        let (resolve, dev_deps) = *cx;
         let (id, stage) = *self;
+        let deps = |dev: bool| {
+            resolve.deps(id).move_iter().flat_map(|a| a).filter(|dep| {
+                *dep != id && dev_deps.contains(&(id, *dep)) == dev
+            }).map(|dep| {
+                (dep, StageLibraries)
+            }).collect::<Vec<(&'a PackageId, TargetStage)>>()
+        };
         match stage {
-            StageStart => {
-                resolve.deps(id).move_iter().flat_map(|a| a).filter(|dep| {
-                    *dep != id
-                }).map(|dep| {
-                    (dep, StageEnd)
-                }).collect()
-            }
+            StageStart => deps(false),
             StageCustomBuild => vec![(id, StageStart)],
             StageLibraries => vec![(id, StageCustomBuild)],
-            StageBinaries => vec![(id, StageLibraries)],
+            StageBinaries => {
+                let mut ret = deps(true);
+                ret.push((id, StageLibraries));
+                ret
+            }
             StageEnd => vec![(id, StageBinaries), (id, StageLibraries)],
         }
     }
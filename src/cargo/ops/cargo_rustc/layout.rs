@@ -48,7 +48,7 @@ use std::io;
 use std::io::{fs, IoResult};
 
 use core::Package;
-use util::hex::short_hash;
+use util::{CargoResult, ChainError, human};
 
 pub struct Layout {
     root: Path,
@@ -81,42 +81,50 @@ impl Layout {
         }
     }
 
-    pub fn prepare(&mut self) -> IoResult<()> {
+    /// Moves the output of the previous build out of the way and creates the
+    /// directories of this one.
+    ///
+    /// This touches every directory the build writes to, so a build directory
+    /// which isn't writable is reported here, before anything is compiled.
+    pub fn prepare(&mut self) -> CargoResult<()> {
         if !self.root.exists() {
-            try!(fs::mkdir_recursive(&self.root, io::UserRWX));
+            try!(check_writable(fs::mkdir_recursive(&self.root, io::UserRWX),
+                                "create directory", &self.root));
         }
 
-        if self.old_deps.exists() {
-            try!(fs::rmdir_recursive(&self.old_deps));
+        for dir in [&self.old_deps, &self.old_root, &self.old_native,
+                    &self.old_fingerprint].iter() {
+            if dir.exists() {
+                try!(check_writable(fs::rmdir_recursive(*dir),
+                                    "remove directory", *dir));
+            }
         }
-        if self.old_root.exists() {
-            try!(fs::rmdir_recursive(&self.old_root));
-        }
-        if self.old_native.exists() {
-            try!(fs::rmdir_recursive(&self.old_native));
-        }
-        if self.old_fingerprint.exists() {
-            try!(fs::rmdir_recursive(&self.old_fingerprint));
-        }
-        if self.deps.exists() {
-            try!(fs::rename(&self.deps, &self.old_deps));
-        }
-        if self.native.exists() {
-            try!(fs::rename(&self.native, &self.old_native));
-        }
-        if self.fingerprint.exists() {
-            try!(fs::rename(&self.fingerprint, &self.old_fingerprint));
+        for &(dir, old) in [(&self.deps, &self.old_deps),
+                            (&self.native, &self.old_native),
+                            (&self.fingerprint, &self.old_fingerprint)].iter() {
+            if dir.exists() {
+                try!(check_writable(fs::rename(dir, old), "move directory", dir));
+            }
         }
 
-        try!(fs::mkdir(&self.deps, io::UserRWX));
-        try!(fs::mkdir(&self.native, io::UserRWX));
-        try!(fs::mkdir(&self.fingerprint, io::UserRWX));
-        try!(fs::mkdir(&self.old_root, io::UserRWX));
+        for dir in [&self.deps, &self.native, &self.fingerprint,
+                    &self.old_root].iter() {
+            try!(check_writable(fs::mkdir(*dir, io::UserRWX),
+                                "create directory", *dir));
+        }
 
-        for file in try!(fs::readdir(&self.root)).iter() {
+        let files = try!(check_writable(fs::readdir(&self.root),
+                                        "read directory", &self.root));
+        for file in files.iter() {
             if !file.is_file() { continue }
-
-            try!(fs::rename(file, &self.old_root.join(file.filename().unwrap())));
+            // Hidden files such as the lock and the rustc cache belong to the
+            // directory itself rather than to any one build.
+            if file.filename_str().map_or(false, |f| f.starts_with(".")) {
+                continue
+            }
+
+            let dst = self.old_root.join(file.filename().unwrap());
+            try!(check_writable(fs::rename(file, &dst), "move file", file));
         }
 
         Ok(())
@@ -137,10 +145,26 @@ impl Layout {
     pub fn old_fingerprint(&self) -> &Path { &self.old_fingerprint }
 
     fn native_name(&self, pkg: &Package) -> String {
-        format!("{}-{}", pkg.get_name(), short_hash(pkg.get_package_id()))
+        format!("{}-{}", pkg.get_name(), pkg_hash(pkg))
     }
 }
 
+/// Explains a failure to `what` the build output at `path`, which is most
+/// likely due to a lack of write permission.
+pub fn check_writable<T>(res: IoResult<T>, what: &str,
+                         path: &Path) -> CargoResult<T> {
+    res.chain_error(|| {
+        human(format!("failed to {} `{}`, check that you have permission to \
+                       write to it", what, path.display()))
+    })
+}
+
+/// The hash identifying `pkg` in the names of the directories and artifacts
+/// generated for it, matching the `-C metadata` its crates are compiled with.
+pub fn pkg_hash(pkg: &Package) -> String {
+    pkg.get_package_id().generate_metadata().metadata
+}
+
 impl Drop for Layout {
     fn drop(&mut self) {
         let _ = fs::rmdir_recursive(&self.old_deps);
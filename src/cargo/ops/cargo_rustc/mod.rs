@@ -1,13 +1,20 @@
 use std::collections::HashSet;
 use std::dynamic_lib::DynamicLibrary;
+use std::fmt::Show;
+use std::io;
 use std::io::{fs, UserRWX};
+use std::io::process::ProcessOutput;
 use std::os;
+use std::sync::Arc;
 use semver::Version;
+use serialize::json;
 
 use core::{SourceMap, Package, PackageId, PackageSet, Target, Resolve};
+use core::{MultiShell, Profile};
 use util;
-use util::{CargoResult, ProcessBuilder, CargoError, human, caused_human};
-use util::{Config, internal, ChainError, Fresh, profile};
+use util::{CargoResult, ProcessBuilder, ProcessError, CargoError, human};
+use util::caused_human;
+use util::{Config, internal, ChainError, Require, Fresh, profile};
 
 use self::job::{Job, Work};
 use self::job_queue::{JobQueue, StageStart, StageCustomBuild, StageLibraries};
@@ -15,14 +22,46 @@ use self::job_queue::{StageBinaries, StageEnd};
 use self::context::{Context, PlatformRequirement, Target, Plugin, PluginAndTarget};
 
 mod context;
+mod custom_build;
 mod fingerprint;
 mod job;
 mod job_queue;
 mod layout;
+mod rustc_info;
 
-#[deriving(PartialEq, Eq)]
+#[deriving(PartialEq, Eq, Hash)]
 enum Kind { KindPlugin, KindTarget }
 
+/// Runs the compiler invocations of a build, letting tools built on top of
+/// Cargo wrap or substitute them.
+///
+/// Each command is handed over fully built, including its `--extern`,
+/// `--out-dir` and `-L` flags. Output is captured with `exec_with_output`
+/// unless it can go straight to the terminal, in which case `exec` is used,
+/// or has to be passed on a line at a time, in which case
+/// `exec_with_streaming` is.
+pub trait Executor: Send + Sync {
+    fn exec(&self, cmd: ProcessBuilder, _pkg: &Package,
+            _target: &Target) -> Result<(), ProcessError> {
+        cmd.exec()
+    }
+
+    fn exec_with_streaming(&self, cmd: ProcessBuilder, _pkg: &Package,
+                           _target: &Target) -> Result<ProcessOutput, ProcessError> {
+        cmd.exec_with_streaming()
+    }
+
+    fn exec_with_output(&self, cmd: ProcessBuilder, _pkg: &Package,
+                        _target: &Target) -> Result<ProcessOutput, ProcessError> {
+        cmd.exec_with_output()
+    }
+}
+
+/// Runs each command as is.
+pub struct DefaultExecutor;
+
+impl Executor for DefaultExecutor {}
+
 // This is a temporary assert that ensures the consistency of the arguments
 // given the current limitations of Cargo. The long term fix is to have each
 // Target know the absolute path to the build location.
@@ -41,35 +80,68 @@ fn uniq_target_dest<'a>(targets: &[&'a Target]) -> Option<&'a str> {
     curr.unwrap()
 }
 
+/// Builds `targets` of `pkg` along with the given `targets` of its workspace
+/// `members`, and all their dependencies.
 pub fn compile_targets<'a>(env: &str, targets: &[&'a Target], pkg: &'a Package,
+                           members: &[(&'a Package, Vec<&'a Target>)],
                            deps: &PackageSet, resolve: &'a Resolve, sources: &'a SourceMap,
-                           config: &'a mut Config<'a>) -> CargoResult<()> {
-    if targets.is_empty() {
+                           config: &'a mut Config<'a>,
+                           exec: Arc<Box<Executor + Send + Sync>>) -> CargoResult<()> {
+    let all_targets = all_targets(targets, members);
+    if all_targets.is_empty() {
         return Ok(());
     }
 
     debug!("compile_targets; targets={}; pkg={}; deps={}", targets, pkg, deps);
 
-    let root = pkg.get_absolute_target_dir();
-    let dest = uniq_target_dest(targets).unwrap_or("");
+    let root = target_dir(config, pkg);
+    let dest = uniq_target_dest(all_targets.as_slice()).unwrap_or("");
     let host_layout = layout::Layout::new(root.join(dest));
     let target_layout = config.target().map(|target| {
         layout::Layout::new(root.join(target).join(dest))
     });
 
     let mut cx = try!(Context::new(env, resolve, sources, deps, config,
-                                   host_layout, target_layout));
+                                   host_layout, target_layout, exec));
     let mut queue = JobQueue::new(cx.resolve, cx.config);
 
     // First ensure that the destination directory exists
     try!(cx.prepare(pkg));
+    for &(member, _) in members.iter() {
+        cx.add_requirements(member);
+    }
+    match cx.config.dep_info_out() {
+        Some(dir) if !dir.exists() => {
+            try!(fs::mkdir_recursive(dir, UserRWX).chain_error(|| {
+                human(format!("could not create directory `{}`",
+                              dir.display()))
+            }));
+        }
+        _ => {}
+    }
 
     // Build up a list of pending jobs, each of which represent compiling a
     // particular package. No actual work is executed as part of this, that's
     // all done later as part of the `execute` function which will run
     // everything in order with proper parallelism.
+    //
+    // When only some workspace members are built rather than the root
+    // package, so are only their own dependencies.
+    let needed: Option<HashSet<PackageId>> = if targets.is_empty() {
+        Some(members.iter().flat_map(|&(member, _)| {
+            transitive_deps(resolve, member.get_package_id()).move_iter()
+        }).collect())
+    } else {
+        None
+    };
+    let mut compiled = HashSet::new();
     for dep in deps.iter() {
-        if dep == pkg { continue }
+        if dep == pkg || members.iter().any(|&(m, _)| m == dep) { continue }
+        match needed {
+            Some(ref needed) if !needed.contains(dep.get_package_id()) => continue,
+            _ => {}
+        }
+        if !compiled.insert(dep.get_package_id()) { continue }
 
         // Only compile lib targets for dependencies
         let targets = dep.get_targets().iter().filter(|target| {
@@ -81,11 +153,157 @@ pub fn compile_targets<'a>(env: &str, targets: &[&'a Target], pkg: &'a Package,
 
     cx.primary();
     try!(compile(targets, pkg, &mut cx, &mut queue));
+    for &(member, ref targets) in members.iter() {
+        try!(compile(targets.as_slice(), member, &mut cx, &mut queue));
+    }
 
     // Now that we've figured out everything that we're going to do, do it!
     queue.execute(cx.config)
 }
 
+/// Returns the files each of `targets` of `pkg` is built into, along with the
+/// target itself, without building anything.
+pub fn target_artifacts<'a>(env: &str, targets: &[&'a Target], pkg: &'a Package,
+                            members: &[(&'a Package, Vec<&'a Target>)],
+                            deps: &PackageSet, resolve: &'a Resolve,
+                            sources: &'a SourceMap, config: &'a mut Config<'a>)
+                            -> CargoResult<Vec<(Target, Vec<Path>)>> {
+    let targets = all_targets(targets, members);
+    if targets.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let root = target_dir(config, pkg);
+    let dest = uniq_target_dest(targets.as_slice()).unwrap_or("");
+    let host_layout = layout::Layout::new(root.join(dest));
+    let target_layout = config.target().map(|target| {
+        layout::Layout::new(root.join(target).join(dest))
+    });
+
+    let exec = Arc::new(box DefaultExecutor as Box<Executor + Send + Sync>);
+    let mut cx = try!(Context::new(env, resolve, sources, deps, config,
+                                   host_layout, target_layout, exec));
+    cx.primary();
+
+    Ok(targets.iter().map(|&target| {
+        let kind = if target.get_profile().is_plugin() {KindPlugin} else {KindTarget};
+        let dir = cx.layout(kind).root().clone();
+        let mut files: Vec<Path> = cx.target_filenames(target).iter().map(|f| {
+            dir.join(f.as_slice())
+        }).collect();
+        files.extend(cx.unhashed_filenames(target).move_iter().map(|(_, f)| {
+            dir.join(f)
+        }));
+        (target.clone(), files)
+    }).collect())
+}
+
+/// The targets of the root package followed by those of the workspace members.
+fn all_targets<'a>(targets: &[&'a Target],
+                   members: &[(&'a Package, Vec<&'a Target>)]) -> Vec<&'a Target> {
+    let mut ret = Vec::from_slice(targets);
+    for &(_, ref targets) in members.iter() {
+        ret.push_all(targets.as_slice());
+    }
+    ret
+}
+
+/// The directory build output goes in: the one configured for this build, or
+/// else the `target` directory of `pkg`.
+fn target_dir(config: &Config, pkg: &Package) -> Path {
+    match config.target_dir() {
+        Some(dir) => dir.clone(),
+        None => pkg.get_absolute_target_dir(),
+    }
+}
+
+/// Returns the target triple of the host, as reported by `rustc`, using the
+/// probes cached in `target_dir` if they are still valid.
+pub fn host_triple(rustc: &str, target_dir: &Path) -> CargoResult<String> {
+    let mut info = rustc_info::RustcInfo::load(target_dir);
+    let version = try!(info.version(rustc));
+    try!(info.save());
+    parse_host_triple(version.as_slice())
+}
+
+/// Finds the host triple in the output of `rustc -v verbose`.
+fn parse_host_triple(version: &str) -> CargoResult<String> {
+    version.lines().filter_map(|line| {
+        if line.starts_with("host: ") {
+            Some(line.slice_from(6).trim().to_string())
+        } else {
+            None
+        }
+    }).next().require(|| {
+        internal("rustc -v verbose did not report the host triple")
+    })
+}
+
+/// Removes the outputs of `pkg` from the output directory `dest` of `root`:
+/// the files of each of its targets, its fingerprints and its native output.
+pub fn clean_package<'a>(pkg: &'a Package, root: &'a Package,
+                         dest: Option<&str>, deps: &'a PackageSet,
+                         resolve: &'a Resolve, sources: &'a SourceMap,
+                         config: &'a mut Config<'a>) -> CargoResult<()> {
+    let target_dir = target_dir(config, root);
+    let dest = dest.unwrap_or("");
+    let host_layout = layout::Layout::new(target_dir.join(dest));
+    let target_layout = config.target().map(|target| {
+        layout::Layout::new(target_dir.join(target).join(dest))
+    });
+
+    let exec = Arc::new(box DefaultExecutor as Box<Executor + Send + Sync>);
+    let mut cx = try!(Context::new("compile", resolve, sources, deps, config,
+                                   host_layout, target_layout, exec));
+    if pkg == root {
+        cx.primary();
+    }
+
+    let mut kinds = vec![KindTarget];
+    if cx.config.target().is_some() {
+        kinds.push(KindPlugin);
+    }
+
+    let mut paths = Vec::new();
+    for &kind in kinds.iter() {
+        let layout = cx.layout(kind);
+        let targets = pkg.get_targets().iter().filter(|t| {
+            !t.get_profile().is_doc()
+        });
+        for target in targets {
+            for filename in cx.target_filenames(target).iter() {
+                paths.push(layout.root().join(filename.as_slice()));
+            }
+            for &(_, ref plain) in cx.unhashed_filenames(target).iter() {
+                paths.push(layout.root().join(plain.as_slice()));
+            }
+        }
+
+        let (_, fingerprint) = fingerprint::dirs(&cx, pkg, kind);
+        paths.push(fingerprint);
+        paths.push(layout.native(pkg));
+    }
+
+    for path in paths.iter() {
+        try!(remove(path, cx.config.shell()));
+    }
+    return Ok(());
+
+    fn remove(path: &Path, shell: &mut MultiShell) -> CargoResult<()> {
+        if !path.exists() { return Ok(()) }
+
+        try!(shell.status("Removing", path.display()));
+        let res = if path.is_dir() {
+            fs::rmdir_recursive(path)
+        } else {
+            fs::unlink(path)
+        };
+        res.chain_error(|| {
+            human(format!("Could not remove `{}`", path.display()))
+        })
+    }
+}
+
 fn compile<'a, 'b>(targets: &[&'a Target], pkg: &'a Package,
                    cx: &mut Context<'a, 'b>,
                    jobs: &mut JobQueue<'a, 'b>) -> CargoResult<()> {
@@ -108,17 +326,37 @@ fn compile<'a, 'b>(targets: &[&'a Target], pkg: &'a Package,
     // First part of the build step of a target is to execute all of the custom
     // build commands.
     let mut build_cmds = Vec::new();
+    let profile = targets[0].get_profile();
     for (i, build_cmd) in pkg.get_manifest().get_build().iter().enumerate() {
-        let work = try!(compile_custom(pkg, build_cmd.as_slice(), cx, i == 0));
+        let work = try!(compile_custom(pkg, build_cmd.as_slice(), profile, cx,
+                                       i == 0));
         build_cmds.push(work);
     }
     let (freshness, dirty, fresh) =
         try!(fingerprint::prepare_build_cmd(cx, pkg));
+
+    // Directives printed by the build commands are remembered next to the
+    // fingerprint, as they're still needed when the commands don't rerun.
+    let (old_output, new_output) = fingerprint::build_output_loc(cx, pkg);
+    let (state1, state2) = (cx.build_state.clone(), cx.build_state.clone());
+    let (id1, id2) = (pkg.get_package_id().clone(),
+                      pkg.get_package_id().clone());
+    let out = jobs.output();
     let dirty = proc() {
-        for cmd in build_cmds.move_iter() { try!(cmd()) }
+        let mut stdout = String::new();
+        for cmd in build_cmds.move_iter() {
+            stdout.push_str(try!(cmd()).as_slice());
+        }
+        try!(custom_build::record(&state1, id1, stdout.as_slice(),
+                                  &new_output, &out));
         dirty()
     };
-    jobs.enqueue(pkg, StageCustomBuild, vec![(Job::new(dirty, fresh), freshness)]);
+    let fresh = proc() {
+        try!(fresh());
+        custom_build::restore(&state2, id2, &old_output, &new_output)
+    };
+    let job = Job::new(dirty, fresh).describe("build command".to_string());
+    jobs.enqueue(pkg, StageCustomBuild, vec![(job, freshness)]);
 
     // After the custom command has run, execute rustc for all targets of our
     // package.
@@ -128,19 +366,53 @@ fn compile<'a, 'b>(targets: &[&'a Target], pkg: &'a Package,
     let (mut libs, mut bins) = (Vec::new(), Vec::new());
     for &target in targets.iter() {
         let work = if target.get_profile().is_doc() {
-            vec![(rustdoc(pkg, target, cx), KindTarget)]
+            let (work, cmd) = rustdoc(pkg, target, cx);
+            vec![(work, KindTarget, cmd)]
         } else {
             let req = cx.get_requirement(pkg, target);
             rustc(pkg, target, cx, req)
         };
 
-        let dst = if target.is_lib() {&mut libs} else {&mut bins};
-        for (work, kind) in work.move_iter() {
+        // Tests and benchmarks of the library are built along with the
+        // binaries, once the dev-dependencies they link to are ready.
+        let harness = target.get_profile().is_test() ||
+                      target.get_profile().is_bench();
+        let dst = if target.is_lib() && !harness {&mut libs} else {&mut bins};
+        for (work, kind, cmd) in work.move_iter() {
             let (freshness, dirty, fresh) =
-                try!(fingerprint::prepare_target(cx, pkg, target, kind));
-
-            let dirty = proc() { try!(work()); dirty() };
-            dst.push((Job::new(dirty, fresh), freshness));
+                try!(fingerprint::prepare_target(cx, pkg, target, kind,
+                                                 cmd.as_slice()));
+            let copies = fingerprint::prepare_dep_info_out(cx, pkg, target,
+                                                           kind);
+            let (dirty, fresh) = match copies {
+                Some((copy1, copy2)) => {
+                    let dirty: Work = proc() { try!(dirty()); copy1() };
+                    let fresh: Work = proc() { try!(fresh()); copy2() };
+                    (dirty, fresh)
+                }
+                None => (dirty, fresh),
+            };
+
+            let dirty: Work = proc() { try!(work()); dirty() };
+            let (dirty, fresh) = if cx.config.json_messages() &&
+                                    !target.get_profile().is_doc() {
+                let (dirty_msg, fresh_msg) = artifact_messages(cx, pkg, target,
+                                                               kind);
+                let dirty: Work = proc() { try!(dirty()); emit_json(dirty_msg) };
+                let fresh: Work = proc() { try!(fresh()); emit_json(fresh_msg) };
+                (dirty, fresh)
+            } else {
+                (dirty, fresh)
+            };
+            let desc = format!("{} `{}`", target_kind(target),
+                               target.get_name());
+            let job = Job::new(dirty, fresh).describe(desc);
+            let job = if target.get_profile().is_doc() && !target.is_bin() {
+                job.documentation()
+            } else {
+                job
+            };
+            dst.push((job, freshness));
         }
     }
     jobs.enqueue(pkg, StageLibraries, libs);
@@ -149,34 +421,75 @@ fn compile<'a, 'b>(targets: &[&'a Target], pkg: &'a Package,
     Ok(())
 }
 
-fn compile_custom(pkg: &Package, cmd: &str,
-                  cx: &Context, first: bool) -> CargoResult<Work> {
+/// Prepares a build command of `pkg`. It runs from the root of the package
+/// and is told about the build through its environment, `profile` being the
+/// profile the package's targets are compiled with.
+fn compile_custom(pkg: &Package, cmd: &str, profile: &Profile,
+                  cx: &Context, first: bool)
+                  -> CargoResult<proc():Send -> CargoResult<String>> {
     // TODO: this needs to be smarter about splitting
     let mut cmd = cmd.split(' ');
     // TODO: this shouldn't explicitly pass `KindTarget` for dest/deps_dir, we
     //       may be building a C lib for a plugin
     let layout = cx.layout(KindTarget);
     let output = layout.native(pkg);
+    let profile_name = match profile.get_env() {
+        "release" => "release",
+        "test" => "test",
+        "bench" => "bench",
+        _ => "dev",
+    };
     let mut p = process(cmd.next().unwrap(), pkg, cx)
                      .env("OUT_DIR", Some(&output))
                      .env("DEPS_DIR", Some(&output))
-                     .env("TARGET", cx.config.target());
+                     .env("TARGET", Some(cx.config.target().unwrap_or(
+                         cx.host_triple.as_slice())))
+                     .env("HOST", Some(cx.host_triple.as_slice()))
+                     .env("OPT_LEVEL", Some(profile.get_opt_level().to_string()))
+                     .env("PROFILE", Some(profile_name))
+                     .env("DEBUG", Some(profile.get_debug().to_string()))
+                     .env("BUILD_DEPS_DIR", Some(cx.layout(KindPlugin).deps()))
+                     .env("CARGO_MANIFEST_DIR", Some(pkg.get_root()));
     for arg in cmd {
         p = p.arg(arg);
     }
+    let deps: Vec<PackageId> = cx.resolve.deps(pkg.get_package_id()).map(|deps| {
+        deps.map(|id| id.clone()).collect()
+    }).unwrap_or(Vec::new());
+    let build_state = cx.build_state.clone();
     Ok(proc() {
         if first {
             try!(fs::mkdir(&output, UserRWX).chain_error(|| {
                 internal("failed to create output directory for build command")
             }));
         }
-        try!(p.exec_with_output().map(|_| ()).map_err(|e| e.mark_human()));
-        Ok(())
+
+        // The metadata of our dependencies is only known once their build
+        // commands have run.
+        let mut p = p;
+        {
+            let state = build_state.lock();
+            for id in deps.iter() {
+                let vars = match state.find(id) {
+                    Some(output) => output.env(id.get_name()),
+                    None => continue,
+                };
+                for (key, value) in vars.move_iter() {
+                    p = p.env(key.as_slice(), Some(value));
+                }
+            }
+        }
+
+        let output = try!(p.exec_with_output().map_err(|e| e.mark_human()));
+        Ok(String::from_utf8_lossy(output.output.as_slice()).into_string())
     })
 }
 
-fn rustc(package: &Package, target: &Target,
-         cx: &mut Context, req: PlatformRequirement) -> Vec<(Work, Kind)> {
+/// Returns the work to compile `target` for each kind it is required for,
+/// along with the exact rustc command which will be run.
+fn rustc<'a, 'b>(package: &'a Package, target: &'a Target,
+                 cx: &mut Context<'a, 'b>,
+                 req: PlatformRequirement) -> Vec<(Work, Kind, String)> {
     let crate_types = target.rustc_crate_types();
     let root = package.get_root();
 
@@ -184,7 +497,19 @@ fn rustc(package: &Package, target: &Target,
          root.display(), target, crate_types, cx.primary, req);
 
     let primary = cx.primary;
-    let rustcs = prepare_rustc(package, target, crate_types, cx, req);
+    let mut rustcs = prepare_rustc(package, target, crate_types, cx, req);
+    rustcs.retain(|&(_, kind)| cx.schedule(package, target, kind));
+    if primary && cx.config.deny_primary_warnings() {
+        rustcs = rustcs.move_iter().map(|(rustc, kind)| {
+            (rustc.arg("-D").arg("warnings"), kind)
+        }).collect();
+    }
+    if primary && !cx.config.target_rustc_args().is_empty() {
+        let args = cx.config.target_rustc_args();
+        rustcs = rustcs.move_iter().map(|(rustc, kind)| {
+            (rustc.args(args), kind)
+        }).collect();
+    }
 
     let _ = cx.config.shell().verbose(|shell| {
         for &(ref rustc, _) in rustcs.iter() {
@@ -193,32 +518,266 @@ fn rustc(package: &Package, target: &Target,
         Ok(())
     });
 
+    // With more than one job, several targets of the primary package may be
+    // compiling at once, so their output is passed on a line at a time to
+    // keep lines from being interleaved.
+    let line_buffered = cx.config.jobs() > 1;
+    let json = cx.config.json_messages();
+
+    // Packages linking against native libraries built by the build commands
+    // of any of their dependencies need to be able to find them as well.
+    let upstream = transitive_deps(cx.resolve, package.get_package_id());
+
     rustcs.move_iter().map(|(rustc, kind)| {
         let name = package.get_name().to_string();
+        let cmd = rustc.to_string();
+        let program = cx.config.rustc().to_string();
+        let package_id = package.get_package_id().to_string();
+        let target_name = target.get_name().to_string();
+        let layout = cx.layout(kind);
+        let links = cx.unhashed_filenames(target).move_iter().map(|(src, dst)| {
+            (layout.root().join(src), layout.root().join(dst))
+        }).collect::<Vec<(Path, Path)>>();
+        let build_state = cx.build_state.clone();
+        let id = package.get_package_id().clone();
+        let upstream = upstream.clone();
+        let exec = cx.exec.clone();
+        let package = package.clone();
+        let target = target.clone();
 
         (proc() {
-            if primary {
+            // Flags requested by the package's build commands, and the
+            // library paths requested by those of its dependencies
+            let rustc = {
+                let state = build_state.lock();
+                let mut rustc = match state.find(&id) {
+                    Some(output) => rustc.args(output.rustc_flags.as_slice()),
+                    None => rustc,
+                };
+                for dep in upstream.iter() {
+                    match state.find(dep) {
+                        Some(output) => {
+                            rustc = rustc.args(output.library_paths().as_slice())
+                        }
+                        None => {}
+                    }
+                }
+                rustc
+            };
+            if json {
+                log!(5, "executing, reporting output as json");
+                let (output, res) = match exec.exec_with_output(rustc, &package,
+                                                                &target) {
+                    Ok(output) => (Some(output), Ok(())),
+                    Err(mut err) => (err.output.take(), Err(err)),
+                };
+                match output {
+                    Some(ref output) if output.error.len() > 0 => {
+                        try!(emit_json(json::encode(&CompilerMessage {
+                            reason: "compiler-message".to_string(),
+                            package_id: package_id.clone(),
+                            target: target_name.clone(),
+                            message: String::from_utf8_lossy(
+                                output.error.as_slice()).into_string(),
+                        })));
+                    }
+                    _ => {}
+                }
+                try!(res.map_err(|err| compile_error(err, name.as_slice(),
+                                                     program.as_slice())));
+            } else if primary && line_buffered {
+                log!(5, "executing primary, streaming output by line");
+                let res = exec.exec_with_streaming(rustc, &package, &target);
+                // The output has already been passed on as it was produced,
+                // so it's dropped from the error to not be printed again.
+                try!(res.map_err(|mut err| {
+                    err.output.take();
+                    compile_error(err, name.as_slice(), program.as_slice())
+                }));
+            } else if primary {
                 log!(5, "executing primary");
-                try!(rustc.exec().chain_error(|| {
-                    human(format!("Could not compile `{}`.", name))
+                try!(exec.exec(rustc, &package, &target).map_err(|err| {
+                    compile_error(err, name.as_slice(), program.as_slice())
                 }))
             } else {
                 log!(5, "executing deps");
-                try!(rustc.exec_with_output().and(Ok(())).map_err(|err| {
-                    caused_human(format!("Could not compile `{}`.\n{}",
-                                         name, err.output().unwrap()), err)
-                }))
+                // The warnings of dependencies are kept quiet, but when one
+                // fails to compile its errors are shown just like those of
+                // the primary package.
+                let res = match exec.exec_with_output(rustc, &package, &target) {
+                    Ok(..) => Ok(()),
+                    Err(mut err) => {
+                        match err.output.take() {
+                            Some(output) => try!(print_output(&output)),
+                            None => {}
+                        }
+                        Err(err)
+                    }
+                };
+                try!(res.map_err(|err| compile_error(err, name.as_slice(),
+                                                     program.as_slice())))
+            }
+            for &(ref src, ref dst) in links.iter() {
+                try!(link_or_copy(src, dst));
             }
             Ok(())
-        }, kind)
+        }, kind, cmd)
     }).collect()
 }
 
+/// Returns every package which `id` depends on, directly or not.
+fn transitive_deps(resolve: &Resolve, id: &PackageId) -> Vec<PackageId> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![id];
+    let mut ret = Vec::new();
+    loop {
+        let pkg = match stack.pop() { Some(pkg) => pkg, None => break };
+        for dep in resolve.deps(pkg).move_iter().flat_map(|deps| deps) {
+            if seen.insert(dep) {
+                ret.push(dep.clone());
+                stack.push(dep);
+            }
+        }
+    }
+    ret
+}
+
+/// Describes rustc failing to compile package `name`, or not being found at
+/// all when trying to run the compiler `rustc`.
+fn compile_error(err: ProcessError, name: &str,
+                 rustc: &str) -> Box<CargoError + Send> {
+    if err.never_started() {
+        missing_tool("rustc", rustc, err)
+    } else {
+        caused_human(format!("Could not compile `{}`.", name), err)
+    }
+}
+
+/// Explains that `tool` has to be installed when `cmd` couldn't be started.
+fn missing_tool(tool: &str, cmd: &str, err: ProcessError) -> Box<CargoError + Send> {
+    caused_human(format!("Cargo requires `{}` to be installed and in PATH \
+                          (tried to execute `{}`)", tool, cmd), err)
+}
+
+/// Prints the captured output of a compiler in one piece, so that it doesn't
+/// get mixed up with the output of any other compiler running at the time.
+fn print_output(output: &ProcessOutput) -> CargoResult<()> {
+    if output.output.len() > 0 {
+        try!(io::stdio::stdout_raw().write(output.output.as_slice()));
+    }
+    if output.error.len() > 0 {
+        try!(io::stdio::stderr_raw().write(output.error.as_slice()));
+    }
+    Ok(())
+}
+
+/// Makes `src` available at `dst` as well, replacing whatever was there. A
+/// hard link is used where possible, falling back to a copy.
+fn link_or_copy(src: &Path, dst: &Path) -> CargoResult<()> {
+    if dst.exists() {
+        try!(fs::unlink(dst).chain_error(|| {
+            human(format!("failed to remove `{}`", dst.display()))
+        }));
+    }
+    fs::link(src, dst).or_else(|_| fs::copy(src, dst)).chain_error(|| {
+        human(format!("failed to link or copy `{}` to `{}`", src.display(),
+                      dst.display()))
+    })
+}
+
+#[deriving(Encodable)]
+struct CompilerMessage {
+    reason: String,
+    package_id: String,
+    target: String,
+    message: String,
+}
+
+#[deriving(Encodable)]
+struct BuildProgress {
+    reason: String,
+    status: String,
+    message: String,
+}
+
+#[deriving(Encodable)]
+struct CompilerArtifact {
+    reason: String,
+    package_id: String,
+    package_name: String,
+    package_version: String,
+    target: String,
+    kind: String,
+    filenames: Vec<String>,
+    fresh: bool,
+}
+
+/// Returns the JSON messages announcing the artifacts of `target`, for when
+/// it is rebuilt and for when it is fresh respectively.
+fn artifact_messages(cx: &Context, pkg: &Package, target: &Target,
+                     kind: Kind) -> (String, String) {
+    let layout = cx.layout(kind);
+    let mut artifact = CompilerArtifact {
+        reason: "compiler-artifact".to_string(),
+        package_id: pkg.get_package_id().to_string(),
+        package_name: pkg.get_name().to_string(),
+        package_version: pkg.get_version().to_string(),
+        target: target.get_name().to_string(),
+        kind: target_kind(target).to_string(),
+        filenames: cx.target_filenames(target).iter().map(|f| {
+            layout.root().join(f.as_slice()).display().to_string()
+        }).collect(),
+        fresh: false,
+    };
+    let dirty = json::encode(&artifact);
+    artifact.fresh = true;
+    (dirty, json::encode(&artifact))
+}
+
+/// Names the kind of `target`, as in "bin `foo`".
+fn target_kind(target: &Target) -> &'static str {
+    let profile = target.get_profile();
+    if profile.is_doc() {
+        "doc"
+    } else if profile.is_test() {
+        "test"
+    } else if profile.is_bench() {
+        "bench"
+    } else if target.is_lib() {
+        "lib"
+    } else {
+        "bin"
+    }
+}
+
+/// Reports the progress of the build, as a status line for people or as a
+/// "build-progress" JSON message when `--message-format json` was requested.
+pub fn report_status<T: Show>(config: &mut Config, status: &str,
+                              message: T) -> CargoResult<()> {
+    if config.json_messages() {
+        emit_json(json::encode(&BuildProgress {
+            reason: "build-progress".to_string(),
+            status: status.to_string(),
+            message: message.to_string(),
+        }))
+    } else {
+        config.shell().status(status, message)
+    }
+}
+
+/// Writes a single JSON message to stdout as one line.
+fn emit_json(mut msg: String) -> CargoResult<()> {
+    msg.push_char('\n');
+    try!(io::stdio::stdout_raw().write(msg.as_bytes()));
+    Ok(())
+}
+
 fn prepare_rustc(package: &Package, target: &Target, crate_types: Vec<&str>,
                  cx: &Context, req: PlatformRequirement)
                  -> Vec<(ProcessBuilder, Kind)> {
-    let base = process("rustc", package, cx);
+    let base = process(cx.config.rustc(), package, cx);
     let base = build_base_args(base, target, crate_types.as_slice());
+    let base = build_feature_args(base, package, cx);
 
     let target_cmd = build_plugin_args(base.clone(), cx, package, target, KindTarget);
     let plugin_cmd = build_plugin_args(base, cx, package, target, KindPlugin);
@@ -236,11 +795,12 @@ fn prepare_rustc(package: &Package, target: &Target, crate_types: Vec<&str>,
 }
 
 
-fn rustdoc(package: &Package, target: &Target, cx: &mut Context) -> Work {
+fn rustdoc(package: &Package, target: &Target,
+           cx: &mut Context) -> (Work, String) {
     // Can't document binaries, but they have a doc target listed so we can
     // build documentation of dependencies even when `cargo doc` is run.
     if target.is_bin() {
-        return proc() Ok(())
+        return (proc() Ok(()), String::new())
     }
 
     let kind = KindTarget;
@@ -250,6 +810,7 @@ fn rustdoc(package: &Package, target: &Target, cx: &mut Context) -> Work {
     let rustdoc = rustdoc.arg(target.get_src_path())
                          .arg("-o").arg(cx_root)
                          .arg("--crate-name").arg(target.get_name());
+    let rustdoc = build_feature_args(rustdoc, package, cx);
     let rustdoc = build_deps_args(rustdoc, target, package, cx, kind);
 
     log!(5, "commands={}", rustdoc);
@@ -260,19 +821,25 @@ fn rustdoc(package: &Package, target: &Target, cx: &mut Context) -> Work {
 
     let primary = cx.primary;
     let name = package.get_name().to_string();
-    proc() {
-        if primary {
-            try!(rustdoc.exec().chain_error(|| {
-                human(format!("Could not document `{}`.", name))
-            }))
+    let cmd = rustdoc.to_string();
+    (proc() {
+        let res = if primary {
+            rustdoc.exec()
         } else {
-            try!(rustdoc.exec_with_output().and(Ok(())).map_err(|err| {
-                caused_human(format!("Could not document `{}`.\n{}",
-                                     name, err.output().unwrap()), err)
-            }))
-        }
-        Ok(())
-    }
+            rustdoc.exec_with_output().and(Ok(()))
+        };
+        res.map_err(|err| {
+            if err.never_started() {
+                missing_tool("rustdoc", "rustdoc", err)
+            } else if primary {
+                caused_human(format!("Could not document `{}`.", name), err)
+            } else {
+                caused_human(format!("Could not document `{}`.\n{}", name,
+                                     err.output().unwrap_or(String::new())),
+                             err)
+            }
+        })
+    }, cmd)
 }
 
 fn build_base_args(mut cmd: ProcessBuilder,
@@ -295,19 +862,34 @@ fn build_base_args(mut cmd: ProcessBuilder,
         cmd = cmd.arg("--opt-level").arg(profile.get_opt_level().to_string());
     }
 
-    // Right now -g is a little buggy, so we're not passing -g just yet
-    // if profile.get_debug() {
-    //     into.push("-g".to_string());
-    // }
+    // Right now -g is a little buggy, so it's only passed when a manifest
+    // explicitly asks for `debug = true` in one of its profiles.
+    if profile.get_debuginfo() {
+        cmd = cmd.arg("-g");
+    }
 
     if !profile.get_debug() {
         cmd = cmd.args(["--cfg", "ndebug"]);
     }
 
+    // LTO is only meaningful for (and only accepted by rustc for) executables
+    if profile.get_lto() && target.is_bin() {
+        cmd = cmd.args(["-C", "lto"]);
+    }
+
+    match profile.get_codegen_units() {
+        Some(n) => cmd = cmd.arg("-C").arg(format!("codegen-units={}", n)),
+        None => {}
+    }
+
     if profile.is_test() {
         cmd = cmd.arg("--test");
     }
 
+    if profile.is_bench() {
+        cmd = cmd.args(["--cfg", "bench"]);
+    }
+
     match metadata {
         Some(m) => {
             cmd = cmd.arg("-C").arg(format!("metadata={}", m.metadata));
@@ -319,6 +901,20 @@ fn build_base_args(mut cmd: ProcessBuilder,
     return cmd;
 }
 
+/// Passes the activated features of `pkg` to the compiler as
+/// `--cfg feature="name"`.
+fn build_feature_args(mut cmd: ProcessBuilder, pkg: &Package,
+                      cx: &Context) -> ProcessBuilder {
+    let mut features = match cx.resolve.features(pkg.get_package_id()) {
+        Some(features) => features.iter().collect::<Vec<&String>>(),
+        None => return cmd,
+    };
+    features.sort();
+    for feature in features.iter() {
+        cmd = cmd.arg("--cfg").arg(format!("feature=\"{}\"", feature));
+    }
+    cmd
+}
 
 fn build_plugin_args(mut cmd: ProcessBuilder, cx: &Context, pkg: &Package,
                      target: &Target, kind: Kind) -> ProcessBuilder {
@@ -360,8 +956,24 @@ fn build_deps_args(mut cmd: ProcessBuilder, target: &Target, package: &Package,
     // native dependencies.
     cmd = push_native_dirs(cmd, &layout, package, cx, &mut HashSet::new());
 
-    for &(_, target) in cx.dep_targets(package).iter() {
-        cmd = link_to(cmd, target, cx, kind, Dependency);
+    // Dev-dependencies are only linked into tests, benchmarks and examples,
+    // never into the library or binaries which they test.
+    let env = target.get_profile().get_env();
+    let wants_dev = env == "test" || env == "bench";
+
+    for &(dep, target) in cx.dep_targets(package).iter() {
+        let dependency = context::find_dependency(package,
+                                                  dep.get_package_id(), false);
+        match dependency {
+            Some(d) if !d.is_transitive() && !wants_dev => continue,
+            _ => {}
+        }
+
+        // A dependency may have been renamed in the manifest, in which case
+        // it's passed to rustc under that name instead.
+        let name = dependency.and_then(|d| d.get_rename())
+                             .unwrap_or(target.get_name());
+        cmd = link_to(cmd, name, target, cx, kind, Dependency);
     }
 
     let mut targets = package.get_targets().iter().filter(|target| {
@@ -370,13 +982,13 @@ fn build_deps_args(mut cmd: ProcessBuilder, target: &Target, package: &Package,
 
     if target.is_bin() {
         for target in targets {
-            cmd = link_to(cmd, target, cx, kind, LocalLib);
+            cmd = link_to(cmd, target.get_name(), target, cx, kind, LocalLib);
         }
     }
 
     return cmd;
 
-    fn link_to(mut cmd: ProcessBuilder, target: &Target,
+    fn link_to(mut cmd: ProcessBuilder, name: &str, target: &Target,
                cx: &Context, kind: Kind, reason: LinkReason) -> ProcessBuilder {
         // If this target is itself a plugin *or* if it's being linked to a
         // plugin, then we want the plugin directory. Otherwise we want the
@@ -389,7 +1001,7 @@ fn build_deps_args(mut cmd: ProcessBuilder, target: &Target, package: &Package,
 
         for filename in cx.target_filenames(target).iter() {
             let mut v = Vec::new();
-            v.push_all(target.get_name().as_bytes());
+            v.push_all(name.as_bytes());
             v.push(b'=');
             match reason {
                 Dependency => v.push_all(layout.deps().as_vec()),
@@ -433,6 +1045,10 @@ pub fn process<T: ToCStr>(cmd: T, pkg: &Package, cx: &Context) -> ProcessBuilder
     util::process(cmd)
         .cwd(pkg.get_root())
         .env(DynamicLibrary::envvar(), Some(search_path.as_slice()))
+        .env("CARGO_PKG_NAME", Some(pkg.get_name()))
+        .env("CARGO_PKG_AUTHORS",
+             Some(pkg.get_manifest().get_authors().connect(":")))
+        .env("CARGO_PKG_VERSION", Some(pkg.get_version().to_string()))
         .env("CARGO_PKG_VERSION_MAJOR", Some(pkg.get_version().major.to_string()))
         .env("CARGO_PKG_VERSION_MINOR", Some(pkg.get_version().minor.to_string()))
         .env("CARGO_PKG_VERSION_PATCH", Some(pkg.get_version().patch.to_string()))
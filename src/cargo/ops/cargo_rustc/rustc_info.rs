@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::io::{fs, File, UserRWX};
+use std::os;
+use serialize::json;
+
+use util::{CargoResult, CargoError, ChainError, ProcessBuilder, human, process};
+
+/// The output of the probes run against `rustc` before compiling anything,
+/// cached in the target directory.
+///
+/// Each entry is keyed by the path, modification time and size of the
+/// compiler binary, so replacing the compiler runs the probes again.
+pub struct RustcInfo {
+    path: Path,
+    entries: HashMap<String, String>,
+    dirty: bool,
+}
+
+impl RustcInfo {
+    /// Loads the cache kept in `dir`, starting out empty if there is none or
+    /// it can't be read.
+    pub fn load(dir: &Path) -> RustcInfo {
+        let path = dir.join(".rustc-info");
+        let entries = File::open(&path).read_to_string().ok().and_then(|s| {
+            json::decode(s.as_slice()).ok()
+        }).unwrap_or(HashMap::new());
+        RustcInfo { path: path, entries: entries, dirty: false }
+    }
+
+    /// The output of `rustc -v verbose`.
+    pub fn version(&mut self, rustc: &str) -> CargoResult<String> {
+        let key = binary_key(rustc).map(|k| format!("version {}", k));
+        self.probe(key, || run(process(rustc).arg("-v").arg("verbose")))
+    }
+
+    /// The output of asking `rustc` for the names of a dylib and an
+    /// executable named `-` when compiling for `target`.
+    pub fn file_names(&mut self, rustc: &str,
+                      target: Option<&str>) -> CargoResult<String> {
+        let key = binary_key(rustc).map(|k| {
+            format!("file-names {} {}", k, target.unwrap_or("host"))
+        });
+        self.probe(key, || {
+            let cmd = process(rustc).arg("-")
+                                    .arg("--crate-name").arg("-")
+                                    .arg("--crate-type").arg("dylib")
+                                    .arg("--crate-type").arg("bin")
+                                    .arg("--print-file-name");
+            run(match target {
+                Some(s) => cmd.arg("--target").arg(s),
+                None => cmd,
+            })
+        })
+    }
+
+    /// Writes the cache back out if any probe had to be run.
+    pub fn save(&self) -> CargoResult<()> {
+        if !self.dirty { return Ok(()) }
+        (|| {
+            try!(fs::mkdir_recursive(&self.path.dir_path(), UserRWX));
+            let mut file = try!(File::create(&self.path));
+            try!(file.write_str(json::encode(&self.entries).as_slice()));
+            Ok(())
+        }).chain_error(|| {
+            human(format!("failed to write `{}`", self.path.display()))
+        })
+    }
+
+    fn probe(&mut self, key: Option<String>,
+             run: || -> CargoResult<String>) -> CargoResult<String> {
+        match key.as_ref().and_then(|k| self.entries.find(k)) {
+            Some(output) => return Ok(output.clone()),
+            None => {}
+        }
+        let output = try!(run());
+        match key {
+            Some(key) => {
+                self.entries.insert(key, output.clone());
+                self.dirty = true;
+            }
+            None => {}
+        }
+        Ok(output)
+    }
+}
+
+/// Runs a probe, explaining that rustc has to be installed if it can't be.
+fn run(cmd: ProcessBuilder) -> CargoResult<String> {
+    let output = try!(cmd.exec_with_output().map_err(|err| {
+        if err.never_started() {
+            let shown = cmd.to_string();
+            super::missing_tool("rustc", shown.as_slice().trim_chars('`'), err)
+        } else {
+            err.box_error()
+        }
+    }));
+    Ok(String::from_utf8_lossy(output.output.as_slice()).into_string())
+}
+
+/// Identifies the compiler binary which `rustc` names, either directly or
+/// through `PATH`, by its location, modification time and size. Nothing is
+/// cached for a binary which can't be found.
+fn binary_key(rustc: &str) -> Option<String> {
+    let rustc = Path::new(rustc);
+    let path = if rustc.filename() != Some(rustc.as_vec()) {
+        Some(os::make_absolute(&rustc))
+    } else {
+        os::getenv("PATH").and_then(|paths| {
+            os::split_paths(paths.as_slice()).move_iter().map(|dir| {
+                Path::new(dir).join(rustc.as_vec())
+            }).find(|candidate| candidate.is_file())
+        })
+    };
+    path.and_then(|path| {
+        fs::stat(&path).ok().map(|stat| {
+            format!("{} {} {}", path.display(), stat.modified, stat.size)
+        })
+    })
+}
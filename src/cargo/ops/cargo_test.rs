@@ -0,0 +1,55 @@
+use std::os;
+
+use ops;
+use util::{CargoResult, human, process};
+
+pub struct TestOptions<'a> {
+    pub compile_opts: ops::CompileOptions<'a>,
+    pub args: &'a [String],
+    /// Only compile the test binaries, printing where they are instead of
+    /// running them (`--no-run`)
+    pub no_run: bool,
+}
+
+/// Compiles all test targets of the package at `manifest_path` and then runs
+/// each resulting test binary in turn.
+///
+/// A failing test binary does not prevent the remaining binaries from being
+/// run. Once all of them have finished, any failures are reported together as
+/// one error listing the offending binaries.
+pub fn run_tests(manifest_path: &Path,
+                 options: &mut TestOptions) -> CargoResult<()> {
+    let compilation = try!(ops::compile(manifest_path,
+                                        &mut options.compile_opts));
+
+    if options.no_run {
+        let cwd = os::getcwd();
+        for exe in compilation.tests.iter() {
+            let exe = match exe.path_relative_from(&cwd) {
+                Some(path) => path,
+                None => exe.clone(),
+            };
+            try!(options.compile_opts.shell.status("Executable", exe.display()));
+        }
+        return Ok(())
+    }
+
+    let mut failures = Vec::new();
+    for exe in compilation.tests.iter() {
+        match process(exe.clone()).args(options.args).exec() {
+            Ok(()) => {}
+            Err(e) => {
+                let file = exe.filename_str().unwrap().to_string();
+                debug!("test binary failed; file={}; err={}", file, e);
+                failures.push(file);
+            }
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(human(format!("the following test binaries failed:\n    {}",
+                          failures.connect("\n    "))))
+    }
+}
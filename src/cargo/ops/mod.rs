@@ -1,18 +1,38 @@
-pub use self::cargo_clean::clean;
-pub use self::cargo_compile::{compile, CompileOptions};
+pub use self::cargo_clean::{clean, CleanOptions};
+pub use self::cargo_compile::{compile, compile_with_exec, CompileOptions};
+pub use self::cargo_compile::{Compilation, Artifact, output_artifacts};
+pub use self::cargo_compile::{MessageFormat, Human, Json};
+pub use self::cargo_compile::target_dir;
 pub use self::cargo_read_manifest::{read_manifest,read_package,read_packages};
-pub use self::cargo_rustc::compile_targets;
+pub use self::cargo_read_manifest::{read_packages_to_depth, DEFAULT_MAX_DEPTH};
+pub use self::cargo_read_manifest::{read_workspace_members, workspace_summary};
+pub use self::cargo_read_manifest::workspace_source_ids;
+pub use self::cargo_rustc::{compile_targets, clean_package, host_triple};
+pub use self::cargo_rustc::target_artifacts;
+pub use self::cargo_rustc::{Executor, DefaultExecutor};
 pub use self::cargo_run::run;
-pub use self::cargo_new::{new, NewOptions};
+pub use self::cargo_fetch::fetch;
+pub use self::cargo_output_metadata::{output_metadata, ExportInfo};
+pub use self::cargo_output_metadata::OutputMetadataOptions;
+pub use self::cargo_test::{run_tests, TestOptions};
+pub use self::cargo_bench::{run_benches, BenchOptions};
+pub use self::cargo_new::{new, NewOptions, VersionControl, GitRepo, HgRepo, NoVcs};
 pub use self::cargo_doc::{doc, DocOptions};
 pub use self::cargo_generate_lockfile::{generate_lockfile, write_resolve};
 pub use self::cargo_generate_lockfile::{update_lockfile, load_lockfile};
+pub use self::cargo_generate_lockfile::check_locked;
+pub use self::cargo_generate_lockfile::UpdateOptions;
+pub use self::cargo_generate_lockfile::package_checksum;
 
 mod cargo_clean;
 mod cargo_compile;
 mod cargo_read_manifest;
 mod cargo_rustc;
 mod cargo_run;
+mod cargo_fetch;
+mod cargo_output_metadata;
+mod cargo_test;
+mod cargo_bench;
 mod cargo_new;
 mod cargo_doc;
 mod cargo_generate_lockfile;
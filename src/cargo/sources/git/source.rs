@@ -4,11 +4,12 @@ use std::hash::Hasher;
 use std::hash::sip::SipHasher;
 use std::str;
 
-use core::source::{Source, SourceId, GitKind, Location, Remote, Local};
+use core::source::{Source, SourceId, GitKind, GitReference, Rev};
+use core::source::{Location, Remote, Local};
 use core::{Package, PackageId, Summary, Registry, Dependency};
-use util::{CargoResult, Config, to_hex};
+use util::{CargoResult, Config, human, to_hex};
 use sources::PathSource;
-use sources::git::utils::{GitReference, GitRemote, Master, Other, GitRevision};
+use sources::git::utils::{GitRemote, GitRevision, git_installed};
 
 /* TODO: Refactor GitSource to delegate to a PathSource
  */
@@ -16,7 +17,7 @@ pub struct GitSource<'a, 'b> {
     remote: GitRemote,
     reference: GitReference,
     db_path: Path,
-    checkout_path: Path,
+    checkouts_path: Path,
     source_id: SourceId,
     path_source: Option<PathSource>,
     rev: Option<GitRevision>,
@@ -33,25 +34,28 @@ impl<'a, 'b> GitSource<'a, 'b> {
             _ => fail!("Not a git source; id={}", source_id)
         };
 
-        let remote = GitRemote::new(source_id.get_location());
+        let remote = GitRemote::new(source_id.get_location())
+            .with_ssh_command(config.git_ssh_command().map(|s| s.to_string()))
+            .with_offline(config.offline());
         let ident = ident(source_id.get_location());
 
         let db_path = config.git_db_path()
             .join(ident.as_slice());
 
-        let checkout_path = config.git_checkout_path()
-            .join(ident.as_slice()).join(reference.as_slice());
+        // Each revision gets its own checkout, shared by every project
+        // which uses it.
+        let checkouts_path = config.git_checkout_path().join(ident.as_slice());
 
         let reference = match source_id.precise {
-            Some(ref s) => s,
-            None => reference,
+            Some(ref s) => Rev(s.clone()),
+            None => reference.clone(),
         };
 
         GitSource {
             remote: remote,
-            reference: GitReference::for_str(reference.as_slice()),
+            reference: reference,
             db_path: db_path,
-            checkout_path: checkout_path,
+            checkouts_path: checkouts_path,
             source_id: source_id.clone(),
             path_source: None,
             rev: None,
@@ -62,15 +66,36 @@ impl<'a, 'b> GitSource<'a, 'b> {
     pub fn get_location(&self) -> &Location {
         self.remote.get_location()
     }
-}
 
-fn ident(location: &Location) -> String {
-    let hasher = SipHasher::new_with_keys(0,0);
+    /// Whether the requested reference can be found in the local database,
+    /// meaning the source can be updated without touching the network.
+    pub fn is_cached(&self) -> bool {
+        self.remote.rev_for(&self.db_path, &self.reference).is_ok()
+    }
+
+    /// Explains that `git` couldn't be found, naming the dependency by its
+    /// repository as the database directory does.
+    pub fn missing_git_message(&self) -> String {
+        let location = self.remote.get_location();
+        format!("`git` is required to fetch dependency `{}` from {}, check \
+                 that it is installed and in PATH", repository_name(location),
+                location)
+    }
 
+    /// Explains that the requested reference can't be fetched because
+    /// network access is disabled.
+    pub fn offline_message(&self) -> String {
+        format!("Could not fetch `{}` in offline mode, {} is not in the \
+                 local cache.", self.remote.get_location(), self.reference)
+    }
+}
+
+/// The name of the repository at `location`, the last component of its path.
+fn repository_name(location: &Location) -> String {
     // FIXME: this really should be able to not use to_str() everywhere, but the
     //        compiler seems to currently ask for static lifetimes spuriously.
     //        Perhaps related to rust-lang/rust#15144
-    let ident = match *location {
+    match *location {
         Local(ref path) => {
             let last = path.components().last().unwrap();
             str::from_utf8(last).unwrap().to_string()
@@ -80,8 +105,13 @@ fn ident(location: &Location) -> String {
             let path = canonicalize_url(path.as_slice());
             path.as_slice().split('/').last().unwrap().to_string()
         }
-    };
+    }
+}
+
+fn ident(location: &Location) -> String {
+    let hasher = SipHasher::new_with_keys(0,0);
 
+    let ident = repository_name(location);
     let ident = if ident.as_slice() == "" {
         "_empty".to_string()
     } else {
@@ -140,9 +170,10 @@ impl<'a, 'b> Show for GitSource<'a, 'b> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         try!(write!(f, "git repo at {}", self.remote.get_location()));
 
-        match self.reference {
-            Master => Ok(()),
-            Other(ref reference) => write!(f, " ({})", reference)
+        if self.reference.is_master() {
+            Ok(())
+        } else {
+            write!(f, " ({})", self.reference)
         }
     }
 }
@@ -157,26 +188,49 @@ impl<'a, 'b> Registry for GitSource<'a, 'b> {
 
 impl<'a, 'b> Source for GitSource<'a, 'b> {
     fn update(&mut self) -> CargoResult<()> {
-        let actual_rev = self.remote.rev_for(&self.db_path,
-                                             self.reference.as_slice());
-        let should_update = self.config.update_remotes() || actual_rev.is_err();
+        if !git_installed() {
+            return Err(human(self.missing_git_message()))
+        }
+
+        // An exact revision, such as the one recorded for this source in the
+        // lockfile, never moves, so there is no need to go back to the remote
+        // once the database has it. Only `cargo update` moves the pin.
+        let actual_rev = match self.reference {
+            Rev(ref rev) => {
+                let db = self.remote.db_at(&self.db_path);
+                db.has_ref(rev.as_slice()).and_then(|()| {
+                    self.remote.rev_for(&self.db_path, &self.reference)
+                })
+            }
+            _ => self.remote.rev_for(&self.db_path, &self.reference),
+        };
+        let is_rev = match self.reference { Rev(..) => true, _ => false };
+        let offline = self.config.offline();
+        let should_update = actual_rev.is_err() ||
+                            (self.config.update_remotes() && !is_rev && !offline);
 
         let (repo, actual_rev) = if should_update {
+            if offline {
+                return Err(human(self.offline_message()))
+            }
             try!(self.config.shell().status("Updating",
                 format!("git repository `{}`", self.remote.get_location())));
 
             log!(5, "updating git source `{}`", self.remote);
-            let repo = try!(self.remote.checkout(&self.db_path));
-            let rev = try!(repo.rev_for(self.reference.as_slice()));
+            let repo = try!(self.remote.checkout(&self.db_path,
+                                                 &self.reference,
+                                                 self.config.git_shallow()));
+            let rev = try!(repo.rev_for(&self.reference));
             (repo, rev)
         } else {
             (self.remote.db_at(&self.db_path), actual_rev.unwrap())
         };
 
-        try!(repo.copy_to(actual_rev.clone(), &self.checkout_path));
+        let checkout_path = self.checkouts_path.join(actual_rev.as_slice());
+        try!(repo.copy_to(actual_rev.clone(), &checkout_path));
 
         let source_id = self.source_id.with_precise(actual_rev.to_string());
-        let path_source = PathSource::new(&self.checkout_path, &source_id);
+        let path_source = PathSource::new(&checkout_path, &source_id);
 
         self.path_source = Some(path_source);
         self.rev = Some(actual_rev);
@@ -2,45 +2,16 @@ use std::fmt;
 use std::fmt::{Show,Formatter};
 use std::io::{UserDir};
 use std::io::fs::{mkdir_recursive,rmdir_recursive};
+use std::os;
 use serialize::{Encodable,Encoder};
 
-use core::source::Location;
-use util::{CargoResult, ChainError, ProcessBuilder, process, human};
-
-#[deriving(PartialEq,Clone,Encodable)]
-pub enum GitReference {
-    Master,
-    Other(String)
-}
+use core::source::{Location, Remote, GitReference, Branch, Tag, Rev};
+use util::{CargoResult, CargoError, ChainError, ProcessBuilder, ProcessError};
+use util::{process, human, caused_human};
 
 #[deriving(PartialEq,Clone,Encodable)]
 pub struct GitRevision(String);
 
-impl GitReference {
-    pub fn for_str<S: Str>(string: S) -> GitReference {
-        if string.as_slice() == "master" {
-            Master
-        } else {
-            Other(string.as_slice().to_string())
-        }
-    }
-}
-
-impl Str for GitReference {
-    fn as_slice(&self) -> &str {
-        match *self {
-            Master => "master",
-            Other(ref string) => string.as_slice()
-        }
-    }
-}
-
-impl Show for GitReference {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        self.as_slice().fmt(f)
-    }
-}
-
 impl Str for GitRevision {
     fn as_slice(&self) -> &str {
         let GitRevision(ref me) = *self;
@@ -66,6 +37,13 @@ macro_rules! git_output(
     })
 )
 
+// Like `git!`, but for commands which talk to the remote
+macro_rules! git_remote(
+    ($remote:expr, $path:expr, $($arg:expr),+) => (
+        try!($remote.git_network(&$path, process("git")$(.arg($arg))*))
+    )
+)
+
 macro_rules! errln(
     ($($arg:tt)*) => (let _ = writeln!(::std::io::stdio::stderr(), $($arg)*))
 )
@@ -75,6 +53,8 @@ macro_rules! errln(
 #[deriving(PartialEq,Clone,Show)]
 pub struct GitRemote {
     location: Location,
+    ssh_command: Option<String>,
+    offline: bool,
 }
 
 #[deriving(PartialEq,Clone,Encodable)]
@@ -143,26 +123,99 @@ impl<E, S: Encoder<E>> Encodable<S, E> for GitCheckout {
 
 impl GitRemote {
     pub fn new(location: &Location) -> GitRemote {
-        GitRemote { location: location.clone() }
+        GitRemote {
+            location: location.clone(),
+            ssh_command: None,
+            offline: false,
+        }
+    }
+
+    /// Sets the command used to connect to SSH remotes, instead of
+    /// `ssh -o BatchMode=yes`.
+    pub fn with_ssh_command(mut self, cmd: Option<String>) -> GitRemote {
+        self.ssh_command = cmd;
+        self
+    }
+
+    /// Makes every operation which would talk to the remote fail instead.
+    /// Looking things up in a database which was already fetched still works.
+    pub fn with_offline(mut self, offline: bool) -> GitRemote {
+        self.offline = offline;
+        self
     }
 
     pub fn get_location(&self) -> &Location {
         &self.location
     }
 
-    pub fn rev_for<S: Str>(&self, path: &Path, reference: S)
-                           -> CargoResult<GitRevision> {
-        Ok(GitRevision(git_output!(*path, "rev-parse", reference.as_slice())))
+    /// Resolves `reference` to a revision in the database at `path`.
+    ///
+    /// Branches and tags are looked up under `refs/heads` and `refs/tags`
+    /// respectively, so a tag is never confused with a branch of the same
+    /// name. A `Rev` must name a commit, and an abbreviated one is expanded
+    /// to the full sha so that it can be recorded in the lockfile.
+    pub fn rev_for(&self, path: &Path, reference: &GitReference)
+                   -> CargoResult<GitRevision> {
+        let cmd = match *reference {
+            Branch(ref s) => {
+                process("git").arg("rev-parse").arg("--verify")
+                              .arg(format!("refs/heads/{}", s))
+            }
+            Tag(ref s) => {
+                process("git").arg("rev-parse").arg("--verify")
+                              .arg(format!("refs/tags/{}^{{commit}}", s))
+            }
+            Rev(ref s) => {
+                process("git").arg("rev-parse").arg("--verify")
+                              .arg(format!("{}^{{commit}}", s))
+            }
+        };
+
+        let output = try!(git_output(path, cmd).chain_error(|| {
+            human(format!("failed to find {} in `{}`", reference,
+                          self.location))
+        }));
+
+        Ok(GitRevision(output))
     }
 
-    pub fn checkout(&self, into: &Path) -> CargoResult<GitDatabase> {
+    /// Brings the database at `into` up to date with the remote, cloning it
+    /// first if it doesn't exist yet.
+    ///
+    /// If `shallow` is set and `reference` is a tag or an exact revision, only
+    /// that commit is fetched (with `--depth 1`) rather than the whole
+    /// history. Should that not turn up `reference`, for example because the
+    /// remote refuses to serve a bare revision, a full fetch is done instead.
+    pub fn checkout(&self, into: &Path, reference: &GitReference,
+                    shallow: bool) -> CargoResult<GitDatabase> {
+        let shallow = shallow && match *reference {
+            Branch(..) => false,
+            Tag(..) | Rev(..) => true,
+        };
+
+        if shallow {
+            match self.fetch_shallow(into, reference) {
+                Ok(()) if self.rev_for(into, reference).is_ok() => {
+                    return Ok(self.db_at(into))
+                }
+                Ok(()) => {
+                    log!(5, "shallow fetch of {} didn't find it, fetching \
+                             everything", reference);
+                }
+                Err(e) => {
+                    log!(5, "shallow fetch of {} failed, fetching \
+                             everything: {}", reference, e);
+                }
+            }
+        }
+
         if into.exists() {
             try!(self.fetch_into(into));
         } else {
             try!(self.clone_into(into));
         }
 
-        Ok(GitDatabase { remote: self.clone(), path: into.clone() })
+        Ok(self.db_at(into))
     }
 
     pub fn db_at(&self, db_path: &Path) -> GitDatabase {
@@ -170,8 +223,48 @@ impl GitRemote {
     }
 
     fn fetch_into(&self, path: &Path) -> CargoResult<()> {
-        Ok(git!(*path, "fetch", "--force", "--quiet", "--tags",
-                &self.location, "refs/heads/*:refs/heads/*"))
+        // A database which started out shallow needs the rest of its history
+        // now that more than a single commit is wanted.
+        if path.join("shallow").exists() {
+            git_remote!(self, *path, "fetch", "--force", "--quiet",
+                        "--unshallow", &self.location);
+        }
+        Ok(git_remote!(self, *path, "fetch", "--force", "--quiet", "--tags",
+                       &self.location, "refs/heads/*:refs/heads/*"))
+    }
+
+    fn fetch_shallow(&self, path: &Path,
+                     reference: &GitReference) -> CargoResult<()> {
+        // A tag can be cloned directly, but don't leave a half-finished
+        // clone behind for the full fetch to trip over.
+        match *reference {
+            Tag(ref s) if !path.exists() => {
+                let dirname = Path::new(path.dirname());
+                try!(mkdir_recursive(path, UserDir));
+                let res = self.git_network(&dirname, process("git")
+                    .arg("clone").arg("--bare").arg("--quiet")
+                    .arg("--depth").arg("1").arg("--branch").arg(s.as_slice())
+                    .arg(&self.location).arg(path));
+                if res.is_err() {
+                    let _ = rmdir_recursive(path);
+                }
+                return res
+            }
+            _ => {}
+        }
+
+        if !path.exists() {
+            try!(mkdir_recursive(path, UserDir));
+            git!(*path, "init", "--bare", "--quiet");
+        }
+
+        let refspec = match *reference {
+            Tag(ref s) => format!("refs/tags/{0}:refs/tags/{0}", s),
+            Rev(ref s) => s.clone(),
+            Branch(..) => unreachable!(),
+        };
+        Ok(git_remote!(self, *path, "fetch", "--force", "--quiet",
+                       "--depth", "1", &self.location, refspec))
     }
 
     fn clone_into(&self, path: &Path) -> CargoResult<()> {
@@ -179,8 +272,53 @@ impl GitRemote {
 
         try!(mkdir_recursive(path, UserDir));
 
-        Ok(git!(dirname, "clone", &self.location, path, "--bare",
-                "--no-hardlinks", "--quiet"))
+        Ok(git_remote!(self, dirname, "clone", &self.location, path, "--bare",
+                       "--no-hardlinks", "--quiet"))
+    }
+
+    /// Runs a git command which talks to this remote.
+    ///
+    /// Git is never allowed to prompt for credentials, as there may be no
+    /// terminal to answer it, so a remote which needs them fails right away
+    /// instead of hanging. SSH remotes still authenticate through an agent
+    /// or keys. The output of git is captured to explain such failures.
+    fn git_network(&self, path: &Path, cmd: ProcessBuilder) -> CargoResult<()> {
+        if self.offline {
+            return Err(human(format!("Could not fetch `{}` in offline mode.",
+                                     self.location)))
+        }
+        let mut cmd = git(path, cmd).env("GIT_TERMINAL_PROMPT", Some("0"));
+        if self.is_ssh() {
+            match self.ssh_command {
+                Some(ref ssh) => {
+                    cmd = cmd.env("GIT_SSH_COMMAND", Some(ssh.as_slice()));
+                }
+                None if os::getenv("GIT_SSH_COMMAND").is_none() &&
+                        os::getenv("GIT_SSH").is_none() => {
+                    cmd = cmd.env("GIT_SSH_COMMAND",
+                                  Some("ssh -o BatchMode=yes"));
+                }
+                None => {}
+            }
+        }
+
+        cmd.exec_with_output().map(|_| ()).map_err(|err| {
+            let stderr = err.output.as_ref().map(|out| {
+                to_str(out.error.as_slice())
+            }).unwrap_or(String::new());
+            caused_human(format!("Executing {} failed\n{}", cmd,
+                                 stderr.as_slice().trim_right()), err)
+        })
+    }
+
+    fn is_ssh(&self) -> bool {
+        match self.location {
+            Remote(ref url) => {
+                url.scheme.as_slice() == "ssh" ||
+                    url.scheme.as_slice() == "git+ssh"
+            }
+            _ => false,
+        }
     }
 }
 
@@ -189,47 +327,61 @@ impl GitDatabase {
         &self.path
     }
 
+    /// Checks out `rev` into `dest`.
+    ///
+    /// Checkouts are keyed by revision, so one which is already at `dest` is
+    /// used as-is, without touching the database, as long as its `HEAD` is
+    /// still `rev`. Anything else means the checkout was tampered with or
+    /// left half-finished, and it is cloned again from scratch.
     pub fn copy_to(&self, rev: GitRevision, dest: &Path)
                    -> CargoResult<GitCheckout> {
-        let checkout = try!(GitCheckout::clone_into(dest, self.clone(),
-                                                    rev.clone()));
-
-        match self.remote.rev_for(dest, "HEAD") {
-            Ok(ref head) if rev == *head => return Ok(checkout),
-            _ => try!(checkout.fetch()),
+        let checkout = GitCheckout::new(dest, self.clone(), rev.clone());
+
+        if dest.join(".git").exists() {
+            match git_output(dest, process("git").arg("rev-parse").arg("HEAD")) {
+                Ok(ref head) if rev.as_slice() == head.as_slice() &&
+                                self.has_ref(rev.as_slice()).is_ok() => {
+                    return Ok(checkout)
+                }
+                _ => {
+                    log!(5, "checkout at {} is not at {}, cloning it again",
+                         dest.display(), rev);
+                }
+            }
         }
 
-        try!(checkout.reset());
+        try!(checkout.clone_repo());
+        if checkout.reset().is_err() {
+            try!(checkout.fetch());
+            try!(checkout.reset());
+        }
         try!(checkout.update_submodules());
 
         Ok(checkout)
     }
 
-    pub fn rev_for<S: Str>(&self, reference: S) -> CargoResult<GitRevision> {
+    pub fn rev_for(&self, reference: &GitReference)
+                   -> CargoResult<GitRevision> {
         self.remote.rev_for(&self.path, reference)
     }
 
+    /// Checks that `reference` names a commit which is present in the
+    /// database.
     pub fn has_ref<S: Str>(&self, reference: S) -> CargoResult<()> {
-        git_output!(self.path, "rev-parse", "--verify", reference.as_slice());
+        git_output!(self.path, "rev-parse", "--verify",
+                    format!("{}^{{commit}}", reference.as_slice()));
         Ok(())
     }
 }
 
 impl GitCheckout {
-    fn clone_into(into: &Path, database: GitDatabase,
-                  revision: GitRevision) -> CargoResult<GitCheckout> {
-        let checkout = GitCheckout {
+    fn new(into: &Path, database: GitDatabase,
+           revision: GitRevision) -> GitCheckout {
+        GitCheckout {
             location: into.clone(),
             database: database,
             revision: revision,
-        };
-
-        // If the git checkout already exists, we don't need to clone it again
-        if !checkout.location.join(".git").exists() {
-            try!(checkout.clone_repo());
         }
-
-        Ok(checkout)
     }
 
     fn get_source(&self) -> &Path {
@@ -257,7 +409,6 @@ impl GitCheckout {
 
         git!(dirname, "clone", "--no-checkout", "--quiet",
              self.get_source(), &self.location);
-        try!(self.reset());
 
         Ok(())
     }
@@ -292,12 +443,53 @@ impl GitCheckout {
                 self.revision.as_slice()))
     }
 
+    /// Updates each submodule listed in `.gitmodules` in turn, following the
+    /// tip of its `branch` there if one is set.
     fn update_submodules(&self) -> CargoResult<()> {
-        Ok(git!(self.location, "submodule", "update", "--init",
-                "--recursive", "--quiet"))
+        let paths = match gitmodules_config(&self.location, "--get-regexp",
+                                            r"^submodule\..*\.path$") {
+            Some(paths) => paths,
+            None => return Ok(()),
+        };
+
+        for line in paths.as_slice().lines() {
+            let mut parts = line.splitn(' ', 1);
+            let (key, path) = match (parts.next(), parts.next()) {
+                (Some(key), Some(path)) => (key, path.trim()),
+                _ => continue,
+            };
+            let name = key.slice("submodule.".len(), key.len() - ".path".len());
+            let branch = gitmodules_config(&self.location, "--get",
+                                           format!("submodule.{}.branch",
+                                                   name).as_slice());
+
+            let mut cmd = process("git").arg("submodule").arg("update")
+                                        .arg("--init").arg("--recursive")
+                                        .arg("--quiet");
+            if branch.is_some() {
+                cmd = cmd.arg("--remote");
+            }
+            let cmd = git(&self.location, cmd.arg("--").arg(path));
+            try!(cmd.exec().map_err(|err| {
+                caused_human(format!("failed to update submodule `{}`", path),
+                             git_error(&cmd, err))
+            }));
+        }
+        Ok(())
     }
 }
 
+/// Looks `key` up in the `.gitmodules` of the checkout at `path` with the
+/// `git config` option `op`, returning `None` if it's not set there.
+fn gitmodules_config(path: &Path, op: &str, key: &str) -> Option<String> {
+    if !path.join(".gitmodules").exists() { return None }
+    let cmd = git(path, process("git").arg("config").arg("-f")
+                                      .arg(".gitmodules").arg(op).arg(key));
+    cmd.exec_with_output().ok().map(|output| {
+        to_str(output.output.as_slice()).as_slice().trim_right().to_string()
+    })
+}
+
 fn git(path: &Path, cmd: ProcessBuilder) -> ProcessBuilder {
     debug!("Executing {} @ {}", cmd, path.display());
 
@@ -306,19 +498,33 @@ fn git(path: &Path, cmd: ProcessBuilder) -> ProcessBuilder {
 
 fn git_inherit(path: &Path, cmd: ProcessBuilder) -> CargoResult<()> {
     let cmd = git(path, cmd);
-    cmd.exec().chain_error(|| {
-        human(format!("Executing {} failed", cmd))
-    })
+    cmd.exec().map_err(|err| git_error(&cmd, err))
 }
 
 fn git_output(path: &Path, cmd: ProcessBuilder) -> CargoResult<String> {
     let cmd = git(path, cmd);
-    let output = try!(cmd.exec_with_output().chain_error(||
-        human(format!("Executing {} failed", cmd))));
+    let output = try!(cmd.exec_with_output().map_err(|err| {
+        git_error(&cmd, err)
+    }));
 
     Ok(to_str(output.output.as_slice()).as_slice().trim_right().to_string())
 }
 
+/// Describes `cmd` failing.
+fn git_error(cmd: &ProcessBuilder, err: ProcessError) -> Box<CargoError + Send> {
+    caused_human(format!("Executing {} failed", cmd), err)
+}
+
+/// Whether `git` can be found at all. Sources check this before running any
+/// git command, so that a missing git is explained the same way whichever
+/// command would have failed first.
+pub fn git_installed() -> bool {
+    match process("git").arg("--version").exec_with_output() {
+        Err(ref err) if err.never_started() => false,
+        _ => true,
+    }
+}
+
 fn to_str(vec: &[u8]) -> String {
     String::from_utf8_lossy(vec).into_string()
 }
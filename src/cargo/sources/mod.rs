@@ -1,7 +1,9 @@
 pub use self::path::PathSource;
 pub use self::git::GitSource;
 pub use self::registry::DummyRegistrySource;
+pub use self::vendor::VendorSource;
 
 pub mod path;
 pub mod git;
 pub mod registry;
+pub mod vendor;
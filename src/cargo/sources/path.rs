@@ -1,11 +1,13 @@
 use std::cmp;
 use std::fmt::{Show, Formatter};
 use std::fmt;
-use std::io::fs;
+use std::hash::Hasher;
+use std::hash::sip::SipHasher;
+use std::io::{fs, FileStat};
 
 use core::{Package, PackageId, Summary, SourceId, Source, Dependency, Registry};
 use ops;
-use util::{CargoResult, internal, internal_error};
+use util::{CargoResult, internal, internal_error, to_hex};
 
 pub struct PathSource {
     id: SourceId,
@@ -104,34 +106,80 @@ impl Source for PathSource {
             return Err(internal_error("BUG: source was not updated", ""));
         }
 
-        let mut max = 0;
-
+        // Every file of the package counts, so that adding, removing or
+        // renaming one is noticed just like modifying it.
+        let mut files = Vec::new();
         for pkg in self.packages.iter().filter(|p| *p == pkg) {
             let loc = pkg.get_manifest_path().dir_path();
-            max = cmp::max(max, try!(walk(&loc, true)));
+            let manifest = pkg.get_manifest();
+            try!(walk_matching(&loc, |path| manifest.includes_file(path),
+                               |path, stat| {
+                files.push((path.display().to_string(), stat.modified,
+                            stat.size));
+            }));
         }
+        files.sort();
 
-        return Ok(max.to_string());
+        let hasher = SipHasher::new_with_keys(0, 0);
+        Ok(to_hex(hasher.hash(&files)))
+    }
+}
+
+/// Returns the newest modification time of any file beneath `path`.
+///
+/// The `target` directory and `Cargo.lock` directly inside of `path` are
+/// skipped, as are any nested packages and build directories.
+pub fn last_modified(path: &Path) -> CargoResult<u64> {
+    last_modified_matching(path, |_| true)
+}
 
-        fn walk(path: &Path, is_root: bool) -> CargoResult<u64> {
-            if !path.is_dir() {
-                // An fs::stat error here is either because path is a
-                // broken symlink, a permissions error, or a race
-                // condition where this path was rm'ed - either way,
-                // we can ignore the error and treat the path's mtime
-                // as 0.
-                return Ok(fs::stat(path).map(|s| s.modified).unwrap_or(0))
+/// Like `last_modified`, but only considers the files for which `include`,
+/// given the file's path relative to `path`, returns `true`.
+pub fn last_modified_matching(path: &Path, include: |&Path| -> bool)
+                              -> CargoResult<u64> {
+    let mut max = 0;
+    try!(walk_matching(path, include, |_, stat| {
+        max = cmp::max(max, stat.modified);
+    }));
+    Ok(max)
+}
+
+/// Calls `f` with the path, relative to `path`, and the metadata of each file
+/// beneath `path` for which `include` returns `true`, skipping the same files
+/// as `last_modified`.
+fn walk_matching(path: &Path, mut include: |&Path| -> bool,
+                 mut f: |&Path, &FileStat|) -> CargoResult<()> {
+    return walk(path, path, true, &mut include, &mut f);
+
+    fn walk(root: &Path, path: &Path, is_root: bool,
+            include: &mut |&Path| -> bool,
+            f: &mut |&Path, &FileStat|) -> CargoResult<()> {
+        if !path.is_dir() {
+            let relative = path.path_relative_from(root).unwrap();
+            if !(*include)(&relative) {
+                return Ok(())
             }
-            // Don't recurse into any sub-packages that we have
-            if !is_root && path.join("Cargo.toml").exists() { return Ok(0) }
-
-            let mut max = 0;
-            for dir in try!(fs::readdir(path)).iter() {
-                if is_root && dir.filename_str() == Some("target") { continue }
-                if is_root && dir.filename_str() == Some("Cargo.lock") { continue }
-                max = cmp::max(max, try!(walk(dir, false)));
+            // An fs::stat error here is either because path is a
+            // broken symlink, a permissions error, or a race
+            // condition where this path was rm'ed - either way,
+            // we can ignore the error and skip the path.
+            match fs::stat(path) {
+                Ok(stat) => (*f)(&relative, &stat),
+                Err(..) => {}
             }
-            return Ok(max)
+            return Ok(())
         }
+        // Don't recurse into any sub-packages that we have
+        if !is_root && path.join("Cargo.toml").exists() { return Ok(()) }
+        // Nor into build output relocated into the package, which is
+        // recognized by the lock taken on it while building
+        if !is_root && path.join(".cargo-lock").exists() { return Ok(()) }
+
+        for dir in try!(fs::readdir(path)).iter() {
+            if is_root && dir.filename_str() == Some("target") { continue }
+            if is_root && dir.filename_str() == Some("Cargo.lock") { continue }
+            try!(walk(root, dir, false, include, f));
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,123 @@
+use std::fmt::{Show, Formatter};
+use std::fmt;
+use std::io::fs;
+
+use core::{Package, PackageId, Summary, SourceId, Source, Dependency, Registry};
+use ops;
+use util::{CargoResult, ChainError, human, internal, hash_tree, to_hex};
+
+/// A source which provides the packages of the central registry from local
+/// directories of unpacked packages instead, for builds which must not touch
+/// the network.
+///
+/// Each subdirectory of a vendor directory holds one package along with its
+/// `Cargo.toml`.
+pub struct VendorSource {
+    id: SourceId,
+    dirs: Vec<Path>,
+    updated: bool,
+    packages: Vec<Package>,
+}
+
+impl VendorSource {
+    pub fn new(id: &SourceId, dirs: Vec<Path>) -> VendorSource {
+        log!(5, "new; id={}; dirs={}", id,
+             dirs.iter().map(|d| d.display().to_string())
+                 .collect::<Vec<String>>());
+
+        VendorSource {
+            id: id.clone(),
+            dirs: dirs,
+            updated: false,
+            packages: Vec::new(),
+        }
+    }
+
+    fn dir_list(&self) -> String {
+        self.dirs.iter().map(|d| format!("`{}`", d.display()))
+                 .collect::<Vec<String>>().connect(", ")
+    }
+}
+
+impl Show for VendorSource {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "the vendored packages in {}", self.dir_list())
+    }
+}
+
+impl Registry for VendorSource {
+    fn query(&mut self, dep: &Dependency) -> CargoResult<Vec<Summary>> {
+        let mut summaries: Vec<Summary> = self.packages.iter()
+                                              .map(|p| p.get_summary().clone())
+                                              .collect();
+        let ret = try!(summaries.query(dep));
+
+        // Everything the registry was asked for must be vendored, so say where
+        // we looked rather than leaving the resolver to report a missing
+        // package.
+        if ret.is_empty() && dep.get_source_id() == &self.id {
+            let found = self.packages.iter().filter(|p| {
+                p.get_name() == dep.get_name()
+            }).map(|p| p.get_version().to_string()).collect::<Vec<String>>();
+            let found = if found.is_empty() {
+                String::new()
+            } else {
+                format!(" (found versions: {})", found.connect(", "))
+            };
+            return Err(human(format!("no package matching `{} {}` was found \
+                                      in the vendor directory {}{}",
+                                     dep.get_name(), dep.get_version_req(),
+                                     self.dir_list(), found)))
+        }
+        Ok(ret)
+    }
+}
+
+impl Source for VendorSource {
+    fn update(&mut self) -> CargoResult<()> {
+        if self.updated { return Ok(()) }
+
+        for dir in self.dirs.iter() {
+            let mut entries = try!(fs::readdir(dir).chain_error(|| {
+                human(format!("failed to read vendor directory `{}`",
+                              dir.display()))
+            }));
+            entries.sort();
+            for entry in entries.iter() {
+                if !entry.join("Cargo.toml").exists() { continue }
+                let packages = try!(ops::read_packages(entry, &self.id));
+                self.packages.push_all_move(packages);
+            }
+        }
+        self.updated = true;
+        Ok(())
+    }
+
+    fn download(&self, _: &[PackageId]) -> CargoResult<()> {
+        // Vendored packages are already on the local file system
+        Ok(())
+    }
+
+    fn get(&self, ids: &[PackageId]) -> CargoResult<Vec<Package>> {
+        log!(5, "getting packages; ids={}", ids);
+
+        Ok(self.packages.iter()
+           .filter(|pkg| ids.iter().any(|id| pkg.get_package_id() == id))
+           .map(|pkg| pkg.clone())
+           .collect())
+    }
+
+    fn fingerprint(&self, pkg: &Package) -> CargoResult<String> {
+        if !self.updated {
+            return Err(internal("BUG: source was not updated"));
+        }
+
+        let root = pkg.get_manifest_path().dir_path();
+        let hash = try!(hash_tree(&root, |p| {
+            p.dir_path() == root && p.filename_str() == Some("target")
+        }).chain_error(|| {
+            human(format!("failed to fingerprint `{}`", root.display()))
+        }));
+        Ok(to_hex(hash))
+    }
+}
@@ -13,8 +13,22 @@ pub struct Config<'a> {
     shell: &'a mut MultiShell,
     jobs: uint,
     target: Option<String>,
+    rustc: String,
     linker: Option<String>,
     ar: Option<String>,
+    dep_info_out: Option<Path>,
+    target_dir: Option<Path>,
+    hash_contents: bool,
+    locked: bool,
+    offline: bool,
+    git_shallow: bool,
+    git_ssh_command: Option<String>,
+    json_messages: bool,
+    keep_going: bool,
+    vendor_dirs: Vec<Path>,
+    target_rustc_args: Vec<String>,
+    timings: bool,
+    deny_primary_warnings: bool,
 }
 
 impl<'a> Config<'a> {
@@ -25,6 +39,7 @@ impl<'a> Config<'a> {
         if jobs == Some(0) {
             return Err(human("jobs must be at least 1"))
         }
+        let frozen = os::getenv("CARGO_FROZEN").is_some();
         Ok(Config {
             home_path: try!(os::homedir().require(|| {
                 human("Cargo couldn't find your home directory. \
@@ -34,8 +49,22 @@ impl<'a> Config<'a> {
             shell: shell,
             jobs: jobs.unwrap_or(os::num_cpus()),
             target: target,
+            rustc: os::getenv("RUSTC").unwrap_or("rustc".to_string()),
             ar: None,
             linker: None,
+            dep_info_out: None,
+            target_dir: None,
+            hash_contents: false,
+            locked: frozen || os::getenv("CARGO_LOCKED").is_some(),
+            offline: frozen || os::getenv("CARGO_OFFLINE").is_some(),
+            git_shallow: false,
+            git_ssh_command: None,
+            json_messages: false,
+            keep_going: false,
+            vendor_dirs: Vec::new(),
+            target_rustc_args: Vec::new(),
+            timings: false,
+            deny_primary_warnings: false,
         })
     }
 
@@ -65,6 +94,19 @@ impl<'a> Config<'a> {
         self.target.as_ref().map(|t| t.as_slice())
     }
 
+    /// Overrides the compiler used unless `RUSTC` is set in the environment,
+    /// which always wins.
+    pub fn set_rustc(&mut self, rustc: String) {
+        if os::getenv("RUSTC").is_none() {
+            self.rustc = rustc;
+        }
+    }
+
+    /// The path of the compiler to invoke, `rustc` unless overridden.
+    pub fn rustc(&self) -> &str {
+        self.rustc.as_slice()
+    }
+
     pub fn set_ar(&mut self, ar: String) { self.ar = Some(ar); }
 
     pub fn set_linker(&mut self, linker: String) { self.linker = Some(linker); }
@@ -75,6 +117,140 @@ impl<'a> Config<'a> {
     pub fn ar(&self) -> Option<&str> {
         self.ar.as_ref().map(|t| t.as_slice())
     }
+
+    pub fn set_dep_info_out(&mut self, dir: Path) {
+        self.dep_info_out = Some(dir);
+    }
+
+    /// The directory into which a copy of each target's dep-info is written,
+    /// if one was requested.
+    pub fn dep_info_out(&self) -> Option<&Path> {
+        self.dep_info_out.as_ref()
+    }
+
+    pub fn set_target_dir(&mut self, dir: Path) {
+        self.target_dir = Some(dir);
+    }
+
+    /// The directory all build output is placed in, if it was redirected
+    /// away from the `target` directory of the root package.
+    pub fn target_dir(&self) -> Option<&Path> {
+        self.target_dir.as_ref()
+    }
+
+    /// Requires the lockfile to be used as-is: it is neither generated nor
+    /// rewritten, and the checksums recorded in it must match. Also enabled
+    /// by `CARGO_LOCKED` or `CARGO_FROZEN` in the environment.
+    pub fn set_locked(&mut self) {
+        self.locked = true;
+    }
+
+    /// Forbids any network access. Also enabled by `CARGO_OFFLINE` or
+    /// `CARGO_FROZEN` in the environment.
+    pub fn set_offline(&mut self) {
+        self.offline = true;
+    }
+
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Whether the build is both locked and offline.
+    pub fn frozen(&self) -> bool {
+        self.locked && self.offline
+    }
+
+    /// Fetch only the requested commit of git dependencies pinned to a tag
+    /// or revision, rather than their whole history.
+    pub fn set_git_shallow(&mut self, shallow: bool) {
+        self.git_shallow = shallow;
+    }
+
+    pub fn git_shallow(&self) -> bool {
+        self.git_shallow
+    }
+
+    /// The command git uses to connect to SSH remotes, if configured.
+    pub fn set_git_ssh_command(&mut self, cmd: String) {
+        self.git_ssh_command = Some(cmd);
+    }
+
+    pub fn git_ssh_command(&self) -> Option<&str> {
+        self.git_ssh_command.as_ref().map(|s| s.as_slice())
+    }
+
+    pub fn set_json_messages(&mut self, json: bool) {
+        self.json_messages = json;
+    }
+
+    /// Whether compiler diagnostics and build progress are reported as
+    /// line-delimited JSON on stdout instead of as human readable text.
+    pub fn json_messages(&self) -> bool {
+        self.json_messages
+    }
+
+    pub fn set_keep_going(&mut self, keep_going: bool) {
+        self.keep_going = keep_going;
+    }
+
+    /// Whether targets which don't depend on a failed target keep being
+    /// built, all failures being reported at the end.
+    pub fn keep_going(&self) -> bool {
+        self.keep_going
+    }
+
+    pub fn set_timings(&mut self, timings: bool) {
+        self.timings = timings;
+    }
+
+    /// Whether to report how long each package took to compile once the
+    /// build has finished.
+    pub fn timings(&self) -> bool {
+        self.timings
+    }
+
+    pub fn set_deny_primary_warnings(&mut self, deny: bool) {
+        self.deny_primary_warnings = deny;
+    }
+
+    /// Whether warnings in the root package are turned into errors, leaving
+    /// the warnings of its dependencies alone.
+    pub fn deny_primary_warnings(&self) -> bool {
+        self.deny_primary_warnings
+    }
+
+    pub fn set_target_rustc_args(&mut self, args: Vec<String>) {
+        self.target_rustc_args = args;
+    }
+
+    /// Flags appended to the compiler invocations of the root package only.
+    pub fn target_rustc_args(&self) -> &[String] {
+        self.target_rustc_args.as_slice()
+    }
+
+    pub fn set_hash_contents(&mut self, hash: bool) {
+        self.hash_contents = hash;
+    }
+
+    /// Whether freshness checks fall back to comparing the contents of files
+    /// whose mtime is not older than the last build.
+    pub fn hash_contents(&self) -> bool {
+        self.hash_contents
+    }
+
+    pub fn set_vendor_dirs(&mut self, dirs: Vec<Path>) {
+        self.vendor_dirs = dirs;
+    }
+
+    /// Directories of unpacked packages which provide the packages of the
+    /// registry instead of the registry itself.
+    pub fn vendor_dirs(&self) -> &[Path] {
+        self.vendor_dirs.as_slice()
+    }
 }
 
 #[deriving(Eq,PartialEq,Clone,Encodable,Decodable)]
@@ -88,6 +264,7 @@ pub enum ConfigValueValue {
     String(String),
     List(Vec<String>),
     Table(HashMap<String, ConfigValue>),
+    Boolean(bool),
 }
 
 impl fmt::Show for ConfigValueValue {
@@ -96,6 +273,7 @@ impl fmt::Show for ConfigValueValue {
             String(ref string) => write!(f, "{}", string),
             List(ref list) => write!(f, "{}", list),
             Table(ref table) => write!(f, "{}", table),
+            Boolean(b) => write!(f, "{}", b),
         }
     }
 }
@@ -106,6 +284,7 @@ impl<E, S: Encoder<E>> Encodable<S, E> for ConfigValueValue {
             String(ref string) => string.encode(s),
             List(ref list) => list.encode(s),
             Table(ref table) => table.encode(s),
+            Boolean(b) => b.encode(s),
         }
     }
 }
@@ -128,6 +307,7 @@ impl ConfigValue {
     fn from_toml(path: &Path, toml: toml::Value) -> CargoResult<ConfigValue> {
         let value = match toml {
             toml::String(val) => String(val),
+            toml::Boolean(val) => Boolean(val),
             toml::Array(val) => {
                 List(try!(result::collect(val.move_iter().map(|toml| {
                     match toml {
@@ -155,6 +335,10 @@ impl ConfigValue {
                 mem::swap(old, new);
                 self.path = path;
             }
+            (&Boolean(ref mut old), Boolean(new)) => {
+                *old = new;
+                self.path = path;
+            }
             (&List(ref mut old), List(ref mut new)) => {
                 old.extend(mem::replace(new, Vec::new()).move_iter());
                 self.path.extend(path.move_iter());
@@ -183,6 +367,7 @@ impl ConfigValue {
         match self.value {
             Table(_) => Err(internal("expected a string, but found a table")),
             List(_) => Err(internal("expected a string, but found a list")),
+            Boolean(_) => Err(internal("expected a string, but found a boolean")),
             String(ref s) => Ok(s.as_slice()),
         }
     }
@@ -191,6 +376,7 @@ impl ConfigValue {
         match self.value {
             String(_) => Err(internal("expected a table, but found a string")),
             List(_) => Err(internal("expected a table, but found a list")),
+            Boolean(_) => Err(internal("expected a table, but found a boolean")),
             Table(ref table) => Ok(table),
         }
     }
@@ -199,9 +385,19 @@ impl ConfigValue {
         match self.value {
             String(_) => Err(internal("expected a list, but found a string")),
             Table(_) => Err(internal("expected a list, but found a table")),
+            Boolean(_) => Err(internal("expected a list, but found a boolean")),
             List(ref list) => Ok(list.as_slice()),
         }
     }
+
+    pub fn boolean(&self) -> CargoResult<bool> {
+        match self.value {
+            String(_) => Err(internal("expected a boolean, but found a string")),
+            Table(_) => Err(internal("expected a boolean, but found a table")),
+            List(_) => Err(internal("expected a boolean, but found a list")),
+            Boolean(b) => Ok(b),
+        }
+    }
 }
 
 impl ConfigValueValue {
@@ -210,6 +406,7 @@ impl ConfigValueValue {
             Table(..) => "table",
             List(..) => "array",
             String(..) => "string",
+            Boolean(..) => "boolean",
         }
     }
 }
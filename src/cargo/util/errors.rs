@@ -163,6 +163,12 @@ impl Show for ProcessError {
 }
 
 impl ProcessError {
+    /// Whether the process couldn't be started at all, which usually means
+    /// that its program isn't installed.
+    pub fn never_started(&self) -> bool {
+        self.exit.is_none()
+    }
+
     pub fn output(&self) -> Option<String> {
         match self.output {
             Some(ref out) => {
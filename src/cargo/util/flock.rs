@@ -0,0 +1,106 @@
+use std::io::fs;
+use std::io::UserRWX;
+
+use core::MultiShell;
+use util::{CargoResult, ChainError, human};
+
+/// An exclusive advisory lock on a file, held until the value is dropped.
+///
+/// Dropping the lock releases it, so it is given up even when the task
+/// holding it fails, and the operating system releases it should the whole
+/// process die.
+pub struct FileLock {
+    fd: imp::Fd,
+}
+
+impl FileLock {
+    /// Locks the file at `path`, creating it and its parent directories if
+    /// needed.
+    ///
+    /// If another process holds the lock already, a note saying that we are
+    /// waiting for the lock on `what` is printed before blocking until it is
+    /// released.
+    pub fn lock(path: &Path, what: &str,
+                shell: &mut MultiShell) -> CargoResult<FileLock> {
+        try!(fs::mkdir_recursive(&path.dir_path(), UserRWX).chain_error(|| {
+            human(format!("failed to create directory `{}`",
+                          path.dir_path().display()))
+        }));
+        let fd = try!(imp::open(path).chain_error(|| {
+            human(format!("failed to open `{}`", path.display()))
+        }));
+        let lock = FileLock { fd: fd };
+
+        if imp::try_lock(lock.fd).is_err() {
+            try!(shell.status("Blocking",
+                              format!("waiting for file lock on {}", what)));
+            try!(imp::lock(lock.fd).chain_error(|| {
+                human(format!("failed to lock `{}`", path.display()))
+            }));
+        }
+        Ok(lock)
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        imp::unlock(self.fd);
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::io::{IoResult, IoError};
+    use libc;
+
+    pub type Fd = libc::c_int;
+
+    static LOCK_EX: libc::c_int = 2;
+    static LOCK_NB: libc::c_int = 4;
+    static LOCK_UN: libc::c_int = 8;
+
+    extern {
+        fn flock(fd: libc::c_int, operation: libc::c_int) -> libc::c_int;
+    }
+
+    pub fn open(path: &Path) -> IoResult<Fd> {
+        let fd = path.with_c_str(|p| unsafe {
+            libc::open(p, libc::O_RDWR | libc::O_CREAT,
+                       libc::S_IRUSR | libc::S_IWUSR)
+        });
+        if fd < 0 { Err(IoError::last_error()) } else { Ok(fd) }
+    }
+
+    pub fn try_lock(fd: Fd) -> IoResult<()> {
+        check(unsafe { flock(fd, LOCK_EX | LOCK_NB) })
+    }
+
+    pub fn lock(fd: Fd) -> IoResult<()> {
+        check(unsafe { flock(fd, LOCK_EX) })
+    }
+
+    pub fn unlock(fd: Fd) {
+        unsafe {
+            flock(fd, LOCK_UN);
+            libc::close(fd);
+        }
+    }
+
+    fn check(ret: libc::c_int) -> IoResult<()> {
+        if ret < 0 { Err(IoError::last_error()) } else { Ok(()) }
+    }
+}
+
+// TODO: lock files on windows as well, for now only one build at a time is
+//       expected there.
+#[cfg(windows)]
+mod imp {
+    use std::io::IoResult;
+
+    pub type Fd = ();
+
+    pub fn open(_path: &Path) -> IoResult<Fd> { Ok(()) }
+    pub fn try_lock(_fd: Fd) -> IoResult<()> { Ok(()) }
+    pub fn lock(_fd: Fd) -> IoResult<()> { Ok(()) }
+    pub fn unlock(_fd: Fd) {}
+}
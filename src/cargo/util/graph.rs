@@ -30,6 +30,13 @@ impl<N: Eq + Hash + Clone> Graph<N> {
             .insert(child);
     }
 
+    pub fn unlink(&mut self, node: &N, child: &N) {
+        match self.nodes.find_mut(node) {
+            Some(children) => { children.remove(child); }
+            None => {}
+        }
+    }
+
     pub fn get_nodes(&self) -> &HashMap<N, HashSet<N>> {
         &self.nodes
     }
@@ -1,4 +1,6 @@
 use std::{io,os};
+use std::hash::Hash;
+use std::hash::sip::SipState;
 use std::io::fs;
 
 pub fn realpath(original: &Path) -> io::IoResult<Path> {
@@ -38,3 +40,93 @@ pub fn realpath(original: &Path) -> io::IoResult<Path> {
     return Ok(result);
 }
 
+
+/// Hashes the relative path and contents of every file underneath `root`.
+///
+/// Directories for which `skip` returns `true` are not descended into. Files
+/// are visited in sorted order so the result only depends on the contents of
+/// the tree, not on the order in which the filesystem lists it.
+pub fn hash_tree(root: &Path, mut skip: |&Path| -> bool) -> io::IoResult<u64> {
+    let mut state = SipState::new_with_keys(0, 0);
+    try!(walk(root, root, &mut state, &mut skip));
+    return Ok(state.result());
+
+    fn walk(root: &Path, path: &Path, state: &mut SipState,
+            skip: &mut |&Path| -> bool) -> io::IoResult<()> {
+        let mut entries = try!(fs::readdir(path));
+        entries.sort();
+        for entry in entries.iter() {
+            if entry.is_dir() {
+                if !(*skip)(entry) {
+                    try!(walk(root, entry, state, skip));
+                }
+                continue
+            }
+            let relative = entry.path_relative_from(root).unwrap();
+            relative.as_vec().hash(state);
+            try!(fs::File::open(entry).read_to_end()).hash(state);
+        }
+        Ok(())
+    }
+}
+
+/// Matches the `/` separated `path` against a shell style `glob`.
+///
+/// `?` matches any single character and `*` any run of characters, neither
+/// of them crossing a `/`, while `**` also matches across directories.
+pub fn glob_matches(glob: &str, path: &str) -> bool {
+    return matches(glob.as_bytes(), path.as_bytes());
+
+    fn matches(glob: &[u8], path: &[u8]) -> bool {
+        if glob.is_empty() { return path.is_empty() }
+
+        let star = '*' as u8;
+        let slash = '/' as u8;
+        if glob[0] == star && glob.len() > 1 && glob[1] == star {
+            let rest = glob.slice_from(2);
+            // `**/` also matches no directories at all
+            if !rest.is_empty() && rest[0] == slash &&
+               matches(rest.slice_from(1), path) {
+                return true
+            }
+            return range(0, path.len() + 1).any(|i| {
+                matches(rest, path.slice_from(i))
+            })
+        }
+        if glob[0] == star {
+            for i in range(0, path.len() + 1) {
+                if matches(glob.slice_from(1), path.slice_from(i)) {
+                    return true
+                }
+                if i < path.len() && path[i] == slash { break }
+            }
+            return false
+        }
+        if path.is_empty() { return false }
+        if glob[0] == '?' as u8 {
+            path[0] != slash && matches(glob.slice_from(1), path.slice_from(1))
+        } else {
+            glob[0] == path[0] && matches(glob.slice_from(1), path.slice_from(1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::glob_matches;
+
+    #[test]
+    fn globs() {
+        assert!(glob_matches("docs", "docs"));
+        assert!(!glob_matches("docs", "docs/index.md"));
+        assert!(glob_matches("*.swp", ".main.rs.swp"));
+        assert!(!glob_matches("*.swp", "src/.main.rs.swp"));
+        assert!(glob_matches("src/*.rs", "src/main.rs"));
+        assert!(!glob_matches("src/*.rs", "src/bin/main.rs"));
+        assert!(glob_matches("src/**/*.rs", "src/main.rs"));
+        assert!(glob_matches("src/**/*.rs", "src/bin/main.rs"));
+        assert!(glob_matches("**/*.swp", "src/.main.rs.swp"));
+        assert!(glob_matches("src/?.rs", "src/a.rs"));
+        assert!(!glob_matches("src/?.rs", "src/ab.rs"));
+    }
+}
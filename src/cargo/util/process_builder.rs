@@ -2,8 +2,10 @@ use std::fmt;
 use std::fmt::{Show, Formatter};
 use std::os;
 use std::c_str::CString;
+use std::io::{BufferedReader, Buffer, stdio};
 use std::io::process::{Command, ProcessOutput, InheritFd};
 use std::collections::HashMap;
+use std::task;
 
 use util::{ProcessError, process_error};
 
@@ -90,6 +92,42 @@ impl ProcessBuilder {
         }
     }
 
+    /// Runs the process, passing each line it writes to stdout or stderr on
+    /// as soon as it is complete while also capturing both.
+    ///
+    /// Every line is written out in one go, so several processes streaming at
+    /// once are interleaved line by line rather than in the middle of a line.
+    pub fn exec_with_streaming(&self) -> Result<ProcessOutput, ProcessError> {
+        let msg = || format!("Could not execute process `{}`",
+                             self.debug_string());
+
+        let mut child = try!(self.build_command().spawn().map_err(|e| {
+            process_error(msg(), Some(e), None, None)
+        }));
+        drop(child.stdin.take());
+
+        let stderr = child.stderr.take().unwrap();
+        let (tx, rx) = channel();
+        task::spawn(proc() {
+            tx.send(forward_lines(stderr, stdio::stderr_raw()));
+        });
+        let output = forward_lines(child.stdout.take().unwrap(),
+                                   stdio::stdout_raw());
+        let error = rx.recv();
+
+        let status = try!(child.wait().map_err(|e| {
+            process_error(msg(), Some(e), None, None)
+        }));
+        let output = ProcessOutput { status: status, output: output, error: error };
+
+        if output.status.success() {
+            Ok(output)
+        } else {
+            Err(process_error(msg(), None, Some(&output.status),
+                              Some(&output)))
+        }
+    }
+
     pub fn build_command(&self) -> Command {
         let mut command = Command::new(self.program.as_bytes_no_nul());
         command.cwd(&self.cwd);
@@ -118,6 +156,23 @@ impl ProcessBuilder {
     }
 }
 
+/// Copies `src` to `dst` a line at a time until it ends, returning everything
+/// which was read. Failing to write to `dst` doesn't stop the capture.
+fn forward_lines<R: Reader, W: Writer>(src: R, mut dst: W) -> Vec<u8> {
+    let mut src = BufferedReader::new(src);
+    let mut captured = Vec::new();
+    loop {
+        match src.read_until(b'\n') {
+            Ok(line) => {
+                let _ = dst.write(line.as_slice());
+                captured.push_all(line.as_slice());
+            }
+            Err(..) => break,
+        }
+    }
+    captured
+}
+
 pub fn process<T: ToCStr>(cmd: T) -> ProcessBuilder {
     ProcessBuilder {
         program: cmd.to_c_str(),
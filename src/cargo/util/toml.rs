@@ -5,7 +5,8 @@ use std::io::fs;
 use std::str;
 use toml;
 
-use core::{SourceId, GitKind};
+use core::{SourceId, GitKind, GitReference};
+use core::source::{Branch, Tag, Rev};
 use core::manifest::{LibKind, Lib, Dylib, Profile};
 use core::{Summary, Manifest, Target, Dependency, PackageId};
 use core::package_id::Metadata;
@@ -23,6 +24,7 @@ pub struct Layout {
     bins: Vec<Path>,
     examples: Vec<Path>,
     tests: Vec<Path>,
+    benches: Vec<Path>,
 }
 
 impl Layout {
@@ -59,6 +61,7 @@ pub fn project_layout(root_path: &Path) -> Layout {
     let mut bins = vec!();
     let mut examples = vec!();
     let mut tests = vec!();
+    let mut benches = vec!();
 
     if root_path.join("src/lib.rs").exists() {
         lib = Some(root_path.join("src/lib.rs"));
@@ -71,12 +74,15 @@ pub fn project_layout(root_path: &Path) -> Layout {
 
     try_add_files(&mut tests, root_path, "tests");
 
+    try_add_files(&mut benches, root_path, "benches");
+
     Layout {
         root: root_path.clone(),
         lib: lib,
         bins: bins,
         examples: examples,
         tests: tests,
+        benches: benches,
     }
 }
 
@@ -158,6 +164,7 @@ type TomlLibTarget = TomlTarget;
 type TomlBinTarget = TomlTarget;
 type TomlExampleTarget = TomlTarget;
 type TomlTestTarget = TomlTarget;
+type TomlBenchTarget = TomlTarget;
 
 /*
  * TODO: Make all struct fields private
@@ -177,7 +184,10 @@ pub struct DetailedTomlDependency {
     git: Option<String>,
     branch: Option<String>,
     tag: Option<String>,
-    rev: Option<String>
+    rev: Option<String>,
+    package: Option<String>,
+    optional: Option<bool>,
+    features: Option<Vec<String>>,
 }
 
 #[deriving(Encodable,Decodable,PartialEq,Clone)]
@@ -188,8 +198,71 @@ pub struct TomlManifest {
     bin: Option<Vec<TomlBinTarget>>,
     example: Option<Vec<TomlExampleTarget>>,
     test: Option<Vec<TomlTestTarget>>,
+    bench: Option<Vec<TomlBenchTarget>>,
+    dependencies: Option<HashMap<String, TomlDependency>>,
+    dev_dependencies: Option<HashMap<String, TomlDependency>>,
+    build_dependencies: Option<HashMap<String, TomlDependency>>,
+    profile: Option<TomlProfiles>,
+    target: Option<HashMap<String, TomlPlatform>>,
+    features: Option<HashMap<String, Vec<String>>>,
+    replace: Option<HashMap<String, TomlDependency>>,
+    workspace: Option<TomlWorkspace>,
+}
+
+/// The `[workspace]` section, listing the directories of the packages which
+/// are built and locked together with this one.
+#[deriving(Encodable,Decodable,PartialEq,Clone)]
+pub struct TomlWorkspace {
+    members: Option<Vec<String>>,
+}
+
+/// The `[target.<triple>]` section, holding what only applies when building
+/// for that exact target triple.
+#[deriving(Encodable,Decodable,PartialEq,Clone)]
+pub struct TomlPlatform {
     dependencies: Option<HashMap<String, TomlDependency>>,
-    dev_dependencies: Option<HashMap<String, TomlDependency>>
+}
+
+#[deriving(Encodable,Decodable,PartialEq,Clone)]
+pub struct TomlProfiles {
+    dev: Option<TomlProfile>,
+    release: Option<TomlProfile>,
+}
+
+#[deriving(Encodable,Decodable,PartialEq,Clone)]
+pub struct TomlProfile {
+    opt_level: Option<uint>,
+    debug: Option<bool>,
+    lto: Option<bool>,
+    codegen_units: Option<uint>,
+}
+
+impl TomlProfiles {
+    fn validate(&self) -> CargoResult<()> {
+        for &(name, profile) in [("dev", &self.dev),
+                                 ("release", &self.release)].iter() {
+            let profile = match *profile {
+                Some(ref profile) => profile,
+                None => continue,
+            };
+            match profile.opt_level {
+                Some(level) if level > 3 => {
+                    return Err(human(format!("`profile.{}.opt-level` must be \
+                                              between 0 and 3, found {}",
+                                             name, level)))
+                }
+                _ => {}
+            }
+            match profile.codegen_units {
+                Some(0) => {
+                    return Err(human(format!("`profile.{}.codegen-units` must \
+                                              be at least 1", name)))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
 }
 
 #[deriving(Decodable,Encodable,PartialEq,Clone,Show)]
@@ -199,6 +272,11 @@ pub struct TomlProject {
     pub version: String,
     pub authors: Vec<String>,
     build: Option<TomlBuildCommandsList>,
+    build_env: Option<Vec<String>>,
+    build_dir: Option<String>,
+    default_target: Option<String>,
+    exclude: Option<Vec<String>>,
+    include: Option<Vec<String>>,
 }
 
 #[deriving(Encodable,Decodable,PartialEq,Clone,Show)]
@@ -233,6 +311,7 @@ fn inferred_lib_target(name: &str, layout: &Layout) -> Vec<TomlTarget> {
             crate_type: None,
             path: Some(TomlPath(lib.clone())),
             test: None,
+            bench: None,
             plugin: None,
             doc: None,
         }]
@@ -254,6 +333,7 @@ fn inferred_bin_targets(name: &str, layout: &Layout) -> Vec<TomlTarget> {
                 crate_type: None,
                 path: Some(TomlPath(bin.clone())),
                 test: None,
+                bench: None,
                 plugin: None,
                 doc: None,
             }
@@ -261,6 +341,34 @@ fn inferred_bin_targets(name: &str, layout: &Layout) -> Vec<TomlTarget> {
     }).collect()
 }
 
+/// Checks that the bins inferred from the files of a package, which are named
+/// after those files, don't share a name with the library, apart from
+/// `src/main.rs`. Bins sharing a name with each other are caught along with
+/// all other targets which would overwrite each other's output once the
+/// manifest has been read.
+fn check_inferred_bins(bins: &[TomlTarget], libs: &[TomlTarget],
+                       root: &Path) -> CargoResult<()> {
+    let display = |bin: &TomlTarget| {
+        let path = bin.path.get_ref().to_path();
+        path.path_relative_from(root).unwrap_or(path).display().to_string()
+    };
+    let main = root.join("src/main.rs");
+
+    for bin in bins.iter() {
+        match libs.head() {
+            Some(lib) if lib.name == bin.name &&
+                         bin.path.get_ref().to_path() != main => {
+                return Err(human(format!("binary `{}` has the same name as \
+                                          the library, rename it or give it a \
+                                          `[[bin]]` section with a name of its \
+                                          own", display(bin))))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 fn inferred_example_targets(layout: &Layout) -> Vec<TomlTarget> {
     layout.examples.iter().filter_map(|ex| {
         ex.filestem_str().map(|name| {
@@ -269,6 +377,7 @@ fn inferred_example_targets(layout: &Layout) -> Vec<TomlTarget> {
                 crate_type: None,
                 path: Some(TomlPath(ex.clone())),
                 test: None,
+                bench: None,
                 plugin: None,
                 doc: None,
             }
@@ -284,6 +393,23 @@ fn inferred_test_targets(layout: &Layout) -> Vec<TomlTarget> {
                 crate_type: None,
                 path: Some(TomlPath(ex.clone())),
                 test: None,
+                bench: None,
+                plugin: None,
+                doc: None,
+            }
+        })
+    }).collect()
+}
+
+fn inferred_bench_targets(layout: &Layout) -> Vec<TomlTarget> {
+    layout.benches.iter().filter_map(|ex| {
+        ex.filestem_str().map(|name| {
+            TomlTarget {
+                name: name.to_string(),
+                crate_type: None,
+                path: Some(TomlPath(ex.clone())),
+                test: None,
+                bench: None,
                 plugin: None,
                 doc: None,
             }
@@ -303,6 +429,10 @@ impl TomlManifest {
         }));
 
         let pkgid = try!(project.to_package_id(source_id));
+        match self.profile {
+            Some(ref profiles) => try!(profiles.validate()),
+            None => {}
+        }
         let metadata = pkgid.generate_metadata();
 
         // If we have no lib at all, use the inferred lib if available
@@ -319,6 +449,7 @@ impl TomlManifest {
                         crate_type: t.crate_type.clone(),
                         path: layout.lib.as_ref().map(|p| TomlPath(p.clone())),
                         test: t.test,
+                        bench: t.bench,
                         plugin: t.plugin,
                         doc: t.doc,
                     }
@@ -328,25 +459,46 @@ impl TomlManifest {
             }).collect()
         };
 
+        let inferred_bins = inferred_bin_targets(project.name.as_slice(), layout);
         let bins = if self.bin.is_none() || self.bin.get_ref().is_empty() {
-            inferred_bin_targets(project.name.as_slice(), layout)
+            try!(check_inferred_bins(inferred_bins.as_slice(), lib.as_slice(),
+                                     &layout.root));
+            inferred_bins
         } else {
             let bin = layout.main();
 
-            self.bin.get_ref().iter().map(|t| {
+            let mut bins: Vec<TomlTarget> = self.bin.get_ref().iter().map(|t| {
                 if bin.is_some() && t.path.is_none() {
                     TomlTarget {
                         name: t.name.clone(),
                         crate_type: t.crate_type.clone(),
                         path: bin.as_ref().map(|&p| TomlPath(p.clone())),
                         test: t.test,
+                        bench: t.bench,
                         plugin: None,
                         doc: t.doc,
                     }
                 } else {
                     t.clone()
                 }
-            }).collect()
+            }).collect();
+
+            // Files in `src/bin` are still built alongside the `[[bin]]`
+            // sections, unless one of the sections already names or builds
+            // them.
+            let main = layout.root.join("src/main.rs");
+            let extra: Vec<TomlTarget> = inferred_bins.move_iter().filter(|inferred| {
+                let path = inferred.path.get_ref().to_path();
+                path != main && !bins.iter().any(|b| {
+                    b.name == inferred.name || b.path.as_ref().map_or(false, |p| {
+                        layout.root.join(p.to_path()) == path
+                    })
+                })
+            }).collect();
+            try!(check_inferred_bins(extra.as_slice(), lib.as_slice(),
+                                     &layout.root));
+            bins.push_all_move(extra);
+            bins
         };
 
         let examples = if self.example.is_none() || self.example.get_ref().is_empty() {
@@ -361,12 +513,30 @@ impl TomlManifest {
             self.test.get_ref().iter().map(|t| t.clone()).collect()
         };
 
+        let benches = if self.bench.is_none() || self.bench.get_ref().is_empty() {
+            inferred_bench_targets(layout)
+        } else {
+            self.bench.get_ref().iter().map(|t| t.clone()).collect()
+        };
+
+        // Without `src/lib.rs` or an explicit `path`, a `[[lib]]` falls back
+        // to `src/<name>.rs`, which is only supported for older packages.
+        let legacy_lib = lib.as_slice().head().and_then(|l| {
+            if l.path.is_some() { return None }
+            Some(format!("the library `{name}` is loaded from `src/{name}.rs`, \
+                          which is deprecated; rename it to `src/lib.rs` or \
+                          set `path = \"src/{name}.rs\"` in its `[[lib]]` \
+                          section", name = l.name))
+        });
+
         // Get targets
         let targets = normalize(lib.as_slice(),
                                 bins.as_slice(),
                                 examples.as_slice(),
                                 tests.as_slice(),
-                                &metadata);
+                                benches.as_slice(),
+                                &metadata,
+                                self.profile.as_ref());
 
         if targets.is_empty() {
             debug!("manifest has no build targets; project={}", self.project);
@@ -384,27 +554,100 @@ impl TomlManifest {
             };
 
             // Collect the deps
-            try!(process_dependencies(&mut cx, false, self.dependencies.as_ref()));
-            try!(process_dependencies(&mut cx, true, self.dev_dependencies.as_ref()));
+            try!(process_dependencies(&mut cx, Normal, None,
+                                      self.dependencies.as_ref()));
+            try!(process_dependencies(&mut cx, Development, None,
+                                      self.dev_dependencies.as_ref()));
+            try!(process_dependencies(&mut cx, Build, None,
+                                      self.build_dependencies.as_ref()));
+
+            match self.target {
+                Some(ref platforms) => {
+                    for (triple, platform) in platforms.iter() {
+                        try!(process_dependencies(&mut cx, Normal,
+                                                  Some(triple.as_slice()),
+                                                  platform.dependencies.as_ref()));
+                    }
+                }
+                None => {}
+            }
         }
 
+        let replace = try!(self.replacements(&layout.root));
+
         let summary = Summary::new(&pkgid, deps.as_slice());
-        Ok((Manifest::new(
-                &summary,
-                targets.as_slice(),
-                &Path::new("target"),
-                &Path::new("doc"),
-                sources,
-                match project.build {
-                    Some(SingleBuildCommand(ref cmd)) => vec!(cmd.clone()),
-                    Some(MultipleBuildCommands(ref cmd)) => cmd.clone(),
-                    None => Vec::new()
-                }),
-           nested_paths))
+        let summary = try!(summary.with_features(self.features.clone()
+                                                     .unwrap_or(HashMap::new())));
+        let mut manifest = Manifest::new(
+            &summary,
+            targets.as_slice(),
+            &Path::new("target"),
+            &Path::new("doc"),
+            sources,
+            match project.build {
+                Some(SingleBuildCommand(ref cmd)) => vec!(cmd.clone()),
+                Some(MultipleBuildCommands(ref cmd)) => cmd.clone(),
+                None => Vec::new()
+            },
+            project.build_env.clone().unwrap_or(Vec::new()),
+            project.build_dir.as_ref().map(|s| Path::new(s.as_slice())),
+            project.default_target.clone(),
+            project.exclude.clone().unwrap_or(Vec::new()),
+            project.include.clone().unwrap_or(Vec::new()),
+            replace);
+        for warning in legacy_lib.move_iter() {
+            manifest.add_warning(warning);
+        }
+
+        let members: Vec<Path> = self.workspace.as_ref().and_then(|w| {
+            w.members.as_ref()
+        }).map(|members| {
+            members.iter().map(|m| Path::new(m.as_slice())).collect()
+        }).unwrap_or(Vec::new());
+        nested_paths.push_all(members.as_slice());
+        manifest.set_workspace_members(members);
+
+        Ok((manifest, nested_paths))
     }
+
+    /// Parses the `[replace]` table, whose keys are `name:version` and whose
+    /// values give the `path`, relative to `root`, of the replacement.
+    fn replacements(&self, root: &Path) -> CargoResult<Vec<PackageId>> {
+        let table = match self.replace {
+            Some(ref table) => table,
+            None => return Ok(Vec::new()),
+        };
+        let mut ret = Vec::new();
+        for (spec, dep) in table.iter() {
+            let parts: Vec<&str> = spec.as_slice().splitn(':', 1).collect();
+            if parts.len() != 2 {
+                return Err(human(format!("replacement `{}` must be of the \
+                                          form `name:version`", spec)))
+            }
+            let path = match *dep {
+                DetailedDep(ref details) if details.path.is_some() => {
+                    details.path.get_ref()
+                }
+                _ => return Err(human(format!("replacement for `{}` must \
+                                               specify a `path`", spec))),
+            };
+            let source_id = SourceId::for_path(&root.join(path.as_slice()));
+            ret.push(try!(PackageId::new(parts[0], parts[1], &source_id)));
+        }
+        ret.sort();
+        Ok(ret)
+    }
+}
+
+/// The table of the manifest a dependency was declared in.
+enum DependencyKind {
+    Normal,
+    Development,
+    Build,
 }
 
-fn process_dependencies<'a>(cx: &mut Context<'a>, dev: bool,
+fn process_dependencies<'a>(cx: &mut Context<'a>, kind: DependencyKind,
+                            platform: Option<&str>,
                             new_deps: Option<&HashMap<String, TomlDependency>>)
                             -> CargoResult<()> {
     let dependencies = match new_deps {
@@ -412,23 +655,40 @@ fn process_dependencies<'a>(cx: &mut Context<'a>, dev: bool,
         None => return Ok(())
     };
     for (n, v) in dependencies.iter() {
-        let (version, source_id) = match *v {
+        let (version, source_id, package, optional, features) = match *v {
             SimpleDep(ref string) => {
-                (Some(string.clone()), SourceId::for_central())
+                (Some(string.clone()), SourceId::for_central(), None, false,
+                 Vec::new())
             },
             DetailedDep(ref details) => {
-                let reference = details.branch.clone()
-                    .or_else(|| details.tag.clone())
-                    .or_else(|| details.rev.clone())
-                    .unwrap_or_else(|| "master".to_string());
+                let keys = [("branch", &details.branch), ("tag", &details.tag),
+                            ("rev", &details.rev)];
+                let given: Vec<&str> = keys.iter().filter(|&&(_, v)| {
+                    v.is_some()
+                }).map(|&(k, _)| k).collect();
+                if given.len() > 1 {
+                    return Err(human(format!("dependency ({}) specifies both \
+                                              `{}` and `{}`; only one of \
+                                              `branch`, `tag`, or `rev` is \
+                                              allowed", n, given[0], given[1])))
+                }
+                let reference = details.branch.clone().map(Branch)
+                    .or_else(|| details.tag.clone().map(Tag))
+                    .or_else(|| details.rev.clone().map(Rev))
+                    .unwrap_or_else(|| GitReference::master());
 
+                let optional = details.optional.unwrap_or(false);
                 let new_source_id = match details.git {
                     Some(ref git) => {
                         let kind = GitKind(reference.clone());
                         let loc = try!(Location::parse(git.as_slice()));
                         let source_id = SourceId::new(kind, loc);
                         // TODO: Don't do this for path
-                        cx.source_ids.push(source_id.clone());
+                        // Optional dependencies are only fetched once a
+                        // feature enables them.
+                        if !optional {
+                            cx.source_ids.push(source_id.clone());
+                        }
                         Some(source_id)
                     }
                     None => {
@@ -439,15 +699,31 @@ fn process_dependencies<'a>(cx: &mut Context<'a>, dev: bool,
                     }
                 }.unwrap_or(SourceId::for_central());
 
-                (details.version.clone(), new_source_id)
+                (details.version.clone(), new_source_id,
+                 details.package.as_ref(), optional,
+                 details.features.clone().unwrap_or(Vec::new()))
             }
         };
 
-        let mut dep = try!(Dependency::parse(n.as_slice(),
+        // With `package` the key is only the name the crate is known by
+        // locally, the package itself is looked up under its real name.
+        let name = package.map(|p| p.as_slice()).unwrap_or(n.as_slice());
+        let mut dep = try!(Dependency::parse(name,
                        version.as_ref().map(|v| v.as_slice()),
                        &source_id));
 
-        if dev { dep = dep.as_dev() }
+        if name != n.as_slice() { dep = dep.renamed(n.as_slice()) }
+        match kind {
+            Development => dep = dep.as_dev(),
+            Build => dep = dep.as_build(),
+            Normal => {}
+        }
+        if optional { dep = dep.as_optional() }
+        if !features.is_empty() { dep = dep.with_features(features) }
+        match platform {
+            Some(platform) => dep = dep.for_platform(platform),
+            None => {}
+        }
 
         cx.deps.push(dep)
     }
@@ -461,6 +737,7 @@ struct TomlTarget {
     crate_type: Option<Vec<String>>,
     path: Option<TomlPath>,
     test: Option<bool>,
+    bench: Option<bool>,
     doc: Option<bool>,
     plugin: Option<bool>,
 }
@@ -493,27 +770,41 @@ fn normalize(libs: &[TomlLibTarget],
              bins: &[TomlBinTarget],
              examples: &[TomlExampleTarget],
              tests: &[TomlTestTarget],
-             metadata: &Metadata) -> Vec<Target> {
-    log!(4, "normalizing toml targets; lib={}; bin={}; example={}; test={}",
-         libs, bins, examples, tests);
+             benches: &[TomlBenchTarget],
+             metadata: &Metadata,
+             profiles: Option<&TomlProfiles>) -> Vec<Target> {
+    log!(4, "normalizing toml targets; lib={}; bin={}; example={}; test={}; \
+             bench={}", libs, bins, examples, tests, benches);
 
     enum TestDep { Needed, NotNeeded }
 
-    fn target_profiles(target: &TomlTarget, dep: TestDep) -> Vec<Profile> {
-        let mut ret = vec![Profile::default_dev(), Profile::default_release()];
+    fn target_profiles(target: &TomlTarget, dep: TestDep,
+                       profiles: Option<&TomlProfiles>) -> Vec<Profile> {
+        let dev = profiles.and_then(|p| p.dev.as_ref());
+        let release = profiles.and_then(|p| p.release.as_ref());
+        let mut ret = vec![merge(Profile::default_dev(), dev),
+                           merge(Profile::default_release(), release)];
 
         match target.test {
             Some(true) | None => ret.push(Profile::default_test()),
             Some(false) => {}
         }
 
+        match target.bench {
+            Some(true) | None => ret.push(Profile::default_bench()),
+            Some(false) => {}
+        }
+
         match target.doc {
             Some(true) | None => ret.push(Profile::default_doc()),
             Some(false) => {}
         }
 
         match dep {
-            Needed => ret.push(Profile::default_test().test(false)),
+            Needed => {
+                ret.push(Profile::default_test().test(false));
+                ret.push(Profile::default_bench().test(false));
+            }
             _ => {}
         }
 
@@ -524,8 +815,24 @@ fn normalize(libs: &[TomlLibTarget],
         ret
     }
 
+    // Applies the settings of a `[profile.*]` section on top of the defaults
+    fn merge(profile: Profile, toml: Option<&TomlProfile>) -> Profile {
+        let toml = match toml {
+            Some(toml) => toml,
+            None => return profile,
+        };
+        let opt_level = toml.opt_level.unwrap_or(profile.get_opt_level());
+        let debug = toml.debug.unwrap_or(profile.get_debug());
+        let debuginfo = toml.debug.unwrap_or(profile.get_debuginfo());
+        let lto = toml.lto.unwrap_or(profile.get_lto());
+        let codegen_units = toml.codegen_units.or(profile.get_codegen_units());
+        profile.opt_level(opt_level).debug(debug).debuginfo(debuginfo)
+               .lto(lto).codegen_units(codegen_units)
+    }
+
     fn lib_targets(dst: &mut Vec<Target>, libs: &[TomlLibTarget],
-                   dep: TestDep, metadata: &Metadata) {
+                   dep: TestDep, metadata: &Metadata,
+                   profiles: Option<&TomlProfiles>) {
         let l = &libs[0];
         let path = l.path.clone().unwrap_or_else(|| {
             TomlString(format!("src/{}.rs", l.name))
@@ -536,13 +843,12 @@ fn normalize(libs: &[TomlLibTarget],
             vec![if l.plugin == Some(true) {Dylib} else {Lib}]
         });
 
-        for profile in target_profiles(l, dep).iter() {
+        for profile in target_profiles(l, dep, profiles).iter() {
+            // Libs and their tests are built in parallel, and a lib may be
+            // built for plugins as well as for the target, so the profile
+            // (including whether it is a plugin's) goes into the metadata too.
             let mut metadata = metadata.clone();
-            // Libs and their tests are built in parallel, so we need to make
-            // sure that their metadata is different.
-            if profile.is_test() {
-                metadata.mix(&"test");
-            }
+            metadata.mix(profile);
             dst.push(Target::lib_target(l.name.as_slice(), crate_types.clone(),
                                         &path.to_path(), profile,
                                         metadata));
@@ -551,13 +857,14 @@ fn normalize(libs: &[TomlLibTarget],
 
     fn bin_targets(dst: &mut Vec<Target>, bins: &[TomlBinTarget],
                    dep: TestDep, metadata: &Metadata,
+                   profiles: Option<&TomlProfiles>,
                    default: |&TomlBinTarget| -> String) {
         for bin in bins.iter() {
             let path = bin.path.clone().unwrap_or_else(|| {
                 TomlString(default(bin))
             });
 
-            for profile in target_profiles(bin, dep).iter() {
+            for profile in target_profiles(bin, dep, profiles).iter() {
                 let metadata = if profile.is_test() {
                     // Make sure that the name of this test executable doesn't
                     // conflicts with a library that has the same name and is
@@ -578,7 +885,8 @@ fn normalize(libs: &[TomlLibTarget],
 
     fn example_targets(dst: &mut Vec<Target>, examples: &[TomlExampleTarget],
                        default: |&TomlExampleTarget| -> String) {
-        for ex in examples.iter() {
+        // Examples are only built along with the tests
+        for ex in examples.iter().filter(|ex| ex.test != Some(false)) {
             let path = ex.path.clone().unwrap_or_else(|| TomlString(default(ex)));
 
             let profile = &Profile::default_test().test(false);
@@ -600,17 +908,39 @@ fn normalize(libs: &[TomlLibTarget],
             let mut metadata = metadata.clone();
             metadata.mix(&format!("test-{}", test.name));
 
-            let profile = &Profile::default_test();
-            dst.push(Target::test_target(test.name.as_slice(),
+            for profile in [Profile::default_test(),
+                            Profile::default_bench()].iter() {
+                dst.push(Target::test_target(test.name.as_slice(),
+                                             &path.to_path(),
+                                             profile,
+                                             metadata.clone()));
+            }
+        }
+    }
+
+    fn bench_targets(dst: &mut Vec<Target>, benches: &[TomlBenchTarget],
+                     metadata: &Metadata,
+                     default: |&TomlBenchTarget| -> String) {
+        for bench in benches.iter() {
+            let path = bench.path.clone().unwrap_or_else(|| {
+                TomlString(default(bench))
+            });
+
+            // make sure this metadata is different from any same-named libs.
+            let mut metadata = metadata.clone();
+            metadata.mix(&format!("bench-{}", bench.name));
+
+            dst.push(Target::test_target(bench.name.as_slice(),
                                          &path.to_path(),
-                                         profile,
+                                         &Profile::default_bench(),
                                          metadata));
         }
     }
 
     let mut ret = Vec::new();
 
-    let test_dep = if examples.len() > 0 || tests.len() > 0 {
+    let test_dep = if examples.len() > 0 || tests.len() > 0 ||
+                      benches.len() > 0 {
         Needed
     } else {
         NotNeeded
@@ -618,15 +948,15 @@ fn normalize(libs: &[TomlLibTarget],
 
     match (libs, bins) {
         ([_, ..], [_, ..]) => {
-            lib_targets(&mut ret, libs, Needed, metadata);
-            bin_targets(&mut ret, bins, test_dep, metadata,
+            lib_targets(&mut ret, libs, Needed, metadata, profiles);
+            bin_targets(&mut ret, bins, test_dep, metadata, profiles,
                         |bin| format!("src/bin/{}.rs", bin.name));
         },
         ([_, ..], []) => {
-            lib_targets(&mut ret, libs, test_dep, metadata);
+            lib_targets(&mut ret, libs, test_dep, metadata, profiles);
         },
         ([], [_, ..]) => {
-            bin_targets(&mut ret, bins, test_dep, metadata,
+            bin_targets(&mut ret, bins, test_dep, metadata, profiles,
                         |bin| format!("src/{}.rs", bin.name));
         },
         ([], []) => ()
@@ -644,5 +974,8 @@ fn normalize(libs: &[TomlLibTarget],
                         format!("tests/{}.rs", test.name)
                     }});
 
+    bench_targets(&mut ret, benches, metadata,
+                  |bench| format!("benches/{}.rs", bench.name));
+
     ret
 }
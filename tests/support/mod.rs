@@ -1,5 +1,5 @@
 use std::io;
-use std::io::fs;
+use std::io::{fs, MemWriter};
 use std::io::process::{ProcessOutput};
 use std::os;
 use std::path::{Path,BytesContainer};
@@ -7,6 +7,8 @@ use std::str;
 use std::vec::Vec;
 use std::fmt::Show;
 use ham = hamcrest;
+use cargo::core::MultiShell;
+use cargo::core::shell::{Shell, ShellConfig, Never};
 use cargo::util::{process,ProcessBuilder};
 use cargo::util::ProcessError;
 
@@ -495,7 +497,58 @@ pub fn basic_lib_manifest(name: &str) -> String {
     "#, name, name)
 }
 
+/// Compiles a binary named `builder` out of the source `main`, to serve as the
+/// build command of other projects.
+pub fn build_command(main: &str) -> ProjectBuilder {
+    let build = project("builder")
+        .file("Cargo.toml", r#"
+            [project]
+
+            name = "builder"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+        "#)
+        .file("src/main.rs", main);
+    ham::assert_that(build.cargo_process("cargo-build"), execs().with_status(0));
+    build
+}
+
+/// A build command creating the file named by its first argument, which
+/// tells whether it ran.
+pub static MARKER_BUILD_COMMAND: &'static str = r#"
+    use std::os;
+    use std::io::File;
+
+    fn main() {
+        let marker = Path::new(os::args()[1].as_slice());
+        File::create(&marker).unwrap();
+    }
+"#;
+
+/// Runs `cmd`, which has to succeed, and returns whether it ran the build
+/// command leaving `marker` behind.
+pub fn build_command_ran(marker: &Path, cmd: ProcessBuilder) -> bool {
+    if marker.exists() { fs::unlink(marker).unwrap(); }
+    ham::assert_that(cmd, execs().with_status(0));
+    marker.exists()
+}
+
+/// A shell which swallows everything written to it.
+pub fn quiet_shell() -> MultiShell {
+    let shell = || {
+        let config = ShellConfig { color: Never, verbose: false, tty: false };
+        Shell::create(box MemWriter::new() as Box<Writer>, config)
+    };
+    MultiShell::new(shell(), shell(), false)
+}
+
 pub static RUNNING:   &'static str = "     Running";
 pub static COMPILING: &'static str = "   Compiling";
 pub static FRESH:     &'static str = "       Fresh";
 pub static UPDATING:  &'static str = "    Updating";
+pub static REMOVING:  &'static str = "    Removing";
+pub static OPENING:   &'static str = "     Opening";
+pub static FINISHED:  &'static str = "    Finished";
+pub static BUILDING:  &'static str = "    Building";
+pub static FETCHING:  &'static str = "    Fetching";
+pub static DOCUMENTING: &'static str = " Documenting";
@@ -1,10 +1,12 @@
 use cargo::util::{process, ProcessBuilder};
 use hamcrest::{assert_that};
 use std::io;
-use std::io::fs;
+use std::io::{fs, File};
 use std::os;
 use support::paths;
 use support::{project, execs, cargo_dir, mkdir_recursive, ProjectBuilder, ResultTest};
+use support::{basic_bin_manifest, main_file};
+use hamcrest::existing_file;
 
 fn setup() {
 }
@@ -67,3 +69,39 @@ test!(list_commands_non_overlapping {
             .with_status(0)
             .with_stdout("Installed Commands:\n   1\n   2\n   3\n"));
 })
+
+test!(configured_alias {
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", main_file(r#""i am foo""#, []).as_slice())
+        .file(".cargo/config", r#"
+            [alias]
+            b = "build --release"
+        "#);
+
+    assert_that(p.cargo_process("cargo").arg("b"), execs().with_status(0));
+    assert_that(&p.root().join(format!("target/release/foo{}",
+                                       os::consts::EXE_SUFFIX)),
+                existing_file());
+})
+
+test!(builtin_ci_alias_is_locked {
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", main_file(r#""i am foo""#, []).as_slice());
+
+    // There is no lockfile yet, and `ci` may not create one
+    assert_that(p.cargo_process("cargo").arg("ci"), execs().with_status(101));
+
+    assert_that(p.process(cargo_dir().join("cargo")).arg("build"),
+                execs().with_status(0));
+    assert_that(p.process(cargo_dir().join("cargo")).arg("ci"),
+                execs().with_status(0));
+
+    // Warnings in the package itself fail the build
+    File::create(&p.root().join("src/foo.rs")).write_str(r#"
+        fn main() {} fn dead() {}
+    "#).assert();
+    assert_that(p.process(cargo_dir().join("cargo")).arg("ci"),
+                execs().with_status(101));
+})
@@ -0,0 +1,250 @@
+use std::str;
+
+use support::{ResultTest, project, execs, basic_bin_manifest};
+use support::{COMPILING, RUNNING, FINISHED, cargo_dir};
+use hamcrest::{assert_that, existing_dir};
+
+fn setup() {}
+
+test!(cargo_bench_simple {
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", r#"
+            extern crate test;
+
+            fn hello() -> &'static str {
+                "hello"
+            }
+
+            pub fn main() {
+                println!("{}", hello())
+            }
+
+            #[bench]
+            fn bench_hello(b: &mut test::Bencher) {
+                b.iter(|| hello());
+            }"#);
+
+    assert_that(p.cargo_process("cargo-bench"),
+        execs().with_status(0)
+               .with_stdout(format!("{} foo v0.5.0 (file:{})\n\
+                                     {} bench [optimized] target(s) in [..]s\n\
+                                     {} target[..]bench[..]foo-[..]\n\n\
+                                     running 1 test\n\
+                                     test bench_hello ... bench:[..]\n\n\
+                                     test result: ok. 0 passed; 0 failed; \
+                                     0 ignored; 1 measured\n\n",
+                                    COMPILING, p.root().display(), FINISHED,
+                                    RUNNING)));
+})
+
+test!(cargo_bench_cfg_and_filter {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", r#"
+            extern crate test;
+
+            #[cfg(bench)]
+            fn only_benched() {}
+
+            #[bench]
+            fn bench_a(b: &mut test::Bencher) { b.iter(|| only_benched()) }
+
+            #[bench]
+            fn bench_b(b: &mut test::Bencher) { b.iter(|| only_benched()) }
+        "#);
+
+    assert_that(p.cargo_process("cargo-bench").arg("bench_b"),
+        execs().with_status(0)
+               .with_stdout(format!("{} foo v0.0.1 (file:{})\n\
+                                     {} bench [optimized] target(s) in [..]s\n\
+                                     {} target[..]bench[..]foo-[..]\n\n\
+                                     running 1 test\n\
+                                     test bench_b ... bench:[..]\n\n\
+                                     test result: ok. 0 passed; 0 failed; \
+                                     0 ignored; 1 measured\n\n",
+                                    COMPILING, p.root().display(), FINISHED,
+                                    RUNNING)));
+})
+
+test!(cargo_bench_failing_status {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", r#"
+            extern crate test;
+
+            #[bench]
+            fn bench_fails(_b: &mut test::Bencher) { fail!() }
+        "#);
+
+    assert_that(p.cargo_process("cargo-bench"), execs().with_status(101));
+})
+
+test!(benches_dir_is_benchmarked {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("benches/speed.rs", r#"
+            extern crate test;
+
+            #[bench]
+            fn bench_speed(b: &mut test::Bencher) { b.iter(|| 1u + 1) }
+        "#);
+
+    let output = p.cargo_process("cargo-bench").exec_with_output().assert();
+    let out = String::from_utf8(output.output).assert();
+    assert!(out.as_slice().contains("target/bench/speed-"),
+            "bench binary not run\n{}", out);
+    assert!(out.as_slice().contains("test bench_speed ... bench:"),
+            "bench not measured\n{}", out);
+    assert_that(&p.root().join("target/bench"), existing_dir());
+})
+
+test!(explicit_bench_section {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [[bench]]
+            name = "custom"
+            path = "perf/custom.rs"
+        "#)
+        .file("src/lib.rs", "pub fn foo() -> uint { 1 }")
+        .file("perf/custom.rs", r#"
+            extern crate foo;
+            extern crate test;
+
+            #[bench]
+            fn bench_custom(b: &mut test::Bencher) { b.iter(|| foo::foo()) }
+        "#);
+
+    let output = p.cargo_process("cargo-bench").arg("bench_custom")
+                  .exec_with_output().assert();
+    let out = String::from_utf8(output.output).assert();
+    assert!(out.as_slice().contains("target/bench/custom-"),
+            "bench binary not run\n{}", out);
+    assert!(out.as_slice().contains("test bench_custom ... bench:"),
+            "bench not measured\n{}", out);
+})
+
+test!(cargo_bench_is_always_optimized {
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", r#"
+            extern crate test;
+
+            pub fn main() {}
+
+            #[bench]
+            fn bench_hello(b: &mut test::Bencher) { b.iter(|| 1u + 1) }
+        "#);
+
+    assert_that(p.cargo_process("cargo-bench"),
+        execs().with_status(0)
+               .with_stdout(format!("{} foo v0.5.0 (file:{})\n\
+                                     {} bench [optimized] target(s) in [..]s\n\
+                                     {} target[..]bench[..]foo-[..]\n\n\
+                                     running 1 test\n\
+                                     test bench_hello ... bench:[..]\n\n\
+                                     test result: ok. 0 passed; 0 failed; \
+                                     0 ignored; 1 measured\n\n",
+                                    COMPILING, p.root().display(), FINISHED,
+                                    RUNNING)));
+
+    // There is no other mode to choose, so `--release` isn't accepted
+    assert_that(p.process(cargo_dir().join("cargo-bench")).arg("--release"),
+                execs().with_status(1));
+})
+
+test!(cargo_bench_runs_every_binary_despite_failures {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "")
+        .file("benches/a.rs", r#"
+            extern crate test;
+
+            #[bench]
+            fn bench_a(_b: &mut test::Bencher) { fail!() }
+        "#)
+        .file("benches/b.rs", r#"
+            extern crate test;
+
+            #[bench]
+            fn bench_b(_b: &mut test::Bencher) { fail!() }
+        "#);
+
+    let output = p.cargo_process("cargo-bench").exec_with_output();
+    let output = match output {
+        Ok(..) => fail!("cargo-bench should have failed"),
+        Err(e) => e.output.assert(),
+    };
+    assert!(output.status.matches_exit_status(101));
+
+    let out = str::from_utf8(output.output.as_slice()).assert();
+    assert!(out.contains("target/bench/a-"), "bench a not run\n{}", out);
+    assert!(out.contains("target/bench/b-"), "bench b not run\n{}", out);
+
+    let err = str::from_utf8(output.error.as_slice()).assert();
+    assert!(err.contains("the following bench binaries failed:\n"),
+            "failures not reported together\n{}", err);
+    assert!(err.contains("\n    a-"), "failing binary a not listed\n{}", err);
+    assert!(err.contains("\n    b-"), "failing binary b not listed\n{}", err);
+})
+
+test!(bin_without_benches {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [[bin]]
+            name = "util"
+            path = "src/main.rs"
+            bench = false
+        "#)
+        .file("src/lib.rs", r#"
+            extern crate test;
+
+            #[bench]
+            fn bench_lib(b: &mut test::Bencher) { b.iter(|| 1u + 1) }
+        "#)
+        .file("src/main.rs", r#"
+            extern crate test;
+
+            fn main() {}
+
+            #[bench]
+            fn bench_util(_b: &mut test::Bencher) { fail!() }
+        "#);
+
+    let output = p.cargo_process("cargo-bench").exec_with_output().assert();
+    let out = str::from_utf8(output.output.as_slice()).assert();
+    assert!(out.contains("test bench_lib ... bench:"), "lib not benched\n{}", out);
+    assert!(!out.contains("target/bench/util-"), "bin benched\n{}", out);
+    assert!(!out.contains("bench_util"), "bin benched\n{}", out);
+})
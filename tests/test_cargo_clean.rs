@@ -1,5 +1,8 @@
-use support::{project, execs, main_file, basic_bin_manifest};
-use hamcrest::{assert_that, existing_dir, is_not};
+use std::io::File;
+
+use support::{project, execs, main_file, basic_bin_manifest, cargo_dir};
+use support::{COMPILING, FRESH, REMOVING, FINISHED, ResultTest};
+use hamcrest::{assert_that, existing_dir, existing_file, is_not};
 
 fn setup() {
 }
@@ -15,3 +18,147 @@ test!(cargo_clean_simple {
     assert_that(p.cargo_process("cargo-clean"), execs());
     assert_that(&p.build_dir(), is_not(existing_dir()));
 })
+
+test!(clean_single_package {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { bar::bar() }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.baz]
+            path = "../baz"
+        "#)
+        .file("bar/src/lib.rs", r#"
+            extern crate baz;
+            pub fn bar() { baz::baz() }
+        "#)
+        .file("baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("baz/src/lib.rs", "pub fn baz() {}");
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+
+    assert_that(p.process(cargo_dir().join("cargo-clean")).arg("-p").arg("bar"),
+                execs().with_status(0).with_stdout(format!("\
+{removing} {dir}[..]deps[..]libbar-[..].rlib
+{removing} {dir}[..].fingerprint[..]bar-[..]
+", removing = REMOVING, dir = p.root().display())));
+
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0)
+                       .with_stdout(format!("{} baz v0.0.1 (file:{})\n\
+                                             {} bar v0.0.1 (file:{})\n\
+                                             {} foo v0.0.1 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
+                                            FRESH, p.root().join("baz").display(),
+                                            COMPILING, p.root().join("bar").display(),
+                                            COMPILING, p.root().display(),
+                                            FINISHED)));
+})
+
+test!(clean_unknown_package {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "");
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+
+    assert_that(p.process(cargo_dir().join("cargo-clean")).arg("-p").arg("baz"),
+                execs().with_status(101)
+                       .with_stderr("package `baz` is not in the lockfile, \
+                                     valid package names are: bar, foo\n"));
+})
+
+test!(clean_removes_stale_documentation {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", r#"
+            pub fn kept() {}
+            pub fn removed() {}
+        "#);
+    let index = p.root().join("target/doc/search-index.js");
+
+    assert_that(p.cargo_process("cargo-doc"), execs().with_status(0));
+    let contents = File::open(&index).read_to_string().assert();
+    assert!(contents.as_slice().contains("removed"));
+
+    File::create(&p.root().join("src/lib.rs")).write_str(r#"
+        pub fn kept() {}
+    "#).assert();
+    assert_that(p.process(cargo_dir().join("cargo-clean")),
+                execs().with_status(0));
+    assert_that(&p.root().join("target/doc"), is_not(existing_dir()));
+
+    assert_that(p.process(cargo_dir().join("cargo-doc")),
+                execs().with_status(0));
+    let contents = File::open(&index).read_to_string().assert();
+    assert!(contents.as_slice().contains("kept"));
+    assert!(!contents.as_slice().contains("removed"));
+})
+
+test!(clean_keep_doc {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() {}");
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(p.process(cargo_dir().join("cargo-doc")),
+                execs().with_status(0));
+
+    assert_that(p.process(cargo_dir().join("cargo-clean")).arg("--keep-doc"),
+                execs().with_status(0));
+    assert_that(&p.root().join("target/doc/foo/index.html"), existing_file());
+    assert_that(&p.root().join("target/libfoo.rlib"), is_not(existing_file()));
+    assert_that(&p.root().join("target/deps"), is_not(existing_dir()));
+
+    // The documentation is still up to date
+    assert_that(p.process(cargo_dir().join("cargo-doc")),
+                execs().with_status(0).with_stdout(format!("\
+{fresh} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", fresh = FRESH, finished = FINISHED, dir = p.root().display())));
+})
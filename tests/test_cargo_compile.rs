@@ -1,10 +1,17 @@
-use std::io::{fs, TempDir};
+use std::io;
+use std::io::{fs, File, TempDir};
+use std::io::process::ExitStatus;
 use std::os;
 use std::path;
+use std::str;
 
 use support::{ResultTest, project, execs, main_file, basic_bin_manifest};
-use support::{COMPILING, RUNNING, cargo_dir, ProjectBuilder};
-use hamcrest::{assert_that, existing_file};
+use support::basic_lib_manifest;
+use support::{COMPILING, RUNNING, FRESH, FINISHED, cargo_dir, ProjectBuilder};
+use support::{build_command, build_command_ran, MARKER_BUILD_COMMAND};
+use support::quiet_shell;
+use support::paths::PathExt;
+use hamcrest::{assert_that, existing_file, existing_dir, is_not};
 use cargo;
 use cargo::util::{process, realpath};
 
@@ -133,6 +140,34 @@ test!(cargo_compile_with_invalid_code_in_deps {
     assert_that(p.cargo_process("cargo-build"), execs().with_status(101));
 })
 
+test!(cargo_compile_with_invalid_code_in_dep_shows_errors {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() {}")
+        .file("bar/Cargo.toml", basic_lib_manifest("bar").as_slice())
+        .file("bar/src/lib.rs", "invalid rust code!");
+
+    assert_that(p.cargo_process("cargo-build"),
+        execs()
+        .with_status(101)
+        .with_stderr(format!("\
+{filename}:1:1: 1:8 error: expected item but found `invalid`
+{filename}:1 invalid rust code!
+             ^~~~~~~
+Could not compile `bar`.
+
+To learn more, run the command again with --verbose.\n",
+            filename = format!("src{}lib.rs", path::SEP)).as_slice()));
+})
+
 test!(cargo_compile_with_warnings_in_the_root_package {
     let p = project("foo")
         .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
@@ -198,9 +233,11 @@ test!(cargo_compile_with_warnings_in_a_dep_package {
     assert_that(p.cargo_process("cargo-build"),
         execs()
         .with_stdout(format!("{} bar v0.5.0 (file:{})\n\
-                              {} foo v0.5.0 (file:{})\n",
+                              {} foo v0.5.0 (file:{})\n\
+                              {} dev [unoptimized] target(s) in [..]s\n",
                              COMPILING, bar.display(),
-                             COMPILING, main.display()))
+                             COMPILING, main.display(),
+                             FINISHED))
         .with_stderr(""));
 
     assert_that(&p.bin("foo"), existing_file());
@@ -210,6 +247,230 @@ test!(cargo_compile_with_warnings_in_a_dep_package {
       execs().with_stdout("test passed\n"));
 })
 
+test!(deny_primary_warnings_leaves_dependencies_alone {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() { bar::bar() }")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {} fn dead() {}");
+
+    assert_that(p.cargo_process("cargo-build").arg("--deny-primary-warnings"),
+                execs().with_status(0));
+    assert_that(&p.bin("foo"), existing_file());
+
+    File::create(&p.root().join("src/main.rs")).write_str(r#"
+        extern crate bar; fn main() { bar::bar() } fn dead() {}
+    "#).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--deny-primary-warnings"),
+                execs().with_status(101));
+})
+
+test!(cargo_compile_with_vendored_dep {
+    let vendor = project("vendor")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", r#"
+            pub fn gimme() -> String { "test passed".to_string() }
+        "#);
+    vendor.build();
+
+    let p = project("foo")
+        .file(".cargo/config", format!(r#"
+            vendor = ['{}']
+        "#, vendor.root().display()).as_slice())
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies]
+            bar = "0.5.0"
+        "#)
+        .file("src/main.rs",
+              main_file(r#""{}", bar::gimme()"#, ["bar"]).as_slice());
+
+    assert_that(p.cargo_process("cargo-build"),
+        execs().with_status(0)
+        .with_stdout(format!("{compiling} bar v0.5.0 ([..])\n\
+                              {compiling} foo v0.5.0 (file:{dir})\n\
+                              {finished} dev [unoptimized] target(s) in [..]s\n",
+                             compiling = COMPILING, finished = FINISHED,
+                             dir = p.root().display())));
+
+    assert_that(
+      cargo::util::process(p.bin("foo")),
+      execs().with_stdout("test passed\n"));
+})
+
+test!(cargo_compile_with_vendored_dep_version_mismatch {
+    let vendor = project("vendor")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.4.0"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "");
+    vendor.build();
+
+    let p = project("foo")
+        .file(".cargo/config", format!(r#"
+            vendor = ['{}']
+        "#, vendor.root().display()).as_slice())
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies]
+            bar = "0.5.0"
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build"),
+        execs().with_status(101)
+        .with_stderr(format!("\
+no package matching `bar [..]0.5.0` was found in the vendor directory `{dir}` \
+(found versions: 0.4.0)
+", dir = vendor.root().display())));
+})
+
+test!(cargo_compile_with_path_override_version_mismatch {
+    let p = project("foo");
+    let a = p.root().join("a");
+    let b = p.root().join("b");
+    let p = p
+        .file(".cargo/config", format!(r#"
+            paths = ['{}', '{}']
+        "#, a.display(), b.display()).as_slice())
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies]
+            bar = "0.5.0"
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() {}")
+        .file("a/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.6.0"
+            authors = []
+        "#)
+        .file("a/src/lib.rs", "")
+        .file("b/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.7.0"
+            authors = []
+        "#)
+        .file("b/src/lib.rs", "");
+    let build = p.cargo_process("cargo-build");
+    let a = realpath(&a).assert();
+    let b = realpath(&b).assert();
+
+    assert_that(build,
+        execs().with_status(101)
+        .with_stderr(format!("\
+path override for `bar` found at file:{a} but its version 0.6.0 does not \
+satisfy requirement [..]0.5.0
+path override for `bar` found at file:{b} but its version 0.7.0 does not \
+satisfy requirement [..]0.5.0
+Could not find `bar` satisfying requirement [..]0.5.0.
+
+To learn more, run the command again with --verbose.
+", a = a.display(), b = b.display())));
+
+    // With the right version in place the override is used as usual
+    File::create(&a.join("Cargo.toml")).write_str(r#"
+        [package]
+        name = "bar"
+        version = "0.5.0"
+        authors = []
+    "#).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+        execs().with_status(0)
+        .with_stdout(format!("\
+{compiling} bar v0.5.0 (file:{a})
+{compiling} foo v0.5.0 (file:{foo})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, a = a.display(),
+   foo = realpath(&p.root()).assert().display())));
+})
+
+test!(cargo_compile_with_path_override_version_mismatch_is_ignored {
+    let p = project("foo");
+    let a = p.root().join("a");
+    let p = p
+        .file(".cargo/config", format!(r#"
+            paths = ['{}']
+        "#, a.display()).as_slice())
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+            version = "0.5.0"
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "")
+        .file("a/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.6.0"
+            authors = []
+        "#)
+        .file("a/src/lib.rs", "");
+    let build = p.cargo_process("cargo-build");
+    let a = realpath(&a).assert();
+    let root = realpath(&p.root()).assert();
+
+    assert_that(build,
+        execs().with_status(0)
+        .with_stdout(format!("\
+{compiling} bar v0.5.0 (file:{root})
+{compiling} foo v0.5.0 (file:{root})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, root = root.display()))
+        .with_stderr(format!("\
+path override for `bar` found at file:{a} but its version 0.6.0 does not \
+satisfy requirement [..]0.5.0
+", a = a.display())));
+})
+
 test!(cargo_compile_with_nested_deps_inferred {
     let mut p = project("foo");
     let bar = p.root().join("bar");
@@ -529,6 +790,23 @@ Version required: *
 
 // test!(compiling_project_with_invalid_manifest)
 
+test!(missing_rustc_is_explained {
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", main_file(r#""i am foo""#, []).as_slice());
+    p.build();
+    let empty = p.root().join("empty");
+    fs::mkdir(&empty, io::UserRWX).assert();
+
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .env("PATH", Some(&empty)),
+                execs().with_status(101).with_stderr("\
+Cargo requires `rustc` to be installed and in PATH (tried to execute `rustc -v verbose`)
+
+To learn more, run the command again with --verbose.
+"));
+})
+
 test!(custom_build {
     let mut build = project("builder");
     build = build
@@ -565,8 +843,10 @@ test!(custom_build {
         "#);
     assert_that(p.cargo_process("cargo-build"),
                 execs().with_status(0)
-                       .with_stdout(format!("   Compiling foo v0.5.0 (file:{})\n",
-                                            p.root().display()))
+                       .with_stdout(format!("   Compiling foo v0.5.0 (file:{})\n\
+                                                {} dev [unoptimized] target(s) \
+                                                in [..]s\n",
+                                            p.root().display(), FINISHED))
                        .with_stderr(""));
 })
 
@@ -629,8 +909,10 @@ test!(custom_multiple_build {
         "#);
     assert_that(p.cargo_process("cargo-build"),
                 execs().with_status(0)
-                       .with_stdout(format!("   Compiling foo v0.5.0 (file:{})\n",
-                                            p.root().display()))
+                       .with_stdout(format!("   Compiling foo v0.5.0 (file:{})\n\
+                                                {} dev [unoptimized] target(s) \
+                                                in [..]s\n",
+                                            p.root().display(), FINISHED))
                        .with_stderr(""));
 })
 
@@ -759,9 +1041,21 @@ test!(custom_build_env_vars {
                 let out = os::getenv("OUT_DIR").unwrap();
                 assert!(out.as_slice().starts_with(r"{}"));
                 assert!(Path::new(out).is_dir());
+
+                assert_eq!(os::getcwd(), Path::new(r"{}"));
+                assert_eq!(os::getenv("CARGO_MANIFEST_DIR").unwrap().as_slice(),
+                           r"{}");
+
+                let host = os::getenv("HOST").unwrap();
+                assert!(host.len() > 0);
+                assert_eq!(os::getenv("TARGET").unwrap(), host);
+                assert_eq!(os::getenv("OPT_LEVEL").unwrap().as_slice(), "0");
+                assert_eq!(os::getenv("PROFILE").unwrap().as_slice(), "dev");
+                assert_eq!(os::getenv("DEBUG").unwrap().as_slice(), "true");
             }}
         "#,
-        p.root().join("target").join("native").join("foo-").display()));
+        p.root().join("target").join("native").join("foo-").display(),
+        p.root().display(), p.root().display()));
     assert_that(build.cargo_process("cargo-build"), execs().with_status(0));
 
 
@@ -819,6 +1113,36 @@ test!(crate_version_env_vars {
       execs().with_stdout("0-5-1 @ alpha.1\n"));
 })
 
+test!(crate_name_and_authors_env_vars {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.1-alpha.1"
+            authors = ["wycats@example.com", "neikos@example.com"]
+
+            [[bin]]
+            name = "foo"
+        "#)
+        .file("src/foo.rs", r#"
+            static NAME: &'static str = env!("CARGO_PKG_NAME");
+            static AUTHORS: &'static str = env!("CARGO_PKG_AUTHORS");
+            static VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+            fn main() {
+                println!("{} {} by {}", NAME, VERSION, AUTHORS);
+            }
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+
+    assert_that(
+      process(p.bin("foo")),
+      execs().with_stdout("foo 0.5.1-alpha.1 by \
+                           wycats@example.com:neikos@example.com\n"));
+})
+
 test!(custom_build_in_dependency {
     let mut p = project("foo");
     let bar = p.root().join("bar");
@@ -880,46 +1204,84 @@ test!(custom_build_in_dependency {
                 execs().with_status(0));
 })
 
-// tests that custom build in dep can be built twice in a row - issue 227
-test!(custom_build_in_dependency_twice {
+test!(custom_build_directives {
+    let build = build_command(r#"
+        use std::os;
+        fn main() {
+            if os::args()[1].as_slice() == "emit" {
+                println!("cargo:rustc-flags=--cfg from_build");
+                println!("cargo:root=/some/path");
+                println!("not a directive");
+            } else {
+                assert_eq!(os::getenv("DEP_BAR_ROOT"),
+                           Some("/some/path".to_string()));
+            }
+        }
+    "#);
+
     let p = project("foo")
-        .file("Cargo.toml", r#"
+        .file("Cargo.toml", format!(r#"
             [project]
 
             name = "foo"
             version = "0.5.0"
             authors = ["wycats@example.com"]
+            build = '{} check'
 
             [[bin]]
             name = "foo"
             [dependencies.bar]
-            path = "./bar"
-            "#)
+            path = "bar"
+        "#, build.bin("builder").display()))
         .file("src/foo.rs", r#"
             extern crate bar;
             fn main() { bar::bar() }
-            "#)
+        "#)
         .file("bar/Cargo.toml", format!(r#"
             [project]
 
             name = "bar"
-            version = "0.0.1"
+            version = "0.5.0"
             authors = ["wycats@example.com"]
-            build = '{}'
-        "#, "echo test"))
+            build = '{} emit'
+        "#, build.bin("builder").display()))
         .file("bar/src/lib.rs", r#"
+            #[cfg(from_build)]
             pub fn bar() {}
         "#);
     assert_that(p.cargo_process("cargo-build"),
                 execs().with_status(0));
+
+    // The directives of a fresh dependency are still passed along
+    File::create(&p.root().join("src/foo.rs")).write_str(r#"
+        extern crate bar;
+        fn main() { bar::bar(); bar::bar() }
+    "#).assert();
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_status(0));
 })
 
-// this is testing that src/<pkg-name>.rs still works (for now)
-test!(many_crate_types_old_style_lib_location {
-    let mut p = project("foo");
-    p = p
+test!(custom_build_links_native_library {
+    let build = build_command(r#"
+        use std::os;
+        use std::io::Command;
+
+        fn main() {
+            let src = Path::new(os::args()[1].as_slice());
+            let out = Path::new(os::getenv("OUT_DIR").unwrap());
+            let obj = out.join("native.o");
+            assert!(Command::new("cc").arg("-c").arg("-fPIC").arg(&src)
+                            .arg("-o").arg(&obj).status().unwrap()
+                            .success());
+            assert!(Command::new("ar").arg("crs")
+                            .arg(out.join("libnative.a")).arg(&obj)
+                            .status().unwrap().success());
+            println!("compiled {}", src.display());
+            println!("cargo:rustc-flags=-L {} -l native", out.display());
+        }
+    "#);
+
+    let p = project("foo")
         .file("Cargo.toml", r#"
             [project]
 
@@ -927,59 +1289,461 @@ test!(many_crate_types_old_style_lib_location {
             version = "0.5.0"
             authors = ["wycats@example.com"]
 
-            [[lib]]
-
-            name = "foo"
-            crate_type = ["rlib", "dylib"]
+            [dependencies.bar]
+            path = "bar"
         "#)
-        .file("src/foo.rs", r#"
-            pub fn foo() {}
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { println!("{}", bar::answer()) }
+        "#)
+        .file("bar/Cargo.toml", format!(r#"
+            [project]
+
+            name = "bar"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+            build = '{} native/answer.c'
+        "#, build.bin("builder").display()))
+        .file("bar/native/answer.c", "int native_answer(void) { return 42; }")
+        .file("bar/src/lib.rs", r#"
+            extern { fn native_answer() -> i32; }
+            pub fn answer() -> i32 { unsafe { native_answer() } }
         "#);
+
+    // What the build command prints besides directives only shows up with
+    // --verbose.
     assert_that(p.cargo_process("cargo-build"),
-                execs().with_status(0));
+                execs().with_status(0).with_stdout(format!("\
+{compiling} bar v0.5.0 (file:{dir})
+{compiling} foo v0.5.0 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+    assert_that(process(p.bin("foo")), execs().with_status(0)
+                                              .with_stdout("42\n"));
+
+    p.root().join("target").rm_rf().assert();
+    let output = p.process(cargo_dir().join("cargo-build")).arg("-v")
+                  .exec_with_output().assert();
+    let out = String::from_utf8(output.output).assert();
+    assert!(out.as_slice().contains("compiled native/answer.c"), "{}", out);
+})
 
-    let files = fs::readdir(&p.root().join("target")).assert();
-    let mut files: Vec<String> = files.iter().filter_map(|f| {
-        match f.filename_str().unwrap() {
-            "deps" => None,
-            s if s.contains("fingerprint") || s.contains("dSYM") => None,
-            s => Some(s.to_string())
+test!(custom_build_unknown_directives_warn {
+    let build = build_command(r#"
+        fn main() {
+            println!("cargo:rustc-frobnicate=yes");
+            println!("cargo:garbage");
         }
-    }).collect();
-    files.sort();
-    let file0 = files[0].as_slice();
-    let file1 = files[1].as_slice();
-    println!("{} {}", file0, file1);
-    assert!(file0.ends_with(".rlib") || file1.ends_with(".rlib"));
-    assert!(file0.ends_with(os::consts::DLL_SUFFIX) ||
-            file1.ends_with(os::consts::DLL_SUFFIX));
-})
+    "#);
 
-test!(many_crate_types_correct {
-    let mut p = project("foo");
-    p = p
-        .file("Cargo.toml", r#"
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
             [project]
 
             name = "foo"
             version = "0.5.0"
             authors = ["wycats@example.com"]
+            build = '{}'
+        "#, build.bin("builder").display()))
+        .file("src/main.rs", "fn main() {}");
 
-            [[lib]]
-
-            name = "foo"
-            crate_type = ["rlib", "dylib"]
-        "#)
-        .file("src/lib.rs", r#"
-            pub fn foo() {}
-        "#);
     assert_that(p.cargo_process("cargo-build"),
-                execs().with_status(0));
+                execs().with_status(0).with_stderr("\
+ignoring unknown directive `rustc-frobnicate` in build command of `foo`
+ignoring invalid output in build command of `foo`: `cargo:garbage`
+"));
+})
 
-    let files = fs::readdir(&p.root().join("target")).assert();
+// tests that custom build in dep can be built twice in a row - issue 227
+test!(custom_build_in_dependency_twice {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [[bin]]
+            name = "foo"
+            [dependencies.bar]
+            path = "./bar"
+            "#)
+        .file("src/foo.rs", r#"
+            extern crate bar;
+            fn main() { bar::bar() }
+            "#)
+        .file("bar/Cargo.toml", format!(r#"
+            [project]
+
+            name = "bar"
+            version = "0.0.1"
+            authors = ["wycats@example.com"]
+            build = '{}'
+        "#, "echo test"))
+        .file("bar/src/lib.rs", r#"
+            pub fn bar() {}
+        "#);
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0));
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+})
+
+test!(build_dependencies_are_compiled_for_build_commands {
+    let p = project("foo");
+    let p = p
+        .file("Cargo.toml", format!(r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+            build = '{}'
+
+            [build-dependencies.helper]
+            path = "helper"
+        "#, p.root().join("build.sh").display()))
+        .file("src/lib.rs", "pub fn foo() {}")
+        .file("build.sh", "#!/bin/sh\n\
+                           ls \"$BUILD_DEPS_DIR\" | grep -q '^libhelper-.*\\.rlib$'\n")
+        .file("helper/Cargo.toml", r#"
+            [project]
+
+            name = "helper"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+        "#)
+        .file("helper/src/lib.rs", "pub fn generate() {}");
+    p.build();
+    fs::chmod(&p.root().join("build.sh"), io::UserRWX).assert();
+
+    let output = p.process(cargo_dir().join("cargo-build")).arg("-v")
+                  .exec_with_output().assert();
+    let stdout = str::from_utf8(output.output.as_slice()).unwrap();
+    assert!(stdout.contains("--crate-name helper"));
+
+    // The library itself is never linked against its build dependencies
+    let foo = stdout.lines().find(|l| l.contains("--crate-name foo")).unwrap();
+    assert!(!foo.contains("helper"), "{}", foo);
+})
+
+test!(custom_build_reruns_when_env_changes {
+    let build = build_command(MARKER_BUILD_COMMAND);
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+            build = '{} {}'
+            build-env = ["FOO"]
+
+            [[bin]] name = "foo"
+        "#, build.bin("builder").display(), build.root().join("ran").display()))
+        .file("src/foo.rs", r#"
+            fn main() {}
+        "#);
+    let marker = build.root().join("ran");
+    let run = |cflags: Option<&str>, foo: Option<&str>| {
+        build_command_ran(&marker, p.process(cargo_dir().join("cargo-build"))
+                                    .env("CFLAGS", cflags).env("FOO", foo))
+    };
+
+    p.build();
+    assert!(run(None, None));
+    assert!(!run(None, None));
+
+    // Setting a variable to the empty string is different from leaving it
+    // unset.
+    assert!(run(Some(""), None));
+    assert!(!run(Some(""), None));
+    assert!(run(Some("-O2"), None));
+
+    // Variables listed in `build-env` are tracked as well
+    assert!(run(Some("-O2"), Some("1")));
+    assert!(!run(Some("-O2"), Some("1")));
+})
+
+test!(custom_build_only_reruns_for_build_dir {
+    let build = build_command(MARKER_BUILD_COMMAND);
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+            build = '{} {}'
+            build-dir = "native"
+
+            [[bin]] name = "foo"
+        "#, build.bin("builder").display(), build.root().join("ran").display()))
+        .file("src/foo.rs", r#"
+            fn main() {}
+        "#)
+        .file("native/input.c", "");
+    let marker = build.root().join("ran");
+    let run = || {
+        build_command_ran(&marker, p.process(cargo_dir().join("cargo-build")))
+    };
+
+    p.build();
+    p.root().move_into_the_past().assert();
+    assert!(run());
+    assert!(!run());
+
+    // Files outside of the build directory don't affect the build command
+    File::create(&p.root().join("src/foo.rs")).write_str(r#"
+        fn main() { println!("changed"); }
+    "#).assert();
+    assert!(!run());
+
+    File::create(&p.root().join("native/input.c")).write_str("int x;").assert();
+    assert!(run());
+    assert!(!run());
+})
+
+test!(custom_build_reruns_when_output_is_removed {
+    let build = build_command(r#"
+        use std::os;
+        use std::io::File;
+
+        fn main() {
+            let marker = Path::new(os::args()[1].as_slice());
+            File::create(&marker).unwrap();
+            let out = Path::new(os::getenv("OUT_DIR").unwrap());
+            File::create(&out.join("libnative.a")).unwrap();
+        }
+    "#);
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+            build = '{} {}'
+
+            [[bin]] name = "foo"
+        "#, build.bin("builder").display(), build.root().join("ran").display()))
+        .file("src/foo.rs", r#"
+            fn main() {}
+        "#);
+    let marker = build.root().join("ran");
+    let run = || {
+        build_command_ran(&marker, p.process(cargo_dir().join("cargo-build")))
+    };
+
+    p.build();
+    assert!(run());
+    assert!(!run());
+
+    let native = fs::readdir(&p.build_dir().join("native")).assert();
+    assert_eq!(native.len(), 1);
+    native[0].rm_rf().assert();
+    assert!(run());
+    assert!(native[0].join("libnative.a").exists());
+    assert!(!run());
+})
+
+test!(custom_build_ignores_excluded_files {
+    let build = build_command(MARKER_BUILD_COMMAND);
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+            build = '{} {}'
+            exclude = ["docs", "**/*.swp"]
+
+            [[bin]] name = "foo"
+        "#, build.bin("builder").display(), build.root().join("ran").display()))
+        .file("src/foo.rs", r#"
+            fn main() {}
+        "#)
+        .file("docs/index.md", "");
+    let marker = build.root().join("ran");
+    let run = || {
+        build_command_ran(&marker, p.process(cargo_dir().join("cargo-build")))
+    };
+
+    p.build();
+    p.root().move_into_the_past().assert();
+    assert!(run());
+    assert!(!run());
+
+    File::create(&p.root().join("docs/index.md")).write_str("# foo").assert();
+    File::create(&p.root().join("src/.foo.rs.swp")).write_str("").assert();
+    assert!(!run());
+
+    File::create(&p.root().join("src/foo.rs")).write_str(r#"
+        fn main() { println!("changed"); }
+    "#).assert();
+    assert!(run());
+    assert!(!run());
+})
+
+test!(custom_build_include_wins_over_exclude {
+    let build = build_command(MARKER_BUILD_COMMAND);
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+            build = '{} {}'
+            include = ["src/**"]
+            exclude = ["src"]
+
+            [[bin]] name = "foo"
+        "#, build.bin("builder").display(), build.root().join("ran").display()))
+        .file("src/foo.rs", r#"
+            fn main() {}
+        "#)
+        .file("README.md", "");
+    let marker = build.root().join("ran");
+    let run = || {
+        build_command_ran(&marker, p.process(cargo_dir().join("cargo-build")))
+    };
+
+    p.build();
+    p.root().move_into_the_past().assert();
+    assert!(run());
+    assert!(!run());
+
+    File::create(&p.root().join("README.md")).write_str("foo").assert();
+    assert!(!run());
+
+    File::create(&p.root().join("src/foo.rs")).write_str(r#"
+        fn main() { println!("changed"); }
+    "#).assert();
+    assert!(run());
+})
+
+// this is testing that src/<pkg-name>.rs still works (for now)
+test!(many_crate_types_old_style_lib_location {
+    let mut p = project("foo");
+    p = p
+        .file("Cargo.toml", r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [[lib]]
+
+            name = "foo"
+            crate_type = ["rlib", "dylib"]
+        "#)
+        .file("src/foo.rs", r#"
+            pub fn foo() {}
+        "#);
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0));
+
+    let files = fs::readdir(&p.root().join("target")).assert();
+    let mut files: Vec<String> = files.iter().filter_map(|f| {
+        match f.filename_str().unwrap() {
+            "deps" | ".cargo-lock" => None,
+            s if s.contains("fingerprint") || s.contains("dSYM") => None,
+            s => Some(s.to_string())
+        }
+    }).collect();
+    files.sort();
+    let file0 = files[0].as_slice();
+    let file1 = files[1].as_slice();
+    println!("{} {}", file0, file1);
+    assert!(file0.ends_with(".rlib") || file1.ends_with(".rlib"));
+    assert!(file0.ends_with(os::consts::DLL_SUFFIX) ||
+            file1.ends_with(os::consts::DLL_SUFFIX));
+})
+
+test!(old_style_lib_location_is_deprecated {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [[lib]]
+            name = "foo"
+        "#)
+        .file("src/foo.rs", "pub fn foo() {}");
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stderr("\
+the library `foo` is loaded from `src/foo.rs`, which is deprecated; rename it \
+to `src/lib.rs` or set `path = \"src/foo.rs\"` in its `[[lib]]` section
+"));
+
+    // Neither the conventional location nor an explicit path warn
+    let p = project("bar")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+
+            [[lib]]
+            name = "bar"
+        "#)
+        .file("src/lib.rs", "pub fn bar() {}")
+        .file("src/bar.rs", "pub fn bar() {}");
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stderr(""));
+
+    let p = project("baz")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.5.0"
+            authors = []
+
+            [[lib]]
+            name = "baz"
+            path = "src/baz.rs"
+        "#)
+        .file("src/baz.rs", "pub fn baz() {}");
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stderr(""));
+})
+
+test!(many_crate_types_correct {
+    let mut p = project("foo");
+    p = p
+        .file("Cargo.toml", r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [[lib]]
+
+            name = "foo"
+            crate_type = ["rlib", "dylib"]
+        "#)
+        .file("src/lib.rs", r#"
+            pub fn foo() {}
+        "#);
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0));
+
+    let files = fs::readdir(&p.root().join("target")).assert();
     let mut files: Vec<String> = files.iter().filter_map(|f| {
         match f.filename_str().unwrap() {
-            "deps" => None,
+            "deps" | ".cargo-lock" => None,
             s if s.contains("fingerprint") || s.contains("dSYM") => None,
             s => Some(s.to_string())
         }
@@ -1008,7 +1772,7 @@ test!(unused_keys {
 
             name = "foo"
         "#)
-        .file("src/foo.rs", r#"
+        .file("src/lib.rs", r#"
             pub fn foo() {}
         "#);
     assert_that(p.cargo_process("cargo-build"),
@@ -1029,7 +1793,7 @@ test!(unused_keys {
             name = "foo"
             build = "foo"
         "#)
-        .file("src/foo.rs", r#"
+        .file("src/lib.rs", r#"
             pub fn foo() {}
         "#);
     assert_that(p.cargo_process("cargo-build"),
@@ -1055,9 +1819,11 @@ test!(self_dependency {
 
             name = "test"
         "#)
-        .file("src/test.rs", "fn main() {}");
+        .file("src/lib.rs", "fn main() {}");
     assert_that(p.cargo_process("cargo-build"),
-                execs().with_status(0));
+                execs().with_status(101).with_stderr("\
+cyclic package dependency: test v0.0.0 -> test v0.0.0
+"));
 })
 
 #[cfg(not(windows))]
@@ -1111,8 +1877,9 @@ test!(verbose_build {
         --dep-info [..] \
         -L {dir}{sep}target \
         -L {dir}{sep}target{sep}deps`
-{compiling} test v0.0.0 (file:{dir})\n",
-running = RUNNING, compiling = COMPILING, sep = path::SEP,
+{compiling} test v0.0.0 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s\n",
+running = RUNNING, compiling = COMPILING, finished = FINISHED, sep = path::SEP,
 dir = p.root().display()
 )));
 })
@@ -1139,42 +1906,221 @@ test!(verbose_release_build {
         --dep-info [..] \
         -L {dir}{sep}target{sep}release \
         -L {dir}{sep}target{sep}release{sep}deps`
-{compiling} test v0.0.0 (file:{dir})\n",
-running = RUNNING, compiling = COMPILING, sep = path::SEP,
+{compiling} test v0.0.0 (file:{dir})
+{finished} release [optimized] target(s) in [..]s\n",
+running = RUNNING, compiling = COMPILING, finished = FINISHED, sep = path::SEP,
 dir = p.root().display()
 )));
 })
 
-test!(verbose_release_build_deps {
-    let mut p = project("foo");
-    p = p
+test!(metadata_differs_between_profiles {
+    let p = project("foo")
         .file("Cargo.toml", r#"
             [package]
-
-            name = "test"
+            name = "foo"
             version = "0.0.0"
             authors = []
-
-            [dependencies.foo]
-            path = "foo"
         "#)
-        .file("src/lib.rs", "")
-        .file("foo/Cargo.toml", r#"
+        .file("src/lib.rs", "");
+
+    fn metadata(output: &[u8]) -> String {
+        let output = str::from_utf8(output).unwrap();
+        let start = output.find_str("metadata=").unwrap() + 9;
+        output.slice_from(start).split(' ').next().unwrap().to_string()
+    }
+
+    let dev = p.cargo_process("cargo-build").arg("-v")
+               .exec_with_output().assert();
+    let release = p.process(cargo_dir().join("cargo-build"))
+                   .arg("-v").arg("--release")
+                   .exec_with_output().assert();
+    assert!(metadata(dev.output.as_slice()) !=
+                metadata(release.output.as_slice()),
+            "dev and release builds share their metadata");
+})
+
+test!(release_profile_from_manifest {
+    let mut p = project("foo");
+    p = p
+        .file("Cargo.toml", r#"
             [package]
 
-            name = "foo"
+            name = "test"
             version = "0.0.0"
             authors = []
 
-            [[lib]]
-            name = "foo"
-            crate_type = ["dylib", "rlib"]
+            [profile.release]
+            opt-level = 2
+            debug = true
+            codegen-units = 4
+            optimise = "yes"
         "#)
-        .file("foo/src/lib.rs", "");
+        .file("src/lib.rs", "");
     assert_that(p.cargo_process("cargo-build").arg("-v").arg("--release"),
                 execs().with_status(0).with_stdout(format!("\
-{running} `rustc {dir}{sep}foo{sep}src{sep}lib.rs --crate-name foo \
-        --crate-type dylib --crate-type rlib \
+{running} `rustc {dir}{sep}src{sep}lib.rs --crate-name test --crate-type lib \
+        --opt-level 2 \
+        -g \
+        -C codegen-units=4 \
+        -C metadata=[..] \
+        -C extra-filename=-[..] \
+        --out-dir {dir}{sep}target{sep}release \
+        --dep-info [..] \
+        -L {dir}{sep}target{sep}release \
+        -L {dir}{sep}target{sep}release{sep}deps`
+{compiling} test v0.0.0 (file:{dir})
+{finished} release [optimized] target(s) in [..]s\n",
+running = RUNNING, compiling = COMPILING, finished = FINISHED, sep = path::SEP,
+dir = p.root().display()
+))
+                       .with_stderr("unused manifest key: \
+                                     profile.release.optimise\n"));
+})
+
+test!(invalid_profile_values {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+
+            name = "foo"
+            version = "0.0.0"
+            authors = []
+
+            [profile.release]
+            opt-level = 7
+        "#)
+        .file("src/lib.rs", "");
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stderr("\
+Cargo.toml is not a valid manifest
+
+`profile.release.opt-level` must be between 0 and 3, found 7
+"));
+
+    let p = project("bar")
+        .file("Cargo.toml", r#"
+            [package]
+
+            name = "bar"
+            version = "0.0.0"
+            authors = []
+
+            [profile.dev]
+            debug = "yes"
+        "#)
+        .file("src/lib.rs", "");
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stderr("\
+Cargo.toml is not a valid manifest
+
+[..]
+"));
+})
+
+test!(changing_profile_rebuilds {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+
+            name = "foo"
+            version = "0.0.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "");
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.0 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s\n",
+compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+
+    File::create(&p.root().join("Cargo.toml")).write_str(r#"
+        [package]
+
+        name = "foo"
+        version = "0.0.0"
+        authors = []
+
+        [profile.dev]
+        opt-level = 1
+    "#).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.0 (file:{dir})
+{finished} dev [optimized] target(s) in [..]s\n",
+compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+})
+
+test!(dev_profile_lto_only_for_executables {
+    let mut p = project("foo");
+    p = p
+        .file("Cargo.toml", r#"
+            [package]
+
+            name = "foo"
+            version = "0.0.0"
+            authors = []
+
+            [profile.dev]
+            opt-level = 1
+            lto = true
+        "#)
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}");
+    assert_that(p.cargo_process("cargo-build").arg("-v"),
+                execs().with_status(0).with_stdout(format!("\
+{running} `rustc {dir}{sep}src{sep}lib.rs --crate-name foo --crate-type lib \
+        --opt-level 1 \
+        -C metadata=[..] \
+        -C extra-filename=-[..] \
+        --out-dir {dir}{sep}target \
+        --dep-info [..] \
+        -L {dir}{sep}target \
+        -L {dir}{sep}target{sep}deps`
+{running} `rustc {dir}{sep}src{sep}main.rs --crate-name foo --crate-type bin \
+        --opt-level 1 \
+        -C lto \
+        --out-dir {dir}{sep}target \
+        --dep-info [..] \
+        -L {dir}{sep}target \
+        -L {dir}{sep}target{sep}deps \
+        --extern foo={dir}{sep}target{sep}libfoo-[..].rlib`
+{compiling} foo v0.0.0 (file:{dir})
+{finished} dev [optimized] target(s) in [..]s\n",
+running = RUNNING, compiling = COMPILING, finished = FINISHED, sep = path::SEP,
+dir = p.root().display()
+)));
+})
+
+test!(verbose_release_build_deps {
+    let mut p = project("foo");
+    p = p
+        .file("Cargo.toml", r#"
+            [package]
+
+            name = "test"
+            version = "0.0.0"
+            authors = []
+
+            [dependencies.foo]
+            path = "foo"
+        "#)
+        .file("src/lib.rs", "")
+        .file("foo/Cargo.toml", r#"
+            [package]
+
+            name = "foo"
+            version = "0.0.0"
+            authors = []
+
+            [[lib]]
+            name = "foo"
+            crate_type = ["dylib", "rlib"]
+        "#)
+        .file("foo/src/lib.rs", "");
+    assert_that(p.cargo_process("cargo-build").arg("-v").arg("--release"),
+                execs().with_status(0).with_stdout(format!("\
+{running} `rustc {dir}{sep}foo{sep}src{sep}lib.rs --crate-name foo \
+        --crate-type dylib --crate-type rlib \
         --opt-level 3 \
         --cfg ndebug \
         -C metadata=[..] \
@@ -1196,9 +2142,11 @@ test!(verbose_release_build_deps {
                      {prefix}foo-[..]{suffix} \
         --extern foo={dir}{sep}target{sep}release{sep}deps/libfoo-[..].rlib`
 {compiling} foo v0.0.0 (file:{dir})
-{compiling} test v0.0.0 (file:{dir})\n",
+{compiling} test v0.0.0 (file:{dir})
+{finished} release [optimized] target(s) in [..]s\n",
                     running = RUNNING,
                     compiling = COMPILING,
+                    finished = FINISHED,
                     dir = p.root().display(),
                     sep = path::SEP,
                     prefix = os::consts::DLL_PREFIX,
@@ -1372,6 +2320,110 @@ test!(bad_cargo_toml_in_target_dir {
     assert_that(process(p.bin("foo")), execs().with_status(0));
 })
 
+test!(package_search_skips_hidden_dirs_and_symlink_loops {
+    let p = project("world")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", r#"
+            fn main() {}
+        "#)
+        .file(".git/Cargo.toml", "bad-toml")
+        .file(".hidden/Cargo.toml", "bad-toml")
+        .symlink(".", "vendor/loop");
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(process(p.bin("foo")), execs().with_status(0));
+})
+
+test!(missing_target_source_is_an_error {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [[bin]]
+            name = "foo"
+            path = "src/missing.rs"
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stdout("").with_stderr("\
+can't find `src/missing.rs` for target `foo`, set its `path` in Cargo.toml if \
+it lives elsewhere
+"));
+})
+
+test!(colliding_target_names_are_an_error {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [[bin]]
+            name = "foo"
+            path = "src/a.rs"
+
+            [[bin]]
+            name = "foo"
+            path = "src/b.rs"
+        "#)
+        .file("src/a.rs", "fn main() {}")
+        .file("src/b.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stdout("").with_stderr("\
+targets named `foo` would overwrite each other's output:
+  src/a.rs
+  src/b.rs
+"));
+})
+
+test!(invalid_target_names_are_errors {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [[bin]]
+            name = "1foo"
+            path = "src/main.rs"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stdout("").with_stderr("\
+invalid target name `1foo`, target names can't start with a digit
+"));
+
+    let p = project("bar")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [[bin]]
+            name = "bin/bar"
+            path = "src/main.rs"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stdout("").with_stderr("\
+invalid target name `bin/bar`, target names can't contain path separators
+"));
+})
+
 test!(lib_with_standard_name {
     let p = project("foo")
         .file("Cargo.toml", r#"
@@ -1392,7 +2444,854 @@ test!(lib_with_standard_name {
                 execs().with_status(0)
                        .with_stdout(format!("\
 {compiling} syntax v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
 ",
-                       compiling = COMPILING,
+                       compiling = COMPILING, finished = FINISHED,
                        dir = p.root().display()).as_slice()));
 })
+
+test!(jobs_do_not_change_artifacts {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+            [dependencies.baz]
+            path = "baz"
+        "#)
+        .file("src/main.rs", r#"
+            extern crate bar;
+            extern crate baz;
+            fn main() { bar::bar(); baz::baz(); }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.baz]
+            path = "../baz"
+        "#)
+        .file("bar/src/lib.rs", r#"
+            extern crate baz;
+            pub fn bar() { baz::baz() }
+        "#)
+        .file("baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("baz/src/lib.rs", "pub fn baz() {}");
+
+    fn artifacts(p: &ProjectBuilder) -> Vec<Path> {
+        let root = p.build_dir();
+        let mut files = fs::walk_dir(&root).assert().map(|f| {
+            f.path_relative_from(&root).unwrap()
+        }).collect::<Vec<Path>>();
+        files.sort();
+        files
+    }
+
+    assert_that(p.cargo_process("cargo-build").arg("-j").arg("1"),
+                execs().with_status(0));
+    let serial = artifacts(&p);
+    assert_that(process(p.bin("foo")), execs().with_status(0));
+
+    assert_that(p.cargo_process("cargo-build").arg("-j").arg("4"),
+                execs().with_status(0));
+    let parallel = artifacts(&p);
+    assert_that(process(p.bin("foo")), execs().with_status(0));
+
+    assert_eq!(serial, parallel);
+})
+
+test!(parallel_primary_warnings_are_whole_lines {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() { let lib_unused = 1u; }")
+        .file("src/main.rs", "fn main() { let bin_unused = 1u; }");
+
+    let output = p.cargo_process("cargo-build").arg("-j").arg("4")
+                  .exec_with_output().assert();
+    let err = String::from_utf8(output.error).assert();
+    for name in ["lib_unused", "bin_unused"].iter() {
+        assert!(err.as_slice().lines().any(|l| {
+            l.contains(format!("warning: unused variable: `{}`", name).as_slice())
+        }), "no whole warning for {}\n{}", name, err);
+    }
+})
+
+// The compiler's output reaches the terminal while it is still running, not
+// only once it has finished: the compiler below waits for its first line to
+// have been seen before it goes on.
+#[cfg(not(windows))]
+test!(parallel_primary_output_is_streamed {
+    let p = project("foo");
+    let seen = p.root().join("seen");
+    let p = p
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() {}")
+        .file("src/main.rs", "extern crate foo; fn main() { foo::foo() }")
+        .file("my-rustc", format!("#!/bin/sh\n\
+            case \"$*\" in *--crate-name*)\n\
+                echo compiler started >&2\n\
+                i=0\n\
+                while [ ! -f '{seen}' ] && [ $i -lt 100 ]; do\n\
+                    sleep 0.1; i=$((i + 1))\n\
+                done\n\
+                [ -f '{seen}' ] || echo output was held back >&2\n\
+            esac\n\
+            exec rustc \"$@\"\n", seen = seen.display()).as_slice());
+    p.build();
+    fs::chmod(&p.root().join("my-rustc"), io::UserRWX).assert();
+
+    let mut child = p.process(cargo_dir().join("cargo-build"))
+                     .arg("-j").arg("4")
+                     .env("RUSTC", Some(p.root().join("my-rustc")))
+                     .build_command().spawn().assert();
+    let mut stderr = io::BufferedReader::new(child.stderr.take().unwrap());
+    assert_eq!(stderr.read_line().assert().as_slice(), "compiler started\n");
+    File::create(&seen).assert();
+
+    let rest = stderr.read_to_string().assert();
+    assert!(child.wait().assert().success(), "build failed\n{}", rest);
+    assert!(!rest.as_slice().contains("held back"), "not streamed\n{}", rest);
+})
+
+// Output which was passed on as it was produced isn't repeated along with
+// the error once the compiler fails.
+test!(parallel_primary_errors_are_printed_once {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "invalid rust code!")
+        .file("src/main.rs", "fn main() {}");
+
+    let output = p.cargo_process("cargo-build").arg("-j").arg("4").arg("-v")
+                  .exec_with_output();
+    let output = match output {
+        Ok(..) => fail!("cargo-build should have failed"),
+        Err(e) => e.output.assert(),
+    };
+    let err = String::from_utf8(output.error).assert();
+    let errors = err.as_slice().lines().filter(|l| {
+        l.contains("error: expected item but found `invalid`")
+    }).count();
+    assert!(errors == 1, "error printed {} times\n{}", errors, err);
+})
+
+test!(queued_jobs_are_skipped_after_a_failure {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [[bin]]
+            name = "a"
+            [[bin]]
+            name = "b"
+            [[bin]]
+            name = "c"
+        "#)
+        .file("src/a.rs", "not rust")
+        .file("src/b.rs", "fn main() {}")
+        .file("src/c.rs", "fn main() {}");
+
+    // With a single job the binaries are built one after the other, so the
+    // ones queued behind the failing one never start.
+    assert_that(p.cargo_process("cargo-build").arg("-j").arg("1"),
+                execs().with_status(101));
+    assert_that(&p.bin("b"), is_not(existing_file()));
+    assert_that(&p.bin("c"), is_not(existing_file()));
+})
+
+test!(dep_info_out {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/main.rs", r#"
+            extern crate bar;
+            mod a;
+            fn main() { bar::bar() }
+        "#)
+        .file("src/a.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {}");
+
+    assert_that(p.cargo_process("cargo-build").arg("--dep-info-out").arg("out"),
+                execs().with_status(0));
+
+    let foo = File::open(&p.root().join("out/bin-foo.d")).read_to_string()
+                   .assert();
+    assert!(foo.as_slice().contains(" src/main.rs"), "{}", foo);
+    assert!(foo.as_slice().contains(" src/a.rs"), "{}", foo);
+    let bar = File::open(&p.root().join("out/lib-bar.d")).read_to_string()
+                   .assert();
+    assert!(bar.as_slice().contains(" bar/src/lib.rs"), "{}", bar);
+
+    // The copies are still written when everything is fresh
+    fs::rmdir_recursive(&p.root().join("out")).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--dep-info-out").arg("out"),
+                execs().with_status(0));
+    assert_that(&p.root().join("out/bin-foo.d"), existing_file());
+    assert_that(&p.root().join("out/lib-bar.d"), existing_file());
+})
+
+test!(select_targets {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() {}")
+        .file("src/bin/a.rs", "extern crate foo; fn main() { foo::foo() }")
+        .file("src/bin/b.rs", "extern crate foo; fn main() { foo::foo() }");
+
+    assert_that(p.cargo_process("cargo-build").arg("--bin").arg("c"),
+                execs().with_status(101).with_stderr("\
+no bin target named `c`, available bin targets: a, b
+"));
+
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--lib"),
+                execs().with_status(0));
+    assert_that(&p.bin("a"), is_not(existing_file()));
+    assert_that(&p.bin("b"), is_not(existing_file()));
+
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--bin").arg("a"),
+                execs().with_status(0));
+    assert_that(&p.bin("a"), existing_file());
+    assert_that(&p.bin("b"), is_not(existing_file()));
+
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--bin").arg("a").arg("--bin").arg("b"),
+                execs().with_status(0));
+    assert_that(&p.bin("b"), existing_file());
+})
+
+test!(select_bin_skips_broken_bin {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() {}")
+        .file("src/bin/good.rs", "extern crate foo; fn main() { foo::foo() }")
+        .file("src/bin/bad.rs", "fn main() {");
+
+    assert_that(p.cargo_process("cargo-build").arg("--bin").arg("good"),
+                execs().with_status(0));
+    assert_that(&p.bin("good"), existing_file());
+    assert_that(&p.bin("bad"), is_not(existing_file()));
+})
+
+test!(select_examples {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() {}")
+        .file("src/main.rs", "fn main() {")
+        .file("examples/a.rs", "extern crate foo; fn main() { foo::foo() }")
+        .file("examples/b.rs", "fn main() {");
+
+    assert_that(p.cargo_process("cargo-build").arg("--example").arg("c"),
+                execs().with_status(101).with_stderr("\
+no example target named `c`, available example targets: a, b
+"));
+
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--example").arg("a"),
+                execs().with_status(0));
+    assert_that(&p.bin("test/a"), existing_file());
+    assert_that(&p.bin("test/b"), is_not(existing_file()));
+
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--example").arg("a").arg("--release"),
+                execs().with_status(1).with_stderr("\
+examples are always built with the `test` profile, so `--example` can't be \
+combined with `--release`
+"));
+})
+
+test!(keep_going {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/bin/a.rs", "invalid rust code!")
+        .file("src/bin/b.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build").arg("--keep-going"),
+                execs().with_status(101).with_stderr(format!("\
+{filename}:1:1: 1:8 error: expected item but found `invalid`
+{filename}:1 invalid rust code!
+             ^~~~~~~
+Could not compile `foo`.
+Could not compile `foo` (bin `a`)
+", filename = format!("src{0}bin{0}a.rs", path::SEP)).as_slice()));
+    assert_that(&p.bin("a"), is_not(existing_file()));
+    assert_that(&p.bin("b"), existing_file());
+
+    // Targets which depend on a failed target are still skipped
+    let p = project("bar")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn bar() {")
+        .file("src/bin/a.rs", "extern crate bar; fn main() { bar::bar() }");
+
+    assert_that(p.cargo_process("cargo-build").arg("--keep-going"),
+                execs().with_status(101));
+    assert_that(&p.bin("a"), is_not(existing_file()));
+})
+
+test!(timings_report_durations_and_critical_path {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() { bar::bar() }")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {}");
+
+    let output = p.cargo_process("cargo-build").arg("--timings")
+                  .exec_with_output().assert();
+    let out = String::from_utf8(output.output).assert();
+    let timings: Vec<&str> = out.as_slice().lines().filter(|l| {
+        l.starts_with("      Timing ")
+    }).collect();
+    assert_eq!(timings.len(), 3);
+    let foo = format!("s foo v0.5.0 (file:{})", p.root().display());
+    let bar = format!("s bar v0.5.0 (file:{})", p.root().display());
+    assert!(timings.iter().any(|l| l.ends_with(foo.as_slice())), "{}", out);
+    assert!(timings.iter().any(|l| l.ends_with(bar.as_slice())), "{}", out);
+    assert!(timings[2].contains("critical path "), "{}", out);
+    assert!(timings[2].ends_with(format!("bar v0.5.0 (file:{dir}) -> foo v0.5.0 (file:{dir})",
+                                         dir = p.root().display()).as_slice()),
+            "{}", out);
+
+    // Without the flag nothing is reported
+    let output = p.process(cargo_dir().join("cargo-build"))
+                  .exec_with_output().assert();
+    let out = String::from_utf8(output.output).assert();
+    assert!(!out.as_slice().contains("Timing"), "{}", out);
+})
+
+test!(bins_inferred_from_src_bin {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "pub fn foo() {}")
+        .file("src/main.rs", "extern crate foo; fn main() { foo::foo() }")
+        .file("src/bin/a.rs", "extern crate foo; fn main() { foo::foo() }")
+        .file("src/bin/b.rs", "extern crate foo; fn main() { foo::foo() }");
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(&p.bin("foo"), existing_file());
+    assert_that(&p.bin("a"), existing_file());
+    assert_that(&p.bin("b"), existing_file());
+})
+
+test!(bins_inferred_alongside_bin_sections {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [[bin]]
+            name = "a"
+            path = "src/other_a.rs"
+        "#)
+        .file("src/other_a.rs", "fn main() {}")
+        .file("src/bin/a.rs", "fn main() { this doesn't compile }")
+        .file("src/bin/b.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(&p.bin("a"), existing_file());
+    assert_that(&p.bin("b"), existing_file());
+})
+
+test!(inferred_bin_name_collisions {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("src/bin/foo.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stderr(format!("\
+targets named `foo` would overwrite each other's output:
+  src{sep}main.rs
+  src{sep}bin{sep}foo.rs
+", sep = path::SEP)));
+
+    let p = project("bar")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("src/lib.rs", "")
+        .file("src/bin/bar.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stderr(format!("\
+Cargo.toml is not a valid manifest
+
+binary `src{sep}bin{sep}bar.rs` has the same name as the library, rename it or \
+give it a `[[bin]]` section with a name of its own
+", sep = path::SEP)));
+})
+
+test!(lib_flag_without_library {
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", main_file(r#""i am foo""#, []).as_slice());
+
+    assert_that(p.cargo_process("cargo-build").arg("--lib"),
+                execs().with_status(101)
+                       .with_stderr("no library targets found\n"));
+})
+
+test!(json_message_format {
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", main_file(r#""i am foo""#, []).as_slice());
+
+    let artifact = |fresh: bool| format!("\
+{{\"reason\":\"build-progress\",\"status\":\"{status}\",\
+\"message\":\"foo v0.5.0 (file:{dir})\"}}
+{{\"reason\":\"compiler-artifact\",\"package_id\":\"foo v0.5.0 (file:{dir})\",\
+\"package_name\":\"foo\",\"package_version\":\"0.5.0\",\"target\":\"foo\",\
+\"kind\":\"bin\",\"filenames\":[\"{dir}{sep}target{sep}foo\"],\"fresh\":{fresh}}}
+{{\"reason\":\"build-progress\",\"status\":\"Finished\",\
+\"message\":\"dev [unoptimized] target(s) in [..]s\"}}
+",
+        dir = p.root().display(), sep = path::SEP, fresh = fresh,
+        status = if fresh {"Fresh"} else {"Compiling"});
+
+    assert_that(p.cargo_process("cargo-build")
+                 .arg("--message-format").arg("json"),
+                execs().with_status(0).with_stdout(artifact(false)));
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--message-format").arg("json"),
+                execs().with_status(0).with_stdout(artifact(true)));
+
+    File::create(&p.root().join("src/foo.rs")).write_str("fn main() {")
+        .assert();
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--message-format").arg("json"),
+                execs().with_status(101).with_stdout("\
+{\"reason\":\"build-progress\",\"status\":\"Compiling\",\
+\"message\":\"foo v0.5.0 (file:[..])\"}
+{\"reason\":\"compiler-message\",\"package_id\":\"foo v0.5.0 (file:[..])\",\
+\"target\":\"foo\",\"message\":\"[..]\"}
+"));
+})
+
+test!(invalid_message_format {
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", main_file(r#""i am foo""#, []).as_slice());
+
+    assert_that(p.cargo_process("cargo-build")
+                 .arg("--message-format").arg("xml"),
+                execs().with_status(1).with_stderr("\
+invalid message format `xml`, expected `human` or `json`
+"));
+})
+
+test!(unhashed_lib_outputs {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [lib]
+            name = "foo"
+            crate_type = ["rlib", "dylib"]
+        "#)
+        .file("src/lib.rs", "pub fn foo() {}");
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+
+    let rlib = p.root().join("target/libfoo.rlib");
+    let dylib = p.root().join(format!("target/{}foo{}", os::consts::DLL_PREFIX,
+                                      os::consts::DLL_SUFFIX));
+    assert_that(&rlib, existing_file());
+    assert_that(&dylib, existing_file());
+
+    // A fresh build leaves the copies alone
+    p.root().move_into_the_past().assert();
+    let before = rlib.stat().assert().modified;
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+    assert_eq!(rlib.stat().assert().modified, before);
+
+    // ... but a rebuild refreshes them
+    File::create(&p.root().join("src/lib.rs")).write_str("pub fn bar() {}")
+        .assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+    assert!(rlib.stat().assert().modified > before);
+    assert_that(&dylib, existing_file());
+})
+
+test!(custom_target_dir {
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", main_file(r#""i am foo""#, []).as_slice());
+
+    let tmpdir = TempDir::new("cargo-target").unwrap();
+    let target_dir = tmpdir.path().join("out");
+
+    assert_that(p.cargo_process("cargo-build")
+                 .env("CARGO_TARGET_DIR", Some(target_dir.clone())),
+                execs().with_status(0));
+    assert_that(&target_dir.join("foo"), existing_file());
+    assert_that(&p.bin("foo"), is_not(existing_file()));
+    assert_that(process(target_dir.join("foo")),
+                execs().with_stdout("i am foo\n"));
+
+    // `--target-dir` is relative to the package and wins over the environment
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--target-dir").arg("out")
+                 .env("CARGO_TARGET_DIR", Some(target_dir.clone())),
+                execs().with_status(0));
+    assert_that(&p.root().join("out/foo"), existing_file());
+
+    assert_that(p.process(cargo_dir().join("cargo-clean"))
+                 .env("CARGO_TARGET_DIR", Some(target_dir.clone())),
+                execs().with_status(0));
+    assert!(!target_dir.exists());
+})
+
+test!(custom_target_dir_holds_whole_layout {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+            build = "true"
+
+            [[bin]]
+            name = "foo"
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/foo.rs", "extern crate bar; fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "");
+    p.build();
+
+    // Relocated into the package itself, the build output must not make the
+    // package look modified.
+    let target_dir = p.root().join("out");
+    let build = || {
+        p.process(cargo_dir().join("cargo-build"))
+         .env("CARGO_TARGET_DIR", Some(target_dir.clone()))
+    };
+
+    let output = build().arg("-v").exec_with_output().assert();
+    let out = String::from_utf8(output.output).assert();
+    assert!(out.as_slice().contains(format!("--out-dir {}",
+                                            target_dir.display()).as_slice()),
+            "real location missing\n{}", out);
+    for dir in ["deps", "native", ".fingerprint"].iter() {
+        assert!(target_dir.join(*dir).is_dir(), "no {}", dir);
+    }
+    assert!(!p.build_dir().exists());
+
+    assert_that(build(), execs().with_status(0).with_stdout(format!("\
+{fresh} bar v0.5.0 (file:{dir})
+{fresh} foo v0.5.0 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", fresh = FRESH, finished = FINISHED, dir = p.root().display())));
+
+    assert_that(build().arg("--release"), execs().with_status(0));
+    assert_that(&target_dir.join("release/foo"), existing_file());
+    assert_that(&target_dir.join("release/deps"), existing_dir());
+})
+
+test!(target_dir_from_config {
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", main_file(r#""i am foo""#, []).as_slice())
+        .file(".cargo/config", r#"
+            [build]
+            target-dir = "out"
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(&p.root().join("out/foo"), existing_file());
+    assert_that(&p.bin("foo"), is_not(existing_file()));
+})
+
+test!(rustc_from_env {
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", main_file(r#""i am foo""#, []).as_slice());
+
+    assert_that(p.cargo_process("cargo-build")
+                 .env("RUSTC", Some("this-rustc-does-not-exist")),
+                execs().with_status(101));
+    assert_that(&p.bin("foo"), is_not(existing_file()));
+})
+
+test!(compile_with_counting_executor {
+    use std::io::process::ProcessOutput;
+    use std::sync::{Arc, Mutex};
+    use cargo::core::{Package, Target};
+    use cargo::ops;
+    use cargo::util::{ProcessBuilder, ProcessError};
+
+    struct Counter { targets: Arc<Mutex<Vec<String>>> }
+
+    impl ops::Executor for Counter {
+        fn exec(&self, cmd: ProcessBuilder, _pkg: &Package,
+                target: &Target) -> Result<(), ProcessError> {
+            self.targets.lock().push(target.get_name().to_string());
+            cmd.exec()
+        }
+
+        fn exec_with_output(&self, cmd: ProcessBuilder, _pkg: &Package,
+                            target: &Target)
+                            -> Result<ProcessOutput, ProcessError> {
+            self.targets.lock().push(target.get_name().to_string());
+            cmd.exec_with_output()
+        }
+    }
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/lib.rs", "extern crate bar;")
+        .file("src/main.rs", "extern crate foo; fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "");
+    p.build();
+
+    let mut shell = quiet_shell();
+    let mut opts = ops::CompileOptions::new("compile", &mut shell);
+
+    let targets = Arc::new(Mutex::new(Vec::new()));
+    let exec = box Counter { targets: targets.clone() };
+    ops::compile_with_exec(&p.root().join("Cargo.toml"), &mut opts,
+                           Arc::new(exec as Box<ops::Executor + Send + Sync>))
+       .assert();
+
+    let mut targets = targets.lock().clone();
+    targets.sort();
+    assert_eq!(targets, vec!["bar".to_string(), "foo".to_string(),
+                             "foo".to_string()]);
+    assert_that(&p.bin("foo"), existing_file());
+})
+
+test!(output_artifacts_without_compiling {
+    use cargo::ops;
+
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [[lib]]
+            name = "foo"
+            crate_type = ["dylib", "rlib"]
+
+            [[bin]]
+            name = "foo"
+        "#)
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "extern crate foo; fn main() {}");
+    p.build();
+
+    let mut shell = quiet_shell();
+    let mut opts = ops::CompileOptions::new("compile", &mut shell);
+
+    let manifest = p.root().join("Cargo.toml");
+    let artifacts = ops::output_artifacts(&manifest, &mut opts).assert();
+    assert!(!p.build_dir().exists());
+
+    let files = artifacts.iter().flat_map(|a| a.files.iter())
+                         .map(|f| f.clone()).collect::<Vec<Path>>();
+    assert!(files.contains(&p.bin("foo")));
+    assert!(files.iter().any(|f| {
+        f.filename_str().unwrap().ends_with(os::consts::DLL_SUFFIX)
+    }));
+    assert!(files.iter().any(|f| {
+        f.filename_str().unwrap().ends_with(".rlib")
+    }));
+    assert!(artifacts.iter().all(|a| a.platform.is_none()));
+
+    ops::compile(&manifest, &mut opts).assert();
+    for file in files.iter() {
+        assert_that(file, existing_file());
+    }
+})
+
+test!(waits_for_target_directory_lock {
+    use std::io::BufferedReader;
+    use cargo::util::FileLock;
+
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", main_file(r#""i am foo""#, []).as_slice());
+    p.build();
+
+    let mut shell = quiet_shell();
+    let lock = FileLock::lock(&p.build_dir().join(".cargo-lock"),
+                              "target directory", &mut shell).assert();
+
+    let mut child = p.process(cargo_dir().join("cargo-build"))
+                     .build_command().spawn().assert();
+    let mut stdout = BufferedReader::new(child.stdout.take().unwrap());
+    loop {
+        let line = stdout.read_line().assert();
+        if line.as_slice().contains("waiting for file lock on target directory") {
+            break
+        }
+    }
+    drop(lock);
+
+    assert!(child.wait().assert().success());
+    assert_that(&p.bin("foo"), existing_file());
+})
+
+test!(read_only_target_directory {
+    // Permissions don't keep anyone from writing a directory on windows
+    if cfg!(windows) { return }
+
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", main_file(r#""i am foo""#, []).as_slice());
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+
+    let target = p.build_dir();
+    fs::chmod(&target, io::UserRead | io::UserExecute).assert();
+    let output = p.process(cargo_dir().join("cargo-build"))
+                  .exec_with_output();
+    fs::chmod(&target, io::UserRWX).assert();
+
+    let output = match output {
+        Ok(..) => fail!("build succeeded in a read-only target directory"),
+        Err(e) => e.output.unwrap(),
+    };
+    assert_eq!(output.status, ExitStatus(101));
+    let out = String::from_utf8(output.output).assert();
+    let err = String::from_utf8(output.error).assert();
+    assert!(!out.as_slice().contains(COMPILING), "rustc was run\n{}", out);
+    assert!(err.as_slice().contains(target.display().to_string().as_slice()),
+            "path missing\n{}", err);
+    assert!(err.as_slice().contains("permission"), "no hint\n{}", err);
+})
+
+test!(color_never_output {
+    let p = project("foo")
+        .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
+        .file("src/foo.rs", main_file(r#""i am foo""#, []).as_slice());
+
+    assert_that(p.cargo_process("cargo-build").arg("--color").arg("never"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.5.0 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--color").arg("sometimes"),
+                execs().with_status(1).with_stderr("\
+argument for --color must be auto, always, or never, but found `sometimes`
+"));
+})
@@ -1,10 +1,14 @@
-use std::io::File;
+use std::io;
+use std::io::{fs, File};
+use std::io::process::ExitStatus;
+use std::os;
+use std::str;
 
 use support::{ProjectBuilder, ResultTest, project, execs, main_file, paths};
 use support::{cargo_dir};
-use support::{COMPILING, FRESH, UPDATING};
+use support::{COMPILING, FRESH, UPDATING, FINISHED, FETCHING};
 use support::paths::PathExt;
-use hamcrest::{assert_that,existing_file};
+use hamcrest::{assert_that, existing_file, is_not};
 use cargo;
 use cargo::util::{ProcessError, process};
 
@@ -87,10 +91,12 @@ test!(cargo_compile_simple_git_dep {
         execs()
         .with_stdout(format!("{} git repository `file:{}`\n\
                               {} dep1 v0.5.0 (file:{}#[..])\n\
-                              {} foo v0.5.0 (file:{})\n",
+                              {} foo v0.5.0 (file:{})\n\
+                              {} dev [unoptimized] target(s) in [..]s\n",
                              UPDATING, git_root.display(),
                              COMPILING, git_root.display(),
-                             COMPILING, root.display()))
+                             COMPILING, root.display(),
+                             FINISHED))
         .with_stderr(""));
 
     assert_that(&project.bin("foo"), existing_file());
@@ -150,11 +156,13 @@ test!(cargo_compile_git_dep_branch {
     assert_that(project.cargo_process("cargo-build"),
         execs()
         .with_stdout(format!("{} git repository `file:{}`\n\
-                              {} dep1 v0.5.0 (file:{}?ref=branchy#[..])\n\
-                              {} foo v0.5.0 (file:{})\n",
+                              {} dep1 v0.5.0 (file:{}?branch=branchy#[..])\n\
+                              {} foo v0.5.0 (file:{})\n\
+                              {} dev [unoptimized] target(s) in [..]s\n",
                              UPDATING, git_root.display(),
                              COMPILING, git_root.display(),
-                             COMPILING, root.display()))
+                             COMPILING, root.display(),
+                             FINISHED))
         .with_stderr(""));
 
     assert_that(&project.bin("foo"), existing_file());
@@ -215,11 +223,13 @@ test!(cargo_compile_git_dep_tag {
     assert_that(project.cargo_process("cargo-build"),
         execs()
         .with_stdout(format!("{} git repository `file:{}`\n\
-                              {} dep1 v0.5.0 (file:{}?ref=v0.1.0#[..])\n\
-                              {} foo v0.5.0 (file:{})\n",
+                              {} dep1 v0.5.0 (file:{}?tag=v0.1.0#[..])\n\
+                              {} foo v0.5.0 (file:{})\n\
+                              {} dev [unoptimized] target(s) in [..]s\n",
                              UPDATING, git_root.display(),
                              COMPILING, git_root.display(),
-                             COMPILING, root.display()))
+                             COMPILING, root.display(),
+                             FINISHED))
         .with_stderr(""));
 
     assert_that(&project.bin("foo"), existing_file());
@@ -229,6 +239,246 @@ test!(cargo_compile_git_dep_tag {
       execs().with_stdout("hello world\n"));
 })
 
+test!(cargo_compile_git_dep_tag_not_branch {
+    let git_project = git_repo("dep1", |project| {
+        project
+            .file("Cargo.toml", r#"
+                [project]
+
+                name = "dep1"
+                version = "0.5.0"
+                authors = ["carlhuda@example.com"]
+            "#)
+            .file("src/lib.rs", r#"
+                pub fn hello() -> &'static str { "tag" }
+            "#)
+    }).assert();
+
+    // A branch with the same name as the tag, pointing at a later commit
+    git_project.process("git").args(["tag", "v1"]).exec_with_output().assert();
+    git_project.process("git").args(["checkout", "-b", "v1"])
+               .exec_with_output().assert();
+    File::create(&git_project.root().join("src/lib.rs")).write_str(r#"
+        pub fn hello() -> &'static str { "branch" }
+    "#).assert();
+    git_project.process("git").args(["commit", "-a", "-m", "branch"])
+               .exec_with_output().assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.dep1]
+
+            git = 'file:{}'
+            tag = "v1"
+        "#, git_project.root().display()))
+        .file("src/main.rs", main_file(r#""{}", dep1::hello()"#, ["dep1"]));
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(cargo::util::process(p.bin("foo")),
+                execs().with_stdout("tag\n"));
+})
+
+test!(shallow_clone_of_tag {
+    let git_project = git_repo("dep1", |project| {
+        project
+            .file("Cargo.toml", r#"
+                [project]
+
+                name = "dep1"
+                version = "0.5.0"
+                authors = ["carlhuda@example.com"]
+            "#)
+            .file("src/lib.rs", r#"
+                pub fn hello() -> &'static str { "v1" }
+            "#)
+    }).assert();
+    git_project.process("git").args(["tag", "v1"]).exec_with_output().assert();
+    File::create(&git_project.root().join("src/lib.rs")).write_str(r#"
+        pub fn hello() -> &'static str { "master" }
+    "#).assert();
+    git_project.process("git").args(["commit", "-a", "-m", "next"])
+               .exec_with_output().assert();
+
+    let manifest = |reference: &str| format!(r#"
+        [project]
+
+        name = "foo"
+        version = "0.5.0"
+        authors = ["wycats@example.com"]
+
+        [dependencies.dep1]
+
+        git = 'file:{}'
+        {}
+    "#, git_project.root().display(), reference);
+
+    let p = project("foo")
+        .file("Cargo.toml", manifest("tag = \"v1\""))
+        .file(".cargo/config", r#"
+            [git]
+            shallow = true
+        "#)
+        .file("src/main.rs", main_file(r#""{}", dep1::hello()"#, ["dep1"]));
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(cargo::util::process(p.bin("foo")),
+                execs().with_stdout("v1\n"));
+
+    let db = fs::readdir(&paths::home().join(".cargo/git/db")).assert();
+    assert_eq!(db.len(), 1);
+    let db = db[0].clone();
+    assert!(db.join("shallow").exists(), "database isn't shallow");
+
+    // Moving to a branch needs the full history after all
+    File::create(&p.root().join("Cargo.toml"))
+         .write_str(manifest("").as_slice()).assert();
+    fs::unlink(&p.root().join("Cargo.lock")).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+    assert_that(cargo::util::process(p.bin("foo")),
+                execs().with_stdout("master\n"));
+    assert!(!db.join("shallow").exists(), "database is still shallow");
+})
+
+test!(shallow_clone_of_rev_behind_branch_tip {
+    let git_project = git_repo("dep1", |project| {
+        project
+            .file("Cargo.toml", r#"
+                [project]
+
+                name = "dep1"
+                version = "0.5.0"
+                authors = ["carlhuda@example.com"]
+            "#)
+            .file("src/lib.rs", r#"
+                pub fn hello() -> &'static str { "old" }
+            "#)
+    }).assert();
+    let rev = git_project.process("git").args(["rev-parse", "HEAD"])
+                         .exec_with_output().assert();
+    let rev = str::from_utf8(rev.output.as_slice()).unwrap().trim().to_string();
+    File::create(&git_project.root().join("src/lib.rs")).write_str(r#"
+        pub fn hello() -> &'static str { "new" }
+    "#).assert();
+    git_project.process("git").args(["commit", "-a", "-m", "next"])
+               .exec_with_output().assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.dep1]
+
+            git = 'file:{}'
+            rev = "{}"
+        "#, git_project.root().display(), rev))
+        .file(".cargo/config", r#"
+            [git]
+            shallow = true
+        "#)
+        .file("src/main.rs", main_file(r#""{}", dep1::hello()"#, ["dep1"]));
+
+    // Whether or not the remote hands out a commit which isn't at the tip of
+    // a branch, the right revision must end up being built.
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(cargo::util::process(p.bin("foo")),
+                execs().with_stdout("old\n"));
+})
+
+test!(shallow_config_ignored_for_branches {
+    let git_project = git_repo("dep1", |project| {
+        project
+            .file("Cargo.toml", r#"
+                [project]
+
+                name = "dep1"
+                version = "0.5.0"
+                authors = ["carlhuda@example.com"]
+            "#)
+            .file("src/lib.rs", r#"
+                pub fn hello() -> &'static str { "master" }
+            "#)
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.dep1]
+
+            git = 'file:{}'
+            branch = "master"
+        "#, git_project.root().display()))
+        .file(".cargo/config", r#"
+            [git]
+            shallow = true
+        "#)
+        .file("src/main.rs", main_file(r#""{}", dep1::hello()"#, ["dep1"]));
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(cargo::util::process(p.bin("foo")),
+                execs().with_stdout("master\n"));
+
+    let db = fs::readdir(&paths::home().join(".cargo/git/db")).assert();
+    assert_eq!(db.len(), 1);
+    assert!(!db[0].join("shallow").exists(), "branch database is shallow");
+})
+
+test!(cargo_compile_git_dep_missing_tag {
+    let git_project = git_repo("dep1", |project| {
+        project
+            .file("Cargo.toml", r#"
+                [project]
+
+                name = "dep1"
+                version = "0.5.0"
+                authors = ["carlhuda@example.com"]
+            "#)
+            .file("src/lib.rs", "")
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.dep1]
+
+            git = 'file:{}'
+            tag = "v9.9.9"
+        "#, git_project.root().display()))
+        .file("src/main.rs", "fn main() {}");
+
+    let output = p.cargo_process("cargo-build").arg("-v").exec_with_output();
+    let output = match output {
+        Ok(..) => fail!("cargo-build should have failed"),
+        Err(e) => e.output.assert(),
+    };
+    assert!(output.status.matches_exit_status(101));
+
+    let err = str::from_utf8(output.error.as_slice()).assert();
+    let expected = format!("failed to find tag `v9.9.9` in `file:{}`",
+                           git_project.root().display());
+    assert!(err.contains(expected.as_slice()), "tag not named\n{}", err);
+})
+
 test!(cargo_compile_with_nested_paths {
     let git_project = git_repo("dep1", |project| {
         project
@@ -402,7 +652,9 @@ test!(cargo_compile_with_short_ssh_git {
         execs()
         .with_stdout("")
         .with_stderr(format!("Cargo.toml is not a valid manifest\n\n\
-                              invalid url `{}`: `Relative URL without a base\n", url)));
+                              invalid url `{}`: `Relative URL without a base`; \
+                              SSH remotes must be written as \
+                              `ssh://git@github.com/a/dep`\n", url)));
 })
 
 test!(two_revs_same_deps {
@@ -522,17 +774,21 @@ test!(recompilation {
     assert_that(p.cargo_process("cargo-build"),
                 execs().with_stdout(format!("{} git repository `file:{}`\n\
                                              {} bar v0.5.0 (file:{}#[..])\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             UPDATING, git_project.root().display(),
                                             COMPILING, git_project.root().display(),
-                                            COMPILING, p.root().display())));
+                                            COMPILING, p.root().display(),
+                                            FINISHED)));
 
     // Don't recompile the second time
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_stdout(format!("{} bar v0.5.0 (file:{}#[..])\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             FRESH, git_project.root().display(),
-                                            FRESH, p.root().display())));
+                                            FRESH, p.root().display(),
+                                            FINISHED)));
 
     // Modify a file manually, shouldn't trigger a recompile
     File::create(&git_project.root().join("src/bar.rs")).write_str(r#"
@@ -541,9 +797,11 @@ test!(recompilation {
 
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_stdout(format!("{} bar v0.5.0 (file:{}#[..])\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             FRESH, git_project.root().display(),
-                                            FRESH, p.root().display())));
+                                            FRESH, p.root().display(),
+                                            FINISHED)));
 
     assert_that(p.process(cargo_dir().join("cargo-update")),
                 execs().with_stdout(format!("{} git repository `file:{}`",
@@ -552,9 +810,11 @@ test!(recompilation {
 
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_stdout(format!("{} bar v0.5.0 (file:{}#[..])\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             FRESH, git_project.root().display(),
-                                            FRESH, p.root().display())));
+                                            FRESH, p.root().display(),
+                                            FINISHED)));
 
     // Commit the changes and make sure we don't trigger a recompile because the
     // lockfile says not to change
@@ -565,22 +825,29 @@ test!(recompilation {
     println!("compile after commit");
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_stdout(format!("{} bar v0.5.0 (file:{}#[..])\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             FRESH, git_project.root().display(),
-                                            FRESH, p.root().display())));
+                                            FRESH, p.root().display(),
+                                            FINISHED)));
     p.root().move_into_the_past().assert();
 
     // Update the dependency and carry on!
     assert_that(p.process(cargo_dir().join("cargo-update")),
-                execs().with_stdout(format!("{} git repository `file:{}`",
+                execs().with_stdout(format!("{} git repository `file:{}`\n\
+                                             {} bar v0.5.0 ([..]) -> \
+                                             v0.5.0 ([..])\n",
                                             UPDATING,
-                                            git_project.root().display())));
+                                            git_project.root().display(),
+                                            UPDATING)));
     println!("going for the last compile");
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_stdout(format!("{} bar v0.5.0 (file:{}#[..])\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             COMPILING, git_project.root().display(),
-                                            COMPILING, p.root().display())));
+                                            COMPILING, p.root().display(),
+                                            FINISHED)));
 })
 
 test!(update_with_shared_deps {
@@ -648,9 +915,11 @@ test!(update_with_shared_deps {
 {compiling} bar v0.5.0 (file:{git}#[..])
 {compiling} [..] v0.5.0 (file:{dir})
 {compiling} [..] v0.5.0 (file:{dir})
-{compiling} foo v0.5.0 (file:{dir})\n",
+{compiling} foo v0.5.0 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s\n",
                     updating = UPDATING, git = git_project.root().display(),
-                    compiling = COMPILING, dir = p.root().display())));
+                    compiling = COMPILING, finished = FINISHED,
+                    dir = p.root().display())));
 
     // Modify a file manually, and commit it
     File::create(&git_project.root().join("src/bar.rs")).write_str(r#"
@@ -660,9 +929,12 @@ test!(update_with_shared_deps {
     git_project.process("git").args(["commit", "-m", "test"]).exec_with_output()
                .assert();
     assert_that(p.process(cargo_dir().join("cargo-update")).arg("dep1"),
-                execs().with_stdout(format!("{} git repository `file:{}`",
+                execs().with_stdout(format!("{} git repository `file:{}`\n\
+                                             {} bar v0.5.0 ([..]) -> \
+                                             v0.5.0 ([..])\n",
                                             UPDATING,
-                                            git_project.root().display())));
+                                            git_project.root().display(),
+                                            UPDATING)));
 
     // Make sure we still only compile one version of the git repo
     assert_that(p.cargo_process("cargo-build"),
@@ -670,7 +942,1072 @@ test!(update_with_shared_deps {
 {compiling} bar v0.5.0 (file:{git}#[..])
 {compiling} [..] v0.5.0 (file:{dir})
 {compiling} [..] v0.5.0 (file:{dir})
-{compiling} foo v0.5.0 (file:{dir})\n",
+{compiling} foo v0.5.0 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s\n",
                     git = git_project.root().display(),
-                    compiling = COMPILING, dir = p.root().display())));
+                    compiling = COMPILING, finished = FINISHED,
+                    dir = p.root().display())));
+})
+
+test!(update_one_package {
+    let bar = git_repo("bar", |project| {
+        project.file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", "pub fn bar() {}")
+    }).assert();
+    let baz = git_repo("baz", |project| {
+        project.file("Cargo.toml", r#"
+                [package]
+                name = "baz"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", "pub fn baz() {}")
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+            [dependencies.baz]
+            git = 'file:{}'
+        "#, bar.root().display(), baz.root().display()))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            extern crate baz;
+            fn main() {}
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    let old_bar = head(&bar);
+    let old_baz = head(&baz);
+
+    // Push a new commit to both repositories
+    for repo in [&bar, &baz].iter() {
+        File::create(&repo.root().join("src/new.rs")).write_str("").assert();
+        repo.process("git").args(["add", "."]).exec_with_output().assert();
+        repo.process("git").args(["commit", "-m", "new"]).exec_with_output()
+            .assert();
+    }
+    let new_bar = head(&bar);
+    let new_baz = head(&baz);
+
+    assert_that(p.process(cargo_dir().join("cargo-update")).arg("-p").arg("qux"),
+                execs().with_status(101).with_stderr("\
+package `qux` is not in the lockfile, valid package names are: bar, baz, foo
+"));
+
+    assert_that(p.process(cargo_dir().join("cargo-update")).arg("-p").arg("bar"),
+                execs().with_status(0)
+                       .with_stdout(format!("{} git repository `file:{}`\n\
+                                             {} bar v0.5.0 ({}) -> v0.5.0 ({})\n",
+                                            UPDATING, bar.root().display(),
+                                            UPDATING, old_bar.as_slice().slice_to(8),
+                                            new_bar.as_slice().slice_to(8))));
+
+    let lock = File::open(&p.root().join("Cargo.lock")).read_to_string()
+                    .assert();
+    let lock = lock.as_slice();
+    assert!(!lock.contains(old_bar.as_slice()) &&
+            lock.contains(new_bar.as_slice()), "bar not updated:\n{}", lock);
+    assert!(lock.contains(old_baz.as_slice()) &&
+            !lock.contains(new_baz.as_slice()), "baz updated:\n{}", lock);
+
+    assert_that(p.process(cargo_dir().join("cargo-update"))
+                 .arg("-p").arg("baz:0.5.0"),
+                execs().with_status(0));
+    let lock = File::open(&p.root().join("Cargo.lock")).read_to_string()
+                    .assert();
+    assert!(lock.as_slice().contains(new_baz.as_slice()),
+            "baz not updated:\n{}", lock);
+
+    fn head(repo: &ProjectBuilder) -> String {
+        let out = repo.process("git").args(["rev-parse", "HEAD"])
+                      .exec_with_output().assert();
+        str::from_utf8(out.output.as_slice()).unwrap().trim().to_string()
+    }
+})
+
+test!(update_precise {
+    let bar = git_repo("bar", |project| {
+        project.file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", "pub fn bar() {}")
+    }).assert();
+    let first = bar.process("git").args(["rev-parse", "HEAD"])
+                   .exec_with_output().assert();
+    let first = str::from_utf8(first.output.as_slice()).unwrap().trim()
+                    .to_string();
+
+    File::create(&bar.root().join("src/new.rs")).write_str("").assert();
+    bar.process("git").args(["add", "."]).exec_with_output().assert();
+    bar.process("git").args(["commit", "-m", "new"]).exec_with_output()
+       .assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+            [dependencies.baz]
+            path = "baz"
+        "#, bar.root().display()))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            extern crate baz;
+            fn main() {}
+        "#)
+        .file("baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+        "#)
+        .file("baz/src/lib.rs", "");
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+
+    assert_that(p.process(cargo_dir().join("cargo-update"))
+                 .arg("-p").arg("bar").arg("--precise").arg(first.as_slice()),
+                execs().with_status(0).with_stdout(format!("\
+{} bar v0.5.0 ([..]) -> v0.5.0 ({})
+", UPDATING, first.as_slice().slice_to(8))));
+    let lock = File::open(&p.root().join("Cargo.lock")).read_to_string()
+                    .assert();
+    assert!(lock.as_slice().contains(first.as_slice()), "{}", lock);
+
+    assert_that(p.process(cargo_dir().join("cargo-update"))
+                 .arg("-p").arg("baz").arg("--precise").arg(first.as_slice()),
+                execs().with_status(101).with_stderr("\
+--precise can only be used with git dependencies, `baz` is not one
+"));
+})
+
+test!(lockfile_pins_git_revision {
+    let bar = git_repo("bar", |project| {
+        project.file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", r#"pub fn bar() -> &'static str { "old" }"#)
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+        "#, bar.root().display()))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { println!("{}", bar::bar()) }
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("old\n"));
+
+    // Move the branch on the remote; the lockfile keeps the old revision and
+    // the local database has it, so the remote isn't even contacted.
+    File::create(&bar.root().join("src/lib.rs"))
+         .write_str(r#"pub fn bar() -> &'static str { "new" }"#).assert();
+    bar.process("git").args(["add", "."]).exec_with_output().assert();
+    bar.process("git").args(["commit", "-m", "new"]).exec_with_output()
+       .assert();
+
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(format!("\
+{fresh} bar v0.5.0 (file:{bar}#[..])
+{fresh} foo v0.5.0 (file:{foo})
+{finished} dev [unoptimized] target(s) in [..]s
+", fresh = FRESH, finished = FINISHED,
+   bar = bar.root().display(), foo = p.root().display())));
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("old\n"));
+
+    // Updating moves the pin
+    assert_that(p.process(cargo_dir().join("cargo-update")),
+                execs().with_status(0));
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("new\n"));
+})
+
+test!(checkouts_are_shared_by_revision {
+    let bar = git_repo("bar", |project| {
+        project.file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", "pub fn bar() {}")
+    }).assert();
+    let rev = bar.process("git").args(["rev-parse", "HEAD"])
+                 .exec_with_output().assert();
+    let rev = str::from_utf8(rev.output.as_slice()).unwrap().trim().to_string();
+
+    let manifest = |name: &str| format!(r#"
+            [package]
+            name = "{}"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+        "#, name, bar.root().display());
+    let main = r#"
+            extern crate bar;
+            fn main() { bar::bar() }
+        "#;
+    let foo = project("foo").file("Cargo.toml", manifest("foo"))
+                            .file("src/main.rs", main);
+    let baz = project("baz").file("Cargo.toml", manifest("baz"))
+                            .file("src/main.rs", main);
+
+    assert_that(foo.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(baz.cargo_process("cargo-build"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} bar v0.5.0 (file:{bar}#[..])
+{compiling} baz v0.5.0 (file:{baz})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, bar = bar.root().display(),
+   baz = baz.root().display())));
+
+    let checkouts = paths::home().join(".cargo/git/checkouts");
+    let repos = fs::readdir(&checkouts).assert();
+    assert_eq!(repos.len(), 1);
+    let revs = fs::readdir(&repos[0]).assert();
+    assert_eq!(revs.len(), 1);
+    assert_eq!(revs[0].filename_str().unwrap(), rev.as_slice());
+
+    // A checkout which has wandered off its revision is cloned again
+    let checkout = revs[0].clone();
+    process("git").cwd(checkout.clone())
+                  .args(["commit", "--allow-empty", "-q", "-m", "oops"])
+                  .env("HOME", Some(paths::home()))
+                  .exec_with_output().assert();
+    assert_that(foo.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+    let head = process("git").cwd(checkout).args(["rev-parse", "HEAD"])
+                             .exec_with_output().assert();
+    assert_eq!(str::from_utf8(head.output.as_slice()).unwrap().trim(),
+               rev.as_slice());
+})
+
+test!(locked_verifies_git_checksums {
+    let git_project = git_repo("bar", |project| {
+        project
+            .file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", r#"
+                pub fn bar() {}
+            "#)
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+        "#, git_project.root().display()))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { bar::bar() }
+        "#);
+
+    // Without a lockfile there's nothing to verify against
+    assert_that(p.cargo_process("cargo-build").arg("--locked"),
+                execs().with_status(101).with_stderr("\
+the lockfile needs to be generated but --locked was passed to prevent this
+"));
+
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+    let lock = File::open(&p.root().join("Cargo.lock")).read_to_string()
+                    .assert();
+    assert!(lock.as_slice().contains("[metadata]\nchecksum bar 0.5.0 (file:"),
+            "no checksum recorded:\n{}", lock);
+
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--locked"),
+                execs().with_status(0));
+
+    // Tamper with the checkout behind cargo's back
+    let checkouts = paths::home().join(".cargo/git/checkouts");
+    let lib = fs::walk_dir(&checkouts).assert().find(|f| {
+        f.as_vec().ends_with(b"src/lib.rs")
+    }).assert();
+    File::create(&lib).write_str("pub fn bar() { fail!() }").assert();
+
+    let mismatch = format!("\
+checksum for `bar v0.5.0 (file:{}#[..])` does not match the lockfile
+expected: [..]
+actual:   [..]
+", git_project.root().display());
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--locked"),
+                execs().with_status(101).with_stderr(mismatch.as_slice()));
+
+    // A checksum already in the lockfile is verified without --locked too
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(101).with_stderr(mismatch.as_slice()));
+})
+
+test!(offline_requires_local_git_database {
+    let git_project = git_repo("bar", |project| {
+        project
+            .file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", r#"
+                pub fn bar() {}
+            "#)
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+        "#, git_project.root().display()))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { bar::bar() }
+        "#);
+
+    assert_that(p.cargo_process("cargo-build").arg("--offline"),
+                execs().with_status(101).with_stderr(format!("\
+Could not fetch `file:{}` in offline mode, branch `master` is not in the \
+local cache.
+", git_project.root().display())));
+
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+
+    // Once the database exists, offline builds use it without updating
+    fs::unlink(&p.root().join("Cargo.lock")).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--offline"),
+                execs().with_status(0));
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .env("CARGO_OFFLINE", Some("1")),
+                execs().with_status(0));
+})
+
+test!(offline_requires_locked_revision {
+    let bar = git_repo("bar", |project| {
+        project
+            .file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", "pub fn bar() {}")
+    }).assert();
+    let head = || {
+        let rev = bar.process("git").args(["rev-parse", "HEAD"])
+                     .exec_with_output().assert();
+        str::from_utf8(rev.output.as_slice()).unwrap().trim().to_string()
+    };
+    let first = head();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+        "#, bar.root().display()))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { bar::bar() }
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--offline"),
+                execs().with_status(0));
+
+    // Lock to a revision which was never fetched into the local database
+    File::create(&bar.root().join("src/new.rs")).write_str("").assert();
+    bar.process("git").args(["add", "."]).exec_with_output().assert();
+    bar.process("git").args(["commit", "-m", "new"]).exec_with_output()
+       .assert();
+    let second = head();
+
+    let lockfile = p.root().join("Cargo.lock");
+    let lock = File::open(&lockfile).read_to_string().assert();
+    let lock = lock.as_slice().replace(first.as_slice(), second.as_slice());
+    File::create(&lockfile).write_str(lock.as_slice()).assert();
+
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--offline"),
+                execs().with_status(101).with_stderr(format!("\
+Could not fetch `file:{}` in offline mode, rev `{}` is not in the local \
+cache.
+", bar.root().display(), second)));
+})
+
+test!(frozen_is_locked_and_offline {
+    let git_project = git_repo("bar", |project| {
+        project
+            .file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", r#"
+                pub fn bar() {}
+            "#)
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+        "#, git_project.root().display()))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { bar::bar() }
+        "#);
+
+    assert_that(p.cargo_process("cargo-build").arg("--frozen"),
+                execs().with_status(101).with_stderr("\
+the lockfile needs to be generated but --locked was passed to prevent this
+"));
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .env("CARGO_FROZEN", Some("1")),
+                execs().with_status(101).with_stderr("\
+the lockfile needs to be generated but --locked was passed to prevent this
+"));
+
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--frozen"),
+                execs().with_status(0));
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .env("CARGO_FROZEN", Some("1")),
+                execs().with_status(0));
+})
+
+test!(offline_lists_every_missing_repository {
+    let bar = git_repo("bar", |project| {
+        project
+            .file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", "")
+    }).assert();
+    let baz = git_repo("baz", |project| {
+        project
+            .file("Cargo.toml", r#"
+                [package]
+                name = "baz"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", "")
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+
+            [dependencies.baz]
+            git = 'file:{}'
+        "#, bar.root().display(), baz.root().display()))
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build").arg("--offline"),
+                execs().with_status(101).with_stderr(format!("\
+Could not fetch `file:{}` in offline mode, branch `master` is not in the \
+local cache.
+Could not fetch `file:{}` in offline mode, branch `master` is not in the \
+local cache.
+", bar.root().display(), baz.root().display())));
+})
+
+test!(abbreviated_rev_is_locked_as_full_sha {
+    let bar = git_repo("bar", |project| {
+        project.file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", r#"pub fn bar() -> &'static str { "old" }"#)
+    }).assert();
+    let rev = bar.process("git").args(["rev-parse", "HEAD"])
+                 .exec_with_output().assert();
+    let rev = str::from_utf8(rev.output.as_slice()).unwrap().trim().to_string();
+    File::create(&bar.root().join("src/lib.rs"))
+         .write_str(r#"pub fn bar() -> &'static str { "new" }"#).assert();
+    bar.process("git").args(["commit", "-a", "-m", "new"]).exec_with_output()
+       .assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+            rev = "{}"
+        "#, bar.root().display(), rev.as_slice().slice_to(7)))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { println!("{}", bar::bar()) }
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stdout(format!("\
+{updating} git repository `file:{bar}`
+{compiling} bar v0.5.0 (file:{bar}?rev={short}#{rev})
+{compiling} foo v0.5.0 (file:{foo})
+{finished} dev [unoptimized] target(s) in [..]s
+", updating = UPDATING, compiling = COMPILING, finished = FINISHED,
+   bar = bar.root().display(), foo = p.root().display(),
+   short = rev.as_slice().slice_to(7), rev = rev.as_slice().slice_to(8))));
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("old\n"));
+
+    let lockfile = File::open(&p.root().join("Cargo.lock")).read_to_string()
+                        .assert();
+    assert!(lockfile.as_slice().contains(format!("#{}\"", rev).as_slice()),
+            "full revision not locked:\n{}", lockfile);
+})
+
+test!(conflicting_git_references {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = "https://example.com/bar"
+            branch = "next"
+            rev = "0123456"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stderr("\
+Cargo.toml is not a valid manifest
+
+dependency (bar) specifies both `branch` and `rev`; only one of `branch`, \
+`tag`, or `rev` is allowed
+"));
+})
+
+test!(replace_git_dependency_with_local_path {
+    let bar = git_repo("bar", |project| {
+        project.file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", r#"pub fn bar() -> &'static str { "git" }"#)
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+
+            [replace]
+            "bar:0.5.0" = {{ path = "bar" }}
+        "#, bar.root().display()))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { println!("{}", bar::bar()) }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+        "#)
+        .file("bar/src/lib.rs", r#"pub fn bar() -> &'static str { "local" }"#);
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stdout(format!("\
+{updating} git repository `file:{bar}`
+{compiling} bar v0.5.0 (file:{foo}/bar)
+{compiling} foo v0.5.0 (file:{foo})
+{finished} dev [unoptimized] target(s) in [..]s
+", updating = UPDATING, compiling = COMPILING, finished = FINISHED,
+   bar = bar.root().display(), foo = p.root().display())));
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("local\n"));
+
+    // The replacement is built from the lockfile on later runs as well
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("local\n"));
+})
+
+test!(replace_of_package_not_in_graph_warns {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [replace]
+            "bar:0.5.0" = { path = "bar" }
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+        "#)
+        .file("bar/src/lib.rs", "");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stderr("\
+replacement `bar:0.5.0` does not match any package in the dependency graph
+"));
+})
+
+test!(fetch_then_build_offline {
+    let bar = git_repo("bar", |project| {
+        project.file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", r#"pub fn bar() -> &'static str { "bar" }"#)
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+        "#, bar.root().display()))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { println!("{}", bar::bar()) }
+        "#);
+
+    assert_that(p.cargo_process("cargo-fetch"),
+                execs().with_status(0).with_stdout(format!("\
+{updating} git repository `file:{bar}`
+{fetching} bar v0.5.0 (file:{bar}#[..])
+", updating = UPDATING, fetching = FETCHING, bar = bar.root().display())));
+    assert_that(&p.root().join("Cargo.lock"), existing_file());
+    assert_that(&p.bin("foo"), is_not(existing_file()));
+
+    // The remote going away doesn't matter any more
+    fs::rename(&bar.root(), &bar.root().with_filename("gone")).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--offline"),
+                execs().with_status(0));
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("bar\n"));
+})
+
+test!(fetch_includes_optional_deps_but_not_path_deps {
+    let bar = git_repo("bar", |project| {
+        project.file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", r#"pub fn bar() -> &'static str { "bar" }"#)
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+            optional = true
+
+            [dependencies.baz]
+            path = "baz"
+        "#, bar.root().display()))
+        .file("src/main.rs", r#"
+            #[cfg(feature = "bar")] extern crate bar;
+            extern crate baz;
+            #[cfg(feature = "bar")] fn main() { println!("{}", bar::bar()) }
+            #[cfg(not(feature = "bar"))] fn main() { baz::baz() }
+        "#)
+        .file("baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+        "#)
+        .file("baz/src/lib.rs", "pub fn baz() {}");
+
+    assert_that(p.cargo_process("cargo-fetch"),
+                execs().with_status(0).with_stdout(format!("\
+{updating} git repository `file:{bar}`
+{fetching} bar v0.5.0 (file:{bar}#[..])
+", updating = UPDATING, fetching = FETCHING, bar = bar.root().display())));
+
+    fs::rename(&bar.root(), &bar.root().with_filename("gone")).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--offline").arg("--features").arg("bar"),
+                execs().with_status(0));
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("bar\n"));
+})
+
+test!(git_checkout_offline_only_uses_the_local_database {
+    let bar = git_repo("bar", |project| {
+        project.file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", "")
+    }).assert();
+    let url = format!("--url=file://{}", bar.root().display());
+
+    assert_that(bar.cargo_process("cargo-git-checkout")
+                   .arg(url.as_slice()).arg("--reference=master")
+                   .arg("--offline"),
+                execs().with_status(1).with_stderr(format!("\
+Couldn't update [..]: Could not fetch `file:{}` in offline mode, branch \
+`master` is not in the local cache.
+", bar.root().display())));
+
+    assert_that(bar.process(cargo_dir().join("cargo-git-checkout"))
+                   .arg(url.as_slice()).arg("--reference=master"),
+                execs().with_status(0));
+
+    // Once fetched, the branch resolves without the network
+    assert_that(bar.process(cargo_dir().join("cargo-git-checkout"))
+                   .arg(url.as_slice()).arg("--reference=master")
+                   .arg("--offline"),
+                execs().with_status(0));
+})
+
+test!(missing_git_is_explained {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.bar]
+            git = "https://example.com/bar"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+    p.build();
+
+    // Only rustc can be found, through its full path
+    let rustc = os::split_paths(os::getenv("PATH").unwrap().as_slice())
+        .move_iter().map(|dir| dir.join("rustc"))
+        .find(|rustc| rustc.is_file()).unwrap();
+    let empty = p.root().join("empty");
+    fs::mkdir(&empty, io::UserRWX).assert();
+
+    let err = p.process(cargo_dir().join("cargo-build")).arg("-v")
+               .env("PATH", Some(&empty)).env("RUSTC", Some(&rustc))
+               .exec_with_output().unwrap_err();
+    assert_eq!(err.exit, Some(ExitStatus(101)));
+    let stderr = String::from_utf8(err.output.unwrap().error).unwrap();
+    assert!(stderr.as_slice().contains("`git` is required to fetch dependency \
+                                        `bar` from https://example.com/bar, \
+                                        check that it is installed and in \
+                                        PATH"),
+            "{}", stderr);
+})
+
+fn dep_with_submodule(sub: &ProjectBuilder) -> ProjectBuilder {
+    let git_project = git_repo("dep1", |project| {
+        project
+            .file("Cargo.toml", r#"
+                [package]
+                name = "dep1"
+                version = "0.5.0"
+                authors = []
+            "#)
+            .file("src/lib.rs", r#"
+                pub fn answer() -> &'static str {
+                    include_str!("../sub/answer.txt")
+                }
+            "#)
+    }).assert();
+    git_project.process("git").args(["submodule", "add",
+                                     sub.root().as_str().unwrap(), "sub"])
+               .exec_with_output().assert();
+    git_project.process("git").args(["commit", "-m", "Add submodule"])
+               .exec_with_output().assert();
+    git_project
+}
+
+test!(submodule_branch_is_followed {
+    let sub = git_repo("sub", |project| {
+        project.file("answer.txt", "one")
+    }).assert();
+    let git = |args: &[&str]| {
+        sub.process("git").args(args).exec_with_output().assert();
+    };
+    git(["checkout", "-b", "stable"]);
+    File::create(&sub.root().join("answer.txt")).write_str("two").assert();
+    git(["commit", "-am", "Stable answer"]);
+    git(["checkout", "master"]);
+
+    let dep = dep_with_submodule(&sub);
+    dep.process("git").args(["config", "-f", ".gitmodules",
+                             "submodule.sub.branch", "stable"])
+       .exec_with_output().assert();
+    dep.process("git").args(["commit", "-am", "Follow stable"])
+       .exec_with_output().assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.dep1]
+            git = 'file:{}'
+        "#, dep.root().display()))
+        .file("src/main.rs", r#"
+            extern crate dep1;
+            fn main() { println!("{}", dep1::answer()) }
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(cargo::util::process(p.bin("foo")),
+                execs().with_status(0).with_stdout("two\n"));
+})
+
+test!(failed_submodule_update_names_the_submodule {
+    let sub = git_repo("sub", |project| {
+        project.file("answer.txt", "one")
+    }).assert();
+
+    let dep = dep_with_submodule(&sub);
+    dep.process("git").args(["config", "-f", ".gitmodules", "submodule.sub.url",
+                             "/nonexistent/sub"])
+       .exec_with_output().assert();
+    dep.process("git").args(["commit", "-am", "Break submodule"])
+       .exec_with_output().assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.dep1]
+            git = 'file:{}'
+        "#, dep.root().display()))
+        .file("src/main.rs", r#"
+            extern crate dep1;
+            fn main() { println!("{}", dep1::answer()) }
+        "#);
+
+    let err = match p.cargo_process("cargo-build").arg("-v").exec_with_output() {
+        Ok(..) => fail!("build succeeded with a broken submodule"),
+        Err(e) => String::from_utf8(e.output.unwrap().error).assert(),
+    };
+    assert!(err.as_slice().contains("failed to update submodule `sub`"),
+            "{}", err);
+})
+
+test!(same_named_packages_from_different_sources {
+    fn utils(project: ProjectBuilder, word: &str) -> ProjectBuilder {
+        project
+            .file("utils/Cargo.toml", r#"
+                [package]
+                name = "utils"
+                version = "0.1.0"
+                authors = []
+            "#)
+            .file("utils/src/lib.rs",
+                  format!("pub fn word() -> &'static str {{ \"{}\" }}", word))
+    }
+    let a = git_repo("a", |project| {
+        utils(project, "one")
+            .file("Cargo.toml", r#"
+                [package]
+                name = "a"
+                version = "0.1.0"
+                authors = []
+
+                [dependencies.utils]
+                path = "utils"
+            "#)
+            .file("src/lib.rs", r#"
+                extern crate utils;
+                pub fn a() -> &'static str { utils::word() }
+            "#)
+    }).assert();
+    let b = git_repo("b", |project| {
+        utils(project, "two")
+            .file("Cargo.toml", r#"
+                [package]
+                name = "b"
+                version = "0.1.0"
+                authors = []
+
+                [dependencies.utils]
+                path = "utils"
+            "#)
+            .file("src/lib.rs", r#"
+                extern crate utils;
+                pub fn b() -> &'static str { utils::word() }
+            "#)
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.a]
+            git = 'file:{}'
+
+            [dependencies.b]
+            git = 'file:{}'
+        "#, a.root().display(), b.root().display()))
+        .file("src/main.rs", r#"
+            extern crate a;
+            extern crate b;
+            fn main() { println!("{} {}", a::a(), b::b()); }
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(process(p.bin("foo")), execs().with_stdout("one two\n"));
+
+    // Each copy of `utils` got artifacts of its own
+    let rlibs = fs::readdir(&p.root().join("target/deps")).assert();
+    let rlibs = rlibs.iter().filter(|f| {
+        f.filename_str().unwrap().starts_with("libutils-")
+    }).count();
+    assert_eq!(rlibs, 2);
+})
+
+test!(renamed_dependency_shares_its_name_with_another {
+    let bar = git_repo("bar", |project| {
+        project.file("Cargo.toml", r#"
+                [package]
+                name = "bar"
+                version = "0.5.0"
+                authors = ["wycats@example.com"]
+            "#)
+            .file("src/lib.rs", r#"pub fn bar() -> &'static str { "git" }"#)
+    }).assert();
+
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.bar]
+            git = 'file:{}'
+
+            [dependencies.local_bar]
+            path = "bar"
+            package = "bar"
+        "#, bar.root().display()))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            extern crate local_bar;
+            fn main() { println!("{} {}", bar::bar(), local_bar::bar()) }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+        "#)
+        .file("bar/src/lib.rs", r#"pub fn bar() -> &'static str { "local" }"#);
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("git local\n"));
 })
@@ -1,9 +1,9 @@
-use std::io::File;
+use std::io::{fs, File};
 
-use support::{ResultTest, project, execs, main_file, cargo_dir};
-use support::{COMPILING, FRESH};
+use support::{ResultTest, ProjectBuilder, project, execs, main_file, cargo_dir};
+use support::{COMPILING, FRESH, FINISHED};
 use support::paths::PathExt;
-use hamcrest::{assert_that, existing_file};
+use hamcrest::{assert_that, existing_file, is_not};
 use cargo;
 use cargo::util::{process};
 
@@ -73,10 +73,12 @@ test!(cargo_compile_with_nested_deps_shorthand {
     assert_that(p.cargo_process("cargo-build"),
         execs().with_stdout(format!("{} baz v0.5.0 (file:{})\n\
                                      {} bar v0.5.0 (file:{})\n\
-                                     {} foo v0.5.0 (file:{})\n",
+                                     {} foo v0.5.0 (file:{})\n\
+                                     {} dev [unoptimized] target(s) in [..]s\n",
                                     COMPILING, p.root().display(),
                                     COMPILING, p.root().display(),
-                                    COMPILING, p.root().display())));
+                                    COMPILING, p.root().display(),
+                                    FINISHED)));
 
     assert_that(&p.bin("foo"), existing_file());
 
@@ -102,8 +104,14 @@ test!(cargo_compile_with_root_dev_deps {
             [[bin]]
             name = "foo"
         "#)
-        .file("src/main.rs",
-              main_file(r#""{}", bar::gimme()"#, ["bar"]).as_slice());
+        .file("src/main.rs", r#"
+            #[cfg(test)] extern crate bar;
+
+            fn main() {}
+
+            #[test]
+            fn gimme() { assert_eq!(bar::gimme(), "zoidberg") }
+        "#);
     let p2 = project("bar")
         .file("Cargo.toml", r#"
             [package]
@@ -119,17 +127,27 @@ test!(cargo_compile_with_root_dev_deps {
         "#);
 
     p2.build();
+
+    // Dev-dependencies are left out of plain builds
     assert_that(p.cargo_process("cargo-build"),
-        execs().with_stdout(format!("{} bar v0.5.0 (file:{})\n\
-                                     {} foo v0.5.0 (file:{})\n",
+        execs().with_stdout(format!("{} foo v0.5.0 (file:{})\n\
+                                     {} dev [unoptimized] target(s) in [..]s\n",
                                     COMPILING, p.root().display(),
-                                    COMPILING, p.root().display())));
+                                    FINISHED)));
 
     assert_that(&p.bin("foo"), existing_file());
 
-    assert_that(
-      cargo::util::process(p.bin("foo")),
-      execs().with_stdout("zoidberg\n"));
+    assert_that(p.process(cargo_dir().join("cargo-test")),
+        execs().with_stdout(format!("{} bar v0.5.0 (file:{})\n\
+                                     {} foo v0.5.0 (file:{})\n\
+                                     {} test [unoptimized] target(s) in [..]s\n\n\
+                                     running 1 test\n\
+                                     test gimme ... ok\n\n\
+                                     test result: ok. 1 passed; 0 failed; \
+                                     0 ignored; 0 measured\n\n",
+                                    COMPILING, p.root().display(),
+                                    COMPILING, p.root().display(),
+                                    FINISHED)));
 })
 
 test!(cargo_compile_with_transitive_dev_deps {
@@ -175,9 +193,11 @@ test!(cargo_compile_with_transitive_dev_deps {
 
     assert_that(p.cargo_process("cargo-build"),
         execs().with_stdout(format!("{} bar v0.5.0 (file:{})\n\
-                                     {} foo v0.5.0 (file:{})\n",
+                                     {} foo v0.5.0 (file:{})\n\
+                                     {} dev [unoptimized] target(s) in [..]s\n",
+                                    COMPILING, p.root().display(),
                                     COMPILING, p.root().display(),
-                                    COMPILING, p.root().display())));
+                                    FINISHED)));
 
     assert_that(&p.bin("foo"), existing_file());
 
@@ -222,23 +242,29 @@ test!(no_rebuild_dependency {
     // First time around we should compile both foo and bar
     assert_that(p.cargo_process("cargo-build"),
                 execs().with_stdout(format!("{} bar v0.5.0 (file:{})\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             COMPILING, bar.display(),
-                                            COMPILING, p.root().display())));
+                                            COMPILING, p.root().display(),
+                                            FINISHED)));
     // This time we shouldn't compile bar
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_stdout(format!("{} bar v0.5.0 (file:{})\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             FRESH, bar.display(),
-                                            FRESH, p.root().display())));
+                                            FRESH, p.root().display(),
+                                            FINISHED)));
     p.root().move_into_the_past().assert();
 
     p.build(); // rebuild the files (rewriting them in the process)
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_stdout(format!("{} bar v0.5.0 (file:{})\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             COMPILING, bar.display(),
-                                            COMPILING, p.root().display())));
+                                            COMPILING, p.root().display(),
+                                            FINISHED)));
 })
 
 test!(deep_dependencies_trigger_rebuild {
@@ -297,17 +323,21 @@ test!(deep_dependencies_trigger_rebuild {
     assert_that(p.cargo_process("cargo-build"),
                 execs().with_stdout(format!("{} baz v0.5.0 (file:{})\n\
                                              {} bar v0.5.0 (file:{})\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             COMPILING, baz.display(),
                                             COMPILING, bar.display(),
-                                            COMPILING, p.root().display())));
+                                            COMPILING, p.root().display(),
+                                            FINISHED)));
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_stdout(format!("{} baz v0.5.0 (file:{})\n\
                                              {} bar v0.5.0 (file:{})\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             FRESH, baz.display(),
                                             FRESH, bar.display(),
-                                            FRESH, p.root().display())));
+                                            FRESH, p.root().display(),
+                                            FINISHED)));
 
     // Make sure an update to baz triggers a rebuild of bar
     //
@@ -320,10 +350,12 @@ test!(deep_dependencies_trigger_rebuild {
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_stdout(format!("{} baz v0.5.0 (file:{})\n\
                                              {} bar v0.5.0 (file:{})\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             COMPILING, baz.display(),
                                             COMPILING, bar.display(),
-                                            COMPILING, p.root().display())));
+                                            COMPILING, p.root().display(),
+                                            FINISHED)));
 
     // Make sure an update to bar doesn't trigger baz
     p.root().move_into_the_past().assert();
@@ -334,10 +366,12 @@ test!(deep_dependencies_trigger_rebuild {
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_stdout(format!("{} baz v0.5.0 (file:{})\n\
                                              {} bar v0.5.0 (file:{})\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             FRESH, baz.display(),
                                             COMPILING, bar.display(),
-                                            COMPILING, p.root().display())));
+                                            COMPILING, p.root().display(),
+                                            FINISHED)));
 })
 
 test!(no_rebuild_two_deps {
@@ -396,18 +430,22 @@ test!(no_rebuild_two_deps {
     assert_that(p.cargo_process("cargo-build"),
                 execs().with_stdout(format!("{} baz v0.5.0 (file:{})\n\
                                              {} bar v0.5.0 (file:{})\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             COMPILING, baz.display(),
                                             COMPILING, bar.display(),
-                                            COMPILING, p.root().display())));
+                                            COMPILING, p.root().display(),
+                                            FINISHED)));
     assert_that(&p.bin("foo"), existing_file());
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_stdout(format!("{} baz v0.5.0 (file:{})\n\
                                              {} bar v0.5.0 (file:{})\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             FRESH, baz.display(),
                                             FRESH, bar.display(),
-                                            FRESH, p.root().display())));
+                                            FRESH, p.root().display(),
+                                            FINISHED)));
     assert_that(&p.bin("foo"), existing_file());
 })
 
@@ -447,9 +485,11 @@ test!(nested_deps_recompile {
 
     assert_that(p.cargo_process("cargo-build"),
                 execs().with_stdout(format!("{} bar v0.5.0 (file:{})\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             COMPILING, bar.display(),
-                                            COMPILING, p.root().display())));
+                                            COMPILING, p.root().display(),
+                                            FINISHED)));
     p.root().move_into_the_past().assert();
 
     File::create(&p.root().join("src/foo.rs")).write_str(r#"
@@ -459,9 +499,11 @@ test!(nested_deps_recompile {
     // This shouldn't recompile `bar`
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_stdout(format!("{} bar v0.5.0 (file:{})\n\
-                                             {} foo v0.5.0 (file:{})\n",
+                                             {} foo v0.5.0 (file:{})\n\
+                                             {} dev [unoptimized] target(s) in [..]s\n",
                                             FRESH, bar.display(),
-                                            COMPILING, p.root().display())));
+                                            COMPILING, p.root().display(),
+                                            FINISHED)));
 })
 
 test!(error_message_for_missing_manifest {
@@ -481,6 +523,7 @@ test!(error_message_for_missing_manifest {
 
             name = "foo"
         "#)
+       .file("src/foo.rs", "")
        .file("src/bar/not-a-manifest", "");
 
     assert_that(p.cargo_process("cargo-build"),
@@ -526,3 +569,525 @@ test!(override_self {
     assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
 
 })
+
+test!(renamed_dependency {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dependencies.zoidberg]
+
+            version = "0.5.0"
+            path = "bar"
+            package = "bar"
+
+            [[bin]]
+
+            name = "foo"
+        "#)
+        .file("src/foo.rs",
+              main_file(r#""{}", zoidberg::gimme()"#, ["zoidberg"]).as_slice())
+        .file("bar/Cargo.toml", r#"
+            [project]
+
+            name = "bar"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+        "#)
+        .file("bar/src/lib.rs", r#"
+            pub fn gimme() -> &'static str {
+                "zoidberg"
+            }
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"),
+        execs().with_stdout(format!("{} bar v0.5.0 (file:{})\n\
+                                     {} foo v0.5.0 (file:{})\n\
+                                     {} dev [unoptimized] target(s) in [..]s\n",
+                                    COMPILING, p.root().display(),
+                                    COMPILING, p.root().display(),
+                                    FINISHED)));
+
+    assert_that(
+      cargo::util::process(p.bin("foo")),
+      execs().with_stdout("zoidberg\n"));
+})
+
+test!(cyclic_path_deps {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.2.0"
+            authors = []
+
+            [dependencies.foo]
+            path = ".."
+        "#)
+        .file("bar/src/lib.rs", "");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stderr("\
+cyclic package dependency: foo v0.1.0 -> bar v0.2.0 -> foo v0.1.0
+"));
+})
+
+test!(cyclic_path_deps_through_three_packages {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.2.0"
+            authors = []
+
+            [dependencies.baz]
+            path = "../baz"
+        "#)
+        .file("bar/src/lib.rs", "")
+        .file("baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.3.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "../bar"
+        "#)
+        .file("baz/src/lib.rs", "");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stderr("\
+cyclic package dependency: bar v0.2.0 -> baz v0.3.0 -> bar v0.2.0
+"));
+})
+
+test!(cyclic_path_deps_back_to_the_root {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.2.0"
+            authors = []
+
+            [dependencies.baz]
+            path = "../baz"
+        "#)
+        .file("bar/src/lib.rs", "")
+        .file("baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.3.0"
+            authors = []
+
+            [dependencies.foo]
+            path = ".."
+        "#)
+        .file("baz/src/lib.rs", "");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stderr("\
+cyclic package dependency: foo v0.1.0 -> bar v0.2.0 -> baz v0.3.0 -> foo v0.1.0
+"));
+})
+
+test!(broken_dev_dependency_only_breaks_tests {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+
+            [dev_dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/lib.rs", r#"
+            #[cfg(test)] extern crate bar;
+            pub fn foo() {}
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = ["wycats@example.com"]
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stdout(format!("\
+{} foo v0.5.0 (file:{})
+{} dev [unoptimized] target(s) in [..]s
+", COMPILING, p.root().display(), FINISHED)));
+
+    // The lockfile still records the dev-dependency
+    let lock = File::open(&p.root().join("Cargo.lock")).read_to_string()
+                    .assert();
+    assert!(lock.as_slice().contains("name = \"bar\""), "{}", lock);
+
+    assert_that(p.process(cargo_dir().join("cargo-test")),
+                execs().with_status(101));
+})
+
+test!(cyclic_dev_dependency {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            authors = []
+
+            [dev-dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.2.0"
+            authors = []
+
+            [dependencies.foo]
+            path = ".."
+        "#)
+        .file("bar/src/lib.rs", "extern crate foo;");
+
+    // Only the tests of `foo` need `bar`, which is built against the library
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0));
+    assert_that(p.process(cargo_dir().join("cargo-test")),
+                execs().with_status(0));
+})
+
+test!(same_package_at_two_locations {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "a"
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() {}")
+        .file("a/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("a/src/lib.rs", "")
+        .file("b/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("b/src/lib.rs", "");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stderr(format!("\
+Could not read `bar v0.5.0`, it is found at more than one location.
+  {dir}[..]a[..]Cargo.toml
+  {dir}[..]b[..]Cargo.toml
+", dir = p.root().display())));
+})
+
+test!(highest_version_of_duplicate_package_wins {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "a"
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() { bar::new() }")
+        .file("a/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("a/src/lib.rs", "pub fn old() {}")
+        .file("b/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.6.0"
+            authors = []
+        "#)
+        .file("b/src/lib.rs", "pub fn new() {}");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0)
+                       .with_stdout(format!("\
+{compiling} bar v0.6.0 (file:{dir})
+{compiling} foo v0.5.0 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display()))
+                       .with_stderr("\
+found more than one `bar` package: v0.[..], v0.[..]; using the highest \
+version, v0.6.0
+"));
+})
+
+test!(conflicting_version_requirements {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+            [dependencies.qux]
+            path = "qux"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.baz]
+            path = "../baz5"
+            version = "=0.5.0"
+        "#)
+        .file("bar/src/lib.rs", "")
+        .file("qux/Cargo.toml", r#"
+            [package]
+            name = "qux"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.baz]
+            path = "../baz6"
+            version = "=0.6.0"
+        "#)
+        .file("qux/src/lib.rs", "")
+        .file("baz5/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("baz5/src/lib.rs", "")
+        .file("baz6/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.6.0"
+            authors = []
+        "#)
+        .file("baz6/src/lib.rs", "");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stderr("\
+No single version of `baz` satisfies all of its requirements.
+Required by `bar v0.5.0`: [..]0.5.0 (found v0.5.0)
+Required by `qux v0.5.0`: [..]0.6.0 (found v0.6.0)
+"));
+})
+
+test!(changes_to_path_dep_files_cause_rebuilds {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/main.rs", "extern crate bar; fn main() { bar::bar() }")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {}")
+        .file("bar/src/unused.rs", "");
+    let compiling = format!("{} bar v0.5.0 (file:{})\n\
+                             {} foo v0.5.0 (file:{})\n\
+                             {} dev [unoptimized] target(s) in [..]s\n",
+                            COMPILING, p.root().join("bar").display(),
+                            COMPILING, p.root().display(), FINISHED);
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stdout(compiling.as_slice()));
+    p.root().move_into_the_past().assert();
+
+    // Editing a file of the dependency ...
+    File::create(&p.root().join("bar/src/lib.rs"))
+         .write_str("pub fn bar() { println!(\"bar\") }").assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(compiling.as_slice()));
+    assert_that(process(p.bin("foo")), execs().with_stdout("bar\n"));
+
+    // ... or removing one which isn't its newest rebuilds it
+    fs::unlink(&p.root().join("bar/src/unused.rs")).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(compiling.as_slice()));
+})
+
+test!(locked_names_the_dependency_missing_from_the_lockfile {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "")
+        .file("baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("baz/src/lib.rs", "");
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--locked"),
+                execs().with_status(0));
+
+    File::create(&p.root().join("Cargo.toml")).write_str(r#"
+        [package]
+        name = "foo"
+        version = "0.5.0"
+        authors = []
+
+        [dependencies.bar]
+        path = "bar"
+
+        [dependencies.baz]
+        path = "baz"
+    "#).assert();
+
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--locked"),
+                execs().with_status(101).with_stderr("\
+the lockfile needs to be updated but --locked was passed to prevent this; \
+`baz *` is not satisfied by Cargo.lock
+"));
+})
+
+fn workspace() -> ProjectBuilder {
+    let member = |name: &str| format!(r#"
+        [package]
+        name = "{}"
+        version = "0.5.0"
+        authors = []
+
+        [dependencies.common]
+        path = "../common"
+    "#, name);
+    project("ws")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "ws"
+            version = "0.5.0"
+            authors = []
+
+            [workspace]
+            members = ["a", "b"]
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("a/Cargo.toml", member("a"))
+        .file("a/src/main.rs", "extern crate common; fn main() { common::common() }")
+        .file("b/Cargo.toml", member("b"))
+        .file("b/src/main.rs", "extern crate common; fn main() { common::common() }")
+        .file("common/Cargo.toml", r#"
+            [package]
+            name = "common"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("common/src/lib.rs", "pub fn common() {}")
+}
+
+test!(workspace_members_are_built_and_locked_together {
+    let p = workspace();
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(&p.bin("ws"), existing_file());
+    assert_that(&p.bin("a"), existing_file());
+    assert_that(&p.bin("b"), existing_file());
+
+    // A single lockfile at the root covers every member
+    assert_that(&p.root().join("a/Cargo.lock"), is_not(existing_file()));
+    assert_that(&p.root().join("b/Cargo.lock"), is_not(existing_file()));
+    let lock = File::open(&p.root().join("Cargo.lock")).read_to_string().assert();
+    for name in ["a", "b", "common"].iter() {
+        let entry = format!("name = \"{}\"", name);
+        assert_eq!(lock.as_slice().match_indices(entry.as_slice()).count(), 1);
+    }
+})
+
+test!(workspace_member_selected_with_p {
+    let p = workspace();
+
+    assert_that(p.cargo_process("cargo-build").arg("-p").arg("a"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} common v0.5.0 (file:{dir})
+{compiling} a v0.5.0 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+    assert_that(&p.bin("a"), existing_file());
+    assert_that(&p.bin("b"), is_not(existing_file()));
+    assert_that(&p.bin("ws"), is_not(existing_file()));
+
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("-p").arg("c"),
+                execs().with_status(101).with_stderr("\
+package `c` is not a member of the workspace
+"));
+})
+
@@ -1,14 +1,15 @@
 // Currently the only cross compilers available via nightlies are on linux/osx,
 // so we can only run these tests on those platforms
-#![cfg(target_os = "linux")]
-#![cfg(target_os = "macos")]
+#![cfg(any(target_os = "linux", target_os = "macos"))]
 
+use std::io::fs;
 use std::os;
 use std::path;
 
-use support::{project, execs, basic_bin_manifest};
-use support::{RUNNING, COMPILING, cargo_dir};
-use hamcrest::{assert_that, existing_file};
+use support::{project, execs, basic_bin_manifest, paths, ResultTest};
+use support::{RUNNING, COMPILING, FINISHED, BUILDING, cargo_dir};
+use hamcrest::{assert_that, existing_file, is_not};
+use cargo::ops;
 use cargo::util::process;
 
 fn setup() {
@@ -22,6 +23,13 @@ fn alternate() -> &'static str {
     }
 }
 
+/// Whether `dir` holds a file whose name starts with `prefix`.
+fn has_file_starting_with(dir: &Path, prefix: &str) -> bool {
+    fs::readdir(dir).assert().iter().any(|f| {
+        f.filename_str().unwrap().starts_with(prefix)
+    })
+}
+
 test!(simple_cross {
     let p = project("foo")
         .file("Cargo.toml", basic_bin_manifest("foo").as_slice())
@@ -148,6 +156,16 @@ test!(plugin_deps {
                 execs().with_status(0));
     assert_that(&foo.target_bin(target, "foo"), existing_file());
 
+    // The plugin is loaded by the compiler, so it's built for the host while
+    // everything else is built for the target.
+    let host_deps = foo.build_dir().join("deps");
+    let target_deps = foo.build_dir().join(target).join("deps");
+    let plugin = format!("{}bar-", os::consts::DLL_PREFIX);
+    assert!(has_file_starting_with(&host_deps, plugin.as_slice()));
+    assert!(!has_file_starting_with(&target_deps, plugin.as_slice()));
+    assert!(has_file_starting_with(&target_deps, "libbaz-"));
+    assert!(!has_file_starting_with(&host_deps, "libbaz-"));
+
     assert_that(
       process(foo.target_bin(target, "foo")),
       execs().with_status(0));
@@ -264,9 +282,11 @@ test!(linker_and_ar {
     -L {dir}{sep}target{sep}{target} \
     -L {dir}{sep}target{sep}{target}{sep}deps`
 {compiling} foo v0.5.0 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
 ",
                             running = RUNNING,
                             compiling = COMPILING,
+                            finished = FINISHED,
                             dir = p.root().display(),
                             target = target,
                             sep = path::SEP,
@@ -336,3 +356,271 @@ test!(plugin_with_extra_dylib_dep {
     assert_that(foo.cargo_process("cargo-build").arg("--target").arg(target),
                 execs().with_status(0));
 })
+
+test!(manifest_default_target {
+    let target = alternate();
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+            default-target = "{}"
+
+            [[bin]]
+            name = "foo"
+        "#, target))
+        .file("src/foo.rs", r#"
+            use std::os;
+            fn main() {
+                assert_eq!(os::consts::ARCH, "x86");
+            }
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0));
+    assert_that(&p.target_bin(target, "foo"), existing_file());
+    assert_that(&p.bin("foo"), is_not(existing_file()));
+
+    assert_that(
+      process(p.target_bin(target, "foo")),
+      execs().with_status(0));
+
+    // An explicit `--target` takes precedence
+    let host = ops::host_triple("rustc", &paths::root()).unwrap();
+    fs::rmdir_recursive(&p.root().join("target")).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--target").arg(host.as_slice()),
+                execs().with_status(0));
+    assert_that(&p.target_bin(host.as_slice(), "foo"), existing_file());
+    assert_that(&p.target_bin(target, "foo"), is_not(existing_file()));
+})
+
+test!(platform_specific_dependencies {
+    let target = alternate();
+    let p = project("foo")
+        .file("Cargo.toml", format!(r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [target.{}.dependencies.bar]
+            path = "bar"
+
+            [target.x86_64-pc-windows-gnu.dependencies.baz]
+            path = "baz"
+        "#, target))
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { bar::bar(); }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {}")
+        .file("baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("baz/src/lib.rs", "this does not compile");
+
+    assert_that(p.cargo_process("cargo-build").arg("--target").arg(target),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} bar v0.0.1 (file:{dir})
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+",
+        compiling = COMPILING, finished = FINISHED,
+        dir = p.root().display())));
+    assert_that(&p.target_bin(target, "foo"), existing_file());
+})
+
+test!(explicit_host_target_for_tests_and_examples {
+    let host = ops::host_triple("rustc", &paths::root()).unwrap();
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", r#"
+            pub fn foo() -> int { 1 }
+
+            #[test] fn unit() { assert_eq!(foo(), 1) }
+        "#)
+        .file("tests/bar.rs", r#"
+            extern crate foo;
+            #[test] fn integration() { assert_eq!(foo::foo(), 1) }
+        "#)
+        .file("examples/ex.rs", r#"
+            extern crate foo;
+            fn main() { foo::foo(); }
+        "#);
+
+    assert_that(p.cargo_process("cargo-test").arg("--target").arg(host.as_slice()),
+                execs().with_status(0));
+
+    let ex = format!("ex{}", os::consts::EXE_SUFFIX);
+    assert_that(&p.build_dir().join(host.as_slice()).join("test").join(ex.as_slice()),
+                existing_file());
+    assert_that(&p.build_dir().join("test").join(ex.as_slice()),
+                is_not(existing_file()));
+})
+
+test!(cross_lib {
+    let target = alternate();
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [lib]
+            name = "foo"
+            crate-type = ["rlib", "dylib"]
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/lib.rs", r#"
+            extern crate bar;
+            pub fn foo() { bar::bar() }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {}");
+
+    assert_that(p.cargo_process("cargo-build").arg("--target").arg(target),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} bar v0.0.1 (file:{dir})
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+
+    let out = p.build_dir().join(target);
+    assert_that(&out.join("libfoo.rlib"), existing_file());
+    assert_that(&out.join(format!("{}foo{}", os::consts::DLL_PREFIX,
+                                  os::consts::DLL_SUFFIX)),
+                existing_file());
+    assert!(has_file_starting_with(&out, "libfoo-"));
+    assert!(has_file_starting_with(&out.join("deps"), "libbar-"));
+    assert_that(&p.build_dir().join("libfoo.rlib"), is_not(existing_file()));
+    assert!(!p.build_dir().join("deps").exists() ||
+            !has_file_starting_with(&p.build_dir().join("deps"), "libbar-"));
+})
+
+test!(diamond_with_plugin_builds_common_once_per_platform {
+    let host = ops::host_triple("rustc", &paths::root()).unwrap();
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+            [dependencies.baz]
+            path = "baz"
+        "#)
+        .file("src/main.rs", r#"
+            #![feature(phase)]
+            #[phase(plugin)] extern crate bar;
+            extern crate baz;
+            fn main() { baz::baz() }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [[lib]]
+            name = "bar"
+            plugin = true
+
+            [dependencies.common]
+            path = "../common"
+        "#)
+        .file("bar/src/lib.rs", "extern crate common;")
+        .file("baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.common]
+            path = "../common"
+        "#)
+        .file("baz/src/lib.rs", r#"
+            extern crate common;
+            pub fn baz() { common::common() }
+        "#)
+        .file("common/Cargo.toml", r#"
+            [package]
+            name = "common"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("common/src/lib.rs", "pub fn common() {}");
+
+    let rustc_lines = |output: Vec<u8>| {
+        let output = String::from_utf8(output).assert();
+        output.as_slice().lines().filter(|line| {
+            line.contains(RUNNING) && line.contains("--crate-name common ")
+        }).count()
+    };
+
+    // Without a target everything shares one platform, so `common` is built
+    // once even though a plugin needs it as well.
+    let output = p.cargo_process("cargo-build").arg("-v")
+                  .exec_with_output().assert();
+    assert_eq!(rustc_lines(output.output), 1);
+
+    // With an explicit target it is built once for the host, for the plugin,
+    // and once for the target.
+    fs::rmdir_recursive(&p.root().join("target")).assert();
+    let output = p.process(cargo_dir().join("cargo-build")).arg("-v")
+                  .arg("--target").arg(host.as_slice())
+                  .exec_with_output().assert();
+    assert_eq!(rustc_lines(output.output), 2);
+})
+
+test!(multiple_targets {
+    let host = ops::host_triple("rustc", &paths::root()).unwrap();
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build")
+                 .arg("--target").arg(alternate())
+                 .arg("--target").arg(host.as_slice()),
+                execs().with_status(0).with_stdout(format!("\
+{building} for target `{alternate}`
+{compiling} foo v0.0.1 (file:{dir})
+{building} for target `{host}`
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", building = BUILDING, compiling = COMPILING, finished = FINISHED,
+   alternate = alternate(), host = host, dir = p.root().display())));
+
+    assert_that(&p.target_bin(alternate(), "foo"), existing_file());
+    assert_that(&p.target_bin(host.as_slice(), "foo"), existing_file());
+})
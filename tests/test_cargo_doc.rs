@@ -1,5 +1,8 @@
-use support::{project, execs, cargo_dir};
-use support::{COMPILING, FRESH};
+use std::io::fs;
+use std::str;
+
+use support::{project, execs, cargo_dir, ResultTest};
+use support::{COMPILING, DOCUMENTING, FRESH, OPENING, FINISHED};
 use hamcrest::{assert_that, existing_file, existing_dir, is_not};
 
 fn setup() {
@@ -20,8 +23,10 @@ test!(simple {
     assert_that(p.cargo_process("cargo-doc"),
                 execs().with_status(0).with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
+{documenting} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
 ",
-        compiling = COMPILING,
+        compiling = COMPILING, documenting = DOCUMENTING, finished = FINISHED,
         dir = p.root().display()).as_slice()));
     assert_that(&p.root().join("target/doc"), existing_dir());
     assert_that(&p.root().join("target/doc/foo/index.html"), existing_file());
@@ -45,8 +50,10 @@ test!(no_build_main {
     assert_that(p.cargo_process("cargo-doc"),
                 execs().with_status(0).with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
+{documenting} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
 ",
-        compiling = COMPILING,
+        compiling = COMPILING, documenting = DOCUMENTING, finished = FINISHED,
         dir = p.root().display()).as_slice()));
 })
 
@@ -81,15 +88,18 @@ test!(doc_twice {
     assert_that(p.cargo_process("cargo-doc"),
                 execs().with_status(0).with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
+{documenting} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
 ",
-        compiling = COMPILING,
+        compiling = COMPILING, documenting = DOCUMENTING, finished = FINISHED,
         dir = p.root().display()).as_slice()));
 
     assert_that(p.process(cargo_dir().join("cargo-doc")),
                 execs().with_status(0).with_stdout(format!("\
 {fresh} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
 ",
-        fresh = FRESH,
+        fresh = FRESH, finished = FINISHED,
         dir = p.root().display()).as_slice()));
 })
 
@@ -118,25 +128,29 @@ test!(doc_deps {
             pub fn bar() {}
         "#);
 
-    assert_that(p.cargo_process("cargo-doc"),
+    assert_that(p.cargo_process("cargo-doc").arg("--deps"),
                 execs().with_status(0).with_stdout(format!("\
 {compiling} bar v0.0.1 (file:{dir})
+{documenting} bar v0.0.1 (file:{dir})
 {compiling} foo v0.0.1 (file:{dir})
+{documenting} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
 ",
-        compiling = COMPILING,
+        compiling = COMPILING, documenting = DOCUMENTING, finished = FINISHED,
         dir = p.root().display()).as_slice()));
 
     assert_that(&p.root().join("target/doc"), existing_dir());
     assert_that(&p.root().join("target/doc/foo/index.html"), existing_file());
     assert_that(&p.root().join("target/doc/bar/index.html"), existing_file());
 
-    assert_that(p.process(cargo_dir().join("cargo-doc"))
+    assert_that(p.process(cargo_dir().join("cargo-doc")).arg("--deps")
                  .env("RUST_LOG", Some("cargo::ops::cargo_rustc::fingerprint")),
                 execs().with_status(0).with_stdout(format!("\
 {fresh} bar v0.0.1 (file:{dir})
 {fresh} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
 ",
-        fresh = FRESH,
+        fresh = FRESH, finished = FINISHED,
         dir = p.root().display()).as_slice()));
 
     assert_that(&p.root().join("target/doc"), existing_dir());
@@ -169,17 +183,94 @@ test!(doc_no_deps {
             pub fn bar() {}
         "#);
 
-    assert_that(p.cargo_process("cargo-doc").arg("--no-deps"),
+    // Only the local package is documented unless asked otherwise
+    assert_that(p.cargo_process("cargo-doc"),
                 execs().with_status(0).with_stdout(format!("\
 {compiling} bar v0.0.1 (file:{dir})
 {compiling} foo v0.0.1 (file:{dir})
+{documenting} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
 ",
-        compiling = COMPILING,
+        compiling = COMPILING, documenting = DOCUMENTING, finished = FINISHED,
         dir = p.root().display()).as_slice()));
 
     assert_that(&p.root().join("target/doc"), existing_dir());
     assert_that(&p.root().join("target/doc/foo/index.html"), existing_file());
     assert_that(&p.root().join("target/doc/bar/index.html"), is_not(existing_file()));
+
+    // The dependency is still compiled so that rustdoc can find it
+    let deps = fs::readdir(&p.root().join("target/deps")).assert();
+    assert!(deps.iter().any(|f| {
+        f.filename_str().unwrap().starts_with("libbar-")
+    }), "bar was not compiled");
+})
+
+test!(doc_diamond_documents_each_crate_once {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+            [dependencies.baz]
+            path = "baz"
+        "#)
+        .file("src/lib.rs", r#"
+            extern crate bar;
+            extern crate baz;
+            pub fn foo() {}
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.common]
+            path = "../common"
+        "#)
+        .file("bar/src/lib.rs", r#"
+            extern crate common;
+            pub fn bar() {}
+        "#)
+        .file("baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.common]
+            path = "../common"
+        "#)
+        .file("baz/src/lib.rs", r#"
+            extern crate common;
+            pub fn baz() {}
+        "#)
+        .file("common/Cargo.toml", r#"
+            [package]
+            name = "common"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("common/src/lib.rs", r#"
+            pub fn common() {}
+        "#);
+
+    let output = p.cargo_process("cargo-doc").arg("--deps").exec_with_output().assert();
+    let stdout = str::from_utf8(output.output.as_slice()).unwrap();
+    let documented: Vec<&str> = stdout.lines().filter(|l| {
+        l.starts_with(DOCUMENTING)
+    }).collect();
+    assert_eq!(documented.len(), 4);
+    assert_eq!(documented.iter().filter(|l| l.contains("common")).count(), 1);
+
+    for name in ["foo", "bar", "baz", "common"].iter() {
+        assert_that(&p.root().join(format!("target/doc/{}/index.html", name)),
+                    existing_file());
+    }
 })
 
 test!(doc_only_bin {
@@ -207,9 +298,55 @@ test!(doc_only_bin {
             pub fn bar() {}
         "#);
 
-    assert_that(p.cargo_process("cargo-doc"),
+    assert_that(p.cargo_process("cargo-doc").arg("--deps"),
                 execs().with_status(0));
 
     assert_that(&p.root().join("target/doc"), existing_dir());
     assert_that(&p.root().join("target/doc/bar/index.html"), existing_file());
 })
+
+test!(doc_open {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", r#"
+            pub fn foo() {}
+        "#);
+
+    let index = p.root().join("target/doc/foo/index.html");
+    assert_that(p.cargo_process("cargo-doc").arg("--open")
+                 .env("BROWSER", Some("echo")),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.1 (file:{dir})
+{documenting} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+{opening} {index}
+{index}
+",
+        compiling = COMPILING, documenting = DOCUMENTING, finished = FINISHED,
+        opening = OPENING,
+        dir = p.root().display(), index = index.display()).as_slice()));
+})
+
+test!(doc_open_without_lib {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", r#"
+            fn main() {}
+        "#);
+
+    assert_that(p.cargo_process("cargo-doc").arg("--open")
+                 .env("BROWSER", Some("echo")),
+                execs().with_status(0).with_stderr("\
+no documentation to open, `foo` has no library target
+"));
+})
@@ -0,0 +1,419 @@
+use std::io::File;
+use std::str;
+
+use support::{project, execs, cargo_dir, ResultTest};
+use support::{COMPILING, FINISHED};
+use hamcrest::{assert_that, existing_file};
+use cargo::util::process;
+
+fn setup() {
+}
+
+test!(optional_dep_is_not_built_without_feature {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+            optional = true
+
+            [features]
+            fast = ["bar"]
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "this does not compile");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+})
+
+test!(feature_enables_optional_dep {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+            optional = true
+
+            [features]
+            fast = ["bar"]
+        "#)
+        .file("src/main.rs", r#"
+            #[cfg(feature = "fast")] extern crate bar;
+
+            #[cfg(feature = "fast")]
+            fn main() { println!("{}", bar::bar()) }
+            #[cfg(not(feature = "fast"))]
+            fn main() { println!("slow") }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", r#"pub fn bar() -> &'static str { "fast" }"#);
+
+    assert_that(p.cargo_process("cargo-build").arg("--features").arg("fast"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} bar v0.0.1 (file:{dir})
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+    assert_that(&p.bin("foo"), existing_file());
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("fast\n"));
+
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("slow\n"));
+})
+
+test!(optional_dep_name_is_a_feature {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+            optional = true
+        "#)
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { bar::bar() }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "pub fn bar() {}");
+
+    assert_that(p.cargo_process("cargo-build").arg("--features").arg("bar"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} bar v0.0.1 (file:{dir})
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+})
+
+test!(dependency_enables_features_of_dep {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+            features = ["baz"]
+        "#)
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { bar::bar() }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.baz]
+            path = "baz"
+            optional = true
+        "#)
+        .file("bar/src/lib.rs", r#"
+            #[cfg(feature = "baz")] extern crate baz;
+            #[cfg(feature = "baz")] pub fn bar() { baz::baz() }
+        "#)
+        .file("bar/baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/baz/src/lib.rs", "pub fn baz() {}");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} baz v0.0.1 (file:{dir})
+{compiling} bar v0.0.1 (file:{dir})
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+})
+
+test!(non_optional_dep_as_feature {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+
+            [features]
+            fast = ["bar"]
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stderr("\
+Cargo.toml is not a valid manifest
+
+feature `fast` includes `bar`, but `bar` is not an optional dependency
+"));
+})
+
+test!(unknown_feature {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build").arg("--features").arg("fast"),
+                execs().with_status(101).with_stderr("\
+package `foo v0.0.1` does not have the feature `fast`
+"));
+})
+
+test!(default_feature_unless_suppressed {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+            optional = true
+
+            [features]
+            default = ["bar"]
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "this does not compile");
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(101));
+
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--no-default-features"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+})
+
+test!(default_feature_is_passed_to_rustc {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [features]
+            default = []
+        "#)
+        .file("src/main.rs", r#"
+            #[cfg(feature = "default")]
+            fn main() { println!("default") }
+            #[cfg(not(feature = "default"))]
+            fn main() { println!("none") }
+        "#);
+
+    let output = p.cargo_process("cargo-build").arg("-v")
+                  .exec_with_output().assert();
+    let stdout = str::from_utf8(output.output.as_slice()).unwrap();
+    assert!(stdout.contains("--cfg feature=\"default\""), "{}", stdout);
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("default\n"));
+
+    // Toggling the feature rebuilds the crate
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .arg("--no-default-features"),
+                execs().with_status(0));
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("none\n"));
+})
+
+test!(default_features_of_dependencies {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/main.rs", r#"
+            extern crate bar;
+            fn main() { println!("{}", bar::bar()) }
+        "#)
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+
+            [features]
+            default = []
+        "#)
+        .file("bar/src/lib.rs", r#"
+            #[cfg(feature = "default")]
+            pub fn bar() -> &'static str { "default" }
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    assert_that(process(p.bin("foo")),
+                execs().with_status(0).with_stdout("default\n"));
+})
+
+test!(lockfile_is_the_same_whichever_features_are_built {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+            optional = true
+
+            [features]
+            fast = ["bar"]
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "");
+    let lockfile = p.root().join("Cargo.lock");
+
+    assert_that(p.cargo_process("cargo-build").arg("--features").arg("fast"),
+                execs().with_status(0));
+    let built = File::open(&lockfile).read_to_string().assert();
+    assert!(built.as_slice().contains("bar 0.0.1"), "{}", built);
+
+    assert_that(p.process(cargo_dir().join("cargo-generate-lockfile")),
+                execs().with_status(0));
+    let generated = File::open(&lockfile).read_to_string().assert();
+    assert_eq!(built, generated);
+})
+
+test!(disjoint_features_of_shared_dep_are_pointed_out {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.a]
+            path = "a"
+            [dependencies.b]
+            path = "b"
+        "#)
+        .file("src/main.rs", r#"
+            extern crate a;
+            extern crate b;
+            fn main() { a::a(); b::b(); }
+        "#)
+        .file("a/Cargo.toml", r#"
+            [package]
+            name = "a"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.common]
+            path = "../common"
+            features = ["x"]
+        "#)
+        .file("a/src/lib.rs", r#"
+            extern crate common;
+            pub fn a() { common::x() }
+        "#)
+        .file("b/Cargo.toml", r#"
+            [package]
+            name = "b"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.common]
+            path = "../common"
+            features = ["y"]
+        "#)
+        .file("b/src/lib.rs", r#"
+            extern crate common;
+            pub fn b() { common::y() }
+        "#)
+        .file("common/Cargo.toml", r#"
+            [package]
+            name = "common"
+            version = "0.0.1"
+            authors = []
+
+            [features]
+            x = []
+            y = []
+        "#)
+        .file("common/src/lib.rs", r#"
+            #[cfg(feature = "x")] pub fn x() {}
+            #[cfg(feature = "y")] pub fn y() {}
+        "#);
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stderr("\
+`a v0.0.1` (x) and `b v0.0.1` (y) ask for disjoint features of \
+`common v0.0.1`, which is built once with all of them enabled
+"));
+})
@@ -1,7 +1,8 @@
-use std::io::{fs, File};
+use std::io::{fs, File, UserRWX};
+use std::str;
 
 use support::{project, execs};
-use support::{COMPILING, cargo_dir, ResultTest, FRESH};
+use support::{COMPILING, cargo_dir, ResultTest, FRESH, FINISHED};
 use support::paths::PathExt;
 use hamcrest::{assert_that, existing_file};
 
@@ -23,19 +24,22 @@ test!(modifying_and_moving {
     assert_that(p.cargo_process("cargo-build"),
                 execs().with_status(0).with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
-", compiling = COMPILING, dir = p.root().display())));
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
 
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_status(0).with_stdout(format!("\
 {fresh} foo v0.0.1 (file:{dir})
-", fresh = FRESH, dir = p.root().display())));
+{finished} dev [unoptimized] target(s) in [..]s
+", fresh = FRESH, finished = FINISHED, dir = p.root().display())));
     p.root().move_into_the_past().assert();
 
     File::create(&p.root().join("src/a.rs")).write_str("fn main() {}").assert();
     assert_that(p.process(cargo_dir().join("cargo-build")),
                 execs().with_status(0).with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
-", compiling = COMPILING, dir = p.root().display())));
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
 
     fs::rename(&p.root().join("src/a.rs"), &p.root().join("src/b.rs")).assert();
     assert_that(p.process(cargo_dir().join("cargo-build")),
@@ -62,7 +66,8 @@ test!(modify_only_some_files {
     assert_that(p.cargo_process("cargo-build"),
                 execs().with_status(0).with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
-", compiling = COMPILING, dir = p.root().display())));
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
     assert_that(p.process(cargo_dir().join("cargo-test")),
                 execs().with_status(0));
 
@@ -83,7 +88,8 @@ test!(modify_only_some_files {
                  .env("RUST_LOG", Some("cargo::ops::cargo_rustc::fingerprint")),
                 execs().with_status(0).with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
-", compiling = COMPILING, dir = p.root().display())));
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
     assert_that(&p.bin("foo"), existing_file());
 
     // Make sure the tests don't recompile the lib
@@ -91,3 +97,340 @@ test!(modify_only_some_files {
     assert_that(p.process(cargo_dir().join("cargo-test")),
                 execs().with_status(0));
 })
+
+test!(content_freshness_ignores_touched_files {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file(".cargo/config", r#"
+            [build]
+            freshness = "content"
+        "#)
+        .file("src/main.rs", r#"
+            mod a; fn main() {}
+        "#)
+        .file("src/a.rs", "");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+    p.root().move_into_the_past().assert();
+
+    // Rewriting a file with the same contents only bumps its mtime
+    File::create(&p.root().join("src/a.rs")).write_str("").assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(format!("\
+{fresh} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", fresh = FRESH, finished = FINISHED, dir = p.root().display())));
+
+    File::create(&p.root().join("src/a.rs")).write_str("fn foo() {}").assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+})
+
+test!(invalid_freshness_config {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file(".cargo/config", r#"
+            [build]
+            freshness = "magic"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(101).with_stderr("\
+`build.freshness` must be either `mtime` or `content`, found `magic`
+"));
+})
+
+test!(toggling_release_stays_fresh {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    let output = |status: &str, profile: &str| {
+        format!("{} foo v0.0.1 (file:{})\n{} {} target(s) in [..]s\n",
+                status, p.root().display(), FINISHED, profile)
+    };
+    let dev = "dev [unoptimized]";
+    let release = "release [optimized]";
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stdout(output(COMPILING, dev)));
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--release"),
+                execs().with_status(0).with_stdout(output(COMPILING, release)));
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(output(FRESH, dev)));
+    assert_that(p.process(cargo_dir().join("cargo-build")).arg("--release"),
+                execs().with_status(0).with_stdout(output(FRESH, release)));
+})
+
+test!(changing_rustc_flags_rebuilds {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    let compiling = format!("{} foo v0.0.1 (file:{})\n\
+                             {} dev [unoptimized] target(s) in [..]s\n",
+                            COMPILING, p.root().display(), FINISHED);
+    let fresh = format!("{} foo v0.0.1 (file:{})\n\
+                         {} dev [unoptimized] target(s) in [..]s\n",
+                        FRESH, p.root().display(), FINISHED);
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stdout(compiling.as_slice()));
+
+    // Configuring a linker adds `-C linker=cc` to the rustc invocation
+    fs::mkdir(&p.root().join(".cargo"), UserRWX).assert();
+    File::create(&p.root().join(".cargo/config")).write_str(r#"
+        [target]
+        linker = "cc"
+    "#).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(compiling.as_slice()));
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(fresh.as_slice()));
+
+    fs::unlink(&p.root().join(".cargo/config")).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(compiling.as_slice()));
+})
+
+test!(corrupt_fingerprint_is_noted {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+
+    let dirs = fs::readdir(&p.root().join("target/.fingerprint")).assert();
+    assert_eq!(dirs.len(), 1);
+    File::create(&dirs[0].join("bin-foo")).write_str("garbage!").assert();
+    File::create(&dirs[0].join("dep-bin-foo")).write_str("garbage").assert();
+
+    let output = p.process(cargo_dir().join("cargo-build")).arg("-v")
+                  .exec_with_output().assert();
+    let out = str::from_utf8(output.output.as_slice()).assert();
+    let err = str::from_utf8(output.error.as_slice()).assert();
+    assert!(out.contains(COMPILING), "not rebuilt\n{}", out);
+    assert!(err.contains("fingerprint `") && err.contains("bin-foo` is corrupt"),
+            "fingerprint not noted\n{}", err);
+    assert!(err.contains("dep-info `") &&
+            err.contains("dep-bin-foo` is corrupt (not in an understood format)"),
+            "dep-info not noted\n{}", err);
+
+    // The rebuild repairs the cache
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(format!("\
+{fresh} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", fresh = FRESH, finished = FINISHED, dir = p.root().display())));
+})
+
+#[cfg(not(windows))]
+test!(changing_rustc_rebuilds {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("my-rustc", "#!/bin/sh\nexec rustc \"$@\"\n");
+    p.build();
+    fs::chmod(&p.root().join("my-rustc"), UserRWX).assert();
+
+    let compiling = format!("{} foo v0.0.1 (file:{})\n\
+                             {} dev [unoptimized] target(s) in [..]s\n",
+                            COMPILING, p.root().display(), FINISHED);
+    let fresh = format!("{} foo v0.0.1 (file:{})\n\
+                         {} dev [unoptimized] target(s) in [..]s\n",
+                        FRESH, p.root().display(), FINISHED);
+
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(compiling.as_slice()));
+
+    // Switching to another compiler through the environment rebuilds ...
+    let rustc = p.root().join("my-rustc");
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .env("RUSTC", Some(rustc.clone())),
+                execs().with_status(0).with_stdout(compiling.as_slice()));
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .env("RUSTC", Some(rustc.clone())),
+                execs().with_status(0).with_stdout(fresh.as_slice()));
+
+    // ... as does switching through the configuration
+    fs::mkdir(&p.root().join(".cargo"), UserRWX).assert();
+    File::create(&p.root().join(".cargo/config")).write_str(r#"
+        [build]
+        rustc = "rustc"
+    "#).assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(compiling.as_slice()));
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(fresh.as_slice()));
+})
+
+test!(rustc_probes_are_cached_across_builds {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("my-rustc", "#!/bin/sh\n\
+                           echo \"$@\" >> \"$(dirname $0)/rustc.log\"\n\
+                           exec rustc \"$@\"\n");
+    p.build();
+    fs::chmod(&p.root().join("my-rustc"), UserRWX).assert();
+    let rustc = p.root().join("my-rustc");
+    let log = p.root().join("rustc.log");
+
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .env("RUSTC", Some(rustc.clone())),
+                execs().with_status(0));
+    assert_that(&p.root().join("target/.rustc-info"), existing_file());
+    let invocations = File::open(&log).read_to_string().assert();
+
+    // A fresh build doesn't need to ask the compiler anything
+    assert_that(p.process(cargo_dir().join("cargo-build"))
+                 .env("RUSTC", Some(rustc.clone())),
+                execs().with_status(0));
+    assert_eq!(File::open(&log).read_to_string().assert(), invocations);
+})
+
+test!(include_outside_of_package_root_is_fresh {
+    let p = project("ws")
+        .file("foo/Cargo.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file("foo/src/main.rs", r#"
+            fn main() { println!("{}", include!("../shared/data.rs")) }
+        "#)
+        .file("shared/data.rs", "1i");
+    p.build();
+    let root = p.root().join("foo");
+
+    assert_that(p.process(cargo_dir().join("cargo-build")).cwd(root.clone()),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = root.display())));
+
+    assert_that(p.process(cargo_dir().join("cargo-build")).cwd(root.clone()),
+                execs().with_status(0).with_stdout(format!("\
+{fresh} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", fresh = FRESH, finished = FINISHED, dir = root.display())));
+
+    p.root().move_into_the_past().assert();
+    File::create(&p.root().join("shared/data.rs")).write_str("2i").assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")).cwd(root.clone()),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = root.display())));
+})
+
+test!(file_name_with_spaces_is_fresh {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file("src/main.rs", r#"
+            #[path = "my mod.rs"] mod a;
+            fn main() {}
+        "#)
+        .file("src/my mod.rs", "");
+
+    assert_that(p.cargo_process("cargo-build"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(format!("\
+{fresh} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", fresh = FRESH, finished = FINISHED, dir = p.root().display())));
+})
+
+test!(rebuilds_leave_unchanged_fingerprints_alone {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            authors = []
+            version = "0.0.1"
+        "#)
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}");
+    // The dep-info files are rewritten by every compilation, unlike the
+    // fingerprints themselves.
+    let mtimes = || {
+        let dir = p.root().join("target/.fingerprint");
+        let mut files = fs::walk_dir(&dir).assert().filter(|f| {
+            f.is_file() && !f.filename_str().unwrap().starts_with("dep-")
+        }).map(|f| {
+            (f.display().to_string(), fs::stat(&f).assert().modified)
+        }).collect::<Vec<(String, u64)>>();
+        files.sort();
+        files
+    };
+
+    assert_that(p.cargo_process("cargo-build"), execs().with_status(0));
+    p.root().move_into_the_past().assert();
+    let before = mtimes();
+    assert!(before.len() > 0);
+
+    // Touching a file without changing it rebuilds the package, but the
+    // fingerprints come out the same
+    File::create(&p.root().join("src/main.rs")).write_str("fn main() {}")
+        .assert();
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display())));
+    assert_eq!(mtimes(), before);
+})
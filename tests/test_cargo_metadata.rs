@@ -0,0 +1,114 @@
+use std::str;
+use serialize::json;
+
+use support::{ProjectBuilder, ResultTest, project};
+use hamcrest::{assert_that, equal_to};
+
+fn setup() {
+}
+
+fn metadata(p: &ProjectBuilder, args: &[&str]) -> json::Json {
+    let output = p.cargo_process("cargo-metadata").args(args)
+                  .exec_with_output().assert();
+    let stdout = str::from_utf8(output.output.as_slice()).unwrap();
+    json::from_str(stdout).assert()
+}
+
+fn field<'a>(json: &'a json::Json, key: &str) -> &'a json::Json {
+    json.find(&key.to_string()).expect(format!("missing `{}`", key).as_slice())
+}
+
+fn string<'a>(json: &'a json::Json, key: &str) -> &'a str {
+    field(json, key).as_string().unwrap()
+}
+
+fn strings(json: &json::Json, key: &str) -> Vec<String> {
+    field(json, key).as_list().unwrap().iter().map(|s| {
+        s.as_string().unwrap().to_string()
+    }).collect()
+}
+
+fn nested_path_deps() -> ProjectBuilder {
+    project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.5.0"
+            authors = []
+
+            [dependencies.baz]
+            path = "baz"
+        "#)
+        .file("bar/src/lib.rs", "")
+        .file("bar/baz/Cargo.toml", r#"
+            [package]
+            name = "baz"
+            version = "0.5.0"
+            authors = []
+        "#)
+        .file("bar/baz/src/lib.rs", "")
+}
+
+test!(nested_path_deps_graph {
+    let p = nested_path_deps();
+    let json = metadata(&p, []);
+
+    let id = |dir: &Path, name: &str| {
+        format!("{} 0.5.0 (path+file:{})", name, dir.display())
+    };
+    let foo = id(&p.root(), "foo");
+    let bar = id(&p.root().join("bar"), "bar");
+    let baz = id(&p.root().join("bar/baz"), "baz");
+
+    assert_that(string(&json, "root"), equal_to(foo.as_slice()));
+
+    let packages = field(&json, "packages").as_list().unwrap();
+    let names: Vec<&str> = packages.iter().map(|p| string(p, "name")).collect();
+    assert_that(names, equal_to(vec!["bar", "baz", "foo"]));
+
+    let baz_pkg = &packages[1];
+    assert_that(string(baz_pkg, "id"), equal_to(baz.as_slice()));
+    assert_that(string(baz_pkg, "version"), equal_to("0.5.0"));
+    assert_that(string(baz_pkg, "manifest_path"),
+                equal_to(p.root().join("bar/baz/Cargo.toml").display()
+                          .to_string().as_slice()));
+    let targets = field(baz_pkg, "targets").as_list().unwrap();
+    assert_that(targets.len(), equal_to(1u));
+    assert_that(string(&targets[0], "name"), equal_to("baz"));
+    assert_that(strings(&targets[0], "kind"), equal_to(vec!["lib".to_string()]));
+    assert_that(string(&targets[0], "src_path"),
+                equal_to(p.root().join("bar/baz/src/lib.rs").display()
+                          .to_string().as_slice()));
+
+    let nodes = field(&json, "resolve").as_list().unwrap();
+    let edges: Vec<(String, Vec<String>)> = nodes.iter().map(|n| {
+        (string(n, "id").to_string(), strings(n, "dependencies"))
+    }).collect();
+    assert_that(edges, equal_to(vec![
+        (bar.clone(), vec![baz.clone()]),
+        (baz.clone(), vec![]),
+        (foo.clone(), vec![bar.clone()]),
+    ]));
+})
+
+test!(no_deps_describes_only_the_root {
+    let p = nested_path_deps();
+    let json = metadata(&p, ["--no-deps"]);
+
+    let packages = field(&json, "packages").as_list().unwrap();
+    assert_that(packages.len(), equal_to(1u));
+    assert_that(string(&packages[0], "name"), equal_to("foo"));
+    let targets = field(&packages[0], "targets").as_list().unwrap();
+    assert_that(strings(&targets[0], "kind"), equal_to(vec!["bin".to_string()]));
+    assert!(field(&json, "resolve").is_null());
+})
@@ -16,22 +16,32 @@ fn my_process(s: &str) -> ProcessBuilder {
 }
 
 fn cargo_process(s: &str) -> ProcessBuilder {
+    // Keep git from finding the repository the tests themselves run in
     process(cargo_dir().join(s))
         .cwd(paths::root())
         .env("HOME", Some(paths::home()))
+        .env("GIT_CEILING_DIRECTORIES", Some(paths::root().dir_path()))
 }
 
 test!(simple_lib {
     os::setenv("USER", "foo");
-    assert_that(cargo_process("cargo-new").arg("foo"),
+    assert_that(cargo_process("cargo-new").arg("foo").arg("--lib"),
                 execs().with_status(0));
 
     assert_that(&paths::root().join("foo"), existing_dir());
     assert_that(&paths::root().join("foo/Cargo.toml"), existing_file());
     assert_that(&paths::root().join("foo/src/lib.rs"), existing_file());
+    assert!(!paths::root().join("foo/src/main.rs").exists());
+
+    let toml = paths::root().join("foo/Cargo.toml");
+    let toml = File::open(&toml).read_to_string().assert();
+    assert!(toml.as_slice().contains("[[lib]]"), "{}", toml);
+    assert!(!toml.as_slice().contains("[[bin]]"), "{}", toml);
 
     assert_that(cargo_process("cargo-build").cwd(paths::root().join("foo")),
                 execs().with_status(0));
+    assert_that(cargo_process("cargo-test").cwd(paths::root().join("foo")),
+                execs().with_status(0));
 })
 
 test!(simple_bin {
@@ -48,11 +58,14 @@ test!(simple_bin {
     assert_that(&paths::root().join(format!("foo/target/foo{}",
                                             os::consts::EXE_SUFFIX)),
                 existing_file());
+    assert_that(process(paths::root().join("foo/target/foo")),
+                execs().with_status(0).with_stdout("Hello, world!\n"));
+    assert!(!paths::root().join("foo/src/lib.rs").exists());
 })
 
 test!(simple_git {
     os::setenv("USER", "foo");
-    assert_that(cargo_process("cargo-new").arg("foo").arg("--git"),
+    assert_that(cargo_process("cargo-new").arg("foo").arg("--lib").arg("--git"),
                 execs().with_status(0));
 
     assert_that(&paths::root().join("foo"), existing_dir());
@@ -61,10 +74,50 @@ test!(simple_git {
     assert_that(&paths::root().join("foo/.git"), existing_dir());
     assert_that(&paths::root().join("foo/.gitignore"), existing_file());
 
+    let ignore = paths::root().join("foo/.gitignore");
+    let ignore = File::open(&ignore).read_to_string().assert();
+    assert_eq!(ignore.as_slice(), "/target\n/Cargo.lock\n");
+
     assert_that(cargo_process("cargo-build").cwd(paths::root().join("foo")),
                 execs().with_status(0));
 })
 
+test!(git_is_the_default_vcs {
+    assert_that(cargo_process("cargo-new").arg("foo").env("USER", Some("foo")),
+                execs().with_status(0));
+
+    assert_that(&paths::root().join("foo/.git"), existing_dir());
+    let ignore = paths::root().join("foo/.gitignore");
+    let ignore = File::open(&ignore).read_to_string().assert();
+    assert_eq!(ignore.as_slice(), "/target\n");
+})
+
+test!(no_vcs {
+    assert_that(cargo_process("cargo-new").arg("foo").arg("--vcs").arg("none")
+                                          .env("USER", Some("foo")),
+                execs().with_status(0));
+
+    assert_that(&paths::root().join("foo/Cargo.toml"), existing_file());
+    assert!(!paths::root().join("foo/.git").exists());
+    assert!(!paths::root().join("foo/.gitignore").exists());
+})
+
+test!(invalid_vcs {
+    assert_that(cargo_process("cargo-new").arg("foo").arg("--vcs").arg("svn"),
+                execs().with_status(1)
+                       .with_stderr("invalid version control system `svn`, \
+                                     expected `git`, `hg` or `none`\n"));
+})
+
+test!(inside_existing_git_repo {
+    my_process("git").arg("init").exec_with_output().assert();
+    assert_that(cargo_process("cargo-new").arg("foo").env("USER", Some("foo")),
+                execs().with_status(0));
+
+    assert!(!paths::root().join("foo/.git").exists());
+    assert_that(&paths::root().join("foo/.gitignore"), existing_file());
+})
+
 test!(no_argument {
     assert_that(cargo_process("cargo-new"),
                 execs().with_status(1)
@@ -80,10 +133,24 @@ test!(existing {
     fs::mkdir(&dst, UserRWX).assert();
     assert_that(cargo_process("cargo-new").arg("foo"),
                 execs().with_status(101)
-                       .with_stderr(format!("Destination `{}` already exists\n",
+                       .with_stderr(format!("Destination `{}` already exists, \
+                                             pass --force to overwrite it\n",
                                             dst.display())));
 })
 
+test!(existing_with_force {
+    let dst = paths::root().join("foo");
+    fs::mkdir(&dst, UserRWX).assert();
+    File::create(&dst.join("README")).write_str("hi").assert();
+    assert_that(cargo_process("cargo-new").arg("foo").arg("--bin")
+                                          .arg("--force")
+                                          .env("USER", Some("foo")),
+                execs().with_status(0));
+
+    assert_that(&dst.join("README"), existing_file());
+    assert_that(&dst.join("src/main.rs"), existing_file());
+})
+
 test!(finds_author_user {
     assert_that(cargo_process("cargo-new").arg("foo").env("USER", Some("foo")),
                 execs().with_status(0));
@@ -93,6 +160,15 @@ test!(finds_author_user {
     assert!(toml.as_slice().contains(r#"authors = ["foo"]"#));
 })
 
+test!(no_author {
+    assert_that(cargo_process("cargo-new").arg("foo").env("USER", None::<&str>),
+                execs().with_status(0));
+
+    let toml = paths::root().join("foo/Cargo.toml");
+    let toml = File::open(&toml).read_to_string().assert();
+    assert!(toml.as_slice().contains("authors = []"), "{}", toml);
+})
+
 test!(finds_author_git {
     my_process("git").args(["config", "--global", "user.name", "bar"])
                      .exec().assert();
@@ -105,3 +181,59 @@ test!(finds_author_git {
     let toml = File::open(&toml).read_to_string().assert();
     assert!(toml.as_slice().contains(r#"authors = ["bar <baz>"]"#));
 })
+
+test!(bin_is_the_default {
+    assert_that(cargo_process("cargo-new").arg("foo").env("USER", Some("foo")),
+                execs().with_status(0));
+
+    assert_that(&paths::root().join("foo/src/main.rs"), existing_file());
+    assert!(!paths::root().join("foo/src/lib.rs").exists());
+
+    let toml = paths::root().join("foo/Cargo.toml");
+    let toml = File::open(&toml).read_to_string().assert();
+    assert!(toml.as_slice().contains("[[bin]]"), "{}", toml);
+})
+
+test!(lib_and_bin_conflict {
+    assert_that(cargo_process("cargo-new").arg("foo").arg("--lib").arg("--bin"),
+                execs().with_status(1)
+                       .with_stderr("can't specify both --lib and --bin\n"));
+})
+
+test!(existing_manifest_is_not_overwritten {
+    let dst = paths::root().join("foo");
+    fs::mkdir(&dst, UserRWX).assert();
+    File::create(&dst.join("Cargo.toml")).write_str("hi").assert();
+    assert_that(cargo_process("cargo-new").arg("foo").arg("--force")
+                                          .env("USER", Some("foo")),
+                execs().with_status(101)
+                       .with_stderr(format!("`{}` already contains a \
+                                             Cargo.toml, which `cargo new` \
+                                             will not overwrite\n",
+                                            dst.display())));
+
+    let toml = File::open(&dst.join("Cargo.toml")).read_to_string().assert();
+    assert_eq!(toml.as_slice(), "hi");
+})
+
+test!(name_override {
+    assert_that(cargo_process("cargo-new").arg("1foo")
+                                          .env("USER", Some("foo")),
+                execs().with_status(101)
+                       .with_stderr("`1foo` is not a valid crate name, use \
+                                     --name to choose another one\n"));
+
+    assert_that(cargo_process("cargo-new").arg("1foo").arg("--name").arg("foo")
+                                          .env("USER", Some("foo")),
+                execs().with_status(0));
+
+    let toml = paths::root().join("1foo/Cargo.toml");
+    let toml = File::open(&toml).read_to_string().assert();
+    assert!(toml.as_slice().contains(r#"name = "foo""#), "{}", toml);
+
+    assert_that(cargo_process("cargo-build").cwd(paths::root().join("1foo")),
+                execs().with_status(0));
+    assert_that(&paths::root().join(format!("1foo/target/foo{}",
+                                            os::consts::EXE_SUFFIX)),
+                existing_file());
+})
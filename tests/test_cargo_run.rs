@@ -1,7 +1,7 @@
 use std::path;
 
-use support::{project, execs};
-use support::{COMPILING, RUNNING};
+use support::{project, execs, cargo_dir};
+use support::{COMPILING, RUNNING, FRESH, FINISHED};
 use hamcrest::{assert_that, existing_file};
 
 fn setup() {
@@ -22,10 +22,12 @@ test!(simple {
     assert_that(p.cargo_process("cargo-run"),
                 execs().with_status(0).with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
 {running} `target{sep}foo`
 hello
 ",
         compiling = COMPILING,
+        finished = FINISHED,
         running = RUNNING,
         dir = p.root().display(),
         sep = path::SEP).as_slice()));
@@ -67,6 +69,39 @@ test!(exit_code {
                 execs().with_status(2));
 })
 
+test!(exit_code_and_args_are_passed_through {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", r#"
+            fn main() {
+                for arg in std::os::args().iter().skip(1) {
+                    println!("{}", arg);
+                }
+                std::os::set_exit_status(3);
+            }
+        "#);
+
+    assert_that(p.cargo_process("cargo-run").arg("--").arg("a b").arg("--c"),
+                execs().with_status(3).with_stdout(format!("\
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+{running} `target{sep}foo a b --c`
+a b
+--c
+",
+        compiling = COMPILING,
+        finished = FINISHED,
+        running = RUNNING,
+        dir = p.root().display(),
+        sep = path::SEP).as_slice())
+                       .with_stderr(""));
+})
+
 test!(no_main_file {
     let p = project("foo")
         .file("Cargo.toml", r#"
@@ -79,6 +114,88 @@ test!(no_main_file {
 
     assert_that(p.cargo_process("cargo-run"),
                 execs().with_status(101)
-                       .with_stderr("`src/main.rs` must be present for \
+                       .with_stderr("a bin target must be available for \
                                      `cargo run`\n"));
 })
+
+test!(too_many_bins {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "")
+        .file("src/bin/a.rs", "")
+        .file("src/bin/b.rs", "");
+
+    assert_that(p.cargo_process("cargo-run"),
+                execs().with_status(101)
+                       .with_stderr("`cargo run` requires that a project only \
+                                     have one executable; use the `--bin` \
+                                     option to specify which one to run \
+                                     (one of: a, b)\n"));
+})
+
+test!(specify_name {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "")
+        .file("src/bin/a.rs", r#"
+            fn main() { println!("hello a.rs"); }
+        "#)
+        .file("src/bin/b.rs", r#"
+            fn main() {
+                println!("hello b.rs");
+                assert_eq!(std::os::args().get(1).as_slice(), "world");
+            }
+        "#);
+
+    assert_that(p.cargo_process("cargo-run").arg("--bin").arg("a"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+{running} `target{sep}a`
+hello a.rs
+",
+        compiling = COMPILING,
+        finished = FINISHED,
+        running = RUNNING,
+        dir = p.root().display(),
+        sep = path::SEP).as_slice()));
+
+    assert_that(p.process(cargo_dir().join("cargo-run"))
+                 .arg("--bin").arg("b").arg("--").arg("world"),
+                execs().with_status(0).with_stdout(format!("\
+{fresh} foo v0.0.1 (file:{dir})
+{finished} dev [unoptimized] target(s) in [..]s
+{running} `target{sep}b world`
+hello b.rs
+",
+        fresh = FRESH,
+        finished = FINISHED,
+        running = RUNNING,
+        dir = p.root().display(),
+        sep = path::SEP).as_slice()));
+})
+
+test!(unknown_bin {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [project]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-run").arg("--bin").arg("bar"),
+                execs().with_status(101)
+                       .with_stderr("no bin target named `bar` to run\n"));
+})
@@ -0,0 +1,121 @@
+use support::{ResultTest, project, execs, cargo_dir};
+use hamcrest::{assert_that, existing_file};
+
+fn setup() {
+}
+
+test!(trailing_flags_are_passed_to_the_selected_target {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", r#"
+            #[cfg(extra)]
+            fn main() {}
+        "#);
+
+    assert_that(p.cargo_process("cargo-rustc").arg("--").arg("--cfg")
+                 .arg("extra"),
+                execs().with_status(0));
+    assert_that(&p.bin("foo"), existing_file());
+
+    // Without the flag the fingerprint no longer matches, so the binary is
+    // rebuilt and loses its `main`.
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(101));
+})
+
+test!(dependencies_build_with_default_flags {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [dependencies.bar]
+            path = "bar"
+        "#)
+        .file("src/lib.rs", "extern crate bar;")
+        .file("bar/Cargo.toml", r#"
+            [package]
+            name = "bar"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("bar/src/lib.rs", "");
+
+    let output = p.cargo_process("cargo-rustc").arg("-v").arg("--lib")
+                  .arg("--").arg("--cfg").arg("extra")
+                  .exec_with_output().assert();
+    let out = String::from_utf8(output.output).assert();
+    let rustc = |name: &str| {
+        out.as_slice().lines().find(|l| {
+            l.contains(format!("--crate-name {}", name).as_slice())
+        }).expect(format!("no rustc for {}\n{}", name, out).as_slice())
+          .to_string()
+    };
+    assert!(rustc("foo").as_slice().contains("--cfg extra"), "{}", out);
+    assert!(!rustc("bar").as_slice().contains("--cfg extra"), "{}", out);
+})
+
+test!(more_than_one_target_is_an_error {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-rustc").arg("--").arg("--cfg")
+                 .arg("extra"),
+                execs().with_status(101).with_stderr("\
+extra arguments to `rustc` can only be passed to one target, consider \
+filtering the package by passing e.g. `--lib` or `--bin NAME`
+"));
+})
+
+test!(example_in_release_mode_is_an_error {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "")
+        .file("examples/a.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-rustc").arg("--example").arg("a")
+                 .arg("--release"),
+                execs().with_status(1).with_stderr("\
+examples are always built with the `test` profile, so `--example` can't be \
+combined with `--release`
+"));
+})
+
+test!(locked_requires_a_lockfile {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/main.rs", "fn main() {}");
+
+    assert_that(p.cargo_process("cargo-rustc").arg("--locked"),
+                execs().with_status(101).with_stderr("\
+the lockfile needs to be generated but --locked was passed to prevent this
+"));
+    assert_that(p.process(cargo_dir().join("cargo-build")),
+                execs().with_status(0));
+    assert_that(p.process(cargo_dir().join("cargo-rustc")).arg("--locked"),
+                execs().with_status(0));
+})
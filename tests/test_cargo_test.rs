@@ -2,7 +2,7 @@ use std::path;
 use std::str;
 
 use support::{project, execs, basic_bin_manifest, basic_lib_manifest};
-use support::{COMPILING, cargo_dir, ResultTest, FRESH};
+use support::{COMPILING, cargo_dir, ResultTest, FRESH, FINISHED};
 use support::paths::PathExt;
 use hamcrest::{assert_that, existing_file};
 use cargo::util::process;
@@ -34,11 +34,13 @@ test!(cargo_test_simple {
         execs().with_stdout("hello\n"));
 
     assert_that(p.process(cargo_dir().join("cargo-test")),
-        execs().with_stdout(format!("{} foo v0.5.0 (file:{})\n\n\
+        execs().with_stdout(format!("{} foo v0.5.0 (file:{})\n\
+                                    {} test [unoptimized] target(s) in [..]s\n\n\
                                     running 1 test\n\
                                     test test_hello ... ok\n\n\
                                     test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured\n\n",
-                                    COMPILING, p.root().display())));
+                                    COMPILING, p.root().display(),
+                                    FINISHED)));
 })
 
 test!(many_similar_names {
@@ -95,7 +97,8 @@ test!(cargo_test_failing_test {
         execs().with_stdout("hello\n"));
 
     assert_that(p.process(cargo_dir().join("cargo-test")),
-        execs().with_stdout(format!("{} foo v0.5.0 (file:{})\n\n\
+        execs().with_stdout(format!("{} foo v0.5.0 (file:{})\n\
+                                    {} test [unoptimized] target(s) in [..]s\n\n\
                                     running 1 test\n\
                                     test test_hello ... FAILED\n\n\
                                     failures:\n\n\
@@ -106,12 +109,13 @@ test!(cargo_test_failing_test {
                                     failures:\n    test_hello\n\n\
                                     test result: FAILED. 0 passed; 1 failed; \
                                     0 ignored; 0 measured\n\n",
-                                    COMPILING, p.root().display(),
+                                    COMPILING, p.root().display(), FINISHED,
                                     sep = path::SEP))
-              .with_stderr(format!("\
+              .with_stderr("\
 task '<main>' failed at 'Some tests failed', [..]
-Could not execute process `{test}[..]` (status=101)
-", test = p.root().join("target/test/foo").display()))
+the following test binaries failed:
+    foo[..]
+")
               .with_status(101));
 })
 
@@ -200,13 +204,14 @@ test!(test_with_deep_lib_dep {
                        .with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
 {compiling} bar v0.0.1 (file:{dir})
+{finished} test [unoptimized] target(s) in [..]s
 
 running 1 test
 test bar_test ... ok
 
 test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured\n\n\
                        ",
-                       compiling = COMPILING,
+                       compiling = COMPILING, finished = FINISHED,
                        dir = p.root().display()).as_slice()));
 })
 
@@ -334,26 +339,28 @@ test!(pass_through_command_line {
                 execs().with_status(0)
                        .with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
+{finished} test [unoptimized] target(s) in [..]s
 
 running 1 test
 test bar ... ok
 
 test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured\n\n\
                        ",
-                       compiling = COMPILING,
+                       compiling = COMPILING, finished = FINISHED,
                        dir = p.root().display()).as_slice()));
 
     assert_that(p.cargo_process("cargo-test").arg("foo"),
                 execs().with_status(0)
                        .with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
+{finished} test [unoptimized] target(s) in [..]s
 
 running 1 test
 test foo ... ok
 
 test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured\n\n\
                        ",
-                       compiling = COMPILING,
+                       compiling = COMPILING, finished = FINISHED,
                        dir = p.root().display()).as_slice()));
 })
 
@@ -449,16 +456,44 @@ test!(lib_with_standard_name {
                 execs().with_status(0)
                        .with_stdout(format!("\
 {compiling} syntax v0.0.1 (file:{dir})
+{finished} test [unoptimized] target(s) in [..]s
 
 running 1 test
 test test ... ok
 
 test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured\n\n\
                        ",
-                       compiling = COMPILING,
+                       compiling = COMPILING, finished = FINISHED,
                        dir = p.root().display()).as_slice()));
 })
 
+test!(bin_without_tests {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+
+            [[bin]]
+            name = "foo"
+            test = false
+
+            [[example]]
+            name = "ex"
+            test = false
+        "#)
+        .file("src/main.rs", "
+            fn main() {}
+
+            #[test]
+            fn test() { fail!() }
+        ")
+        .file("examples/ex.rs", "fn main() {");
+
+    assert_that(p.cargo_process("cargo-test"), execs().with_status(0));
+})
+
 test!(lib_with_standard_name2 {
     let p = project("foo")
         .file("Cargo.toml", r#"
@@ -487,13 +522,14 @@ test!(lib_with_standard_name2 {
                 execs().with_status(0)
                        .with_stdout(format!("\
 {compiling} syntax v0.0.1 (file:{dir})
+{finished} test [unoptimized] target(s) in [..]s
 
 running 1 test
 test test ... ok
 
 test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured\n\n\
                        ",
-                       compiling = COMPILING,
+                       compiling = COMPILING, finished = FINISHED,
                        dir = p.root().display()).as_slice()));
 })
 
@@ -545,26 +581,28 @@ test!(test_dylib {
                 execs().with_status(0)
                        .with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
+{finished} test [unoptimized] target(s) in [..]s
 
 running 1 test
 test foo ... ok
 
 test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured\n\n\
                        ",
-                       compiling = COMPILING,
+                       compiling = COMPILING, finished = FINISHED,
                        dir = p.root().display()).as_slice()));
     p.root().move_into_the_past().assert();
     assert_that(p.process(cargo_dir().join("cargo-test")),
                 execs().with_status(0)
                        .with_stdout(format!("\
 {fresh} foo v0.0.1 (file:{dir})
+{finished} test [unoptimized] target(s) in [..]s
 
 running 1 test
 test foo ... ok
 
 test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured\n\n\
                        ",
-                       fresh = FRESH,
+                       fresh = FRESH, finished = FINISHED,
                        dir = p.root().display()).as_slice()));
 })
 
@@ -586,25 +624,127 @@ test!(test_twice_with_build_cmd {
                 execs().with_status(0)
                        .with_stdout(format!("\
 {compiling} foo v0.0.1 (file:{dir})
+{finished} test [unoptimized] target(s) in [..]s
 
 running 1 test
 test foo ... ok
 
 test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured\n\n\
                        ",
-                       compiling = COMPILING,
+                       compiling = COMPILING, finished = FINISHED,
                        dir = p.root().display()).as_slice()));
 
     assert_that(p.process(cargo_dir().join("cargo-test")),
                 execs().with_status(0)
                        .with_stdout(format!("\
 {fresh} foo v0.0.1 (file:{dir})
+{finished} test [unoptimized] target(s) in [..]s
 
 running 1 test
 test foo ... ok
 
 test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured\n\n\
                        ",
-                       fresh = FRESH,
+                       fresh = FRESH, finished = FINISHED,
                        dir = p.root().display()).as_slice()));
 })
+
+test!(failing_binary_does_not_stop_other_tests {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "
+            #[test] fn lib_test() { fail!() }
+        ")
+        .file("tests/bar.rs", "
+            #[test] fn bar_test() {}
+        ");
+
+    let output = p.cargo_process("cargo-test").exec_with_output();
+    let output = match output {
+        Ok(..) => fail!("cargo-test should have failed"),
+        Err(e) => e.output.assert(),
+    };
+    assert!(output.status.matches_exit_status(101));
+
+    let out = str::from_utf8(output.output.as_slice()).assert();
+    assert!(out.contains("test lib_test ... FAILED"), "lib_test missing\n{}", out);
+    assert!(out.contains("test bar_test ... ok"), "bar_test missing\n{}", out);
+
+    let err = str::from_utf8(output.error.as_slice()).assert();
+    assert!(err.contains("the following test binaries failed:\n    foo"),
+            "failing binary not listed\n{}", err);
+    assert!(!err.contains("    bar"), "passing binary listed\n{}", err);
+})
+
+test!(test_args_passed_to_every_binary {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "
+            #[test] fn lib_wanted() {}
+            #[test] fn lib_skipped() {}
+        ")
+        .file("tests/bar.rs", "
+            #[test] fn bar_wanted() {}
+            #[test] fn bar_skipped() {}
+        ");
+
+    let output = p.cargo_process("cargo-test").arg("--").arg("wanted")
+                  .exec_with_output().assert();
+    let out = str::from_utf8(output.output.as_slice()).assert();
+    assert!(out.contains("test lib_wanted ... ok"), "lib_wanted missing\n{}", out);
+    assert!(out.contains("test bar_wanted ... ok"), "bar_wanted missing\n{}", out);
+    assert!(!out.contains("skipped"), "filter not applied\n{}", out);
+})
+
+test!(test_filter_without_separator {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "
+            pub mod bar {
+                #[test] fn wanted() {}
+            }
+            #[test] fn skipped() {}
+        ");
+
+    let output = p.cargo_process("cargo-test").arg("bar::wanted")
+                  .exec_with_output().assert();
+    let out = str::from_utf8(output.output.as_slice()).assert();
+    assert!(out.contains("test bar::wanted ... ok"), "wanted missing\n{}", out);
+    assert!(!out.contains("skipped"), "filter not applied\n{}", out);
+})
+
+test!(no_run {
+    let p = project("foo")
+        .file("Cargo.toml", r#"
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            authors = []
+        "#)
+        .file("src/lib.rs", "
+            #[test] fn foo() { fail!() }
+        ");
+
+    assert_that(p.cargo_process("cargo-test").arg("--no-run"),
+                execs().with_status(0).with_stdout(format!("\
+{compiling} foo v0.0.1 (file:{dir})
+{finished} test [unoptimized] target(s) in [..]s
+  Executable target{sep}[..]foo-[..]
+", compiling = COMPILING, finished = FINISHED, dir = p.root().display(),
+   sep = path::SEP)));
+})
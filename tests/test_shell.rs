@@ -1,7 +1,7 @@
 use support::{ResultTest,Tap,shell_writes};
 use hamcrest::{assert_that};
 use std::io::{MemWriter, BufWriter, IoResult};
-use cargo::core::shell::{Shell,ShellConfig};
+use cargo::core::shell::{Shell,ShellConfig,Auto,Always,Never};
 use term::{Terminal,TerminfoTerminal,color};
 
 fn setup() {
@@ -12,7 +12,7 @@ fn writer(buf: &mut [u8]) -> Box<Writer> {
 }
 
 test!(non_tty {
-    let config = ShellConfig { color: true, verbose: true, tty: false };
+    let config = ShellConfig { color: Auto, verbose: true, tty: false };
     let mut buf: Vec<u8> = Vec::from_elem(9, 0 as u8);
 
     Shell::create(writer(buf.as_mut_slice()), config).tap(|shell| {
@@ -22,7 +22,7 @@ test!(non_tty {
 })
 
 test!(color_explicitly_disabled {
-    let config = ShellConfig { color: false, verbose: true, tty: true };
+    let config = ShellConfig { color: Never, verbose: true, tty: true };
     let mut buf: Vec<u8> = Vec::from_elem(9, 0 as u8);
 
     Shell::create(writer(buf.as_mut_slice()), config).tap(|shell| {
@@ -36,7 +36,23 @@ test!(colored_shell {
         Terminal::new(MemWriter::new());
     if term.is_none() { return }
 
-    let config = ShellConfig { color: true, verbose: true, tty: true };
+    let config = ShellConfig { color: Auto, verbose: true, tty: true };
+    let mut buf: Vec<u8> = Vec::from_elem(100, 0 as u8);
+
+    Shell::create(writer(buf.as_mut_slice()), config).tap(|shell| {
+        shell.say("Hey Alex", color::RED).assert();
+        let buf = buf.as_slice().slice_to(buf.iter().position(|a| *a == 0).unwrap());
+        assert_that(buf, shell_writes(colored_output("Hey Alex\n",
+                                                     color::RED).assert()));
+    });
+})
+
+test!(color_forced_without_tty {
+    let term: Option<TerminfoTerminal<MemWriter>> =
+        Terminal::new(MemWriter::new());
+    if term.is_none() { return }
+
+    let config = ShellConfig { color: Always, verbose: true, tty: false };
     let mut buf: Vec<u8> = Vec::from_elem(100, 0 as u8);
 
     Shell::create(writer(buf.as_mut_slice()), config).tap(|shell| {
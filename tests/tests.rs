@@ -4,6 +4,7 @@
 extern crate term;
 extern crate cargo;
 extern crate hamcrest;
+extern crate serialize;
 
 #[phase(plugin, link)]
 extern crate log;
@@ -35,3 +36,7 @@ mod test_cargo_compile_plugins;
 mod test_cargo_doc;
 mod test_cargo_freshness;
 mod test_cargo_generate_lockfile;
+mod test_cargo_bench;
+mod test_cargo_features;
+mod test_cargo_metadata;
+mod test_cargo_rustc;